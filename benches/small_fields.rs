@@ -0,0 +1,39 @@
+//! Сравнение `encode_field` и `encode_field_small` на мелких полях
+//!
+//! Запуск: `cargo bench --features smallvec`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use custom_codec::{encode_field, encode_field_small, Field, Value};
+
+const ITERS: u32 = 2_000_000;
+
+fn bench(name: &str, mut f: impl FnMut() -> usize) -> Duration {
+    // прогрев
+    for _ in 0..ITERS / 10 {
+        black_box(f());
+    }
+    let start = Instant::now();
+    for _ in 0..ITERS {
+        black_box(f());
+    }
+    let elapsed = start.elapsed();
+    println!("{name:<24} {:>8.1} нс/поле", elapsed.as_nanos() as f64 / ITERS as f64);
+    elapsed
+}
+
+fn main() {
+    let fields = [
+        Field { key: "id".into(), value: Value::Int64(1_234_567) },
+        Field { key: "ok".into(), value: Value::Bool(true) },
+        Field { key: "name".into(), value: Value::String("probe".into()) },
+    ];
+    for field in &fields {
+        let field = black_box(field);
+        println!("{}:", field.key);
+        let heap = bench("  encode_field", || encode_field(field).unwrap().len());
+        let stack = bench("  encode_field_small", || encode_field_small(field).unwrap().len());
+        println!("  ускорение: {:.2}x", heap.as_secs_f64() / stack.as_secs_f64());
+    }
+}
@@ -0,0 +1,102 @@
+//! Разбор атрибутов `#[codec(...)]`
+
+use syn::{Attribute, LitStr, Path, Result, Token};
+
+/// Значение поля, отсутствующего в сообщении
+pub enum DefaultValue {
+    /// `#[codec(default)]`
+    Trait,
+    /// `#[codec(default = "path")]`
+    Path(Path),
+}
+
+/// Атрибуты поля структуры или варианта
+#[derive(Default)]
+pub struct FieldAttrs {
+    pub rename: Option<String>,
+    pub skip: bool,
+    pub default: Option<DefaultValue>,
+}
+
+impl FieldAttrs {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut out = FieldAttrs::default();
+        for attr in codec_attrs(attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    out.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("skip") {
+                    out.skip = true;
+                } else if meta.path.is_ident("default") {
+                    out.default = Some(match meta.input.peek(Token![=]) {
+                        true => DefaultValue::Path(meta.value()?.parse::<LitStr>()?.parse()?),
+                        false => DefaultValue::Trait,
+                    });
+                } else {
+                    return Err(meta.error("неизвестный атрибут поля"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(out)
+    }
+}
+
+/// Атрибуты перечисления
+pub struct EnumAttrs {
+    /// ключ поля с именем варианта
+    pub tag: String,
+    /// ключ поля с содержимым кортежного варианта
+    pub content: String,
+}
+
+impl EnumAttrs {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut out = EnumAttrs { tag: "type".into(), content: "value".into() };
+        for attr in codec_attrs(attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    out.tag = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.path.is_ident("content") {
+                    out.content = meta.value()?.parse::<LitStr>()?.value();
+                } else {
+                    return Err(meta.error("неизвестный атрибут перечисления"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(out)
+    }
+}
+
+/// Атрибуты варианта перечисления
+#[derive(Default)]
+pub struct VariantAttrs {
+    pub rename: Option<String>,
+    /// вариант для неизвестных имён
+    pub other: bool,
+}
+
+impl VariantAttrs {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut out = VariantAttrs::default();
+        for attr in codec_attrs(attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    out.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("other") {
+                    out.other = true;
+                } else {
+                    return Err(meta.error("неизвестный атрибут варианта"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(out)
+    }
+}
+
+/// Атрибуты `codec` среди прочих
+pub fn codec_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|a| a.path().is_ident("codec"))
+}
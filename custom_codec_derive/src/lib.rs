@@ -0,0 +1,356 @@
+//! `#[derive(Encode, Decode)]` для трейтов `custom_codec::Encode` и
+//! `custom_codec::Decode`
+//!
+//! Используется через признак `derive` крейта `custom_codec`; описание
+//! атрибутов `#[codec(...)]` — в документации `custom_codec::Encode`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as Tokens;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{
+    parse_macro_input, parse_quote, Data, DataEnum, DeriveInput, Error, Fields, Generics, Ident,
+    Result,
+};
+
+mod attr;
+
+use attr::{codec_attrs, DefaultValue, EnumAttrs, FieldAttrs, VariantAttrs};
+
+#[proc_macro_derive(Encode, attributes(codec))]
+pub fn derive_encode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_encode(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(Decode, attributes(codec))]
+pub fn derive_decode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_decode(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// Поле структуры или варианта с разобранными атрибутами
+struct Member {
+    /// имя поля: `x` или `0`
+    member: Tokens,
+    /// переменная, с которой поле связывается в образце
+    binding: Ident,
+    ty: syn::Type,
+    key: String,
+    attrs: FieldAttrs,
+}
+
+fn members(fields: &Fields) -> Result<Vec<Member>> {
+    let mut out = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+        let (member, binding, key) = match &field.ident {
+            // ключ `r#type` — `type`, как у кода `codegen`
+            Some(ident) => (quote!(#ident), ident.clone(), ident.unraw().to_string()),
+            None => {
+                if let Some(attr) = codec_attrs(&field.attrs).next() {
+                    let msg = "атрибуты codec допускаются только у именованных полей";
+                    return Err(Error::new_spanned(attr, msg));
+                }
+                let index = syn::Index::from(i);
+                (quote!(#index), format_ident!("__f{}", i), i.to_string())
+            }
+        };
+        let key = attrs.rename.clone().unwrap_or(key);
+        out.push(Member { member, binding, ty: field.ty.clone(), key, attrs });
+    }
+    Ok(out)
+}
+
+/// Образец `path { x, .. }` / `path(__f0, __f1)` / `path`, связывающий
+/// незапропущенные поля
+fn pattern(path: &Tokens, fields: &Fields, members: &[Member]) -> Tokens {
+    let bindings = members.iter().filter(|m| !m.attrs.skip).map(|m| &m.binding);
+    match fields {
+        Fields::Named(_) => quote!(#path { #(#bindings,)* .. }),
+        Fields::Unnamed(_) => quote!(#path(#(#bindings),*)),
+        Fields::Unit => quote!(#path),
+    }
+}
+
+/// Обобщённые параметры с ограничением `bound` на каждый тип
+fn bounded(generics: &Generics, bound: syn::Path) -> Generics {
+    let mut generics = generics.clone();
+    let params: Vec<_> = generics.type_params().map(|p| p.ident.clone()).collect();
+    let clause = generics.make_where_clause();
+    for ident in params {
+        clause.predicates.push(parse_quote!(#ident: #bound));
+    }
+    generics
+}
+
+fn struct_attrs(input: &DeriveInput) -> Result<()> {
+    match codec_attrs(&input.attrs).next() {
+        Some(attr) => Err(Error::new_spanned(attr, "атрибуты codec у структуры не поддерживаются")),
+        None => Ok(()),
+    }
+}
+
+fn expand_encode(input: &DeriveInput) -> Result<Tokens> {
+    let body = match &input.data {
+        Data::Struct(data) => {
+            struct_attrs(input)?;
+            let members = members(&data.fields)?;
+            let pat = pattern(&quote!(Self), &data.fields, &members);
+            let value = encode_payload(&data.fields, &members);
+            quote! {
+                let #pat = self;
+                #value
+            }
+        }
+        Data::Enum(data) => encode_enum(input, data)?,
+        Data::Union(_) => return Err(Error::new_spanned(input, "объединения не поддерживаются")),
+    };
+    let name = &input.ident;
+    let generics = bounded(&input.generics, parse_quote!(::custom_codec::Encode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::custom_codec::Encode for #name #ty_generics #where_clause {
+            fn encode_value(&self) -> ::custom_codec::Value {
+                #body
+            }
+        }
+    })
+}
+
+/// Поля сообщения для именованных полей, связанных образцом
+fn encode_fields(members: &[Member]) -> Vec<Tokens> {
+    let members = members.iter().filter(|m| !m.attrs.skip);
+    members.map(|m| field(&m.key, &encode_binding(m))).collect()
+}
+
+fn encode_binding(m: &Member) -> Tokens {
+    let binding = &m.binding;
+    quote!(::custom_codec::Encode::encode_value(#binding))
+}
+
+fn field(key: &str, value: &Tokens) -> Tokens {
+    quote! {
+        ::custom_codec::Field { key: ::std::string::String::from(#key), value: #value }
+    }
+}
+
+/// Значение структуры из полей, связанных образцом
+fn encode_payload(fields: &Fields, members: &[Member]) -> Tokens {
+    match fields {
+        Fields::Named(_) => {
+            let fields = encode_fields(members);
+            quote!(::custom_codec::Value::Message(::std::vec![#(#fields),*]))
+        }
+        Fields::Unnamed(_) if members.len() == 1 => encode_binding(&members[0]),
+        Fields::Unnamed(_) => {
+            let items = members.iter().map(encode_binding);
+            quote!(::custom_codec::Value::List(::std::vec![#(#items),*]))
+        }
+        Fields::Unit => quote!(::custom_codec::Value::Null),
+    }
+}
+
+/// Вариант перечисления с разобранными атрибутами
+struct Variant<'a> {
+    ident: &'a Ident,
+    fields: &'a Fields,
+    members: Vec<Member>,
+    name: String,
+    attrs: VariantAttrs,
+}
+
+fn variants<'a>(data: &'a DataEnum, attrs: &EnumAttrs) -> Result<Vec<Variant<'a>>> {
+    let mut out: Vec<Variant<'a>> = Vec::new();
+    for v in &data.variants {
+        let vattrs = VariantAttrs::parse(&v.attrs)?;
+        if vattrs.other && !matches!(v.fields, Fields::Unit) {
+            let msg = "#[codec(other)] допускается только у варианта без полей";
+            return Err(Error::new_spanned(v, msg));
+        }
+        if vattrs.other && out.iter().any(|o| o.attrs.other) {
+            return Err(Error::new_spanned(v, "вариант #[codec(other)] уже задан"));
+        }
+        let members = members(&v.fields)?;
+        if let Some(m) = members.iter().find(|m| m.key == attrs.tag && !m.attrs.skip) {
+            let msg = format!("ключ поля {:?} совпадает с ключом варианта", m.key);
+            return Err(Error::new_spanned(&m.ty, msg));
+        }
+        let name = vattrs.rename.clone().unwrap_or_else(|| v.ident.unraw().to_string());
+        out.push(Variant { ident: &v.ident, fields: &v.fields, members, name, attrs: vattrs });
+    }
+    Ok(out)
+}
+
+fn encode_enum(input: &DeriveInput, data: &DataEnum) -> Result<Tokens> {
+    let attrs = EnumAttrs::parse(&input.attrs)?;
+    let arms = variants(data, &attrs)?.into_iter().map(|v| {
+        let ident = v.ident;
+        let pat = pattern(&quote!(Self::#ident), v.fields, &v.members);
+        let name = &v.name;
+        let tag = field(&attrs.tag, &quote! {
+            ::custom_codec::Value::String(::std::string::String::from(#name))
+        });
+        let rest = match v.fields {
+            Fields::Named(_) => encode_fields(&v.members),
+            Fields::Unnamed(_) => {
+                let payload = encode_payload(v.fields, &v.members);
+                vec![field(&attrs.content, &payload)]
+            }
+            Fields::Unit => Vec::new(),
+        };
+        quote! {
+            #pat => ::custom_codec::Value::Message(::std::vec![#tag, #(#rest),*]),
+        }
+    });
+    Ok(quote! {
+        match self {
+            #(#arms)*
+        }
+    })
+}
+
+fn expand_decode(input: &DeriveInput) -> Result<Tokens> {
+    let body = match &input.data {
+        Data::Struct(data) => {
+            struct_attrs(input)?;
+            let members = members(&data.fields)?;
+            match &data.fields {
+                Fields::Named(_) => {
+                    let value = decode_named(&quote!(Self), &members);
+                    quote! {
+                        let fields = ::custom_codec::__private::message_fields(v)?;
+                        ::core::result::Result::Ok(#value)
+                    }
+                }
+                Fields::Unnamed(_) => decode_unnamed(&quote!(Self), &members),
+                Fields::Unit => quote! {
+                    match v {
+                        ::custom_codec::Value::Null => ::core::result::Result::Ok(Self),
+                        other => ::core::result::Result::Err(
+                            ::custom_codec::ValueError::invalid_type("Null", other),
+                        ),
+                    }
+                },
+            }
+        }
+        Data::Enum(data) => decode_enum(input, data)?,
+        Data::Union(_) => return Err(Error::new_spanned(input, "объединения не поддерживаются")),
+    };
+    let name = &input.ident;
+    let generics = bounded(&input.generics, parse_quote!(::custom_codec::Decode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::custom_codec::Decode for #name #ty_generics #where_clause {
+            fn decode_value(
+                v: &::custom_codec::Value,
+            ) -> ::core::result::Result<Self, ::custom_codec::ValueError> {
+                #body
+            }
+        }
+    })
+}
+
+/// `path { .. }` из полей сообщения в переменной `fields`
+fn decode_named(path: &Tokens, members: &[Member]) -> Tokens {
+    let inits = members.iter().map(|m| {
+        let member = &m.member;
+        let value = decode_member(m);
+        quote!(#member: #value)
+    });
+    quote!(#path { #(#inits),* })
+}
+
+/// `Result` со значением `path(..)` из значения в переменной `v`
+fn decode_unnamed(path: &Tokens, members: &[Member]) -> Tokens {
+    let decode = |m: &Member, v: Tokens| {
+        let ty = &m.ty;
+        quote!(<#ty as ::custom_codec::Decode>::decode_value(#v)?)
+    };
+    if members.len() == 1 {
+        let value = decode(&members[0], quote!(v));
+        return quote!(::core::result::Result::Ok(#path(#value)));
+    }
+    let len = members.len();
+    let items = members.iter().enumerate().map(|(i, m)| decode(m, quote!(&items[#i])));
+    quote! {
+        let items = ::custom_codec::__private::tuple_items(v, #len)?;
+        ::core::result::Result::Ok(#path(#(#items),*))
+    }
+}
+
+/// Значение именованного поля при чтении из `fields`
+fn decode_member(m: &Member) -> Tokens {
+    if m.attrs.skip {
+        return quote!(::core::default::Default::default());
+    }
+    let (ty, key) = (&m.ty, &m.key);
+    let missing = match &m.attrs.default {
+        Some(DefaultValue::Trait) => quote!(::core::default::Default::default()),
+        Some(DefaultValue::Path(path)) => quote!(#path()),
+        None => quote! {
+            match <#ty as ::custom_codec::Decode>::decode_missing() {
+                ::core::option::Option::Some(value) => value,
+                ::core::option::Option::None => {
+                    return ::core::result::Result::Err(::custom_codec::ValueError::MissingField {
+                        key: ::std::string::String::from(#key),
+                    })
+                }
+            }
+        },
+    };
+    quote! {
+        match ::custom_codec::__private::find_field(fields, #key) {
+            ::core::option::Option::Some(value) => {
+                <#ty as ::custom_codec::Decode>::decode_value(value)
+                    .map_err(|e| e.in_field(#key))?
+            }
+            ::core::option::Option::None => #missing,
+        }
+    }
+}
+
+fn decode_enum(input: &DeriveInput, data: &DataEnum) -> Result<Tokens> {
+    let attrs = EnumAttrs::parse(&input.attrs)?;
+    let variants = variants(data, &attrs)?;
+    let (tag, content) = (&attrs.tag, &attrs.content);
+    let arms = variants.iter().map(|v| {
+        let ident = v.ident;
+        let path = quote!(Self::#ident);
+        let name = &v.name;
+        let value = match v.fields {
+            Fields::Named(_) => {
+                let value = decode_named(&path, &v.members);
+                quote!(::core::result::Result::Ok(#value))
+            }
+            Fields::Unnamed(_) => {
+                let inner = decode_unnamed(&path, &v.members);
+                quote! {{
+                    let v = ::custom_codec::__private::required_field(fields, #content)?;
+                    (|| -> ::core::result::Result<Self, ::custom_codec::ValueError> { #inner })()
+                        .map_err(|e| e.in_field(#content))
+                }}
+            }
+            Fields::Unit => quote!(::core::result::Result::Ok(#path)),
+        };
+        quote!(#name => #value,)
+    });
+    let unknown = match variants.iter().find(|v| v.attrs.other) {
+        Some(v) => {
+            let ident = v.ident;
+            quote!(::core::result::Result::Ok(Self::#ident))
+        }
+        None => quote! {
+            ::core::result::Result::Err(::custom_codec::ValueError::UnknownVariant {
+                name: ::std::string::String::from(name),
+            })
+        },
+    };
+    Ok(quote! {
+        let fields = ::custom_codec::__private::message_fields(v)?;
+        match ::custom_codec::__private::variant_name(fields, #tag)? {
+            #(#arms)*
+            name => #unknown,
+        }
+    })
+}
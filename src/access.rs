@@ -0,0 +1,122 @@
+//! Доступ к содержимому `Value` без сопоставления с образцом
+//!
+//! `as_*` у `Value` возвращают `None` при несовпадении типа, `expect_*`
+//! у `Field` — `ValueError` с ключом поля. Числа читаются по правилам
+//! `Decode`: целые — из любого целого значения, если помещаются в тип.
+
+use crate::{Decode, Field, Value, ValueError};
+
+macro_rules! numbers {
+    ($($as:ident, $expect:ident => $t:ty),* $(,)?) => {
+        impl Value {
+            $(
+                pub fn $as(&self) -> Option<$t> {
+                    <$t>::decode_value(self).ok()
+                }
+            )*
+        }
+
+        impl Field {
+            $(
+                pub fn $expect(&self) -> Result<$t, ValueError> {
+                    <$t>::decode_value(&self.value).map_err(|e| e.in_field(&self.key))
+                }
+            )*
+        }
+    };
+}
+
+numbers! {
+    as_i32, expect_i32 => i32,
+    as_i64, expect_i64 => i64,
+    as_u64, expect_u64 => u64,
+    as_f32, expect_f32 => f32,
+    as_f64, expect_f64 => f64,
+    as_bool, expect_bool => bool,
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Поля вложенного сообщения
+    pub fn as_message(&self) -> Option<&[Field]> {
+        match self {
+            Value::Message(fields) => Some(fields),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+}
+
+impl Field {
+    pub fn expect_str(&self) -> Result<&str, ValueError> {
+        self.value.as_str().ok_or_else(|| self.invalid_type("String"))
+    }
+
+    pub fn expect_bytes(&self) -> Result<&[u8], ValueError> {
+        self.value.as_bytes().ok_or_else(|| self.invalid_type("Bytes"))
+    }
+
+    pub fn expect_message(&self) -> Result<&[Field], ValueError> {
+        self.value.as_message().ok_or_else(|| self.invalid_type("Message"))
+    }
+
+    pub fn expect_list(&self) -> Result<&[Value], ValueError> {
+        self.value.as_list().ok_or_else(|| self.invalid_type("List"))
+    }
+
+    fn invalid_type(&self, expected: &'static str) -> ValueError {
+        ValueError::invalid_type(expected, &self.value).in_field(&self.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accessors() {
+        assert_eq!(Value::Int32(7).as_i32(), Some(7));
+        assert_eq!(Value::UInt8(7).as_i64(), Some(7));
+        assert_eq!(Value::Int64(i64::MAX).as_i32(), None);
+        assert_eq!(Value::Float32(0.5).as_f64(), Some(0.5));
+        assert_eq!(Value::Bool(true).as_str(), None);
+        assert_eq!(Value::from("s").as_str(), Some("s"));
+        assert_eq!(Value::Bytes(vec![1]).as_bytes(), Some(&[1u8][..]));
+        let inner = vec![Field { key: "k".into(), value: Value::Null }];
+        assert_eq!(Value::Message(inner.clone()).as_message(), Some(&inner[..]));
+        assert!(inner[0].value.is_null());
+    }
+
+    #[test]
+    fn expect_names_field() {
+        let f = Field { key: "age".into(), value: Value::String("x".into()) };
+        assert_eq!(f.expect_str(), Ok("x"));
+        let err = f.expect_i32().unwrap_err();
+        assert_eq!(err.to_string(), "поле \"age\": ожидался Int32, получен String");
+        let err = f.expect_message().unwrap_err();
+        assert_eq!(err, ValueError::invalid_type("Message", &f.value).in_field("age"));
+    }
+}
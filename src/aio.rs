@@ -0,0 +1,134 @@
+//! Асинхронные чтение и запись полей поверх `tokio::io`
+//!
+//! Заголовок поля читается ровно настолько, насколько нужно для его
+//! разбора, поэтому из потока не забирается ни байта следующего поля.
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::decode::{Decoder, Reader};
+use crate::{encode_field_with, DecodeError, DecodeOptions, EncodeError, EncodeOptions, Field};
+
+/// Чтение одного поля из `AsyncRead`
+///
+/// Как и `decode_field_from`, функцию можно вызывать повторно для
+/// последовательного чтения полей.
+pub async fn read_field<R: AsyncRead + Unpin>(r: &mut R) -> Result<Field, DecodeError> {
+    read_field_with(r, &DecodeOptions::unlimited()).await
+}
+
+/// Чтение одного поля из `AsyncRead` с ограничениями
+///
+/// Длины проверяются по заголовку, до чтения данных поля.
+pub async fn read_field_with<R: AsyncRead + Unpin>(
+    r: &mut R,
+    opts: &DecodeOptions,
+) -> Result<Field, DecodeError> {
+    let mut buf = Vec::new();
+    loop {
+        let mut dec = Decoder::new(Reader::new(&buf), opts);
+        let (len, last) = match dec.field_end() {
+            Ok(end) => (end - buf.len(), true),
+            Err(DecodeError::UnexpectedEof { .. }) => (dec.input.missing(), false),
+            Err(e) => return Err(e),
+        };
+        // при обрыве потока ошибку с точным смещением даст декодер
+        if !read_more(r, &mut buf, len).await? || last {
+            break;
+        }
+    }
+    Decoder::new(Reader::new(&buf), opts).read_field()
+}
+
+/// Дочитывание `len` байт в конец `buf`; `false`, если поток кончился раньше
+///
+/// Буфер растёт по мере поступления данных, так что испорченная длина
+/// не приводит к огромной аллокации.
+async fn read_more<R: AsyncRead + Unpin>(
+    r: &mut R,
+    buf: &mut Vec<u8>,
+    len: usize,
+) -> Result<bool, DecodeError> {
+    match r.take(len as u64).read_to_end(buf).await {
+        Ok(n) => Ok(n == len),
+        Err(e) => Err(DecodeError::Io { offset: buf.len(), kind: e.kind() }),
+    }
+}
+
+/// Запись поля в `AsyncWrite`
+///
+/// Поле кодируется в память целиком и отправляется одним `write_all`.
+pub async fn write_field<W: AsyncWrite + Unpin>(w: &mut W, f: &Field) -> Result<(), EncodeError> {
+    write_field_with(w, f, &EncodeOptions::default()).await
+}
+
+/// Запись поля в `AsyncWrite` с настройками
+pub async fn write_field_with<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    f: &Field,
+    opts: &EncodeOptions,
+) -> Result<(), EncodeError> {
+    let buf = encode_field_with(f, opts)?;
+    w.write_all(&buf).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_field, encode_field, Limit, Profile, Value};
+
+    fn sample() -> Field {
+        Field {
+            key: "user".into(),
+            value: Value::Message(vec![
+                Field { key: "name".into(), value: Value::String("Rust".into()) },
+                Field { key: "raw".into(), value: Value::Bytes(vec![0, 1, 2]) },
+            ]),
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trip_over_pipe() {
+        let b = Field { key: "n".into(), value: Value::Int32(-5) };
+        for profile in [Profile::STANDARD, Profile::compact()] {
+            let enc = EncodeOptions { profile, ..EncodeOptions::default() };
+            let dec = DecodeOptions { profile, ..DecodeOptions::default() };
+            // маленький буфер канала заставляет читать по частям
+            let (mut tx, mut rx) = tokio::io::duplex(3);
+            let fields = [sample(), b.clone()];
+            let writer = async {
+                for f in &fields {
+                    write_field_with(&mut tx, f, &enc).await.unwrap();
+                }
+                drop(tx);
+            };
+            let reader = async {
+                let a = read_field_with(&mut rx, &dec).await.unwrap();
+                let b = read_field_with(&mut rx, &dec).await.unwrap();
+                let eof = read_field_with(&mut rx, &dec).await.unwrap_err();
+                (a, b, eof)
+            };
+            let ((), (a, b, eof)) = tokio::join!(writer, reader);
+            assert_eq!([a, b], fields);
+            assert_eq!(eof, DecodeError::UnexpectedEof { offset: 0 });
+        }
+    }
+
+    #[tokio::test]
+    async fn errors_match_slice_errors() {
+        let enc = encode_field(&sample()).unwrap();
+        for cut in [3, 9, 20, enc.len() - 1] {
+            let part = &enc[..cut];
+            let err = read_field(&mut &part[..]).await.unwrap_err();
+            assert_eq!(err, decode_field(part).unwrap_err());
+        }
+
+        // объявленная длина 1 GiB отвергается до чтения данных
+        let mut enc = vec![5, 0, 0, 0, 1, b'b'];
+        enc.extend_from_slice(&(1u32 << 30).to_be_bytes());
+        assert_eq!(
+            read_field_with(&mut &enc[..], &DecodeOptions::default()).await.unwrap_err(),
+            DecodeError::LimitExceeded { offset: 6, limit: Limit::ValueLength }
+        );
+    }
+}
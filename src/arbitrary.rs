@@ -0,0 +1,139 @@
+//! `Arbitrary` для `Value` и `Field`: входы для cargo-fuzz
+//!
+//! Из любых байт получается конечное дерево: вложенность контейнеров
+//! ограничена, у `Value::arbitrary` — четырьмя уровнями. Значения
+//! кодируются и декодируются обратно в равные: `Timestamp` и `Decimal`
+//! всегда допустимы, а вместо NaN выбирается ноль.
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::decimal::MAX_SCALE;
+use crate::{Decimal, Field, Timestamp, Value};
+
+/// Вложенность по умолчанию
+const MAX_DEPTH: usize = 4;
+/// Наибольшее число элементов одного контейнера
+const MAX_ITEMS: usize = 8;
+/// Число скалярных вариантов `Value`; они идут первыми в `arbitrary_depth`
+const SCALARS: u8 = 17;
+
+impl Value {
+    /// Значение с не более чем `max_depth` уровнями вложенных контейнеров
+    pub fn arbitrary_depth(u: &mut Unstructured<'_>, max_depth: usize) -> Result<Value> {
+        let last = if max_depth == 0 { SCALARS - 1 } else { SCALARS + 3 };
+        Ok(match u.int_in_range(0..=last)? {
+            0 => Value::Int8(u.arbitrary()?),
+            1 => Value::Int16(u.arbitrary()?),
+            2 => Value::Int32(u.arbitrary()?),
+            3 => Value::Int64(u.arbitrary()?),
+            4 => Value::UInt8(u.arbitrary()?),
+            5 => Value::UInt16(u.arbitrary()?),
+            6 => Value::UInt32(u.arbitrary()?),
+            7 => Value::UInt64(u.arbitrary()?),
+            8 => Value::Float32(Some(u.arbitrary::<f32>()?).filter(|f| !f.is_nan()).unwrap_or(0.0)),
+            9 => Value::Float64(Some(u.arbitrary::<f64>()?).filter(|f| !f.is_nan()).unwrap_or(0.0)),
+            10 => Value::Bool(u.arbitrary()?),
+            11 => Value::String(u.arbitrary()?),
+            12 => Value::Bytes(u.arbitrary()?),
+            13 => Value::Null,
+            14 => Value::Timestamp(u.arbitrary()?),
+            15 => Value::Uuid(u.arbitrary()?),
+            16 => Value::Decimal(u.arbitrary()?),
+            17 => Value::Message(items(u, |u| Field::arbitrary_depth(u, max_depth - 1))?),
+            18 => Value::List(items(u, |u| Value::arbitrary_depth(u, max_depth - 1))?),
+            19 => Value::Map(items(u, |u| {
+                let key = Value::arbitrary_depth(u, max_depth - 1)?;
+                Ok((key, Value::arbitrary_depth(u, max_depth - 1)?))
+            })?),
+            _ => {
+                let payload = match u.arbitrary()? {
+                    true => Some(Box::new(Value::arbitrary_depth(u, max_depth - 1)?)),
+                    false => None,
+                };
+                Value::Enum { variant: u.arbitrary()?, name: u.arbitrary()?, payload }
+            }
+        })
+    }
+}
+
+impl Field {
+    /// Поле, значение которого не глубже `max_depth` уровней
+    pub fn arbitrary_depth(u: &mut Unstructured<'_>, max_depth: usize) -> Result<Field> {
+        Ok(Field { key: u.arbitrary()?, value: Value::arbitrary_depth(u, max_depth)? })
+    }
+}
+
+/// До `MAX_ITEMS` элементов; на исчерпанном входе — ни одного
+fn items<'a, T>(
+    u: &mut Unstructured<'a>,
+    mut item: impl FnMut(&mut Unstructured<'a>) -> Result<T>,
+) -> Result<Vec<T>> {
+    let len = u.int_in_range(0..=MAX_ITEMS)?;
+    (0..len).map(|_| item(u)).collect()
+}
+
+impl<'a> Arbitrary<'a> for Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Value::arbitrary_depth(u, MAX_DEPTH)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Field {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Field::arbitrary_depth(u, MAX_DEPTH)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Timestamp {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Timestamp { secs: u.arbitrary()?, nanos: u.int_in_range(0..=999_999_999)? })
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (12, Some(12))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Decimal {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Decimal { mantissa: u.arbitrary()?, scale: u.int_in_range(0..=MAX_SCALE)? })
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (17, Some(17))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_field, encode_field};
+
+    #[test]
+    fn arbitrary_fields_roundtrip() {
+        // детерминированный псевдослучайный вход
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let data: Vec<u8> = (0..64 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let mut u = Unstructured::new(&data);
+        let mut count = 0;
+        while !u.is_empty() {
+            let f = Field::arbitrary(&mut u).unwrap();
+            assert_eq!(decode_field(&encode_field(&f).unwrap()).unwrap(), f);
+            count += 1;
+        }
+        assert!(count > 10);
+
+        // без вложенных контейнеров
+        for chunk in data.chunks(32) {
+            let v = Value::arbitrary_depth(&mut Unstructured::new(chunk), 0).unwrap();
+            assert!(!matches!(v, Value::Message(_) | Value::List(_) | Value::Map(_)));
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Декодирование в арену `bumpalo`
+//!
+//! Строки, байты и векторы полей размещаются в переданной `Bump`, так что
+//! всё декодированное освобождается разом вместе с ареной. В куче остаётся
+//! только служебный стек декодера на время вызова.
+
+use std::borrow::Cow;
+
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+
+use crate::decode::{Decoder, Input, Reader, Seq, Tree, UNLIMITED};
+use crate::{utf8, Decimal, DecodeError, DecodeOptions, Field, Timestamp, Value};
+
+/// `Value`, размещённое в арене
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArenaValue<'b> {
+    Int32(i32),
+    Float32(f32),
+    Bool(bool),
+    String(&'b str),
+    Bytes(&'b [u8]),
+    Message(BumpVec<'b, ArenaField<'b>>),
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    Null,
+    List(BumpVec<'b, ArenaValue<'b>>),
+    Map(BumpVec<'b, (ArenaValue<'b>, ArenaValue<'b>)>),
+    Timestamp(Timestamp),
+    Uuid([u8; 16]),
+    Decimal(Decimal),
+    Enum { variant: u32, name: Option<&'b str>, payload: Option<&'b ArenaValue<'b>> },
+    Int8(i8),
+    Int16(i16),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    /// Значение неизвестного типа, см. `Value::Unknown`
+    Unknown { type_code: u8, raw: &'b [u8] },
+}
+
+/// `Field`, размещённое в арене
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArenaField<'b> {
+    pub key: &'b str,
+    pub value: ArenaValue<'b>,
+}
+
+impl ArenaValue<'_> {
+    /// Копия значения вне арены
+    pub fn to_owned(&self) -> Value {
+        match self {
+            ArenaValue::Int32(i) => Value::Int32(*i),
+            ArenaValue::Float32(f) => Value::Float32(*f),
+            ArenaValue::Bool(b) => Value::Bool(*b),
+            ArenaValue::String(s) => Value::String((*s).to_owned()),
+            ArenaValue::Bytes(b) => Value::Bytes(b.to_vec()),
+            ArenaValue::Message(fields) => {
+                Value::Message(fields.iter().map(ArenaField::to_owned).collect())
+            }
+            ArenaValue::Int64(i) => Value::Int64(*i),
+            ArenaValue::UInt64(u) => Value::UInt64(*u),
+            ArenaValue::Float64(f) => Value::Float64(*f),
+            ArenaValue::Null => Value::Null,
+            ArenaValue::List(items) => {
+                Value::List(items.iter().map(ArenaValue::to_owned).collect())
+            }
+            ArenaValue::Map(entries) => {
+                Value::Map(entries.iter().map(|(k, v)| (k.to_owned(), v.to_owned())).collect())
+            }
+            ArenaValue::Timestamp(ts) => Value::Timestamp(*ts),
+            ArenaValue::Uuid(u) => Value::Uuid(*u),
+            ArenaValue::Decimal(d) => Value::Decimal(*d),
+            ArenaValue::Enum { variant, name, payload } => Value::Enum {
+                variant: *variant,
+                name: name.map(str::to_owned),
+                payload: payload.map(|p| Box::new(p.to_owned())),
+            },
+            ArenaValue::Int8(i) => Value::Int8(*i),
+            ArenaValue::Int16(i) => Value::Int16(*i),
+            ArenaValue::UInt8(u) => Value::UInt8(*u),
+            ArenaValue::UInt16(u) => Value::UInt16(*u),
+            ArenaValue::UInt32(u) => Value::UInt32(*u),
+            ArenaValue::Unknown { type_code, raw } => {
+                Value::Unknown { type_code: *type_code, raw: raw.to_vec() }
+            }
+        }
+    }
+}
+
+impl ArenaField<'_> {
+    /// Копия поля вне арены
+    pub fn to_owned(&self) -> Field {
+        Field { key: self.key.to_owned(), value: self.value.to_owned() }
+    }
+}
+
+/// Декодирование одного поля в арену `bump`
+pub fn decode_field_in<'b>(data: &[u8], bump: &'b Bump) -> Result<ArenaField<'b>, DecodeError> {
+    decode_field_in_with(data, bump, &UNLIMITED)
+}
+
+/// То же, что [`decode_field_in`], с ограничениями и профилем
+pub fn decode_field_in_with<'b>(
+    data: &[u8],
+    bump: &'b Bump,
+    opts: &DecodeOptions,
+) -> Result<ArenaField<'b>, DecodeError> {
+    Decoder::new(Reader::new(data), opts).read_tree(&InArena { bump })
+}
+
+/// Декодирование сообщения в арену `bump`
+pub fn decode_message_in<'b>(
+    data: &[u8],
+    bump: &'b Bump,
+    opts: &DecodeOptions,
+) -> Result<BumpVec<'b, ArenaField<'b>>, DecodeError> {
+    let tree = InArena { bump };
+    let mut dec = Decoder::new(Reader::new(data), opts);
+    let mut fields = BumpVec::new_in(bump);
+    while dec.input.pos() < data.len() {
+        fields.push(dec.read_tree(&tree)?);
+    }
+    Ok(fields)
+}
+
+impl<E> Seq<E> for BumpVec<'_, E> {
+    fn push(&mut self, e: E) {
+        BumpVec::push(self, e)
+    }
+}
+
+/// Сборка `ArenaField` в арене
+struct InArena<'b> {
+    bump: &'b Bump,
+}
+
+impl<'a, 'b> Tree<'a> for InArena<'b> {
+    type Str = &'b str;
+    type Value = ArenaValue<'b>;
+    type Field = ArenaField<'b>;
+    type Fields = BumpVec<'b, ArenaField<'b>>;
+    type Items = BumpVec<'b, ArenaValue<'b>>;
+    type Entries = BumpVec<'b, (ArenaValue<'b>, ArenaValue<'b>)>;
+
+    fn str(&self, bytes: Cow<'a, [u8]>, offset: usize) -> Result<&'b str, DecodeError> {
+        let s = utf8::str(&bytes).ok_or(DecodeError::InvalidUtf8 { offset })?;
+        Ok(self.bump.alloc_str(s))
+    }
+
+    fn string(&self, s: &'b str) -> ArenaValue<'b> {
+        ArenaValue::String(s)
+    }
+
+    fn bytes(&self, bytes: Cow<'a, [u8]>) -> ArenaValue<'b> {
+        ArenaValue::Bytes(self.bump.alloc_slice_copy(&bytes))
+    }
+
+    fn unknown(&self, type_code: u8, raw: Cow<'a, [u8]>) -> ArenaValue<'b> {
+        ArenaValue::Unknown { type_code, raw: self.bump.alloc_slice_copy(&raw) }
+    }
+
+    fn scalar(&self, value: Value) -> ArenaValue<'b> {
+        match value {
+            Value::Int32(i) => ArenaValue::Int32(i),
+            Value::Float32(f) => ArenaValue::Float32(f),
+            Value::Bool(b) => ArenaValue::Bool(b),
+            Value::Int64(i) => ArenaValue::Int64(i),
+            Value::UInt64(u) => ArenaValue::UInt64(u),
+            Value::Float64(f) => ArenaValue::Float64(f),
+            Value::Null => ArenaValue::Null,
+            Value::Timestamp(ts) => ArenaValue::Timestamp(ts),
+            Value::Uuid(u) => ArenaValue::Uuid(u),
+            Value::Decimal(d) => ArenaValue::Decimal(d),
+            Value::Int8(i) => ArenaValue::Int8(i),
+            Value::Int16(i) => ArenaValue::Int16(i),
+            Value::UInt8(u) => ArenaValue::UInt8(u),
+            Value::UInt16(u) => ArenaValue::UInt16(u),
+            Value::UInt32(u) => ArenaValue::UInt32(u),
+            Value::String(_)
+            | Value::Bytes(_)
+            | Value::Unknown { .. }
+            | Value::Message(_)
+            | Value::List(_)
+            | Value::Map(_)
+            | Value::Enum { .. } => unreachable!("декодер собирает их сам"),
+        }
+    }
+
+    fn fields(&self) -> Self::Fields {
+        BumpVec::new_in(self.bump)
+    }
+
+    fn items(&self) -> Self::Items {
+        BumpVec::new_in(self.bump)
+    }
+
+    fn entries(&self) -> Self::Entries {
+        BumpVec::new_in(self.bump)
+    }
+
+    fn message(&self, fields: BumpVec<'b, ArenaField<'b>>) -> ArenaValue<'b> {
+        ArenaValue::Message(fields)
+    }
+
+    fn list(&self, items: BumpVec<'b, ArenaValue<'b>>) -> ArenaValue<'b> {
+        ArenaValue::List(items)
+    }
+
+    fn map(&self, entries: BumpVec<'b, (ArenaValue<'b>, ArenaValue<'b>)>) -> ArenaValue<'b> {
+        ArenaValue::Map(entries)
+    }
+
+    fn enumeration(
+        &self,
+        variant: u32,
+        name: Option<&'b str>,
+        payload: Option<ArenaValue<'b>>,
+    ) -> ArenaValue<'b> {
+        let payload = payload.map(|p| &*self.bump.alloc(p));
+        ArenaValue::Enum { variant, name, payload }
+    }
+
+    fn field(&self, key: &'b str, value: ArenaValue<'b>) -> ArenaField<'b> {
+        ArenaField { key, value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_message, encode_message};
+
+    #[test]
+    fn decoded_into_arena() {
+        let fields = vec![
+            Field { key: "s".into(), value: Value::String("arena".into()) },
+            Field {
+                key: "e".into(),
+                value: Value::Enum {
+                    variant: 1,
+                    name: Some("Some".into()),
+                    payload: Some(Box::new(Value::List(vec![Value::Bytes(vec![1, 2])]))),
+                },
+            },
+        ];
+        let enc = encode_message(&fields).unwrap();
+        let mut bump = Bump::new();
+        {
+            let decoded = decode_message_in(&enc, &bump, &DecodeOptions::default()).unwrap();
+            let owned: Vec<Field> = decoded.iter().map(ArenaField::to_owned).collect();
+            assert_eq!(owned, decode_message(&enc).unwrap());
+            let ArenaValue::String(s) = decoded[0].value else { panic!("ожидалась строка") };
+            assert!(!enc.as_ptr_range().contains(&s.as_ptr()));
+        }
+        assert!(bump.allocated_bytes() > 0);
+        bump.reset();
+
+        let f = decode_field_in(&enc, &bump).unwrap();
+        assert_eq!(f.to_owned(), fields[0]);
+    }
+}
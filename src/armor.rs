@@ -0,0 +1,173 @@
+//! Текстовая «броня»: конверт в base64 между строками-границами
+//!
+//! ```text
+//! -----BEGIN CCDC MESSAGE-----
+//! Q0NEQwEAAAAAABkC...
+//! =y/Q5Jg==
+//! -----END CCDC MESSAGE-----
+//! ```
+//!
+//! Внутри — конверт (`encode_enveloped_with`) в base64 строками по 64
+//! символа и строка `=` с CRC-32 конверта (u32 BE) в base64. При чтении
+//! пробелы и переводы строк между границами пропускаются, поэтому броня,
+//! склеенная в одну строку для переменной окружения, тоже читается.
+
+use std::fmt;
+
+use crate::decode::UNLIMITED;
+use crate::{
+    base64, crc32, decode_enveloped_with, encode_enveloped_with, DecodeError, DecodeOptions,
+    EncodeError, EncodeOptions, Field,
+};
+
+/// Строка перед данными
+pub const ARMOR_HEADER: &str = "-----BEGIN CCDC MESSAGE-----";
+
+/// Строка после данных
+pub const ARMOR_FOOTER: &str = "-----END CCDC MESSAGE-----";
+
+/// Длина строки base64
+const LINE_LEN: usize = 64;
+
+/// Длина строки с контрольной суммой: `=` и восемь символов base64
+const CRC_LEN: usize = 9;
+
+/// Броня не снимается
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArmorError {
+    /// Нет строки `ARMOR_HEADER` или `ARMOR_FOOTER` после неё
+    MissingBoundary,
+    /// Данные или контрольная сумма — не base64, либо нет строки `=`
+    InvalidBase64,
+    /// CRC-32 не совпала с данными
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// Конверт внутри брони не декодируется
+    Decode(DecodeError),
+}
+
+impl fmt::Display for ArmorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArmorError::MissingBoundary => f.write_str("нет границ брони"),
+            ArmorError::InvalidBase64 => f.write_str("данные брони — не base64"),
+            ArmorError::ChecksumMismatch { expected, actual } => {
+                write!(f, "CRC-32 брони {actual:#010x}, ожидалась {expected:#010x}")
+            }
+            ArmorError::Decode(e) => write!(f, "конверт в броне: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ArmorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ArmorError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for ArmorError {
+    fn from(e: DecodeError) -> Self {
+        ArmorError::Decode(e)
+    }
+}
+
+/// Сообщение в броне
+///
+/// ```
+/// use custom_codec::{decode_armored, encode_armored, Field, Value};
+///
+/// let fields = vec![Field { key: "id".into(), value: Value::Int32(7) }];
+/// let text = encode_armored(&fields).unwrap();
+/// assert!(text.starts_with("-----BEGIN CCDC MESSAGE-----\n"));
+/// assert_eq!(decode_armored(&text).unwrap(), fields);
+/// ```
+pub fn encode_armored(fields: &[Field]) -> Result<String, EncodeError> {
+    encode_armored_with(fields, &EncodeOptions::default())
+}
+
+/// Сообщение в броне с настройками конверта
+pub fn encode_armored_with(fields: &[Field], opts: &EncodeOptions) -> Result<String, EncodeError> {
+    let data = encode_enveloped_with(fields, opts)?;
+    let body = base64::encode(&data);
+    let crc = base64::encode(&crc32::checksum(&data).to_be_bytes());
+    let mut out = String::with_capacity(body.len() * 65 / 64 + 80);
+    out.push_str(ARMOR_HEADER);
+    out.push('\n');
+    // base64 — ASCII, поэтому деление по байтам не разрывает символы
+    for line in body.as_bytes().chunks(LINE_LEN) {
+        out.push_str(std::str::from_utf8(line).unwrap());
+        out.push('\n');
+    }
+    out.push('=');
+    out.push_str(&crc);
+    out.push('\n');
+    out.push_str(ARMOR_FOOTER);
+    out.push('\n');
+    Ok(out)
+}
+
+/// Сообщение из брони; текст вокруг границ пропускается
+pub fn decode_armored(text: &str) -> Result<Vec<Field>, ArmorError> {
+    decode_armored_with(text, &UNLIMITED)
+}
+
+/// Сообщение из брони с ограничениями; профиль берётся из конверта
+pub fn decode_armored_with(text: &str, opts: &DecodeOptions) -> Result<Vec<Field>, ArmorError> {
+    let start = text.find(ARMOR_HEADER).ok_or(ArmorError::MissingBoundary)? + ARMOR_HEADER.len();
+    let len = text[start..].find(ARMOR_FOOTER).ok_or(ArmorError::MissingBoundary)?;
+    let inner: String = text[start..start + len].split_whitespace().collect();
+    // четыре байта CRC-32 — ровно восемь символов base64 после `=`
+    let split = inner.len().checked_sub(CRC_LEN).ok_or(ArmorError::InvalidBase64)?;
+    let (body, crc) = inner.split_at(split);
+    let crc = crc.strip_prefix('=').and_then(base64::decode).ok_or(ArmorError::InvalidBase64)?;
+    let expected = u32::from_be_bytes(crc.try_into().map_err(|_| ArmorError::InvalidBase64)?);
+    let data = base64::decode(body).ok_or(ArmorError::InvalidBase64)?;
+    let actual = crc32::checksum(&data);
+    if expected != actual {
+        return Err(ArmorError::ChecksumMismatch { expected, actual });
+    }
+    Ok(decode_enveloped_with(&data, opts)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    fn sample() -> Vec<Field> {
+        vec![
+            Field { key: "id".into(), value: Value::Int64(-5) },
+            Field { key: "blob".into(), value: Value::Bytes(vec![0xab; 100]) },
+        ]
+    }
+
+    #[test]
+    fn armored_roundtrip() {
+        let text = encode_armored(&sample()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], ARMOR_HEADER);
+        assert_eq!(lines.last(), Some(&ARMOR_FOOTER));
+        assert!(lines.iter().all(|l| l.len() <= LINE_LEN));
+        assert_eq!(decode_armored(&text).unwrap(), sample());
+
+        // в одну строку и с текстом вокруг, как в письме
+        let one_line = format!("Привет!\n{}\nСпасибо", lines.join(" "));
+        assert_eq!(decode_armored(&one_line).unwrap(), sample());
+    }
+
+    #[test]
+    fn armored_errors() {
+        let text = encode_armored(&sample()).unwrap();
+        assert_eq!(decode_armored(&text[1..]), Err(ArmorError::MissingBoundary));
+
+        // "Q0NE" — начало сигнатуры "CCDC" в base64
+        let corrupted = text.replacen("Q0NE", "Q0NF", 1);
+        assert!(matches!(decode_armored(&corrupted), Err(ArmorError::ChecksumMismatch { .. })));
+
+        let no_crc: Vec<&str> = text.lines().filter(|l| !l.starts_with('=')).collect();
+        let no_crc = no_crc.join("\n");
+        assert_eq!(decode_armored(&no_crc), Err(ArmorError::InvalidBase64));
+    }
+}
@@ -0,0 +1,315 @@
+//! Пакеты однородных сообщений в `arrow_array::RecordBatch` и обратно
+//!
+//! Ключи становятся столбцами в порядке первого появления, строка пакета —
+//! одно сообщение. Тип столбца определяется по первому непустому значению,
+//! остальные значения столбца должны быть того же типа; отсутствующее поле
+//! и `Value::Null` становятся null. Соответствие типов:
+//!
+//! - целые и числа с плавающей точкой — одноимённые типы Arrow;
+//! - `Bool` — `Boolean`, `String` — `Utf8`, `Bytes` — `Binary`;
+//! - `Timestamp` — `Timestamp(Nanosecond, "UTC")`;
+//! - `Decimal` — `Decimal128(38, scale)`, масштаб у всего столбца один;
+//! - `Uuid` — `FixedSizeBinary(16)`;
+//! - столбец только из null — `Null`.
+//!
+//! Вложенные сообщения, списки, отображения и перечисления не
+//! поддерживаются. При обратном преобразовании null не даёт поля, а
+//! `Utf8`/`LargeUtf8`, `Binary`/`LargeBinary` и метки времени в любых
+//! единицах читаются одинаково.
+
+use std::fmt;
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::*;
+use arrow_array::{
+    Array, ArrayRef, BinaryArray, BooleanArray, Decimal128Array, FixedSizeBinaryArray,
+    Float32Array, Float64Array, Int16Array, Int32Array, Int64Array, Int8Array, NullArray,
+    RecordBatch, StringArray, TimestampNanosecondArray, UInt16Array, UInt32Array, UInt64Array,
+    UInt8Array,
+};
+use arrow_schema::{DataType, Field as ArrowField, Schema, TimeUnit};
+
+use crate::decimal::MAX_SCALE;
+use crate::typed::type_name;
+use crate::{Decimal, Field, Timestamp, Value};
+
+/// Сообщения не переводятся в `RecordBatch` или обратно
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordBatchError {
+    /// В столбце `key` значения разных типов
+    MixedTypes { key: String, first: &'static str, found: &'static str },
+    /// В столбце `key` десятичные числа с разным масштабом
+    MixedScale { key: String },
+    /// Значение или тип Arrow без соответствия; `what` — что именно
+    Unsupported { key: String, what: String },
+    /// Arrow отверг собранный пакет
+    Arrow(String),
+}
+
+impl fmt::Display for RecordBatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordBatchError::MixedTypes { key, first, found } => {
+                write!(f, "столбец {key:?}: значения {first} и {found}")
+            }
+            RecordBatchError::MixedScale { key } => {
+                write!(f, "столбец {key:?}: десятичные числа с разным масштабом")
+            }
+            RecordBatchError::Unsupported { key, what } => {
+                write!(f, "столбец {key:?}: не поддерживается {what}")
+            }
+            RecordBatchError::Arrow(msg) => write!(f, "arrow: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for RecordBatchError {}
+
+/// Непустые значения столбца: `None` — null
+struct Column<'a> {
+    key: &'a str,
+    values: Vec<Option<&'a Value>>,
+}
+
+/// Пакет из сообщений: строка на сообщение, столбец на ключ
+pub fn to_record_batch(messages: &[Vec<Field>]) -> Result<RecordBatch, RecordBatchError> {
+    let mut columns: Vec<Column<'_>> = Vec::new();
+    for (row, fields) in messages.iter().enumerate() {
+        for f in fields {
+            let idx = match columns.iter().position(|c| c.key == f.key) {
+                Some(idx) => idx,
+                None => {
+                    columns.push(Column { key: &f.key, values: vec![None; messages.len()] });
+                    columns.len() - 1
+                }
+            };
+            if f.value != Value::Null {
+                columns[idx].values[row] = Some(&f.value);
+            }
+        }
+    }
+
+    let mut schema = Vec::with_capacity(columns.len());
+    let mut arrays = Vec::with_capacity(columns.len());
+    for column in &columns {
+        let array = column_array(column, messages.len())?;
+        schema.push(ArrowField::new(column.key, array.data_type().clone(), true));
+        arrays.push(array);
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(schema)), arrays)
+        .map_err(|e| RecordBatchError::Arrow(e.to_string()))
+}
+
+fn column_array(column: &Column<'_>, rows: usize) -> Result<ArrayRef, RecordBatchError> {
+    let key = || column.key.to_owned();
+    let Some(first) = column.values.iter().flatten().next() else {
+        return Ok(Arc::new(NullArray::new(rows)));
+    };
+    let first_type = type_name(first);
+    for v in column.values.iter().flatten() {
+        if type_name(v) != first_type {
+            return Err(RecordBatchError::MixedTypes {
+                key: key(),
+                first: first_type,
+                found: type_name(v),
+            });
+        }
+    }
+
+    macro_rules! collect {
+        ($variant:ident, $x:ident => $map:expr) => {
+            column
+                .values
+                .iter()
+                .map(|v| match v {
+                    Some(Value::$variant($x)) => Some($map),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+    }
+
+    Ok(match first {
+        Value::Int8(_) => Arc::new(Int8Array::from(collect!(Int8, x => *x))),
+        Value::Int16(_) => Arc::new(Int16Array::from(collect!(Int16, x => *x))),
+        Value::Int32(_) => Arc::new(Int32Array::from(collect!(Int32, x => *x))),
+        Value::Int64(_) => Arc::new(Int64Array::from(collect!(Int64, x => *x))),
+        Value::UInt8(_) => Arc::new(UInt8Array::from(collect!(UInt8, x => *x))),
+        Value::UInt16(_) => Arc::new(UInt16Array::from(collect!(UInt16, x => *x))),
+        Value::UInt32(_) => Arc::new(UInt32Array::from(collect!(UInt32, x => *x))),
+        Value::UInt64(_) => Arc::new(UInt64Array::from(collect!(UInt64, x => *x))),
+        Value::Float32(_) => Arc::new(Float32Array::from(collect!(Float32, x => *x))),
+        Value::Float64(_) => Arc::new(Float64Array::from(collect!(Float64, x => *x))),
+        Value::Bool(_) => Arc::new(BooleanArray::from(collect!(Bool, x => *x))),
+        Value::String(_) => Arc::new(StringArray::from(collect!(String, x => x.as_str()))),
+        Value::Bytes(_) => {
+            Arc::new(BinaryArray::from_opt_vec(collect!(Bytes, x => x.as_slice())))
+        }
+        Value::Timestamp(_) => {
+            let mut nanos = Vec::with_capacity(rows);
+            for ts in collect!(Timestamp, x => x.unix_nanos()) {
+                let ts = ts.map(i64::try_from).transpose().map_err(|_| {
+                    RecordBatchError::Unsupported { key: key(), what: "время вне i64 нс".into() }
+                })?;
+                nanos.push(ts);
+            }
+            Arc::new(TimestampNanosecondArray::from(nanos).with_timezone("UTC"))
+        }
+        Value::Decimal(d) => {
+            let scale = d.scale;
+            let other_scale = |v: &&Value| matches!(v, Value::Decimal(x) if x.scale != scale);
+            if column.values.iter().flatten().any(other_scale) {
+                return Err(RecordBatchError::MixedScale { key: key() });
+            }
+            let array = Decimal128Array::from(collect!(Decimal, x => x.mantissa))
+                .with_precision_and_scale(38, scale as i8)
+                .map_err(|e| RecordBatchError::Arrow(e.to_string()))?;
+            Arc::new(array)
+        }
+        Value::Uuid(_) => {
+            let uuids = collect!(Uuid, x => *x);
+            let array = FixedSizeBinaryArray::try_from_sparse_iter_with_size(uuids.into_iter(), 16)
+                .map_err(|e| RecordBatchError::Arrow(e.to_string()))?;
+            Arc::new(array)
+        }
+        Value::Null => unreachable!("null не попадает в столбец"),
+        Value::Message(_) | Value::List(_) | Value::Map(_) | Value::Enum { .. } => {
+            let what = format!("вложенное значение {first_type}");
+            return Err(RecordBatchError::Unsupported { key: key(), what });
+        }
+        Value::Unknown { .. } => {
+            let what = format!("значение {first_type}");
+            return Err(RecordBatchError::Unsupported { key: key(), what });
+        }
+    })
+}
+
+/// Сообщения из пакета; null не даёт поля
+pub fn from_record_batch(batch: &RecordBatch) -> Result<Vec<Vec<Field>>, RecordBatchError> {
+    let mut messages = vec![Vec::new(); batch.num_rows()];
+    for (field, array) in batch.schema().fields().iter().zip(batch.columns()) {
+        let key = field.name();
+        let values = column_values(key, array)?;
+        for (row, value) in values.into_iter().enumerate() {
+            if let Some(value) = value.filter(|_| array.is_valid(row)) {
+                messages[row].push(Field { key: key.clone(), value });
+            }
+        }
+    }
+    Ok(messages)
+}
+
+fn column_values(key: &str, array: &ArrayRef) -> Result<Vec<Option<Value>>, RecordBatchError> {
+    let len = array.len();
+    macro_rules! each {
+        ($array:expr, $x:ident => $map:expr) => {{
+            let array = $array;
+            (0..len).map(|i| array.is_valid(i).then(|| array.value(i)).map(|$x| $map)).collect()
+        }};
+    }
+    let unsupported = |what: String| RecordBatchError::Unsupported { key: key.to_owned(), what };
+    Ok(match array.data_type() {
+        DataType::Null => vec![None; len],
+        DataType::Int8 => each!(array.as_primitive::<Int8Type>(), x => Value::Int8(x)),
+        DataType::Int16 => each!(array.as_primitive::<Int16Type>(), x => Value::Int16(x)),
+        DataType::Int32 => each!(array.as_primitive::<Int32Type>(), x => Value::Int32(x)),
+        DataType::Int64 => each!(array.as_primitive::<Int64Type>(), x => Value::Int64(x)),
+        DataType::UInt8 => each!(array.as_primitive::<UInt8Type>(), x => Value::UInt8(x)),
+        DataType::UInt16 => each!(array.as_primitive::<UInt16Type>(), x => Value::UInt16(x)),
+        DataType::UInt32 => each!(array.as_primitive::<UInt32Type>(), x => Value::UInt32(x)),
+        DataType::UInt64 => each!(array.as_primitive::<UInt64Type>(), x => Value::UInt64(x)),
+        DataType::Float32 => each!(array.as_primitive::<Float32Type>(), x => Value::Float32(x)),
+        DataType::Float64 => each!(array.as_primitive::<Float64Type>(), x => Value::Float64(x)),
+        DataType::Boolean => each!(array.as_boolean(), x => Value::Bool(x)),
+        DataType::Utf8 => each!(array.as_string::<i32>(), x => Value::String(x.to_owned())),
+        DataType::LargeUtf8 => each!(array.as_string::<i64>(), x => Value::String(x.to_owned())),
+        DataType::Binary => each!(array.as_binary::<i32>(), x => Value::Bytes(x.to_vec())),
+        DataType::LargeBinary => each!(array.as_binary::<i64>(), x => Value::Bytes(x.to_vec())),
+        DataType::FixedSizeBinary(16) => {
+            each!(array.as_fixed_size_binary(), x => Value::Uuid(x.try_into().unwrap()))
+        }
+        DataType::Decimal128(_, scale) if (0..=MAX_SCALE as i8).contains(scale) => {
+            let scale = *scale as u8;
+            each!(array.as_primitive::<Decimal128Type>(), mantissa => {
+                Value::Decimal(Decimal { mantissa, scale })
+            })
+        }
+        DataType::Timestamp(unit, _) => {
+            let nanos_per_unit: i128 = match unit {
+                TimeUnit::Second => 1_000_000_000,
+                TimeUnit::Millisecond => 1_000_000,
+                TimeUnit::Microsecond => 1_000,
+                TimeUnit::Nanosecond => 1,
+            };
+            let raw: Vec<Option<i64>> = match unit {
+                TimeUnit::Second => each!(array.as_primitive::<TimestampSecondType>(), x => x),
+                TimeUnit::Millisecond => {
+                    each!(array.as_primitive::<TimestampMillisecondType>(), x => x)
+                }
+                TimeUnit::Microsecond => {
+                    each!(array.as_primitive::<TimestampMicrosecondType>(), x => x)
+                }
+                TimeUnit::Nanosecond => {
+                    each!(array.as_primitive::<TimestampNanosecondType>(), x => x)
+                }
+            };
+            raw.into_iter()
+                .map(|t| t.map(|t| Timestamp::from_unix_nanos(t as i128 * nanos_per_unit)))
+                .map(|t| t.map(|t| t.map(Value::Timestamp).expect("i64 единиц помещается")))
+                .collect()
+        }
+        other => return Err(unsupported(format!("тип Arrow {other}"))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn record_batch_roundtrip() {
+        let messages = vec![
+            vec![
+                field("id", Value::Int64(1)),
+                field("name", Value::String("a".into())),
+                field("ok", Value::Bool(true)),
+                field("at", Value::Timestamp(Timestamp { secs: 5, nanos: 7 })),
+            ],
+            vec![
+                field("id", Value::Int64(2)),
+                field("blob", Value::Bytes(vec![1])),
+                field("sum", Value::Decimal(Decimal { mantissa: -125, scale: 2 })),
+                field("uuid", Value::Uuid([3; 16])),
+                field("ratio", Value::Float32(0.5)),
+            ],
+        ];
+        let batch = to_record_batch(&messages).unwrap();
+        assert_eq!((batch.num_rows(), batch.num_columns()), (2, 8));
+        let schema = batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["id", "name", "ok", "at", "blob", "sum", "uuid", "ratio"]);
+        assert_eq!(schema.field(5).data_type(), &DataType::Decimal128(38, 2));
+        assert_eq!(from_record_batch(&batch).unwrap(), messages);
+    }
+
+    #[test]
+    fn record_batch_errors() {
+        let mixed = vec![vec![field("x", Value::Int32(1))], vec![field("x", Value::Int64(1))]];
+        let err = RecordBatchError::MixedTypes { key: "x".into(), first: "Int32", found: "Int64" };
+        assert_eq!(to_record_batch(&mixed).unwrap_err(), err);
+
+        let nested = vec![vec![field("m", Value::Message(vec![]))]];
+        assert!(matches!(to_record_batch(&nested), Err(RecordBatchError::Unsupported { .. })));
+
+        // столбец только из null
+        let nulls = vec![vec![field("n", Value::Null)], vec![]];
+        let batch = to_record_batch(&nulls).unwrap();
+        assert_eq!(batch.schema().field(0).data_type(), &DataType::Null);
+        assert_eq!(from_record_batch(&batch).unwrap(), vec![vec![], vec![]]);
+    }
+}
@@ -0,0 +1,144 @@
+//! Разбор аргументов команды: позиционные аргументы и флаги
+//!
+//! Флаги пишутся как `--name`, `--name value` или `--name=value`; после
+//! `--` все аргументы позиционные. Команда забирает свои аргументы, а
+//! `Args::finish` отвергает оставшиеся.
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use crate::CliError;
+
+/// Аргументы одной команды
+#[derive(Debug)]
+pub(crate) struct Args {
+    positional: VecDeque<String>,
+    flags: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    /// `with_value` — флаги, за которыми следует значение
+    pub(crate) fn parse(
+        args: impl IntoIterator<Item = String>,
+        with_value: &[&str],
+    ) -> Result<Args, CliError> {
+        let mut args = args.into_iter();
+        let mut positional = VecDeque::new();
+        let mut flags = Vec::new();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                positional.extend(args);
+                break;
+            }
+            // `-` — stdin, а не флаг
+            if arg.len() < 2 || !arg.starts_with('-') {
+                positional.push_back(arg);
+                continue;
+            }
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None => (arg, None),
+            };
+            let value = match (with_value.contains(&name.as_str()), value) {
+                (true, None) => match args.next() {
+                    Some(value) => Some(value),
+                    None => return Err(CliError::Usage(format!("у флага {name} нет значения"))),
+                },
+                (false, Some(_)) => {
+                    return Err(CliError::Usage(format!("флаг {name} не принимает значения")))
+                }
+                (_, value) => value,
+            };
+            flags.push((name, value));
+        }
+        Ok(Args { positional, flags })
+    }
+
+    /// Был ли флаг без значения
+    pub(crate) fn flag(&mut self, name: &str) -> bool {
+        let before = self.flags.len();
+        self.flags.retain(|(n, _)| n != name);
+        self.flags.len() != before
+    }
+
+    /// Значение флага; при повторах — последнее
+    pub(crate) fn value(&mut self, name: &str) -> Option<String> {
+        self.values(name).pop()
+    }
+
+    /// Значение флага, разобранное как `T`
+    pub(crate) fn parsed<T: FromStr>(&mut self, name: &str) -> Result<Option<T>, CliError> {
+        match self.value(name) {
+            Some(value) => match value.parse() {
+                Ok(v) => Ok(Some(v)),
+                Err(_) => Err(CliError::Usage(format!("неверное значение {name}: `{value}`"))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Значения повторяемого флага по порядку
+    pub(crate) fn values(&mut self, name: &str) -> Vec<String> {
+        let mut values = Vec::new();
+        self.flags.retain_mut(|(n, v)| match n == name {
+            true => {
+                values.extend(v.take());
+                false
+            }
+            false => true,
+        });
+        values
+    }
+
+    /// Следующий позиционный аргумент; `what` называет его в ошибке
+    pub(crate) fn positional(&mut self, what: &str) -> Result<String, CliError> {
+        self.positional.pop_front().ok_or_else(|| CliError::Usage(format!("не указан {what}")))
+    }
+
+    /// Проверка, что команда разобрала все аргументы
+    pub(crate) fn finish(self) -> Result<(), CliError> {
+        if let Some((name, _)) = self.flags.first() {
+            return Err(CliError::Usage(format!("неизвестный флаг {name}")));
+        }
+        match self.positional.front() {
+            Some(arg) => Err(CliError::Usage(format!("лишний аргумент `{arg}`"))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str], with_value: &[&str]) -> Result<Args, CliError> {
+        Args::parse(args.iter().map(|s| s.to_string()), with_value)
+    }
+
+    #[test]
+    fn flags_and_positional() {
+        let args = ["a", "-o", "out", "--json", "--seed=4", "-", "--seed", "5", "--", "--x"];
+        let mut args = parse(&args, &["-o", "--seed"]).unwrap();
+        assert!(args.flag("--json"));
+        assert!(!args.flag("--json"));
+        assert_eq!(args.value("-o").as_deref(), Some("out"));
+        assert_eq!(args.values("--seed"), ["4", "5"]);
+        assert_eq!(args.positional("файл").unwrap(), "a");
+        assert_eq!(args.positional("файл").unwrap(), "-");
+        assert_eq!(args.positional("файл").unwrap(), "--x");
+        assert!(args.positional("файл").is_err());
+        assert!(args.finish().is_ok());
+    }
+
+    #[test]
+    fn rejects_leftovers() {
+        assert!(matches!(parse(&["-o"], &["-o"]), Err(CliError::Usage(_))));
+        assert!(matches!(parse(&["--json=1"], &[]), Err(CliError::Usage(_))));
+        let mut args = parse(&["--count", "x", "--seed", "7"], &["--count", "--seed"]).unwrap();
+        assert!(args.parsed::<u64>("--count").is_err());
+        assert_eq!(args.parsed::<u64>("--seed").unwrap(), Some(7));
+        let err = parse(&["--verbose"], &[]).unwrap().finish().unwrap_err();
+        assert_eq!(err.to_string(), "неизвестный флаг --verbose");
+        assert!(parse(&["a", "b"], &[]).unwrap().finish().is_err());
+    }
+}
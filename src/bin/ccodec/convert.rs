@@ -0,0 +1,117 @@
+//! `ccodec encode` и `ccodec decode`: сообщение в JSON и обратно
+//!
+//! JSON-запись — та же, что у `message_to_json`: объект, в котором каждое
+//! поле — элемент. При чтении целые становятся `Int64`, а строки —
+//! `String`, кроме полей из `--bytes KEY`; без `--json` `decode` печатает
+//! текстовую запись, которая сохраняет точные типы.
+
+use std::io::Write;
+
+use custom_codec::{
+    encode_enveloped_with, encode_message_with, message_from_json, message_to_json, to_text,
+    EncodeOptions, JsonOptions,
+};
+
+use crate::args::Args;
+use crate::CliError;
+
+pub(crate) fn encode(mut args: Args, out: &mut impl Write) -> Result<(), CliError> {
+    let opts = EncodeOptions { profile: crate::profile(&mut args), ..EncodeOptions::default() };
+    let envelope = args.flag("--envelope");
+    let json_opts =
+        JsonOptions { float32: args.flag("--float32"), bytes_keys: args.values("--bytes") };
+    let output = args.value("-o");
+    let path = args.positional("файл JSON")?;
+    args.finish()?;
+
+    let input = crate::read_input(&path)?;
+    let json = serde_json::from_slice(&input)
+        .map_err(|e| CliError::Parse { path: path.clone(), message: e.to_string() })?;
+    let fields = message_from_json(&json, &json_opts).map_err(|e| CliError::json(&path, e))?;
+    let data = match envelope {
+        true => encode_enveloped_with(&fields, &opts)?,
+        false => encode_message_with(&fields, &opts)?,
+    };
+    crate::write_output(output.as_deref(), &data, out)
+}
+
+pub(crate) fn decode(mut args: Args, out: &mut impl Write) -> Result<(), CliError> {
+    let opts = crate::decode_options(&mut args);
+    let json = args.flag("--json");
+    let path = args.positional("файл")?;
+    args.finish()?;
+
+    let fields = crate::decode_input(&crate::read_input(&path)?, &opts)?;
+    match json {
+        true => {
+            serde_json::to_writer_pretty(&mut *out, &message_to_json(&fields))
+                .map_err(std::io::Error::from)?;
+            writeln!(out)?;
+        }
+        false => {
+            for f in &fields {
+                out.write_all(to_text(f).as_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use custom_codec::{decode_enveloped, Field, Value};
+
+    fn temp(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("ccodec-convert-{name}"));
+        path.to_str().unwrap().to_owned()
+    }
+
+    fn args(args: &[&str]) -> Args {
+        Args::parse(args.iter().map(|s| s.to_string()), &["-o", "--bytes"]).unwrap()
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let (input, bin) = (temp("in.json"), temp("out.bin"));
+        std::fs::write(&input, r#"{"user": {"id": 42, "photo": "AQI="}, "ok": true}"#).unwrap();
+        encode(args(&["--envelope", "--bytes", "photo", &input, "-o", &bin]), &mut Vec::new())
+            .unwrap();
+        let fields = decode_enveloped(&std::fs::read(&bin).unwrap()).unwrap();
+        let user = vec![
+            Field { key: "id".into(), value: Value::Int64(42) },
+            Field { key: "photo".into(), value: Value::Bytes(vec![1, 2]) },
+        ];
+        // поля идут в порядке ключей JSON
+        assert_eq!(fields, [
+            Field { key: "user".into(), value: Value::Message(user) },
+            Field { key: "ok".into(), value: Value::Bool(true) },
+        ]);
+
+        let mut out = Vec::new();
+        decode(args(&["--json", &bin]), &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json.to_string(), r#"{"user":{"id":42,"photo":"AQI="},"ok":true}"#);
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(bin).unwrap();
+    }
+
+    #[test]
+    fn decode_text_and_errors() {
+        let (input, bin) = (temp("text.json"), temp("text.bin"));
+        std::fs::write(&input, r#"{"name": "Rust"}"#).unwrap();
+        encode(args(&["--compact", &input, "-o", &bin]), &mut Vec::new()).unwrap();
+        let mut out = Vec::new();
+        decode(args(&["--compact", &bin]), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "name: \"Rust\"\n");
+
+        std::fs::write(&input, "[1, 2]").unwrap();
+        let err = encode(args(&[&input]), &mut Vec::new()).unwrap_err();
+        assert_eq!(err.to_string(), format!("{input}: сообщение должно быть объектом"));
+        std::fs::write(&input, "{").unwrap();
+        let err = encode(args(&[&input]), &mut Vec::new()).unwrap_err();
+        assert!(matches!(err, CliError::Parse { .. }));
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(bin).unwrap();
+    }
+}
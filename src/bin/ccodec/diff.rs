@@ -0,0 +1,136 @@
+//! `ccodec diff`: различия двух сообщений по полям
+//!
+//! ```text
+//! - user.email: "a@example.com"
+//! + user.phone: "+47 000"
+//! ~ user.age: 42 -> 43
+//! ^ user: name, age, phone
+//! ```
+//!
+//! Сравнение — функция `diff` библиотеки: поля сопоставляются по ключу,
+//! вложенные сообщения и списки сравниваются поэлементно; строка `^`
+//! даёт новый порядок полей сообщения, `.` — корня. Как у
+//! `diff(1)`, код выхода 1 означает, что сообщения различаются.
+
+use std::io::Write;
+use std::process::ExitCode;
+
+use custom_codec::{diff, value_to_text, Change, FieldPath, Message, PathSegment};
+
+use crate::args::Args;
+use crate::CliError;
+
+pub(crate) fn run(mut args: Args, out: &mut impl Write) -> Result<ExitCode, CliError> {
+    let opts = crate::decode_options(&mut args);
+    let (a, b) = (args.positional("первый файл")?, args.positional("второй файл")?);
+    args.finish()?;
+    let a = Message::from(crate::decode_input(&crate::read_input(&a)?, &opts)?);
+    let b = Message::from(crate::decode_input(&crate::read_input(&b)?, &opts)?);
+
+    let diff = diff(&a, &b);
+    for change in &diff.changes {
+        let path = path_text(change.path());
+        match change {
+            Change::Added { value, .. } => writeln!(out, "+ {path}: {}", value_to_text(value))?,
+            Change::Removed { value, .. } => writeln!(out, "- {path}: {}", value_to_text(value))?,
+            Change::Changed { before, after, .. } => {
+                writeln!(out, "~ {path}: {} -> {}", value_to_text(before), value_to_text(after))?
+            }
+            Change::Reordered { keys, .. } => {
+                let keys: Vec<_> = keys.iter().map(|k| crate::key_text(k)).collect();
+                let path = if path.is_empty() { "." } else { &path };
+                writeln!(out, "^ {path}: {}", keys.join(", "))?
+            }
+        }
+    }
+    Ok(match diff.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::from(1),
+    })
+}
+
+/// Путь с ключами в записи `key_text`; повтор ключа — `[#n]`
+fn path_text(path: &FieldPath) -> String {
+    let mut text = String::new();
+    for segment in &path.0 {
+        match segment {
+            PathSegment::Key { key, nth } => {
+                if !text.is_empty() {
+                    text.push('.');
+                }
+                text += &crate::key_text(key);
+                if *nth > 0 {
+                    text += &format!("[#{nth}]");
+                }
+            }
+            PathSegment::Index(i) => text += &format!("[{i}]"),
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use custom_codec::{Field, Value};
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn nested_changes() {
+        let a = Message::from(vec![
+            field("id", Value::Int32(1)),
+            field("user", Value::Message(vec![
+                field("age", Value::Int32(42)),
+                field("email", Value::String("a@example.com".into())),
+            ])),
+            field("tags", Value::List(vec![Value::Int32(1), Value::Int32(2)])),
+            field("x", Value::Null),
+            field("x", Value::Null),
+        ]);
+        let b = Message::from(vec![
+            field("id", Value::Int32(1)),
+            field("user", Value::Message(vec![
+                field("age", Value::Int32(43)),
+                field("full name", Value::Null),
+            ])),
+            field("tags", Value::List(vec![Value::Int32(1)])),
+            field("x", Value::Null),
+            field("x", Value::Bool(true)),
+        ]);
+        let paths: Vec<_> = diff(&a, &b).changes.iter().map(|c| path_text(c.path())).collect();
+        assert_eq!(paths, ["user.age", "user.email", "user.\"full name\"", "tags[1]", "x[#1]"]);
+    }
+
+    #[test]
+    fn output_and_exit_code() {
+        let dir = std::env::temp_dir();
+        let (a, b) = (dir.join("ccodec-diff-a.bin"), dir.join("ccodec-diff-b.bin"));
+        let msg = |v| custom_codec::encode_message(&[field("n", Value::Int32(v))]).unwrap();
+        std::fs::write(&a, msg(1)).unwrap();
+        std::fs::write(&b, msg(2)).unwrap();
+        let paths = [a.to_str().unwrap().to_owned(), b.to_str().unwrap().to_owned()];
+
+        let mut out = Vec::new();
+        let code = run(Args::parse(paths.clone(), &[]).unwrap(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "~ n: 1i32 -> 2i32\n");
+        assert_eq!(code, ExitCode::from(1));
+
+        let pair = |a, b| [field(a, Value::Null), field(b, Value::Null)];
+        std::fs::write(&a, custom_codec::encode_message(&pair("a", "b")).unwrap()).unwrap();
+        std::fs::write(&b, custom_codec::encode_message(&pair("b", "a")).unwrap()).unwrap();
+        let mut out = Vec::new();
+        let code = run(Args::parse(paths.clone(), &[]).unwrap(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "^ .: b, a\n");
+        assert_eq!(code, ExitCode::from(1));
+
+        let same = [paths[0].clone(), paths[0].clone()];
+        let mut out = Vec::new();
+        assert_eq!(run(Args::parse(same, &[]).unwrap(), &mut out).unwrap(), ExitCode::SUCCESS);
+        assert!(out.is_empty());
+        std::fs::remove_file(a).unwrap();
+        std::fs::remove_file(b).unwrap();
+    }
+}
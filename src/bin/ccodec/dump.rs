@@ -0,0 +1,196 @@
+//! `ccodec dump`: шестнадцатеричный дамп с разметкой формата
+//!
+//! ```text
+//! 00000000  04                                   name: код типа 4 (String)
+//! 00000001  00 00 00 04                            длина ключа 4
+//! 00000005  6e 61 6d 65                            ключ name
+//! 00000009  00 00 00 04                            длина значения 4
+//! 0000000d  52 75 73 74                            значение "Rust"
+//! ```
+//!
+//! Каждая часть элемента — отдельная строка; длинные части продолжаются
+//! строками по `BYTES_PER_LINE` байт без подписи. У конверта размечаются
+//! поля заголовка, идентификатор схемы и контрольная сумма.
+
+use std::io::{self, Write};
+use std::ops::Range;
+
+use custom_codec::tooling::{inspect, type_name, Node};
+use custom_codec::{value_to_text, Value, HEADER_LEN, MAGIC};
+
+use crate::args::Args;
+use crate::inspect::{label, value_name};
+use crate::CliError;
+
+const BYTES_PER_LINE: usize = 12;
+
+/// Диапазон байт, глубина вложенности и подпись
+type Part = (Range<usize>, usize, String);
+
+pub(crate) fn run(mut args: Args, out: &mut impl Write) -> Result<(), CliError> {
+    let opts = crate::decode_options(&mut args);
+    let path = args.positional("файл")?;
+    args.finish()?;
+    let data = crate::read_input(&path)?;
+    let nodes = inspect(&data, &opts)?;
+
+    let mut parts = Vec::new();
+    let envelope = data.starts_with(&MAGIC);
+    if envelope {
+        envelope_header(&data, &mut parts);
+    }
+    node_parts(&nodes, 6, 0, &mut parts);
+    if envelope {
+        // за телом остаётся только CRC-32
+        let body_end = parts.last().map_or(0, |(range, ..)| range.end);
+        if body_end < data.len() {
+            parts.push((body_end..data.len(), 0, "CRC-32".to_owned()));
+        }
+    }
+    for (range, depth, note) in parts {
+        write_part(out, &data, range, depth, &note)?;
+    }
+    Ok(())
+}
+
+/// Поля заголовка конверта, уже проверенного `inspect`
+fn envelope_header(data: &[u8], parts: &mut Vec<Part>) {
+    let word = |at: usize| u32::from_be_bytes(data[at..at + 4].try_into().unwrap());
+    parts.push((0..4, 0, "сигнатура CCDC".to_owned()));
+    parts.push((4..5, 0, format!("версия {}", data[4])));
+    parts.push((5..6, 0, format!("флаги {:#04x}", data[5])));
+    parts.push((6..7, 0, format!("профиль {:#04x}", data[6])));
+    parts.push((7..HEADER_LEN, 0, format!("длина тела {}", word(7))));
+    // флаг идентификатора схемы
+    if data[5] & 0b10 != 0 {
+        let id = HEADER_LEN..HEADER_LEN + 8;
+        let note = format!("схема {:#010x} v{}", word(HEADER_LEN), word(HEADER_LEN + 4));
+        parts.push((id, 0, note));
+    }
+}
+
+fn node_parts(nodes: &[Node], parent: u8, depth: usize, parts: &mut Vec<Part>) {
+    for (i, node) in nodes.iter().enumerate() {
+        let code_end = node.offset + 1;
+        let label = label(parent, i, node);
+        let inline = node.len_offset == node.end;
+        let note = match (inline, &node.value) {
+            (true, Some(value)) => {
+                let (name, text) = (value_name(Some(value)), value_to_text(value));
+                format!("{label}: {name} {text}, встроено в код типа")
+            }
+            _ => {
+                let name = type_name(node.type_code).unwrap_or("неизвестный");
+                format!("{label}: код типа {} ({name})", node.type_code)
+            }
+        };
+        parts.push((node.offset..code_end, depth, note));
+
+        let inner = depth + 1;
+        if code_end < node.key_offset {
+            let note = format!("длина ключа {}", node.len_offset - node.key_offset);
+            parts.push((code_end..node.key_offset, inner, note));
+        }
+        if let Some(key) = &node.key {
+            let note = format!("ключ {}", crate::key_text(key));
+            parts.push((node.key_offset..node.len_offset, inner, note));
+        }
+        if inline {
+            continue;
+        }
+        let note = format!("длина значения {}", node.end - node.value_offset);
+        parts.push((node.len_offset..node.value_offset, inner, note));
+        match &node.value {
+            Some(Value::Enum { variant, name, .. }) => {
+                let header_end = node.children.first().map_or(node.end, |c| c.offset);
+                let mut note = format!("вариант {variant}");
+                if let Some(name) = name {
+                    note += &format!(", имя {}", value_to_text(&Value::String(name.clone())));
+                }
+                parts.push((node.value_offset..header_end, inner, note));
+            }
+            Some(value) => {
+                let note = format!("значение {}", value_to_text(value));
+                parts.push((node.value_offset..node.end, inner, note))
+            }
+            None => {}
+        }
+        node_parts(&node.children, node.type_code, inner, parts);
+    }
+}
+
+fn write_part(
+    out: &mut impl Write,
+    data: &[u8],
+    range: Range<usize>,
+    depth: usize,
+    note: &str,
+) -> io::Result<()> {
+    let bytes = &data[range.clone()];
+    let hex_width = 3 * BYTES_PER_LINE;
+    let indent = 2 * depth;
+    if bytes.is_empty() {
+        return writeln!(out, "{:08x}  {:hex_width$} {:indent$}{note}", range.start, "", "");
+    }
+    for (i, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        let hex = chunk.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(" ");
+        let offset = range.start + i * BYTES_PER_LINE;
+        match i {
+            0 => writeln!(out, "{offset:08x}  {hex:hex_width$} {:indent$}{note}", "")?,
+            _ => writeln!(out, "{offset:08x}  {hex}")?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use custom_codec::{encode_enveloped_with, encode_message, Checksum, EncodeOptions, Field};
+
+    fn dump(name: &str, data: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("ccodec-dump-{name}.bin"));
+        std::fs::write(&path, data).unwrap();
+        let mut out = Vec::new();
+        run(Args::parse([path.to_str().unwrap().to_owned()], &[]).unwrap(), &mut out).unwrap();
+        std::fs::remove_file(path).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn annotates_parts() {
+        let fields = [
+            Field { key: "name".into(), value: Value::String("Rust".into()) },
+            Field { key: "b".into(), value: Value::Bytes(b"abcdefghijklmn".to_vec()) },
+        ];
+        let expected = "\
+00000000  04                                   name: код типа 4 (String)
+00000001  00 00 00 04                            длина ключа 4
+00000005  6e 61 6d 65                            ключ name
+00000009  00 00 00 04                            длина значения 4
+0000000d  52 75 73 74                            значение \"Rust\"
+00000011  05                                   b: код типа 5 (Bytes)
+00000012  00 00 00 01                            длина ключа 1
+00000016  62                                     ключ b
+00000017  00 00 00 0e                            длина значения 14
+0000001b  61 62 63 64 65 66 67 68 69 6a 6b 6c    значение b\"abcdefghijklmn\"
+00000027  6d 6e
+";
+        assert_eq!(dump("parts", &encode_message(&fields).unwrap()), expected);
+    }
+
+    #[test]
+    fn annotates_envelope() {
+        let fields = [Field { key: "l".into(), value: Value::List(vec![Value::Bool(true)]) }];
+        let opts = EncodeOptions { checksum: Checksum::Crc32, ..EncodeOptions::default() };
+        let text = dump("envelope", &encode_enveloped_with(&fields, &opts).unwrap());
+        let lines: Vec<_> = text.lines().collect();
+        assert!(lines[0].starts_with("00000000  43 43 44 43 "));
+        assert!(lines[0].ends_with(" сигнатура CCDC"));
+        assert!(lines[5].ends_with(" l: код типа 11 (List)"));
+        assert!(lines[9].ends_with("    [0]: код типа 3 (Bool)"));
+        assert!(lines[11].ends_with("      значение true"));
+        assert!(lines[12].ends_with(" CRC-32"));
+        assert_eq!(lines.len(), 13);
+    }
+}
@@ -0,0 +1,93 @@
+//! `ccodec get`: одно значение по пути ключей
+//!
+//! Путь — как у `Message::get_path`: ключи вложенных сообщений через
+//! точку, обратная черта экранирует `.`, `=` и саму себя (`a\.b`).
+//!
+//! Без флагов значение печатается для скриптов: строки без кавычек,
+//! `Bytes` — как есть, числа и `true`/`false`/`null` — текстом, остальное —
+//! JSON. С `--json` любое значение — JSON, с `--hex` `Bytes` —
+//! шестнадцатеричной строкой.
+
+use std::io::Write;
+
+use custom_codec::__private::path_keys;
+use custom_codec::{value_to_json, Message, Value};
+use serde_json::Value as Json;
+
+use crate::args::Args;
+use crate::CliError;
+
+pub(crate) fn run(mut args: Args, out: &mut impl Write) -> Result<(), CliError> {
+    let opts = crate::decode_options(&mut args);
+    let (json, hex) = (args.flag("--json"), args.flag("--hex"));
+    let file = args.positional("файл")?;
+    let path = args.positional("путь")?;
+    args.finish()?;
+    if json && hex {
+        return Err(CliError::Usage("--json и --hex несовместимы".into()));
+    }
+    parse_path(&path)?;
+
+    let msg = Message::from(crate::decode_input(&crate::read_input(&file)?, &opts)?);
+    let value = msg.get_path(&path).ok_or_else(|| CliError::NotFound(path.clone()))?;
+    match (value, json, hex) {
+        (Value::Bytes(bytes), false, true) => {
+            let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+            writeln!(out, "{hex}")?
+        }
+        (Value::Bytes(bytes), false, false) => out.write_all(bytes)?,
+        (value, true, _) => writeln!(out, "{}", value_to_json(value))?,
+        (value, false, _) => match value_to_json(value) {
+            Json::String(s) => writeln!(out, "{s}")?,
+            json => writeln!(out, "{json}")?,
+        },
+    }
+    Ok(())
+}
+
+/// Ключи пути; после `\` допустимы только `.`, `=` и `\`
+pub(crate) fn parse_path(path: &str) -> Result<Vec<String>, CliError> {
+    path_keys(path).ok_or_else(|| {
+        CliError::Usage(format!("путь `{path}`: после \\ ожидается ., = или \\"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use custom_codec::{encode_message, Field};
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn output_modes() {
+        let user = Value::Message(vec![
+            field("name", Value::String("Rust".into())),
+            field("photo", Value::Bytes(vec![0xca, 0xfe])),
+            field("age", Value::UInt8(9)),
+            field("a.b", Value::Null),
+        ]);
+        let path = std::env::temp_dir().join("ccodec-get.bin");
+        std::fs::write(&path, encode_message(&[field("user", user)]).unwrap()).unwrap();
+        let get = |args: &[&str]| {
+            let mut all = vec![path.to_str().unwrap()];
+            all.extend(args);
+            let mut out = Vec::new();
+            run(Args::parse(all.iter().map(|s| s.to_string()), &[]).unwrap(), &mut out)?;
+            Ok::<_, CliError>(out)
+        };
+        assert_eq!(get(&["user.name"]).unwrap(), b"Rust\n");
+        assert_eq!(get(&["user.name", "--json"]).unwrap(), b"\"Rust\"\n");
+        assert_eq!(get(&["user.age"]).unwrap(), b"9\n");
+        assert_eq!(get(&["user.photo"]).unwrap(), [0xca, 0xfe]);
+        assert_eq!(get(&["user.photo", "--hex"]).unwrap(), b"cafe\n");
+        assert_eq!(get(&["user.photo", "--json"]).unwrap(), b"\"yv4=\"\n");
+        assert_eq!(get(&[r"user.a\.b"]).unwrap(), b"null\n");
+        assert!(matches!(get(&["user.zip"]), Err(CliError::NotFound(_))));
+        assert!(matches!(get(&["user.age.x"]), Err(CliError::NotFound(_))));
+        assert!(matches!(get(&[r"user\name"]), Err(CliError::Usage(_))));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+//! `ccodec inspect`: дерево элементов с типами, длинами и смещениями
+//!
+//! ```text
+//! 00000000  user: Message, 19 байт
+//! 0000000d    id: Int64, 8 байт = 42
+//! ```
+//!
+//! Смещение — начало кода типа, длина — длина значения без заголовка.
+
+use std::io::{self, Write};
+
+use custom_codec::tooling::{inspect, type_name, Node};
+use custom_codec::{envelope_schema_id, value_to_text, Value, MAGIC};
+
+use crate::args::Args;
+use crate::CliError;
+
+pub(crate) fn run(mut args: Args, out: &mut impl Write) -> Result<(), CliError> {
+    let opts = crate::decode_options(&mut args);
+    let path = args.positional("файл")?;
+    args.finish()?;
+    let data = crate::read_input(&path)?;
+    let nodes = inspect(&data, &opts)?;
+    if data.starts_with(&MAGIC) {
+        write!(out, "{:08x}  конверт", 0)?;
+        if let Some(id) = envelope_schema_id(&data)? {
+            write!(out, ", схема {id}")?;
+        }
+        writeln!(out)?;
+    }
+    write_nodes(out, &nodes, 6, 0)?;
+    Ok(())
+}
+
+/// Элементы контейнера с кодом типа `parent`
+fn write_nodes(out: &mut impl Write, nodes: &[Node], parent: u8, depth: usize) -> io::Result<()> {
+    for (i, node) in nodes.iter().enumerate() {
+        let label = label(parent, i, node);
+        let indent = 2 * depth;
+        write!(out, "{:08x}  {:indent$}{label}: ", node.offset, "")?;
+        // встроенный скаляр записан в самом коде типа
+        match node.len_offset == node.end {
+            true => write!(out, "{}, встроено", value_name(node.value.as_ref()))?,
+            false => {
+                let name = type_name(node.type_code).map_or_else(
+                    || format!("код {}", node.type_code),
+                    str::to_owned,
+                );
+                write!(out, "{name}, {} байт", node.end - node.value_offset)?
+            }
+        }
+        match &node.value {
+            Some(Value::Enum { variant, name, .. }) => {
+                write!(out, " = вариант {variant}")?;
+                if let Some(name) = name {
+                    write!(out, " {}", value_to_text(&Value::String(name.clone())))?;
+                }
+            }
+            Some(value) => write!(out, " = {}", value_to_text(value))?,
+            None => {}
+        }
+        writeln!(out)?;
+        write_nodes(out, &node.children, node.type_code, depth + 1)?;
+    }
+    Ok(())
+}
+
+/// Подпись `i`-го элемента контейнера с кодом типа `parent`: ключ поля,
+/// номер элемента или пары отображения
+pub(crate) fn label(parent: u8, i: usize, node: &Node) -> String {
+    match (parent, &node.key) {
+        (_, Some(key)) => crate::key_text(key),
+        (11, None) => format!("[{i}]"),
+        (12, None) if i.is_multiple_of(2) => format!("[{}] ключ", i / 2),
+        (12, None) => format!("[{}] значение", i / 2),
+        (_, None) => "содержимое".to_owned(),
+    }
+}
+
+/// Тип встроенного скаляра по его значению
+pub(crate) fn value_name(value: Option<&Value>) -> &'static str {
+    match value {
+        Some(Value::Bool(_)) => "Bool",
+        _ => "Int32",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use custom_codec::{encode_enveloped, encode_message, Field};
+
+    fn render(name: &str, data: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("ccodec-inspect-{name}.bin"));
+        std::fs::write(&path, data).unwrap();
+        let mut out = Vec::new();
+        let args = Args::parse([path.to_str().unwrap().to_owned()], &[]).unwrap();
+        run(args, &mut out).unwrap();
+        std::fs::remove_file(path).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn tree() {
+        let fields = vec![
+            Field {
+                key: "user".into(),
+                value: Value::Message(vec![Field { key: "id".into(), value: Value::Int64(42) }]),
+            },
+            Field { key: "full name".into(), value: Value::List(vec![Value::Null]) },
+        ];
+        let expected = "\
+00000000  user: Message, 19 байт
+0000000d    id: Int64, 8 байт = 42
+00000020  \"full name\": List, 5 байт
+00000032    [0]: Null, 0 байт = null
+";
+        assert_eq!(render("tree", &encode_message(&fields).unwrap()), expected);
+    }
+
+    #[test]
+    fn envelope_and_enum() {
+        let value = Value::Enum { variant: 1, name: Some("On".into()), payload: None };
+        let data = encode_enveloped(&[Field { key: "state".into(), value }]).unwrap();
+        let expected = "\
+00000000  конверт
+0000000b  state: Enum, 11 байт = вариант 1 \"On\"
+";
+        assert_eq!(render("envelope", &data), expected);
+    }
+}
@@ -0,0 +1,292 @@
+//! `ccodec` — консольная утилита для данных custom_codec
+//!
+//! ```text
+//! ccodec inspect [--compact] FILE
+//! ccodec encode [--compact] [--envelope] [--bytes KEY]... [--float32] INPUT.json [-o OUT]
+//! ccodec decode [--compact] [--json] FILE
+//! ccodec get [--compact] [--json | --hex] FILE PATH
+//! ccodec set [--compact] [--type TYPE] [-o OUT] FILE PATH=VALUE...
+//! ccodec diff [--compact] A B
+//! ccodec validate [--compact] --schema SCHEMA.ccs [--message NAME] FILE...
+//! ccodec dump [--compact] FILE
+//! ccodec stats [--compact] [--top N] FILE
+//! ccodec stream (--decode [--compact] | --encode [--compact] [--envelope] [--bytes KEY]...)
+//! ccodec gen --schema SCHEMA.ccs [--message NAME] [--count N] [--seed N] [-o OUT | --dir DIR]
+//! ```
+//!
+//! Вместо `FILE` можно указать `-`, тогда данные читаются из stdin, а
+//! без `-o` результат пишется в stdout.
+//!
+//! Конверт распознаётся по сигнатуре и несёт свой профиль; сообщение без
+//! конверта читается в исходном профиле, а с `--compact` — в
+//! `Profile::compact`.
+
+mod args;
+mod convert;
+mod diff;
+mod dump;
+mod gen;
+mod get;
+mod inspect;
+mod set;
+mod stats;
+mod stream;
+mod validate;
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+use args::Args;
+use custom_codec::{
+    decode_enveloped_with, decode_message_with, DecodeError, DecodeOptions, EncodeError, Field,
+    parse_schemas, value_to_text, JsonError, Profile, Schema, Value, MAGIC,
+};
+
+const USAGE: &str = "\
+использование: ccodec <команда> [аргументы]
+
+команды:
+  inspect FILE                  дерево элементов с типами, длинами и смещениями
+  encode INPUT.json [-o OUT]    сообщение из JSON в двоичный вид
+    --envelope                  в конверте
+    --bytes KEY                 строки поля KEY — Bytes в base64; флаг повторяется
+    --float32                   дробные числа как Float32
+  decode FILE                   поля сообщения в текстовой записи
+    --json                      в JSON
+  get FILE PATH                 значение по пути ключей через точку: user.address.city
+    --json                      в JSON
+    --hex                       Bytes шестнадцатеричной строкой
+  set FILE PATH=VALUE...        изменение полей с перезаписью файла
+    --type TYPE                 тип значений: Bool, Int8…UInt64, Float32, Float64, String,
+                                Bytes (hex) или Null; по умолчанию — тип поля
+    -o OUT                      записать в OUT, а не в FILE
+  dump FILE                     шестнадцатеричный дамп с разметкой частей элементов
+  stats FILE                    байты по путям полей, доля служебных байт
+    --top N                     число крупнейших полей, по умолчанию 10
+  diff A B                      добавленные, удалённые и изменённые поля
+  validate --schema SCHEMA.ccs FILE...
+                                нарушения схемы в JSON Lines
+    --message NAME              сообщение из схемы; по умолчанию первое
+  stream --decode               рамки [длина u32 BE] из stdin в строки JSON
+  stream --encode               строки JSON из stdin в рамки
+    --envelope, --bytes KEY, --float32
+                                как у encode
+  gen --schema SCHEMA.ccs [-o OUT]
+                                случайные сообщения в рамках [длина u32 BE]
+    --count N                   число сообщений, по умолчанию 1
+    --seed N                    начальное значение генератора, по умолчанию 0
+    --dir DIR                   каждое сообщение в свой файл
+    --envelope                  в конверте
+
+общие флаги:
+  --compact                     сообщение без конверта в Profile::compact
+";
+
+/// Ошибка команды; печатается в stderr
+#[derive(Debug)]
+enum CliError {
+    /// Неверные аргументы; печатается вместе со справкой
+    Usage(String),
+    /// Файл не читается или не пишется
+    File { path: String, error: io::Error },
+    /// Не удалась запись в stdout
+    Output(io::Error),
+    /// Входной текст не разбирается
+    Parse { path: String, message: String },
+    /// Нет поля по пути
+    NotFound(String),
+    Decode(DecodeError),
+    Encode(EncodeError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(msg) => f.write_str(msg),
+            CliError::File { path, error } => write!(f, "{path}: {error}"),
+            CliError::Output(error) => write!(f, "запись в stdout: {error}"),
+            CliError::Parse { path, message } => write!(f, "{path}: {message}"),
+            CliError::NotFound(path) => write!(f, "нет поля {path}"),
+            CliError::Decode(e) => write!(f, "данные не декодируются: {e}"),
+            CliError::Encode(e) => write!(f, "сообщение не кодируется: {e}"),
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(e: io::Error) -> Self {
+        CliError::Output(e)
+    }
+}
+
+impl From<DecodeError> for CliError {
+    fn from(e: DecodeError) -> Self {
+        CliError::Decode(e)
+    }
+}
+
+impl From<EncodeError> for CliError {
+    fn from(e: EncodeError) -> Self {
+        CliError::Encode(e)
+    }
+}
+
+impl CliError {
+    fn json(path: &str, e: JsonError) -> Self {
+        CliError::Parse { path: path.to_owned(), message: e.to_string() }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut out = io::stdout().lock();
+    exit_code(run(std::env::args().skip(1), &mut out))
+}
+
+/// Код выхода команды; ошибка печатается в stderr. Закрытый читателем
+/// stdout (`ccodec decode … | head`) — не ошибка: команда молча завершается
+fn exit_code(result: Result<ExitCode, CliError>) -> ExitCode {
+    match result {
+        Ok(code) => code,
+        Err(CliError::Output(e)) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(CliError::Usage(msg)) => {
+            eprintln!("ccodec: {msg}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("ccodec: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(
+    args: impl IntoIterator<Item = String>,
+    out: &mut impl Write,
+) -> Result<ExitCode, CliError> {
+    let mut args = args.into_iter();
+    let command = args.next().ok_or_else(|| CliError::Usage("не указана команда".into()))?;
+    match command.as_str() {
+        "inspect" => inspect::run(Args::parse(args, &[])?, out)?,
+        "encode" => convert::encode(Args::parse(args, &["-o", "--bytes"])?, out)?,
+        "decode" => convert::decode(Args::parse(args, &[])?, out)?,
+        "get" => get::run(Args::parse(args, &[])?, out)?,
+        "set" => set::run(Args::parse(args, &["--type", "-o"])?, out)?,
+        "dump" => dump::run(Args::parse(args, &[])?, out)?,
+        "stats" => stats::run(Args::parse(args, &["--top"])?, out)?,
+        "diff" => return diff::run(Args::parse(args, &[])?, out),
+        "validate" => return validate::run(Args::parse(args, &["--schema", "--message"])?, out),
+        "stream" => stream::run(Args::parse(args, &["--bytes"])?, io::stdin().lock(), out)?,
+        "gen" => {
+            let with_value = ["--schema", "--message", "--count", "--seed", "--dir", "-o"];
+            gen::run(Args::parse(args, &with_value)?, out)?
+        }
+        "help" | "-h" | "--help" => out.write_all(USAGE.as_bytes())?,
+        other => return Err(CliError::Usage(format!("неизвестная команда `{other}`"))),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Содержимое файла; `-` — stdin
+fn read_input(path: &str) -> Result<Vec<u8>, CliError> {
+    let file_error = |error| CliError::File { path: path.to_owned(), error };
+    match path {
+        "-" => {
+            let mut data = Vec::new();
+            io::stdin().lock().read_to_end(&mut data).map_err(file_error)?;
+            Ok(data)
+        }
+        _ => fs::read(path).map_err(file_error),
+    }
+}
+
+/// Запись результата в файл `path` или, без него и для `-`, в `out`
+fn write_output(path: Option<&str>, data: &[u8], out: &mut impl Write) -> Result<(), CliError> {
+    match path {
+        None | Some("-") => Ok(out.write_all(data)?),
+        Some(path) => {
+            fs::write(path, data).map_err(|error| CliError::File { path: path.to_owned(), error })
+        }
+    }
+}
+
+/// Поля сообщения в конверте или без него
+fn decode_input(data: &[u8], opts: &DecodeOptions) -> Result<Vec<Field>, DecodeError> {
+    match data.starts_with(&MAGIC) {
+        true => decode_enveloped_with(data, opts),
+        false => decode_message_with(data, opts),
+    }
+}
+
+/// Схема `name` или, без имени, первая из файла схем `path`
+fn load_schema(path: &str, name: Option<&str>) -> Result<Schema, CliError> {
+    let parse_error = |message| CliError::Parse { path: path.to_owned(), message };
+    let text = String::from_utf8(read_input(path)?)
+        .map_err(|_| parse_error("текст не в UTF-8".into()))?;
+    let schemas = parse_schemas(&text).map_err(|e| parse_error(e.to_string()))?;
+    let schema = match name {
+        Some(name) => schemas.get(name),
+        None => schemas.iter().next(),
+    };
+    schema.cloned().ok_or_else(|| match name {
+        Some(name) => parse_error(format!("нет сообщения {name}")),
+        None => parse_error("нет ни одного сообщения".into()),
+    })
+}
+
+/// Ключ как есть, а с точками, пробелами и спецсимволами — в кавычках
+fn key_text(key: &str) -> String {
+    let plain = !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || "_-".contains(c));
+    match plain {
+        true => key.to_owned(),
+        false => value_to_text(&Value::String(key.to_owned())),
+    }
+}
+
+/// Профиль сообщения без конверта по флагу `--compact`
+fn profile(args: &mut Args) -> Profile {
+    match args.flag("--compact") {
+        true => Profile::compact(),
+        false => Profile::STANDARD,
+    }
+}
+
+/// Ограничения для локальных файлов: без ограничений размера, но с
+/// глубиной по умолчанию
+fn decode_options(args: &mut Args) -> DecodeOptions {
+    DecodeOptions {
+        max_depth: DecodeOptions::default().max_depth,
+        profile: profile(args),
+        ..DecodeOptions::unlimited()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_args(args: &[&str]) -> Result<String, CliError> {
+        let mut out = Vec::new();
+        assert_eq!(run(args.iter().map(|s| s.to_string()), &mut out)?, ExitCode::SUCCESS);
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn commands() {
+        assert!(run_args(&["help"]).unwrap().starts_with("использование"));
+        assert!(matches!(run_args(&[]), Err(CliError::Usage(_))));
+        assert!(matches!(run_args(&["frobnicate"]), Err(CliError::Usage(_))));
+        let err = run_args(&["inspect", "/nonexistent/ccodec.bin"]).unwrap_err();
+        assert!(matches!(err, CliError::File { .. }));
+    }
+
+    #[test]
+    fn broken_pipe_is_silent_success() {
+        let pipe = CliError::Output(io::ErrorKind::BrokenPipe.into());
+        assert_eq!(exit_code(Err(pipe)), ExitCode::SUCCESS);
+        let full = CliError::Output(io::ErrorKind::StorageFull.into());
+        assert_eq!(exit_code(Err(full)), ExitCode::FAILURE);
+        assert_eq!(exit_code(Err(CliError::Usage("x".into()))), ExitCode::from(2));
+    }
+}
@@ -0,0 +1,243 @@
+//! `ccodec set`: изменение полей в двоичном файле
+//!
+//! Присваивание `PATH=VALUE` использует путь `Message::get_path`; знак `=`
+//! в ключе экранируется как `\=`. Значение разбирается в тип поля, которое
+//! заменяется, а новое поле получает тип по виду значения: `true`/`false` —
+//! `Bool`, `null` — `Null`, целое — `Int64`, дробное — `Float64`, остальное —
+//! `String`. Флаг `--type` задаёт тип явно; `Bytes` записываются
+//! шестнадцатеричной строкой. Недостающие сообщения на пути создаются.
+//!
+//! Сообщение в конверте перезаписывается с тем же профилем, контрольной
+//! суммой и идентификатором схемы.
+
+use std::io::Write;
+
+use custom_codec::{
+    encode_enveloped_with, encode_message_with, envelope_options, EncodeOptions, Field, Value,
+    MAGIC,
+};
+
+use crate::args::Args;
+use crate::get::parse_path;
+use crate::CliError;
+
+/// Типы, значения которых задаются в `set`
+const TYPES: &[&str] = &[
+    "Bool", "Null", "Int8", "Int16", "Int32", "Int64", "UInt8", "UInt16", "UInt32", "UInt64",
+    "Float32", "Float64", "String", "Bytes",
+];
+
+pub(crate) fn run(mut args: Args, out: &mut impl Write) -> Result<(), CliError> {
+    let opts = crate::decode_options(&mut args);
+    let ty = args.value("--type");
+    let output = args.value("-o");
+    let file = args.positional("файл")?;
+    let assignments = std::iter::from_fn(|| args.positional("присваивание").ok());
+    let assignments = assignments.collect::<Vec<_>>();
+    args.finish()?;
+    if assignments.is_empty() {
+        return Err(CliError::Usage("не указано присваивание PATH=VALUE".into()));
+    }
+    if let Some(ty) = ty.as_deref().filter(|ty| !TYPES.contains(ty)) {
+        let msg = format!("--type {ty}: ожидается один из {}", TYPES.join(", "));
+        return Err(CliError::Usage(msg));
+    }
+
+    let data = crate::read_input(&file)?;
+    let mut fields = crate::decode_input(&data, &opts)?;
+    for assignment in &assignments {
+        let (path, text) = split_assignment(assignment)?;
+        assign(&mut fields, &parse_path(path)?, text, ty.as_deref())
+            .map_err(|message| CliError::Parse { path: path.to_owned(), message })?;
+    }
+    let encoded = match data.starts_with(&MAGIC) {
+        true => encode_enveloped_with(&fields, &envelope_options(&data)?)?,
+        false => {
+            let opts = EncodeOptions { profile: opts.profile, ..EncodeOptions::default() };
+            encode_message_with(&fields, &opts)?
+        }
+    };
+    crate::write_output(Some(output.as_deref().unwrap_or(&file)), &encoded, out)
+}
+
+/// Путь и значение по первому неэкранированному `=`
+fn split_assignment(assignment: &str) -> Result<(&str, &str), CliError> {
+    let mut escaped = false;
+    for (i, c) in assignment.char_indices() {
+        match c {
+            '=' if !escaped => return Ok((&assignment[..i], &assignment[i + 1..])),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    Err(CliError::Usage(format!("`{assignment}`: ожидается PATH=VALUE")))
+}
+
+/// Запись значения `text` в поле по пути `keys`
+fn assign(
+    fields: &mut Vec<Field>,
+    keys: &[String],
+    text: &str,
+    ty: Option<&str>,
+) -> Result<(), String> {
+    let (key, rest) = keys.split_first().expect("путь не пуст");
+    let index = fields.iter().position(|f| &f.key == key);
+    if rest.is_empty() {
+        let value = match (ty, index) {
+            (Some(ty), _) => parse(ty, text)?,
+            (None, Some(i)) => match scalar_type(&fields[i].value) {
+                Some(ty) => parse(ty, text)?,
+                None => return Err("поле не скаляр; тип задаётся через --type".into()),
+            },
+            (None, None) => infer(text),
+        };
+        match index {
+            Some(i) => fields[i].value = value,
+            None => fields.push(Field { key: key.clone(), value }),
+        }
+        return Ok(());
+    }
+    let i = index.unwrap_or_else(|| {
+        fields.push(Field { key: key.clone(), value: Value::Message(Vec::new()) });
+        fields.len() - 1
+    });
+    match &mut fields[i].value {
+        Value::Message(inner) => assign(inner, rest, text, ty),
+        _ => Err(format!("поле {} не сообщение", crate::key_text(key))),
+    }
+}
+
+/// Тип значения, если оно задаётся в `set`
+fn scalar_type(value: &Value) -> Option<&'static str> {
+    Some(match value {
+        Value::Bool(_) => "Bool",
+        Value::Null => "Null",
+        Value::Int8(_) => "Int8",
+        Value::Int16(_) => "Int16",
+        Value::Int32(_) => "Int32",
+        Value::Int64(_) => "Int64",
+        Value::UInt8(_) => "UInt8",
+        Value::UInt16(_) => "UInt16",
+        Value::UInt32(_) => "UInt32",
+        Value::UInt64(_) => "UInt64",
+        Value::Float32(_) => "Float32",
+        Value::Float64(_) => "Float64",
+        Value::String(_) => "String",
+        Value::Bytes(_) => "Bytes",
+        _ => return None,
+    })
+}
+
+fn infer(text: &str) -> Value {
+    match text {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        "null" => Value::Null,
+        _ => match (text.parse(), text.parse()) {
+            (Ok(n), _) => Value::Int64(n),
+            (_, Ok(x)) => Value::Float64(x),
+            _ => Value::String(text.to_owned()),
+        },
+    }
+}
+
+/// Значение типа `ty` из `TYPES`
+fn parse(ty: &str, text: &str) -> Result<Value, String> {
+    fn num<T: std::str::FromStr>(text: &str, wrap: fn(T) -> Value) -> Option<Value> {
+        text.parse().ok().map(wrap)
+    }
+    let value = match ty {
+        "Bool" => num(text, Value::Bool),
+        "Null" => (text == "null").then_some(Value::Null),
+        "Int8" => num(text, Value::Int8),
+        "Int16" => num(text, Value::Int16),
+        "Int32" => num(text, Value::Int32),
+        "Int64" => num(text, Value::Int64),
+        "UInt8" => num(text, Value::UInt8),
+        "UInt16" => num(text, Value::UInt16),
+        "UInt32" => num(text, Value::UInt32),
+        "UInt64" => num(text, Value::UInt64),
+        "Float32" => num(text, Value::Float32),
+        "Float64" => num(text, Value::Float64),
+        "String" => Some(Value::String(text.to_owned())),
+        "Bytes" => hex(text).map(Value::Bytes),
+        _ => unreachable!("тип проверен по TYPES"),
+    };
+    value.ok_or_else(|| format!("`{text}` не значение типа {ty}"))
+}
+
+fn hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use custom_codec::{decode_enveloped, encode_enveloped_with, Checksum, SchemaId};
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    fn set(fields: &mut Vec<Field>, assignment: &str, ty: Option<&str>) -> Result<(), String> {
+        let (path, text) = split_assignment(assignment).unwrap();
+        assign(fields, &parse_path(path).unwrap(), text, ty)
+    }
+
+    #[test]
+    fn assigns_by_type() {
+        let user = vec![field("age", Value::UInt8(42)), field("raw", Value::Bytes(vec![1]))];
+        let mut fields = vec![field("user", Value::Message(user))];
+        set(&mut fields, "user.age=43", None).unwrap();
+        set(&mut fields, "user.raw=cafe", None).unwrap();
+        set(&mut fields, "user.address.city=Oslo", None).unwrap();
+        set(&mut fields, r"a\=b=1.5", None).unwrap();
+        set(&mut fields, "n=7", Some("Int16")).unwrap();
+        let address = Value::Message(vec![field("city", Value::String("Oslo".into()))]);
+        let user = vec![
+            field("age", Value::UInt8(43)),
+            field("raw", Value::Bytes(vec![0xca, 0xfe])),
+            field("address", address),
+        ];
+        let expected = [
+            field("user", Value::Message(user)),
+            field("a=b", Value::Float64(1.5)),
+            field("n", Value::Int16(7)),
+        ];
+        assert_eq!(fields, expected);
+
+        let err = set(&mut fields, "user.age=300", None);
+        assert_eq!(err, Err("`300` не значение типа UInt8".into()));
+        assert!(set(&mut fields, "user=1", None).unwrap_err().contains("--type"));
+        assert_eq!(set(&mut fields, "n.x=1", None), Err("поле n не сообщение".into()));
+        assert!(split_assignment(r"a\=b").is_err());
+        assert_eq!(parse_path(r"a\.b.c\\d\=").unwrap(), ["a.b", r"c\d="]);
+        assert!(parse_path(r"a\b").is_err());
+    }
+
+    #[test]
+    fn rewrites_envelope() {
+        let opts = EncodeOptions {
+            checksum: Checksum::Crc32,
+            schema_id: Some(SchemaId { id: 7, version: 1 }),
+            ..EncodeOptions::default()
+        };
+        let data = encode_enveloped_with(&[field("age", Value::Int32(1))], &opts).unwrap();
+        let path = std::env::temp_dir().join("ccodec-set.bin");
+        std::fs::write(&path, data).unwrap();
+        let args = [path.to_str().unwrap(), "age=2", "ok=true"].map(String::from);
+        run(Args::parse(args, &["--type", "-o"]).unwrap(), &mut Vec::new()).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(envelope_options(&data), Ok(opts));
+        let expected = [field("age", Value::Int32(2)), field("ok", Value::Bool(true))];
+        assert_eq!(decode_enveloped(&data).unwrap(), expected);
+        let args = [path.to_str().unwrap(), "age=2", "--type", "Enum"].map(String::from);
+        let err = run(Args::parse(args, &["--type", "-o"]).unwrap(), &mut Vec::new());
+        assert!(matches!(err, Err(CliError::Usage(_))));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+//! `ccodec stats`: сколько байт занимают поля и сколько из них служебные
+//!
+//! ```text
+//! всего 71 байт: данные 13 (18.3%), служебные 58 (81.7%)
+//!
+//!   полей     байт   данные  служебные  путь
+//!       1       59       12         47  user
+//!       1       19        8         11  user.id
+//!       1       27        4         23  user.tags
+//!       2       14        4         10  user.tags[*]
+//!       1       12        1         11  ok
+//!
+//! крупнейшие поля:
+//!        59  user
+//!        27  user.tags
+//! ```
+//!
+//! Данные — байты значений скаляров и заголовков вариантов, служебные —
+//! коды типов, ключи, префиксы длин и конверт. Строка таблицы собирает
+//! все поля с одним путём: элементы списков, отображений и содержимое
+//! вариантов обозначены `[*]`.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use custom_codec::tooling::{inspect, Node};
+
+use crate::args::Args;
+use crate::CliError;
+
+/// Поля с одним путём
+#[derive(Debug, Default, PartialEq)]
+struct Row {
+    path: String,
+    count: usize,
+    total: usize,
+    payload: usize,
+}
+
+#[derive(Default)]
+struct Stats {
+    rows: Vec<Row>,
+    index: HashMap<String, usize>,
+    /// размер и путь каждого поля сообщений
+    fields: Vec<(usize, String)>,
+}
+
+pub(crate) fn run(mut args: Args, out: &mut impl Write) -> Result<(), CliError> {
+    let opts = crate::decode_options(&mut args);
+    let top = args.parsed::<usize>("--top")?.unwrap_or(10);
+    let path = args.positional("файл")?;
+    args.finish()?;
+    let data = crate::read_input(&path)?;
+    let nodes = inspect(&data, &opts)?;
+
+    let mut stats = Stats::default();
+    let payload: usize = nodes.iter().map(|n| stats.walk(n, key_path(n), key_path(n))).sum();
+    let total = data.len();
+    let overhead = total - payload;
+    let percent = |n: usize| if total == 0 { 0.0 } else { 100.0 * n as f64 / total as f64 };
+    writeln!(
+        out,
+        "всего {total} байт: данные {payload} ({:.1}%), служебные {overhead} ({:.1}%)",
+        percent(payload),
+        percent(overhead)
+    )?;
+    if stats.rows.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "\n  полей     байт   данные  служебные  путь")?;
+    for row in &stats.rows {
+        let Row { path, count, total, payload } = row;
+        writeln!(out, "{count:>7}  {total:>7}  {payload:>7}  {:>9}  {path}", total - payload)?;
+    }
+    writeln!(out, "\nкрупнейшие поля:")?;
+    // при равном размере — в порядке следования
+    stats.fields.sort_by_key(|&(size, _)| std::cmp::Reverse(size));
+    for (size, path) in stats.fields.iter().take(top) {
+        writeln!(out, "{size:>9}  {path}")?;
+    }
+    Ok(())
+}
+
+impl Stats {
+    /// Учёт элемента и его содержимого; возвращает байты данных в нём
+    ///
+    /// `path` — путь элемента с номерами элементов, `pattern` — с `[*]`.
+    fn walk(&mut self, node: &Node, path: String, pattern: String) -> usize {
+        // строка заводится до содержимого, чтобы таблица шла в порядке обхода
+        let row = match self.index.get(&pattern) {
+            Some(&row) => row,
+            None => {
+                self.index.insert(pattern.clone(), self.rows.len());
+                self.rows.push(Row { path: pattern.clone(), ..Row::default() });
+                self.rows.len() - 1
+            }
+        };
+        let mut payload = match (&node.value, node.children.first()) {
+            // встроенный скаляр: значение записано в коде типа
+            (Some(_), _) if node.len_offset == node.end => 1,
+            // заголовок варианта без содержимого или скаляр
+            (Some(_), None) => node.end - node.value_offset,
+            (Some(_), Some(payload)) => payload.offset - node.value_offset,
+            (None, _) => 0,
+        };
+        for (i, child) in node.children.iter().enumerate() {
+            let (child_path, child_pattern) = match &child.key {
+                Some(key) => (join(&path, key), join(&pattern, key)),
+                None => (format!("{path}[{i}]"), format!("{pattern}[*]")),
+            };
+            payload += self.walk(child, child_path, child_pattern);
+        }
+        let total = node.end - node.offset;
+        let row = &mut self.rows[row];
+        row.count += 1;
+        row.total += total;
+        row.payload += payload;
+        if node.key.is_some() {
+            self.fields.push((total, path));
+        }
+        payload
+    }
+}
+
+/// Путь поля верхнего уровня
+fn key_path(node: &Node) -> String {
+    node.key.as_deref().map(crate::key_text).unwrap_or_default()
+}
+
+fn join(path: &str, key: &str) -> String {
+    match path.is_empty() {
+        true => crate::key_text(key),
+        false => format!("{path}.{}", crate::key_text(key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use custom_codec::{encode_message, Field, Value};
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    fn sample() -> Vec<u8> {
+        let tags = Value::List(vec![Value::String("a".into()), Value::String("bcd".into())]);
+        let user = Value::Message(vec![field("id", Value::Int64(7)), field("tags", tags)]);
+        encode_message(&[field("user", user), field("ok", Value::Bool(true))]).unwrap()
+    }
+
+    #[test]
+    fn rows_by_path() {
+        let data = sample();
+        let nodes = inspect(&data, &custom_codec::DecodeOptions::default()).unwrap();
+        let mut stats = Stats::default();
+        let payload: usize = nodes.iter().map(|n| stats.walk(n, key_path(n), key_path(n))).sum();
+        // Int64, две строки и Bool
+        assert_eq!(payload, 8 + 1 + 3 + 1);
+        let rows: Vec<_> =
+            stats.rows.iter().map(|r| (r.path.as_str(), r.count, r.payload)).collect();
+        assert_eq!(rows, [
+            ("user", 1, 12),
+            ("user.id", 1, 8),
+            ("user.tags", 1, 4),
+            ("user.tags[*]", 2, 4),
+            ("ok", 1, 1),
+        ]);
+        assert_eq!(stats.rows[3].total, 2 * 5 + 4);
+        let top_level = stats.rows.iter().filter(|r| !r.path.contains('.'));
+        assert_eq!(top_level.map(|r| r.total).sum::<usize>(), data.len());
+    }
+
+    #[test]
+    fn report() {
+        let path = std::env::temp_dir().join("ccodec-stats.bin");
+        std::fs::write(&path, sample()).unwrap();
+        let args = ["--top".to_owned(), "2".to_owned(), path.to_str().unwrap().to_owned()];
+        let mut out = Vec::new();
+        run(Args::parse(args, &["--top"]).unwrap(), &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let total = sample().len();
+        let overhead = total - 13;
+        assert!(text.starts_with(&format!("всего {total} байт: данные 13 (")));
+        assert!(text.contains(&format!("служебные {overhead} (")));
+        let largest = format!("крупнейшие поля:\n{:>9}  user\n{:>9}  user.tags\n", total - 12, 27);
+        assert!(text.ends_with(&largest));
+        std::fs::remove_file(path).unwrap();
+    }
+}
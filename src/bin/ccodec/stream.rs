@@ -0,0 +1,130 @@
+//! `ccodec stream`: поток рамок в JSON Lines и обратно
+//!
+//! С `--decode` из stdin читаются рамки `[длина u32 BE][сообщение]`, как у
+//! `FrameReader`, и каждое сообщение печатается строкой JSON; с `--encode`
+//! каждая непустая строка JSON становится рамкой. Вывод сбрасывается после
+//! каждой записи, поэтому команда годится для конвейеров и
+//! процессов-спутников, которые обмениваются сообщениями по одному.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use custom_codec::{
+    encode_enveloped_with, encode_message_with, message_from_json, message_to_json, write_frame,
+    EncodeOptions, FrameReader, JsonOptions,
+};
+
+use crate::args::Args;
+use crate::CliError;
+
+pub(crate) fn run(
+    mut args: Args,
+    input: impl Read,
+    out: &mut impl Write,
+) -> Result<(), CliError> {
+    match (args.flag("--decode"), args.flag("--encode")) {
+        (true, false) => decode(args, input, out),
+        (false, true) => encode(args, input, out),
+        _ => Err(CliError::Usage("нужен ровно один из флагов --decode и --encode".into())),
+    }
+}
+
+fn decode(mut args: Args, mut input: impl Read, out: &mut impl Write) -> Result<(), CliError> {
+    let opts = crate::decode_options(&mut args);
+    args.finish()?;
+
+    let mut reader = FrameReader::new(opts.max_total_len);
+    let mut buf = vec![0; 64 << 10];
+    let mut frames = 0;
+    loop {
+        let n = input.read(&mut buf).map_err(stdin_error)?;
+        if n == 0 {
+            break;
+        }
+        reader.feed(&buf[..n]);
+        while let Some(frame) = reader.next_frame() {
+            frames += 1;
+            let fields = crate::decode_input(&frame?, &opts).map_err(|e| CliError::Parse {
+                path: format!("рамка {frames}"),
+                message: format!("данные не декодируются: {e}"),
+            })?;
+            writeln!(out, "{}", message_to_json(&fields))?;
+            out.flush()?;
+        }
+    }
+    Ok(reader.finish()?)
+}
+
+fn encode(mut args: Args, input: impl Read, out: &mut impl Write) -> Result<(), CliError> {
+    let opts = EncodeOptions { profile: crate::profile(&mut args), ..EncodeOptions::default() };
+    let envelope = args.flag("--envelope");
+    let json_opts =
+        JsonOptions { float32: args.flag("--float32"), bytes_keys: args.values("--bytes") };
+    args.finish()?;
+
+    let mut frame = Vec::new();
+    for (i, line) in BufReader::new(input).lines().enumerate() {
+        let line = line.map_err(stdin_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let path = format!("строка {}", i + 1);
+        let json = serde_json::from_str(&line)
+            .map_err(|e| CliError::Parse { path: path.clone(), message: e.to_string() })?;
+        let fields = message_from_json(&json, &json_opts).map_err(|e| CliError::json(&path, e))?;
+        let data = match envelope {
+            true => encode_enveloped_with(&fields, &opts)?,
+            false => encode_message_with(&fields, &opts)?,
+        };
+        frame.clear();
+        write_frame(&data, &mut frame)?;
+        out.write_all(&frame)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn stdin_error(error: io::Error) -> CliError {
+    CliError::File { path: "stdin".to_owned(), error }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use custom_codec::{encode_framed, Field, Value};
+
+    fn stream(args: &[&str], input: &[u8]) -> Result<Vec<u8>, CliError> {
+        let args = Args::parse(args.iter().map(|s| s.to_string()), &["--bytes"]).unwrap();
+        let mut out = Vec::new();
+        run(args, input, &mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn roundtrip() {
+        // порядок ключей сохраняется в обе стороны
+        let lines = "{\"raw\":\"AQI=\",\"id\":1}\n\n{\"name\":\"Rust\"}\n";
+        let frames = stream(&["--encode", "--envelope", "--bytes", "raw"], lines.as_bytes());
+        let frames = frames.unwrap();
+        assert_eq!(stream(&["--decode"], &frames).unwrap(), lines.replace("\n\n", "\n").as_bytes());
+
+        let fields = [Field { key: "n".into(), value: Value::Int32(7) }];
+        let mut two = encode_framed(&fields).unwrap();
+        two.extend(encode_framed(&[]).unwrap());
+        assert_eq!(stream(&["--decode"], &two).unwrap(), b"{\"n\":7}\n{}\n");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(stream(&[], b""), Err(CliError::Usage(_))));
+        assert!(matches!(stream(&["--decode", "--encode"], b""), Err(CliError::Usage(_))));
+        let err = stream(&["--encode"], b"{}\n[1]\n").unwrap_err();
+        assert_eq!(err.to_string(), "строка 2: сообщение должно быть объектом");
+
+        let mut frames = encode_framed(&[]).unwrap();
+        frames.extend([0, 0, 0, 2, 4, 0]);
+        let err = stream(&["--decode"], &frames).unwrap_err();
+        assert!(err.to_string().starts_with("рамка 2: данные не декодируются"));
+        let err = stream(&["--decode"], &[0, 0, 0, 9, 1]).unwrap_err();
+        assert!(matches!(err, CliError::Decode(_)));
+    }
+}
@@ -0,0 +1,160 @@
+//! `ccodec validate`: проверка файлов по схеме
+//!
+//! Каждое нарушение — строка JSON (JSON Lines):
+//!
+//! ```text
+//! {"actual":"String","expected":"Int64","file":"a.bin","kind":"type_mismatch","path":"age"}
+//! {"file":"bad.bin","kind":"decode","message":"…","offset":17,"path":""}
+//! ```
+//!
+//! Виды: `missing`, `unknown`, `duplicate`, `type_mismatch`, `decode` и
+//! `deprecated`. Поля, как в `decode_with_schema`, сначала дополняются
+//! значениями по умолчанию. Код выхода 1 — хотя бы один файл не прошёл
+//! проверку; устаревшие поля его не меняют.
+
+use std::io::Write;
+use std::process::ExitCode;
+
+use custom_codec::{DecodeOptions, Schema, Violation, ViolationKind};
+use serde_json::{json, Value as Json};
+
+use crate::args::Args;
+use crate::CliError;
+
+pub(crate) fn run(mut args: Args, out: &mut impl Write) -> Result<ExitCode, CliError> {
+    let opts = crate::decode_options(&mut args);
+    let schema_path = args.value("--schema").ok_or_else(|| usage("не указан --schema"))?;
+    let name = args.value("--message");
+    let files = std::iter::from_fn(|| args.positional("файл").ok()).collect::<Vec<_>>();
+    args.finish()?;
+    if files.is_empty() {
+        return Err(usage("не указан файл"));
+    }
+
+    let schema = crate::load_schema(&schema_path, name.as_deref())?;
+
+    let mut valid = true;
+    for file in &files {
+        for mut record in check(&schema, &crate::read_input(file)?, &opts) {
+            valid &= record["kind"] == "deprecated";
+            record["file"] = Json::from(file.as_str());
+            writeln!(out, "{record}")?;
+        }
+    }
+    Ok(match valid {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::from(1),
+    })
+}
+
+fn usage(msg: &str) -> CliError {
+    CliError::Usage(msg.to_owned())
+}
+
+/// Записи о нарушениях в одном файле
+fn check(schema: &Schema, data: &[u8], opts: &DecodeOptions) -> Vec<Json> {
+    let mut fields = match crate::decode_input(data, opts) {
+        Ok(fields) => fields,
+        Err(e) => {
+            let (offset, message) = (e.offset(), e.to_string());
+            return vec![json!({
+                "path": "",
+                "kind": "decode",
+                "offset": offset,
+                "message": message,
+            })];
+        }
+    };
+    schema.apply_defaults(&mut fields);
+    let violations = schema.validate_message(&fields).err().unwrap_or_default();
+    violations.iter().chain(&schema.deprecations(&fields)).map(record).collect()
+}
+
+fn record(v: &Violation) -> Json {
+    let kind = match &v.kind {
+        ViolationKind::Missing => "missing",
+        ViolationKind::Unknown => "unknown",
+        ViolationKind::Duplicate => "duplicate",
+        ViolationKind::Deprecated => "deprecated",
+        ViolationKind::TypeMismatch { expected, found } => {
+            return json!({
+                "path": v.path,
+                "kind": "type_mismatch",
+                "expected": expected,
+                "actual": found,
+            })
+        }
+    };
+    json!({ "path": v.path, "kind": kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use custom_codec::{encode_message, Field, Value};
+
+    const SCHEMA: &str = "
+        message User {
+          required Int64 id;
+          optional Int32 age;
+          optional String login [deprecated];
+        }
+    ";
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    fn temp(name: &str, data: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("ccodec-validate-{name}"));
+        std::fs::write(&path, data).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    fn validate(args: &[&str]) -> (ExitCode, Vec<Json>) {
+        let args = Args::parse(args.iter().map(|s| s.to_string()), &["--schema", "--message"]);
+        let mut out = Vec::new();
+        let code = run(args.unwrap(), &mut out).unwrap();
+        let lines = out.split(|&b| b == b'\n').filter(|l| !l.is_empty());
+        (code, lines.map(|l| serde_json::from_slice(l).unwrap()).collect())
+    }
+
+    #[test]
+    fn reports_violations() {
+        let schema = temp("user.ccs", SCHEMA.as_bytes());
+        let good = vec![field("id", Value::Int64(1)), field("login", Value::String("x".into()))];
+        let good = temp("good.bin", &encode_message(&good).unwrap());
+        let bad = encode_message(&[field("age", Value::String("x".into()))]).unwrap();
+        let bad = temp("bad.bin", &bad);
+
+        let (code, records) = validate(&["--schema", &schema, &good]);
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(records, [json!({ "file": good, "path": "login", "kind": "deprecated" })]);
+
+        let (code, records) = validate(&["--schema", &schema, "--message", "User", &bad]);
+        assert_eq!(code, ExitCode::from(1));
+        assert!(records.contains(&json!({
+            "file": bad,
+            "path": "age",
+            "kind": "type_mismatch",
+            "expected": "Int32",
+            "actual": "String",
+        })));
+        assert!(records.contains(&json!({ "file": bad, "path": "id", "kind": "missing" })));
+    }
+
+    #[test]
+    fn decode_and_schema_errors() {
+        let schema = temp("errors.ccs", SCHEMA.as_bytes());
+        let broken = temp("broken.bin", &[4, 0, 0]);
+        let (code, records) = validate(&["--schema", &schema, &broken]);
+        assert_eq!(code, ExitCode::from(1));
+        assert_eq!(records[0]["kind"], "decode");
+        assert_eq!(records[0]["offset"], 1);
+
+        let args = ["--schema", &schema, "--message", "Nope", &broken].map(String::from);
+        let args = Args::parse(args, &["--schema", "--message"]).unwrap();
+        let err = run(args, &mut Vec::new()).unwrap_err();
+        assert!(err.to_string().ends_with("нет сообщения Nope"));
+    }
+}
@@ -0,0 +1,140 @@
+//! Построение сообщений цепочкой вызовов
+//!
+//! Каждый метод дописывает поле в конец, как `Vec::push`; повтор ключа
+//! даёт второе поле, а не замену. Вложенное сообщение строится
+//! замыканием над новым построителем.
+
+use crate::{Decimal, Field, Message, Timestamp, Value};
+
+/// Построитель [`Message`]
+///
+/// ```
+/// use custom_codec::{Message, Value};
+///
+/// let msg = Message::builder()
+///     .int32("age", 42)
+///     .string("name", "Rust")
+///     .message("addr", |b| b.string("city", "Oslo"))
+///     .build();
+/// assert_eq!(msg.get_path("addr.city"), Some(&Value::String("Oslo".into())));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageBuilder {
+    fields: Vec<Field>,
+}
+
+macro_rules! typed {
+    ($($name:ident($t:ty) => $variant:ident),* $(,)?) => {$(
+        pub fn $name(self, key: impl Into<String>, value: $t) -> Self {
+            self.value(key, Value::$variant(value))
+        }
+    )*};
+}
+
+impl MessageBuilder {
+    /// Поле с произвольным значением
+    pub fn value(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.fields.push(Field { key: key.into(), value: value.into() });
+        self
+    }
+
+    typed! {
+        bool(bool) => Bool,
+        int8(i8) => Int8,
+        int16(i16) => Int16,
+        int32(i32) => Int32,
+        int64(i64) => Int64,
+        uint8(u8) => UInt8,
+        uint16(u16) => UInt16,
+        uint32(u32) => UInt32,
+        uint64(u64) => UInt64,
+        float32(f32) => Float32,
+        float64(f64) => Float64,
+        timestamp(Timestamp) => Timestamp,
+        uuid([u8; 16]) => Uuid,
+        decimal(Decimal) => Decimal,
+    }
+
+    pub fn string(self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.value(key, Value::String(value.into()))
+    }
+
+    pub fn bytes(self, key: impl Into<String>, value: impl Into<Vec<u8>>) -> Self {
+        self.value(key, Value::Bytes(value.into()))
+    }
+
+    pub fn null(self, key: impl Into<String>) -> Self {
+        self.value(key, Value::Null)
+    }
+
+    /// Список из значений, приводимых к `Value`
+    pub fn list<V: Into<Value>>(
+        self,
+        key: impl Into<String>,
+        items: impl IntoIterator<Item = V>,
+    ) -> Self {
+        self.value(key, Value::List(items.into_iter().map(Into::into).collect()))
+    }
+
+    /// Вложенное сообщение, построенное замыканием `build`
+    pub fn message(
+        self,
+        key: impl Into<String>,
+        build: impl FnOnce(MessageBuilder) -> MessageBuilder,
+    ) -> Self {
+        let inner = build(MessageBuilder::default());
+        self.value(key, Value::Message(inner.fields))
+    }
+
+    pub fn build(self) -> Message {
+        Message::from(self.fields)
+    }
+}
+
+impl Message {
+    /// Построитель сообщения
+    pub fn builder() -> MessageBuilder {
+        MessageBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn builds_nested_message() {
+        let msg = Message::builder()
+            .int32("age", 42)
+            .string("name", "Rust")
+            .message("addr", |b| b.string("city", "Oslo").uint16("zip", 150))
+            .list("tags", ["a", "b"])
+            .bytes("raw", [1u8, 2])
+            .null("none")
+            .build();
+        let addr = vec![
+            field("city", Value::String("Oslo".into())),
+            field("zip", Value::UInt16(150)),
+        ];
+        let expected = vec![
+            field("age", Value::Int32(42)),
+            field("name", Value::String("Rust".into())),
+            field("addr", Value::Message(addr)),
+            field("tags", Value::List(vec![Value::String("a".into()), Value::String("b".into())])),
+            field("raw", Value::Bytes(vec![1, 2])),
+            field("none", Value::Null),
+        ];
+        assert_eq!(msg.into_fields(), expected);
+    }
+
+    #[test]
+    fn repeated_keys_and_values() {
+        let msg = Message::builder().value("n", 1u8).value("n", Some(2.5)).build();
+        assert_eq!(msg.fields(), [field("n", Value::UInt8(1)), field("n", Value::Float64(2.5))]);
+        assert_eq!(Message::builder().build(), Message::new());
+    }
+}
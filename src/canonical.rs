@@ -0,0 +1,222 @@
+//! Каноническое кодирование для подписей и дедупликации
+//!
+//! Каноническая форма — исходный профиль, где на каждом уровне поля
+//! упорядочены по ключу, записи `Value::Map` — по байтам закодированного
+//! ключа, ключи не повторяются, NaN приведён к одному представлению,
+//! а `-0.0` заменён на `0.0`. Порядок элементов `Value::List` сохраняется.
+
+use crate::decode::UNLIMITED;
+use crate::encode::encode_element;
+use crate::{
+    decode_message_with, encode_message, DecodeError, DecodeOptions, EncodeError, Field, Profile,
+    Value,
+};
+
+/// Приведение сообщения к канонической форме
+pub fn canonicalize(fields: &[Field]) -> Result<Vec<Field>, EncodeError> {
+    let fields = sort_fields(fields);
+    if let Some(w) = fields.windows(2).find(|w| w[0].key == w[1].key) {
+        return Err(EncodeError::DuplicateField { key: w[0].key.clone() });
+    }
+    for f in &fields {
+        check_value(&f.value)?;
+    }
+    Ok(fields)
+}
+
+/// Детерминированное кодирование: одинаковые сообщения дают одинаковые байты
+pub fn encode_canonical(fields: &[Field]) -> Result<Vec<u8>, EncodeError> {
+    encode_message(&canonicalize(fields)?)
+}
+
+/// Декодирование с отказом от любых данных не в канонической форме
+pub fn decode_canonical(data: &[u8]) -> Result<Vec<Field>, DecodeError> {
+    decode_canonical_with(data, &UNLIMITED)
+}
+
+/// То же, что [`decode_canonical`], с ограничениями; профиль из `opts`
+/// не используется, каноническая форма всегда в исходном профиле
+pub fn decode_canonical_with(
+    data: &[u8],
+    opts: &DecodeOptions,
+) -> Result<Vec<Field>, DecodeError> {
+    let opts = DecodeOptions { profile: Profile::STANDARD, ..opts.clone() };
+    let fields = decode_message_with(data, &opts)?;
+    // повторы не отбрасываются, чтобы найти их смещение ниже
+    let sorted = sort_fields(&fields);
+    let expected =
+        encode_message(&sorted).expect("длины декодированных данных помещаются в 4 байта");
+    if let Some(offset) = expected.iter().zip(data).position(|(a, b)| a != b) {
+        return Err(DecodeError::NonCanonical { offset });
+    }
+    // данные уже упорядочены, повторы стоят рядом
+    match duplicate_in_fields(&sorted, 0) {
+        Some(offset) => Err(DecodeError::DuplicateKey { offset }),
+        None => Ok(fields),
+    }
+}
+
+fn sort_fields(fields: &[Field]) -> Vec<Field> {
+    let mut out: Vec<Field> = fields
+        .iter()
+        .map(|f| Field { key: f.key.clone(), value: normalize(&f.value) })
+        .collect();
+    out.sort_by(|a, b| a.key.cmp(&b.key));
+    out
+}
+
+/// Нормализация значения без проверки повторов
+fn normalize(value: &Value) -> Value {
+    match value {
+        Value::Float32(f) if f.is_nan() => Value::Float32(f32::NAN),
+        Value::Float32(f) if *f == 0.0 => Value::Float32(0.0),
+        Value::Float64(f) if f.is_nan() => Value::Float64(f64::NAN),
+        Value::Float64(f) if *f == 0.0 => Value::Float64(0.0),
+        Value::Message(fields) => Value::Message(sort_fields(fields)),
+        Value::List(items) => Value::List(items.iter().map(normalize).collect()),
+        Value::Map(entries) => {
+            let mut entries: Vec<(Vec<u8>, Value, Value)> = entries
+                .iter()
+                .map(|(k, v)| {
+                    let k = normalize(k);
+                    (encode_element(&k), k, normalize(v))
+                })
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Map(entries.into_iter().map(|(_, k, v)| (k, v)).collect())
+        }
+        Value::Enum { variant, name, payload } => Value::Enum {
+            variant: *variant,
+            name: name.clone(),
+            payload: payload.as_ref().map(|p| Box::new(normalize(p))),
+        },
+        other => other.clone(),
+    }
+}
+
+/// Поиск повторов в нормализованном значении
+fn check_value(value: &Value) -> Result<(), EncodeError> {
+    match value {
+        Value::Message(fields) => {
+            if let Some(w) = fields.windows(2).find(|w| w[0].key == w[1].key) {
+                return Err(EncodeError::DuplicateField { key: w[0].key.clone() });
+            }
+            fields.iter().try_for_each(|f| check_value(&f.value))
+        }
+        Value::List(items) => items.iter().try_for_each(check_value),
+        Value::Map(entries) => {
+            let dup = entries.windows(2).find(|w| same_key(&w[0].0, &w[1].0));
+            if let Some(w) = dup {
+                return Err(EncodeError::DuplicateMapKey { key: w[0].0.clone() });
+            }
+            entries.iter().try_for_each(|(k, v)| check_value(k).and_then(|_| check_value(v)))
+        }
+        Value::Enum { payload: Some(p), .. } => check_value(p),
+        _ => Ok(()),
+    }
+}
+
+/// Ключи отображения сравниваются по закодированным байтам, так что NaN
+/// совпадает с NaN
+fn same_key(a: &Value, b: &Value) -> bool {
+    encode_element(a) == encode_element(b)
+}
+
+/// Смещение первого повторяющегося ключа; `pos` — начало первого поля
+fn duplicate_in_fields(fields: &[Field], mut pos: usize) -> Option<usize> {
+    for (i, f) in fields.iter().enumerate() {
+        if i > 0 && fields[i - 1].key == f.key {
+            return Some(pos);
+        }
+        if let Some(offset) = duplicate_in_value(&f.value, pos + 1 + 4 + f.key.len()) {
+            return Some(offset);
+        }
+        pos += f.encoded_len();
+    }
+    None
+}
+
+/// `pos` — начало префикса длины значения
+fn duplicate_in_value(value: &Value, pos: usize) -> Option<usize> {
+    let mut pos = pos + 4;
+    match value {
+        Value::Message(fields) => duplicate_in_fields(fields, pos),
+        Value::List(items) => items.iter().find_map(|item| {
+            let found = duplicate_in_value(item, pos + 1);
+            pos += 1 + item.encoded_len();
+            found
+        }),
+        Value::Map(entries) => entries.iter().enumerate().find_map(|(i, (k, v))| {
+            if i > 0 && same_key(&entries[i - 1].0, k) {
+                return Some(pos);
+            }
+            let val = pos + 1 + k.encoded_len();
+            let found =
+                duplicate_in_value(k, pos + 1).or_else(|| duplicate_in_value(v, val + 1));
+            pos = val + 1 + v.encoded_len();
+            found
+        }),
+        Value::Enum { name, payload: Some(p), .. } => {
+            duplicate_in_value(p, pos + 4 + 1 + name.as_ref().map_or(0, |n| 4 + n.len()) + 1)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn canonical_is_order_independent() {
+        let a = vec![
+            field("b", Value::Float64(-0.0)),
+            field(
+                "a",
+                Value::Map(vec![(Value::Int32(2), Value::Null), (Value::Int32(1), Value::Null)]),
+            ),
+            field("c", Value::Float32(-f32::NAN)),
+        ];
+        let mut b = a.clone();
+        b.reverse();
+        let enc = encode_canonical(&a).unwrap();
+        assert_eq!(enc, encode_canonical(&b).unwrap());
+        assert_eq!(decode_canonical(&enc).unwrap()[0].key, "a");
+
+        // обычное кодирование того же сообщения каноническим не является
+        assert!(matches!(
+            decode_canonical(&encode_message(&a).unwrap()),
+            Err(DecodeError::NonCanonical { .. })
+        ));
+    }
+
+    #[test]
+    fn duplicates_are_rejected() {
+        let fields = vec![field("k", Value::Int32(1)), field("k", Value::Int32(2))];
+        assert_eq!(
+            encode_canonical(&fields).unwrap_err(),
+            EncodeError::DuplicateField { key: "k".into() }
+        );
+        assert_eq!(
+            decode_canonical(&encode_message(&fields).unwrap()).unwrap_err(),
+            DecodeError::DuplicateKey { offset: 14 }
+        );
+
+        let map = Value::Map(vec![
+            (Value::Float64(0.0), Value::Null),
+            (Value::Float64(-0.0), Value::Null),
+        ]);
+        let fields = vec![field("m", Value::List(vec![map]))];
+        assert_eq!(
+            encode_canonical(&fields).unwrap_err(),
+            EncodeError::DuplicateMapKey { key: Value::Float64(0.0) }
+        );
+        let raw = encode_message(&[field("m", normalize(&fields[0].value))]).unwrap();
+        // m: 1 + 4 + 1 + 4, элемент списка: 1 + 4, запись: 1 + 4 + 8 + 1 + 4
+        assert_eq!(decode_canonical(&raw).unwrap_err(), DecodeError::DuplicateKey { offset: 33 });
+    }
+}
@@ -0,0 +1,333 @@
+//! Преобразование между `Value` и CBOR (RFC 8949) через `ciborium`
+//!
+//! Байтовые строки, отображения с произвольными ключами и вложенные
+//! сообщения переносятся без потерь. Типы, которых нет в CBOR, записываются
+//! тегами:
+//!
+//! - `Timestamp` — тег 1 (секунды от эпохи), если наносекунд нет, иначе
+//!   тег 1001 (RFC 9581) с отображением `{1: секунды, -9: наносекунды}`;
+//! - `Uuid` — тег 37 над 16 байтами;
+//! - `Decimal` — тег 4 `[-scale, мантисса]`, большая мантисса — тегами 2 и 3;
+//! - вариант перечисления — тег [`CBOR_ENUM_TAG`] вне реестра IANA над массивом
+//!   `[номер, имя или null]` с содержимым третьим элементом, если оно есть.
+//!
+//! При чтении целые становятся `Int64`, а не помещающиеся в него — `UInt64`;
+//! числа с плавающей точкой — `Float64`, так как CBOR хранит их в кратчайшей
+//! точной ширине. Отображение, все ключи которого строки, становится
+//! `Value::Message`, остальные — `Value::Map`.
+
+use std::fmt;
+
+use ciborium::value::{Integer, Value as Cbor};
+
+use crate::decimal::MAX_SCALE;
+use crate::{Decimal, Field, Timestamp, Value};
+
+/// Тег варианта перечисления
+pub const CBOR_ENUM_TAG: u64 = 0x6363_0010;
+
+const TAG_EPOCH: u64 = 1;
+const TAG_POS_BIGNUM: u64 = 2;
+const TAG_NEG_BIGNUM: u64 = 3;
+const TAG_DECIMAL: u64 = 4;
+const TAG_UUID: u64 = 37;
+const TAG_EXTENDED_TIME: u64 = 1001;
+
+/// Данные CBOR не переводятся в `Value`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CborError {
+    /// Неверная кодировка CBOR
+    Syntax(String),
+    /// Значение без аналога в формате: `what` — что именно
+    Unsupported { what: &'static str },
+    /// На верхнем уровне ожидалось отображение со строковыми ключами
+    NotAMessage,
+}
+
+impl fmt::Display for CborError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CborError::Syntax(msg) => write!(f, "неверный CBOR: {msg}"),
+            CborError::Unsupported { what } => write!(f, "не поддерживается: {what}"),
+            CborError::NotAMessage => {
+                f.write_str("на верхнем уровне ожидалось отображение со строковыми ключами")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CborError {}
+
+/// Сообщение как отображение CBOR
+pub fn encode_cbor(fields: &[Field]) -> Vec<u8> {
+    let mut out = Vec::new();
+    ciborium::into_writer(&message(fields), &mut out).expect("запись в Vec не завершается ошибкой");
+    out
+}
+
+/// Сообщение из отображения CBOR со строковыми ключами
+pub fn decode_cbor(data: &[u8]) -> Result<Vec<Field>, CborError> {
+    let cbor: Cbor = ciborium::from_reader(data).map_err(|e| CborError::Syntax(e.to_string()))?;
+    match value_from_cbor(&cbor)? {
+        Value::Message(fields) => Ok(fields),
+        _ => Err(CborError::NotAMessage),
+    }
+}
+
+fn message(fields: &[Field]) -> Cbor {
+    Cbor::Map(fields.iter().map(|f| (Cbor::Text(f.key.clone()), value_to_cbor(&f.value))).collect())
+}
+
+fn tag(tag: u64, value: Cbor) -> Cbor {
+    Cbor::Tag(tag, Box::new(value))
+}
+
+/// Целое CBOR; за пределами 64 бит — тег 2 или 3 над байтами числа
+fn int(i: i128) -> Cbor {
+    if let Ok(i) = Integer::try_from(i) {
+        return Cbor::Integer(i);
+    }
+    // отрицательное число записывается как -1 - n
+    let (tag_num, n) = if i < 0 { (TAG_NEG_BIGNUM, !i) } else { (TAG_POS_BIGNUM, i) };
+    let bytes = (n as u128).to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    tag(tag_num, Cbor::Bytes(bytes[start..].to_vec()))
+}
+
+pub fn value_to_cbor(value: &Value) -> Cbor {
+    match value {
+        Value::Int8(i) => Cbor::Integer((*i).into()),
+        Value::Int16(i) => Cbor::Integer((*i).into()),
+        Value::Int32(i) => Cbor::Integer((*i).into()),
+        Value::Int64(i) => Cbor::Integer((*i).into()),
+        Value::UInt8(u) => Cbor::Integer((*u).into()),
+        Value::UInt16(u) => Cbor::Integer((*u).into()),
+        Value::UInt32(u) => Cbor::Integer((*u).into()),
+        Value::UInt64(u) => Cbor::Integer((*u).into()),
+        Value::Float32(f) => Cbor::Float(*f as f64),
+        Value::Float64(f) => Cbor::Float(*f),
+        Value::Bool(b) => Cbor::Bool(*b),
+        Value::String(s) => Cbor::Text(s.clone()),
+        Value::Bytes(b) => Cbor::Bytes(b.clone()),
+        Value::Null => Cbor::Null,
+        Value::Message(fields) => message(fields),
+        Value::List(items) => Cbor::Array(items.iter().map(value_to_cbor).collect()),
+        Value::Map(entries) => Cbor::Map(
+            entries.iter().map(|(k, v)| (value_to_cbor(k), value_to_cbor(v))).collect(),
+        ),
+        Value::Timestamp(ts) if ts.nanos == 0 => tag(TAG_EPOCH, Cbor::Integer(ts.secs.into())),
+        Value::Timestamp(ts) => tag(
+            TAG_EXTENDED_TIME,
+            Cbor::Map(vec![
+                (Cbor::Integer(1.into()), Cbor::Integer(ts.secs.into())),
+                (Cbor::Integer((-9).into()), Cbor::Integer(ts.nanos.into())),
+            ]),
+        ),
+        Value::Uuid(u) => tag(TAG_UUID, Cbor::Bytes(u.to_vec())),
+        Value::Decimal(d) => tag(
+            TAG_DECIMAL,
+            Cbor::Array(vec![Cbor::Integer((-(d.scale as i64)).into()), int(d.mantissa)]),
+        ),
+        Value::Enum { variant, name, payload } => {
+            let mut items = vec![
+                Cbor::Integer((*variant).into()),
+                name.as_ref().map_or(Cbor::Null, |n| Cbor::Text(n.clone())),
+            ];
+            items.extend(payload.as_deref().map(value_to_cbor));
+            tag(CBOR_ENUM_TAG, Cbor::Array(items))
+        }
+        // тега для неизвестного типа нет: код и данные как сообщение
+        Value::Unknown { type_code, raw } => Cbor::Map(vec![
+            (Cbor::Text("type_code".into()), Cbor::Integer((*type_code).into())),
+            (Cbor::Text("raw".into()), Cbor::Bytes(raw.clone())),
+        ]),
+    }
+}
+
+fn unsupported<T>(what: &'static str) -> Result<T, CborError> {
+    Err(CborError::Unsupported { what })
+}
+
+/// Целое, в том числе большое (теги 2 и 3); `None` — не целое
+fn integer(cbor: &Cbor) -> Result<Option<i128>, CborError> {
+    match cbor {
+        Cbor::Integer(i) => Ok(Some(i128::from(*i))),
+        Cbor::Tag(TAG_POS_BIGNUM, v) => bignum(false, v).map(Some),
+        Cbor::Tag(TAG_NEG_BIGNUM, v) => bignum(true, v).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Содержимое тега 2 или 3: байты числа, старшие первыми
+fn bignum(negative: bool, bytes: &Cbor) -> Result<i128, CborError> {
+    let Cbor::Bytes(bytes) = bytes else { return unsupported("большое целое не из байт") };
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    let digits = &bytes[start..];
+    if digits.len() > 16 {
+        return unsupported("целое больше 128 бит");
+    }
+    let mut buf = [0u8; 16];
+    buf[16 - digits.len()..].copy_from_slice(digits);
+    match i128::try_from(u128::from_be_bytes(buf)) {
+        // -1 - n
+        Ok(n) if negative => Ok(!n),
+        Ok(n) => Ok(n),
+        Err(_) => unsupported("целое больше 128 бит"),
+    }
+}
+
+pub fn value_from_cbor(cbor: &Cbor) -> Result<Value, CborError> {
+    Ok(match cbor {
+        Cbor::Integer(i) => {
+            let i = i128::from(*i);
+            match (i64::try_from(i), u64::try_from(i)) {
+                (Ok(i), _) => Value::Int64(i),
+                (_, Ok(u)) => Value::UInt64(u),
+                _ => return unsupported("целое меньше i64::MIN"),
+            }
+        }
+        Cbor::Float(f) => Value::Float64(*f),
+        Cbor::Bool(b) => Value::Bool(*b),
+        Cbor::Text(s) => Value::String(s.clone()),
+        Cbor::Bytes(b) => Value::Bytes(b.clone()),
+        Cbor::Null => Value::Null,
+        Cbor::Array(items) => {
+            Value::List(items.iter().map(value_from_cbor).collect::<Result<_, _>>()?)
+        }
+        Cbor::Map(entries) if entries.iter().all(|(k, _)| k.is_text()) => Value::Message(
+            entries
+                .iter()
+                .map(|(k, v)| {
+                    let key = k.as_text().unwrap_or_default().to_owned();
+                    Ok(Field { key, value: value_from_cbor(v)? })
+                })
+                .collect::<Result<_, CborError>>()?,
+        ),
+        Cbor::Map(entries) => Value::Map(
+            entries
+                .iter()
+                .map(|(k, v)| Ok((value_from_cbor(k)?, value_from_cbor(v)?)))
+                .collect::<Result<_, CborError>>()?,
+        ),
+        Cbor::Tag(tag, inner) => tagged(*tag, inner)?,
+        _ => return unsupported("простое значение CBOR"),
+    })
+}
+
+fn tagged(tag: u64, inner: &Cbor) -> Result<Value, CborError> {
+    match (tag, inner) {
+        (TAG_POS_BIGNUM | TAG_NEG_BIGNUM, _) => {
+            match u64::try_from(bignum(tag == TAG_NEG_BIGNUM, inner)?) {
+                Ok(u) => Ok(Value::UInt64(u)),
+                Err(_) => unsupported("большое целое вне u64"),
+            }
+        }
+        (TAG_EPOCH, Cbor::Integer(secs)) => match i64::try_from(*secs) {
+            Ok(secs) => Ok(Value::Timestamp(Timestamp { secs, nanos: 0 })),
+            Err(_) => unsupported("время вне диапазона"),
+        },
+        (TAG_EPOCH, Cbor::Float(secs)) => {
+            let nanos = (secs * 1e9).round();
+            match Timestamp::from_unix_nanos(nanos as i128) {
+                Some(ts) if nanos.is_finite() => Ok(Value::Timestamp(ts)),
+                _ => unsupported("время вне диапазона"),
+            }
+        }
+        (TAG_EXTENDED_TIME, Cbor::Map(entries)) => {
+            let get = |key: i64| {
+                let found = entries.iter().find(|(k, _)| *k == Cbor::Integer(key.into()));
+                found.and_then(|(_, v)| v.as_integer()).map(i128::from)
+            };
+            let secs = get(1).and_then(|s| i64::try_from(s).ok());
+            let nanos = get(-9).unwrap_or(0);
+            match secs.zip(u32::try_from(nanos).ok()).and_then(|(s, n)| Timestamp::new(s, n)) {
+                Some(ts) => Ok(Value::Timestamp(ts)),
+                None => unsupported("расширенное время без секунд или вне диапазона"),
+            }
+        }
+        (TAG_UUID, Cbor::Bytes(b)) => match <[u8; 16]>::try_from(b.as_slice()) {
+            Ok(u) => Ok(Value::Uuid(u)),
+            Err(_) => unsupported("UUID не из 16 байт"),
+        },
+        (TAG_DECIMAL, Cbor::Array(parts)) if parts.len() == 2 => {
+            let exp = integer(&parts[0])?;
+            let mantissa = integer(&parts[1])?;
+            let scale = exp.and_then(|e| u8::try_from(-e).ok()).filter(|s| *s <= MAX_SCALE);
+            match scale.zip(mantissa) {
+                Some((scale, mantissa)) => Ok(Value::Decimal(Decimal { mantissa, scale })),
+                None => unsupported("десятичная дробь вне диапазона"),
+            }
+        }
+        (CBOR_ENUM_TAG, Cbor::Array(parts)) if matches!(parts.len(), 2 | 3) => {
+            let variant = integer(&parts[0])?.and_then(|v| u32::try_from(v).ok());
+            let name = match &parts[1] {
+                Cbor::Null => None,
+                Cbor::Text(name) => Some(name.clone()),
+                _ => return unsupported("имя варианта не строка"),
+            };
+            let payload = parts.get(2).map(value_from_cbor).transpose()?.map(Box::new);
+            match variant {
+                Some(variant) => Ok(Value::Enum { variant, name, payload }),
+                None => unsupported("номер варианта вне u32"),
+            }
+        }
+        _ => unsupported("тег CBOR"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn cbor_roundtrip() {
+        let fields = vec![
+            field("id", Value::Int64(-7)),
+            field("big", Value::UInt64(u64::MAX)),
+            field("blob", Value::Bytes(vec![0, 255])),
+            field("by_id", Value::Map(vec![(Value::Int64(1), Value::String("a".into()))])),
+            field("inner", Value::Message(vec![field("ok", Value::Bool(true))])),
+            field("items", Value::List(vec![Value::Null, Value::Float64(0.5)])),
+            field("at", Value::Timestamp(Timestamp { secs: 5, nanos: 0 })),
+            field("at_ns", Value::Timestamp(Timestamp { secs: -5, nanos: 7 })),
+            field("id2", Value::Uuid([7; 16])),
+            field("sum", Value::Decimal(Decimal { mantissa: -125, scale: 2 })),
+            field("huge", Value::Decimal(Decimal { mantissa: i128::MIN, scale: 38 })),
+            field(
+                "e",
+                Value::Enum {
+                    variant: 2,
+                    name: Some("Circle".into()),
+                    payload: Some(Box::new(Value::Float64(1.5))),
+                },
+            ),
+            field("unit", Value::Enum { variant: 0, name: None, payload: None }),
+        ];
+        let enc = encode_cbor(&fields);
+        assert_eq!(decode_cbor(&enc).unwrap(), fields);
+
+        // ширина чисел не сохраняется
+        let narrow = [field("n", Value::UInt8(1)), field("f", Value::Float32(0.5))];
+        let wide = [field("n", Value::Int64(1)), field("f", Value::Float64(0.5))];
+        assert_eq!(decode_cbor(&encode_cbor(&narrow)).unwrap(), wide);
+    }
+
+    #[test]
+    fn cbor_errors() {
+        let mut list = Vec::new();
+        ciborium::into_writer(&Cbor::Array(vec![]), &mut list).unwrap();
+        assert_eq!(decode_cbor(&list), Err(CborError::NotAMessage));
+        assert!(matches!(decode_cbor(&[0xff]), Err(CborError::Syntax(_))));
+
+        let bad_uuid = tag(TAG_UUID, Cbor::Bytes(vec![1, 2]));
+        let err = CborError::Unsupported { what: "UUID не из 16 байт" };
+        assert_eq!(value_from_cbor(&bad_uuid), Err(err));
+        assert_eq!(value_from_cbor(&tag(99, Cbor::Null)), Err(CborError::Unsupported {
+            what: "тег CBOR"
+        }));
+    }
+}
@@ -0,0 +1,151 @@
+//! Реализация `tokio_util::codec` для `Framed`, `FramedRead` и `FramedWrite`
+//!
+//! Отдельная рамка не нужна: заголовок поля уже содержит длины ключа и
+//! значения, по которым видна граница поля в потоке.
+
+use ::bytes::BytesMut;
+use tokio_util::codec;
+
+use crate::decode::{Decoder, Reader};
+use crate::{
+    encode_field_to_bytes_with, DecodeError, DecodeOptions, EncodeError, EncodeOptions, Field,
+};
+
+/// Кодек полей для `Framed<TcpStream, CustomCodec>`
+///
+/// Профили в настройках кодирования и декодирования задаются отдельно и
+/// должны совпадать с профилем собеседника.
+#[derive(Debug, Clone, Default)]
+pub struct CustomCodec {
+    encode: EncodeOptions,
+    decode: DecodeOptions,
+    /// смещение начала необработанных данных от начала потока
+    consumed: usize,
+}
+
+impl CustomCodec {
+    /// Кодек со стандартным профилем и ограничениями `DecodeOptions::default()`
+    pub fn new() -> Self {
+        CustomCodec::default()
+    }
+
+    /// Ограничения декодирования применяются к каждому полю и проверяются
+    /// по заголовку, до накопления данных поля
+    pub fn with_options(encode: EncodeOptions, decode: DecodeOptions) -> Self {
+        CustomCodec { encode, decode, consumed: 0 }
+    }
+}
+
+impl codec::Decoder for CustomCodec {
+    type Item = Field;
+    type Error = DecodeError;
+
+    /// Смещения в ошибках отсчитываются от начала потока
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Field>, DecodeError> {
+        let mut dec = Decoder::new(Reader::new(src), &self.decode);
+        let end = match dec.field_end() {
+            Ok(end) if end <= src.len() => end,
+            Ok(end) => {
+                src.reserve(end - src.len());
+                return Ok(None);
+            }
+            Err(DecodeError::UnexpectedEof { .. }) => {
+                let missing = dec.input.missing();
+                src.reserve(missing);
+                return Ok(None);
+            }
+            Err(e) => return Err(e.shifted(self.consumed)),
+        };
+        let frame = src.split_to(end);
+        let field = Decoder::new(Reader::new(&frame), &self.decode)
+            .read_field()
+            .map_err(|e| e.shifted(self.consumed))?;
+        self.consumed += end;
+        Ok(Some(field))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Field>, DecodeError> {
+        match self.decode(src)? {
+            None if !src.is_empty() => {
+                Err(DecodeError::UnexpectedEof { offset: self.consumed + src.len() })
+            }
+            field => Ok(field),
+        }
+    }
+}
+
+impl codec::Encoder<Field> for CustomCodec {
+    type Error = EncodeError;
+
+    fn encode(&mut self, field: Field, dst: &mut BytesMut) -> Result<(), EncodeError> {
+        encode_field_to_bytes_with(&field, dst, &self.encode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode_message_with, Limit, Profile, Value};
+    use tokio_util::codec::{Decoder as _, Encoder as _};
+
+    fn sample() -> Vec<Field> {
+        vec![
+            Field { key: "a".into(), value: Value::String("first".into()) },
+            Field { key: "flag".into(), value: Value::Bool(true) },
+            Field {
+                key: "m".into(),
+                value: Value::Message(vec![Field { key: "x".into(), value: Value::Int64(-3) }]),
+            },
+        ]
+    }
+
+    #[test]
+    fn partial_frames() {
+        for profile in [Profile::STANDARD, Profile::compact()] {
+            let encode = EncodeOptions { profile, ..EncodeOptions::default() };
+            let decode = DecodeOptions { profile, ..DecodeOptions::default() };
+            let mut codec = CustomCodec::with_options(encode.clone(), decode);
+            let mut out = BytesMut::new();
+            for f in sample() {
+                codec.encode(f, &mut out).unwrap();
+            }
+            assert_eq!(out, encode_message_with(&sample(), &encode).unwrap());
+
+            let mut src = BytesMut::new();
+            let mut fields = Vec::new();
+            for b in out.iter() {
+                src.extend_from_slice(&[*b]);
+                while let Some(f) = codec.decode(&mut src).unwrap() {
+                    fields.push(f);
+                }
+            }
+            assert_eq!(fields, sample());
+            assert_eq!(codec.decode_eof(&mut src), Ok(None));
+        }
+    }
+
+    #[test]
+    fn errors_offsets_in_stream() {
+        let mut codec = CustomCodec::new();
+        let mut src = BytesMut::new();
+        codec.encode(sample()[0].clone(), &mut src).unwrap();
+        let first = src.len();
+        codec.encode(sample()[1].clone(), &mut src).unwrap();
+        src.truncate(src.len() - 1);
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(sample()[0].clone()));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        assert_eq!(
+            codec.decode_eof(&mut src),
+            Err(DecodeError::UnexpectedEof { offset: first + src.len() })
+        );
+
+        // длина значения отвергается по заголовку, не дожидаясь данных
+        let opts = DecodeOptions { max_value_len: 2, ..DecodeOptions::default() };
+        let mut codec = CustomCodec::with_options(EncodeOptions::default(), opts);
+        let mut src = BytesMut::from(&[5, 0, 0, 0, 1, b'b', 0, 0, 1, 0][..]);
+        assert_eq!(
+            codec.decode(&mut src),
+            Err(DecodeError::LimitExceeded { offset: 6, limit: Limit::ValueLength })
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! Генерация типизированных структур из файла схем
+//!
+//! Файл схем на языке [`parse_schemas`](crate::parse_schemas) превращается в
+//! код Rust: для каждого сообщения — структура с полями, `to_fields` и
+//! `from_fields`, `encode` и `decode` и реализации [`Encode`](crate::Encode)
+//! и [`Decode`](crate::Decode). Функции рассчитаны на вызов из `build.rs`:
+//!
+//! ```no_run
+//! // build.rs
+//! let out_dir = std::env::var("OUT_DIR").unwrap();
+//! custom_codec::codegen::compile("user.ccs", &out_dir).unwrap();
+//! println!("cargo:rerun-if-changed=user.ccs");
+//! ```
+//!
+//! ```ignore
+//! // src/lib.rs
+//! include!(concat!(env!("OUT_DIR"), "/user.rs"));
+//! ```
+//!
+//! Имена полей — ключи в `snake_case`; ключ, не являющийся именем Rust,
+//! переписывается: `"display name"` становится полем `display_name`.
+//! Необязательные поля — `Option`, `Bytes` — `Vec<u8>`, `Uuid` — `[u8; 16]`,
+//! `Map<K, V>` — `Vec<(K, V)>` в порядке пар, `Any` и `Enum` — сам `Value`.
+//! Отсутствующее необязательное поле не записывается; при чтении лишние
+//! поля пропускаются, `Null` в необязательном поле читается как `None`,
+//! а у поля с `[default = …]` — как значение по умолчанию.
+
+use std::fmt;
+use std::fmt::Write;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    parse_schemas, DecodeError, Schema, SchemaParseError, SchemaType, Schemas, ValueError,
+};
+
+/// Код не сгенерирован
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodegenError {
+    /// Файл схем не прочитан или код не записан
+    Io { path: PathBuf, kind: io::ErrorKind },
+    /// Файл схем не разбирается
+    Parse(SchemaParseError),
+    /// Имя сообщения не годится для имени структуры
+    InvalidName { name: String },
+    /// Два ключа сообщения дают одно имя поля
+    NameClash { message: String, key: String },
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodegenError::Io { path, kind } => write!(f, "{}: {kind}", path.display()),
+            CodegenError::Parse(e) => write!(f, "схема: {e}"),
+            CodegenError::InvalidName { name } => {
+                write!(f, "сообщение {name} не может быть структурой Rust")
+            }
+            CodegenError::NameClash { message, key } => {
+                write!(f, "ключ {key:?} в {message} совпадает с другим по имени поля")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodegenError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SchemaParseError> for CodegenError {
+    fn from(e: SchemaParseError) -> Self {
+        CodegenError::Parse(e)
+    }
+}
+
+/// Ошибка `decode` сгенерированной структуры
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedDecodeError {
+    /// Байты не декодируются в сообщение
+    Decode(DecodeError),
+    /// Поля сообщения не подходят структуре
+    Value(ValueError),
+}
+
+impl fmt::Display for TypedDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypedDecodeError::Decode(e) => e.fmt(f),
+            TypedDecodeError::Value(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TypedDecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TypedDecodeError::Decode(e) => Some(e),
+            TypedDecodeError::Value(e) => Some(e),
+        }
+    }
+}
+
+impl From<DecodeError> for TypedDecodeError {
+    fn from(e: DecodeError) -> Self {
+        TypedDecodeError::Decode(e)
+    }
+}
+
+impl From<ValueError> for TypedDecodeError {
+    fn from(e: ValueError) -> Self {
+        TypedDecodeError::Value(e)
+    }
+}
+
+/// Код для файла схем `schema` в `out_dir`/<имя файла>.rs
+///
+/// Возвращает путь записанного файла.
+pub fn compile(
+    schema: impl AsRef<Path>,
+    out_dir: impl AsRef<Path>,
+) -> Result<PathBuf, CodegenError> {
+    let schema = schema.as_ref();
+    let io_error = |path: &Path, e: io::Error| CodegenError::Io {
+        path: path.to_owned(),
+        kind: e.kind(),
+    };
+    let text = std::fs::read_to_string(schema).map_err(|e| io_error(schema, e))?;
+    let code = generate(&parse_schemas(&text)?)?;
+    let stem = schema.file_stem().unwrap_or(schema.as_os_str());
+    let out = out_dir.as_ref().join(format!("{}.rs", stem.to_string_lossy()));
+    std::fs::write(&out, code).map_err(|e| io_error(&out, e))?;
+    Ok(out)
+}
+
+/// Код структур для всех схем
+pub fn generate(schemas: &Schemas) -> Result<String, CodegenError> {
+    let mut out = String::from("// Сгенерировано custom_codec::codegen, не редактировать\n");
+    for schema in schemas {
+        out.push('\n');
+        message(&mut out, schema)?;
+    }
+    Ok(out)
+}
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do",
+    "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized",
+    "use", "virtual", "where", "while", "yield",
+];
+
+/// Имя поля Rust для ключа сообщения
+fn field_name(key: &str) -> String {
+    let mut name = String::with_capacity(key.len());
+    let mut prev_lower = false;
+    for c in key.chars() {
+        if c.is_uppercase() && prev_lower {
+            name.push('_');
+        }
+        prev_lower = c.is_lowercase() || c.is_numeric();
+        if c.is_alphanumeric() {
+            name.extend(c.to_lowercase());
+        } else if !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    if name.is_empty() || name.starts_with(|c: char| c.is_numeric()) {
+        name.insert(0, '_');
+    }
+    match name.as_str() {
+        "_" | "self" | "super" | "crate" => name.push('_'),
+        kw if KEYWORDS.contains(&kw) => name.insert_str(0, "r#"),
+        _ => {}
+    }
+    name
+}
+
+fn struct_name(schema: &Schema) -> Result<&str, CodegenError> {
+    let name = schema.name().expect("схемы из parse_schemas именованы");
+    if KEYWORDS.contains(&name) || matches!(name, "self" | "Self" | "super" | "crate" | "_") {
+        return Err(CodegenError::InvalidName { name: name.to_owned() });
+    }
+    Ok(name)
+}
+
+fn rust_type(ty: &SchemaType) -> Result<String, CodegenError> {
+    Ok(match ty {
+        SchemaType::Any | SchemaType::Enum => "::custom_codec::Value".into(),
+        SchemaType::Bool => "bool".into(),
+        SchemaType::Int8 => "i8".into(),
+        SchemaType::Int16 => "i16".into(),
+        SchemaType::Int32 => "i32".into(),
+        SchemaType::Int64 => "i64".into(),
+        SchemaType::UInt8 => "u8".into(),
+        SchemaType::UInt16 => "u16".into(),
+        SchemaType::UInt32 => "u32".into(),
+        SchemaType::UInt64 => "u64".into(),
+        SchemaType::Float32 => "f32".into(),
+        SchemaType::Float64 => "f64".into(),
+        SchemaType::String => "::std::string::String".into(),
+        SchemaType::Bytes => "::std::vec::Vec<u8>".into(),
+        SchemaType::Timestamp => "::custom_codec::Timestamp".into(),
+        SchemaType::Uuid => "[u8; 16]".into(),
+        SchemaType::Decimal => "::custom_codec::Decimal".into(),
+        SchemaType::Message(schema) => struct_name(schema)?.to_owned(),
+        SchemaType::List(item) => format!("::std::vec::Vec<{}>", rust_type(item)?),
+        SchemaType::Map(k, v) => {
+            format!("::std::vec::Vec<({}, {})>", rust_type(k)?, rust_type(v)?)
+        }
+    })
+}
+
+/// Выражение `Value` из ссылки `r` на значение типа `ty`
+fn encode_call(ty: &SchemaType, r: &str) -> String {
+    match ty {
+        SchemaType::Bytes => format!("::custom_codec::Value::Bytes({r}.clone())"),
+        SchemaType::Uuid => format!("::custom_codec::Value::Uuid(*{r})"),
+        SchemaType::List(item) => format!(
+            "::custom_codec::Value::List({r}.iter().map({}).collect())",
+            encode_fn(item)
+        ),
+        SchemaType::Map(k, v) => format!(
+            "::custom_codec::Value::Map({r}.iter().map(|(__k, __v)| ({}, {})).collect())",
+            encode_call(k, "__k"),
+            encode_call(v, "__v"),
+        ),
+        _ => format!("::custom_codec::Encode::encode_value({r})"),
+    }
+}
+
+/// Функция из ссылки на значение типа `ty` в `Value`
+fn encode_fn(ty: &SchemaType) -> String {
+    match ty {
+        SchemaType::Bytes | SchemaType::Uuid | SchemaType::List(_) | SchemaType::Map(..) => {
+            format!("|__x| {}", encode_call(ty, "__x"))
+        }
+        _ => "::custom_codec::Encode::encode_value".into(),
+    }
+}
+
+/// Выражение `Result<T, ValueError>` из ссылки `r` на `Value`
+fn decode_call(ty: &SchemaType, r: &str) -> String {
+    match ty {
+        SchemaType::List(item) => {
+            format!("::custom_codec::__private::decode_list({r}, {})", decode_fn(item))
+        }
+        SchemaType::Map(k, v) => format!(
+            "::custom_codec::__private::decode_entries({r}, {}, {})",
+            decode_fn(k),
+            decode_fn(v)
+        ),
+        _ => format!("{}({r})", decode_fn(ty)),
+    }
+}
+
+/// Функция из ссылки на `Value` в `Result<T, ValueError>`
+fn decode_fn(ty: &SchemaType) -> String {
+    match ty {
+        SchemaType::Bytes => "::custom_codec::__private::decode_bytes".into(),
+        SchemaType::Uuid => "::custom_codec::__private::decode_uuid".into(),
+        SchemaType::List(_) | SchemaType::Map(..) => format!("|__v| {}", decode_call(ty, "__v")),
+        _ => "::custom_codec::Decode::decode_value".into(),
+    }
+}
+
+/// Структура и её методы для одной схемы
+fn message(out: &mut String, schema: &Schema) -> Result<(), CodegenError> {
+    let name = struct_name(schema)?;
+    let mut names: Vec<String> = Vec::with_capacity(schema.len());
+    for f in schema.fields() {
+        let field = field_name(&f.key);
+        if names.contains(&field) {
+            let (message, key) = (name.to_owned(), f.key.clone());
+            return Err(CodegenError::NameClash { message, key });
+        }
+        names.push(field);
+    }
+    let fields: Vec<_> = schema.fields().iter().zip(&names).collect();
+
+    writeln!(out, "/// Сообщение `{name}`").unwrap();
+    writeln!(out, "#[derive(Debug, Clone, PartialEq)]").unwrap();
+    writeln!(out, "pub struct {name} {{").unwrap();
+    for (f, field) in &fields {
+        let ty = rust_type(&f.ty)?;
+        if f.required {
+            writeln!(out, "    pub {field}: {ty},").unwrap();
+        } else {
+            writeln!(out, "    pub {field}: ::std::option::Option<{ty}>,").unwrap();
+        }
+    }
+    out.push_str("}\n\n");
+
+    writeln!(out, "impl {name} {{").unwrap();
+    out.push_str("    pub fn to_fields(&self) -> ::std::vec::Vec<::custom_codec::Field> {\n");
+    writeln!(out, "        let {name} {{ {} }} = self;", names.join(", ")).unwrap();
+    let len = fields.len();
+    writeln!(out, "        let mut __fields = ::std::vec::Vec::with_capacity({len});").unwrap();
+    for (f, field) in &fields {
+        let push = |value: &str| {
+            format!(
+                "__fields.push(::custom_codec::Field {{ \
+                 key: ::std::string::String::from({:?}), value: {value} }});",
+                f.key
+            )
+        };
+        if f.required {
+            writeln!(out, "        {}", push(&encode_call(&f.ty, field))).unwrap();
+        } else {
+            writeln!(out, "        if let ::std::option::Option::Some(__x) = {field} {{").unwrap();
+            writeln!(out, "            {}", push(&encode_call(&f.ty, "__x"))).unwrap();
+            out.push_str("        }\n");
+        }
+    }
+    out.push_str("        __fields\n    }\n\n");
+
+    out.push_str(
+        "    pub fn from_fields(\n        __fields: &[::custom_codec::Field],\n    \
+         ) -> ::std::result::Result<Self, ::custom_codec::ValueError> {\n",
+    );
+    writeln!(out, "        ::std::result::Result::Ok({name} {{").unwrap();
+    for (f, field) in &fields {
+        let key = &f.key;
+        let in_field = format!(".map_err(|__e| __e.in_field({key:?}))?");
+        if f.required {
+            let value = format!("::custom_codec::__private::required_field(__fields, {key:?})?");
+            writeln!(out, "            {field}: {}{in_field},", decode_call(&f.ty, &value))
+                .unwrap();
+        } else {
+            let find = format!("::custom_codec::__private::find_field(__fields, {key:?})");
+            writeln!(out, "            {field}: match {find} {{").unwrap();
+            out.push_str(
+                "                ::std::option::Option::None\n                \
+                 | ::std::option::Option::Some(::custom_codec::Value::Null) => ",
+            );
+            match &f.default {
+                Some(default) => {
+                    let text = crate::value_to_text(default);
+                    let value = format!("&::custom_codec::__private::schema_default({text:?})");
+                    writeln!(
+                        out,
+                        "::std::option::Option::Some({}{in_field}),",
+                        decode_call(&f.ty, &value)
+                    )
+                    .unwrap();
+                }
+                None => out.push_str("::std::option::Option::None,\n"),
+            }
+            writeln!(
+                out,
+                "                ::std::option::Option::Some(__v) => \
+                 ::std::option::Option::Some({}{in_field}),",
+                decode_call(&f.ty, "__v")
+            )
+            .unwrap();
+            out.push_str("            },\n");
+        }
+    }
+    out.push_str("        })\n    }\n\n");
+    out.push_str(
+        "    pub fn encode(&self) -> \
+         ::std::result::Result<::std::vec::Vec<u8>, ::custom_codec::EncodeError> {\n        \
+         ::custom_codec::encode_message(&self.to_fields())\n    }\n\n",
+    );
+    out.push_str(
+        "    pub fn decode(\n        data: &[u8],\n    \
+         ) -> ::std::result::Result<Self, ::custom_codec::codegen::TypedDecodeError> {\n        \
+         let fields = ::custom_codec::decode_message(data)?;\n        \
+         ::std::result::Result::Ok(Self::from_fields(&fields)?)\n    }\n}\n\n",
+    );
+
+    writeln!(out, "impl ::custom_codec::Encode for {name} {{").unwrap();
+    out.push_str(
+        "    fn encode_value(&self) -> ::custom_codec::Value {\n        \
+         ::custom_codec::Value::Message(self.to_fields())\n    }\n}\n\n",
+    );
+    writeln!(out, "impl ::custom_codec::Decode for {name} {{").unwrap();
+    out.push_str(
+        "    fn decode_value(\n        v: &::custom_codec::Value,\n    \
+         ) -> ::std::result::Result<Self, ::custom_codec::ValueError> {\n        \
+         Self::from_fields(::custom_codec::__private::message_fields(v)?)\n    }\n}\n",
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_names() {
+        assert_eq!(field_name("id"), "id");
+        assert_eq!(field_name("display name"), "display_name");
+        assert_eq!(field_name("createdAt"), "created_at");
+        assert_eq!(field_name("type"), "r#type");
+        assert_eq!(field_name("self"), "self_");
+        assert_eq!(field_name("2fa"), "_2fa");
+        assert_eq!(field_name("--"), "__");
+        assert_eq!(field_name(""), "__");
+    }
+
+    #[test]
+    fn compile_schema_file() {
+        let dir = std::env::temp_dir().join(format!("custom_codec_codegen_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let schema = dir.join("user.v2.ccs");
+        let text = "message User { required Int64 id; optional List<Bytes> \"avatar set\";\n\
+                    optional String lang [default = \"ru\"]; }";
+        std::fs::write(&schema, text).unwrap();
+
+        let out = compile(&schema, &dir).unwrap();
+        assert_eq!(out, dir.join("user.v2.rs"));
+        let code = std::fs::read_to_string(&out).unwrap();
+        assert!(code.contains("pub struct User {\n    pub id: i64,\n"));
+        assert!(code.contains("pub avatar_set: ::std::option::Option<::std::vec::Vec<"));
+        assert!(code.contains("decode_list(__v, ::custom_codec::__private::decode_bytes)"));
+        assert!(code.contains(r#"schema_default("\"ru\"")"#));
+        let field = crate::Field { key: "x".into(), value: crate::Value::Int32(-1) };
+        let default = crate::Value::Message(vec![field]);
+        let text = crate::value_to_text(&default);
+        assert_eq!(crate::__private::schema_default(&text), default);
+
+        std::fs::write(&schema, "message M { required Int32 a_b; required Int32 \"a b\"; }")
+            .unwrap();
+        let clash = CodegenError::NameClash { message: "M".into(), key: "a b".into() };
+        assert_eq!(compile(&schema, &dir), Err(clash));
+        let missing = compile(dir.join("missing.ccs"), &dir).unwrap_err();
+        assert!(matches!(missing, CodegenError::Io { kind: io::ErrorKind::NotFound, .. }));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+//! Обмен полями без ввода-вывода (sans-IO)
+//!
+//! `Connection` не читает и не пишет сам: входящие байты передаются в
+//! `handle_input`, исходящие забираются через `take_output`. Поэтому его
+//! можно вести из любого цикла событий — mio, io_uring, встраиваемого —
+//! или прямо из тестов.
+
+use crate::{
+    encode_field_into_with, DecodeError, DecodeOptions, EncodeError, EncodeOptions, Field,
+    StreamingDecoder,
+};
+
+/// Событие, полученное из входящих данных
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Поле получено целиком
+    Field(Field),
+    /// Входящие данные испорчены; дальнейший вход игнорируется
+    Error(DecodeError),
+}
+
+/// Состояние обмена полями с одним собеседником
+///
+/// ```
+/// use custom_codec::{Connection, Event, Field, Value};
+///
+/// let mut a = Connection::new();
+/// let mut b = Connection::new();
+/// a.queue_field(&Field { key: "n".into(), value: Value::Int32(7) }).unwrap();
+/// let mut wire = Vec::new();
+/// a.take_output(&mut wire);
+/// let events = b.handle_input(&wire);
+/// assert!(matches!(&events[..], [Event::Field(f)] if f.key == "n"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Connection {
+    decoder: StreamingDecoder,
+    encode: EncodeOptions,
+    output: Vec<u8>,
+}
+
+impl Connection {
+    /// Стандартный профиль и ограничения `DecodeOptions::default()`
+    pub fn new() -> Self {
+        Connection::default()
+    }
+
+    pub fn with_options(encode: EncodeOptions, decode: DecodeOptions) -> Self {
+        Connection { decoder: StreamingDecoder::with_options(decode), encode, output: Vec::new() }
+    }
+
+    /// Обработка пришедших байт: все поля, полученные целиком
+    ///
+    /// После `Event::Error` вход больше не разбирается.
+    pub fn handle_input(&mut self, data: &[u8]) -> Vec<Event> {
+        self.decoder.feed(data);
+        let mut events = Vec::new();
+        while let Some(field) = self.decoder.next_field() {
+            events.push(match field {
+                Ok(field) => Event::Field(field),
+                Err(e) => Event::Error(e),
+            });
+        }
+        events
+    }
+
+    /// Постановка поля в очередь на отправку
+    ///
+    /// При ошибке кодирования очередь не изменяется.
+    pub fn queue_field(&mut self, field: &Field) -> Result<(), EncodeError> {
+        let len = self.output.len();
+        encode_field_into_with(field, &mut self.output, &self.encode).inspect_err(|_| {
+            self.output.truncate(len);
+        })
+    }
+
+    /// Перенос всех ожидающих отправки байт в конец `out`
+    pub fn take_output(&mut self, out: &mut Vec<u8>) {
+        out.append(&mut self.output);
+    }
+
+    /// Есть ли что отправлять
+    pub fn wants_write(&self) -> bool {
+        !self.output.is_empty()
+    }
+
+    /// Закрытие входящего направления: ошибка, если последнее поле
+    /// пришло не целиком
+    pub fn close(self) -> Result<(), DecodeError> {
+        self.decoder.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Limit, Profile, Value};
+
+    fn sample() -> Vec<Field> {
+        vec![
+            Field { key: "a".into(), value: Value::String("first".into()) },
+            Field { key: "n".into(), value: Value::List(vec![Value::Int64(1), Value::Null]) },
+        ]
+    }
+
+    #[test]
+    fn peers_exchange_fields() {
+        let encode = EncodeOptions { profile: Profile::compact(), ..EncodeOptions::default() };
+        let decode = DecodeOptions { profile: Profile::compact(), ..DecodeOptions::default() };
+        let mut a = Connection::with_options(encode.clone(), decode.clone());
+        let mut b = Connection::with_options(encode, decode);
+        for f in &sample() {
+            a.queue_field(f).unwrap();
+        }
+        assert!(a.wants_write());
+        let mut wire = Vec::new();
+        a.take_output(&mut wire);
+        assert!(!a.wants_write());
+
+        // данные приходят кусками произвольного размера
+        let mut events = Vec::new();
+        for chunk in wire.chunks(3) {
+            events.extend(b.handle_input(chunk));
+        }
+        let expected: Vec<Event> = sample().into_iter().map(Event::Field).collect();
+        assert_eq!(events, expected);
+        b.close().unwrap();
+    }
+
+    #[test]
+    fn input_errors() {
+        let opts = DecodeOptions { max_value_len: 2, ..DecodeOptions::default() };
+        let mut conn = Connection::with_options(EncodeOptions::default(), opts);
+        let events = conn.handle_input(&[5, 0, 0, 0, 1, b'b', 0, 0, 1, 0]);
+        let err = DecodeError::LimitExceeded { offset: 6, limit: Limit::ValueLength };
+        assert_eq!(events, [Event::Error(err)]);
+        assert!(conn.handle_input(&[1, 2, 3]).is_empty());
+
+        let mut conn = Connection::new();
+        conn.handle_input(&[1, 0]);
+        assert_eq!(conn.close(), Err(DecodeError::UnexpectedEof { offset: 2 }));
+    }
+}
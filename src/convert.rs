@@ -0,0 +1,142 @@
+//! `From` и `TryFrom` между `Value` и простыми типами
+//!
+//! `Value::from(42)` короче `Value::Int32(42)`. Обратные преобразования
+//! следуют правилам `Decode`: целые читаются из любого целого значения,
+//! если число помещается в тип, `f64` — также из `Float32`.
+
+use std::fmt;
+
+use crate::typed::type_name;
+use crate::{Decimal, Decode, Field, Timestamp, Value};
+
+/// Значение не преобразуется в запрошенный тип
+///
+/// Исходное значение возвращается в `value`, так что его можно
+/// попробовать преобразовать иначе.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionError {
+    /// Запрошенный тип
+    pub expected: &'static str,
+    pub value: Value,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "значение {} не преобразуется в {}", type_name(&self.value), self.expected)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+macro_rules! from {
+    ($($t:ty => $variant:ident),* $(,)?) => {$(
+        impl From<$t> for Value {
+            fn from(v: $t) -> Self {
+                Value::$variant(v)
+            }
+        }
+    )*};
+}
+
+from! {
+    i8 => Int8,
+    i16 => Int16,
+    i32 => Int32,
+    i64 => Int64,
+    u8 => UInt8,
+    u16 => UInt16,
+    u32 => UInt32,
+    u64 => UInt64,
+    f32 => Float32,
+    f64 => Float64,
+    bool => Bool,
+    String => String,
+    Vec<u8> => Bytes,
+    Vec<Field> => Message,
+    Vec<Value> => List,
+    Timestamp => Timestamp,
+    Decimal => Decimal,
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_owned())
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(b: &[u8]) -> Self {
+        Value::Bytes(b.to_vec())
+    }
+}
+
+/// `None` становится `Value::Null`
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Value::Null, Into::into)
+    }
+}
+
+/// Простые типы читаются через `Decode`
+macro_rules! try_decode {
+    ($($t:ty),*) => {$(
+        impl TryFrom<Value> for $t {
+            type Error = ConversionError;
+
+            fn try_from(value: Value) -> Result<Self, ConversionError> {
+                <$t>::decode_value(&value)
+                    .map_err(|_| ConversionError { expected: stringify!($t), value })
+            }
+        }
+    )*};
+}
+
+try_decode!(i8, i16, i32, i64, u8, u16, u32, u64, f32, f64, bool, Timestamp, Decimal);
+
+/// Владеющие типы забираются из значения без копирования
+macro_rules! try_take {
+    ($($t:ty => $variant:ident),*) => {$(
+        impl TryFrom<Value> for $t {
+            type Error = ConversionError;
+
+            fn try_from(value: Value) -> Result<Self, ConversionError> {
+                match value {
+                    Value::$variant(v) => Ok(v),
+                    value => Err(ConversionError { expected: stringify!($t), value }),
+                }
+            }
+        }
+    )*};
+}
+
+try_take!(String => String, Vec<u8> => Bytes, Vec<Field> => Message, Vec<Value> => List);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn into_value() {
+        assert_eq!(Value::from(42), Value::Int32(42));
+        assert_eq!(Value::from(7u8), Value::UInt8(7));
+        assert_eq!(Value::from("k"), Value::String("k".into()));
+        assert_eq!(Value::from(&[1u8, 2][..]), Value::Bytes(vec![1, 2]));
+        assert_eq!(Value::from(None::<bool>), Value::Null);
+        assert_eq!(Value::from(Some(0.5)), Value::Float64(0.5));
+        let list = Value::from(vec![Value::from(true)]);
+        assert_eq!(list, Value::List(vec![Value::Bool(true)]));
+    }
+
+    #[test]
+    fn try_from_value() {
+        assert_eq!(i64::try_from(Value::Int32(-3)), Ok(-3));
+        assert_eq!(String::try_from(Value::from("s")).as_deref(), Ok("s"));
+        assert_eq!(Vec::<u8>::try_from(Value::Bytes(vec![9])), Ok(vec![9]));
+
+        let err = u8::try_from(Value::Int32(300)).unwrap_err();
+        assert_eq!(err, ConversionError { expected: "u8", value: Value::Int32(300) });
+        assert_eq!(err.to_string(), "значение Int32 не преобразуется в u8");
+        let err = String::try_from(Value::Null).unwrap_err();
+        assert_eq!(err.expected, "String");
+    }
+}
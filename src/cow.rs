@@ -0,0 +1,314 @@
+//! Значения, которые могут и заимствовать вход, и владеть данными
+//!
+//! В отличие от `ValueRef`, строки и байты `CowValue` — `Cow`: декодированные
+//! из буфера, они ссылаются на него, но их можно изменить на месте через
+//! `Cow::to_mut`, не копируя остальное дерево.
+
+use std::borrow::Cow;
+
+use crate::decode::{Decoder, Input, Reader, Tree, UNLIMITED};
+use crate::{
+    utf8, Decimal, DecodeError, DecodeOptions, Field, FieldRef, Timestamp, Value, ValueRef,
+};
+
+/// `Value` со строками и байтами в `Cow`
+#[derive(Debug, Clone, PartialEq)]
+pub enum CowValue<'a> {
+    Int32(i32),
+    Float32(f32),
+    Bool(bool),
+    String(Cow<'a, str>),
+    Bytes(Cow<'a, [u8]>),
+    Message(Vec<CowField<'a>>),
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    Null,
+    List(Vec<CowValue<'a>>),
+    Map(Vec<(CowValue<'a>, CowValue<'a>)>),
+    Timestamp(Timestamp),
+    Uuid([u8; 16]),
+    Decimal(Decimal),
+    Enum { variant: u32, name: Option<Cow<'a, str>>, payload: Option<Box<CowValue<'a>>> },
+    Int8(i8),
+    Int16(i16),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    /// Значение неизвестного типа, см. `Value::Unknown`
+    Unknown { type_code: u8, raw: Cow<'a, [u8]> },
+}
+
+/// `Field` с ключом и значением в `Cow`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CowField<'a> {
+    pub key: Cow<'a, str>,
+    pub value: CowValue<'a>,
+}
+
+impl CowValue<'_> {
+    /// Значение, не зависящее от входного буфера; уже принадлежащие
+    /// значению строки и байты не копируются
+    pub fn into_owned(self) -> Value {
+        match self {
+            CowValue::Int32(i) => Value::Int32(i),
+            CowValue::Float32(f) => Value::Float32(f),
+            CowValue::Bool(b) => Value::Bool(b),
+            CowValue::String(s) => Value::String(s.into_owned()),
+            CowValue::Bytes(b) => Value::Bytes(b.into_owned()),
+            CowValue::Message(fields) => {
+                Value::Message(fields.into_iter().map(CowField::into_owned).collect())
+            }
+            CowValue::Int64(i) => Value::Int64(i),
+            CowValue::UInt64(u) => Value::UInt64(u),
+            CowValue::Float64(f) => Value::Float64(f),
+            CowValue::Null => Value::Null,
+            CowValue::List(items) => {
+                Value::List(items.into_iter().map(CowValue::into_owned).collect())
+            }
+            CowValue::Map(entries) => Value::Map(
+                entries.into_iter().map(|(k, v)| (k.into_owned(), v.into_owned())).collect(),
+            ),
+            CowValue::Timestamp(ts) => Value::Timestamp(ts),
+            CowValue::Uuid(u) => Value::Uuid(u),
+            CowValue::Decimal(d) => Value::Decimal(d),
+            CowValue::Enum { variant, name, payload } => Value::Enum {
+                variant,
+                name: name.map(Cow::into_owned),
+                payload: payload.map(|p| Box::new(p.into_owned())),
+            },
+            CowValue::Int8(i) => Value::Int8(i),
+            CowValue::Int16(i) => Value::Int16(i),
+            CowValue::UInt8(u) => Value::UInt8(u),
+            CowValue::UInt16(u) => Value::UInt16(u),
+            CowValue::UInt32(u) => Value::UInt32(u),
+            CowValue::Unknown { type_code, raw } => {
+                Value::Unknown { type_code, raw: raw.into_owned() }
+            }
+        }
+    }
+}
+
+impl CowField<'_> {
+    pub fn into_owned(self) -> Field {
+        Field { key: self.key.into_owned(), value: self.value.into_owned() }
+    }
+}
+
+impl From<Value> for CowValue<'static> {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Int32(i) => CowValue::Int32(i),
+            Value::Float32(f) => CowValue::Float32(f),
+            Value::Bool(b) => CowValue::Bool(b),
+            Value::String(s) => CowValue::String(Cow::Owned(s)),
+            Value::Bytes(b) => CowValue::Bytes(Cow::Owned(b)),
+            Value::Message(fields) => {
+                CowValue::Message(fields.into_iter().map(CowField::from).collect())
+            }
+            Value::Int64(i) => CowValue::Int64(i),
+            Value::UInt64(u) => CowValue::UInt64(u),
+            Value::Float64(f) => CowValue::Float64(f),
+            Value::Null => CowValue::Null,
+            Value::List(items) => CowValue::List(items.into_iter().map(CowValue::from).collect()),
+            Value::Map(entries) => CowValue::Map(
+                entries.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+            ),
+            Value::Timestamp(ts) => CowValue::Timestamp(ts),
+            Value::Uuid(u) => CowValue::Uuid(u),
+            Value::Decimal(d) => CowValue::Decimal(d),
+            Value::Enum { variant, name, payload } => CowValue::Enum {
+                variant,
+                name: name.map(Cow::Owned),
+                payload: payload.map(|p| Box::new((*p).into())),
+            },
+            Value::Int8(i) => CowValue::Int8(i),
+            Value::Int16(i) => CowValue::Int16(i),
+            Value::UInt8(u) => CowValue::UInt8(u),
+            Value::UInt16(u) => CowValue::UInt16(u),
+            Value::UInt32(u) => CowValue::UInt32(u),
+            Value::Unknown { type_code, raw } => {
+                CowValue::Unknown { type_code, raw: Cow::Owned(raw) }
+            }
+        }
+    }
+}
+
+impl From<Field> for CowField<'static> {
+    fn from(field: Field) -> Self {
+        CowField { key: Cow::Owned(field.key), value: field.value.into() }
+    }
+}
+
+impl<'a> From<ValueRef<'a>> for CowValue<'a> {
+    fn from(value: ValueRef<'a>) -> Self {
+        match value {
+            ValueRef::Int32(i) => CowValue::Int32(i),
+            ValueRef::Float32(f) => CowValue::Float32(f),
+            ValueRef::Bool(b) => CowValue::Bool(b),
+            ValueRef::String(s) => CowValue::String(Cow::Borrowed(s)),
+            ValueRef::Bytes(b) => CowValue::Bytes(Cow::Borrowed(b)),
+            ValueRef::Message(fields) => {
+                CowValue::Message(fields.into_iter().map(CowField::from).collect())
+            }
+            ValueRef::Int64(i) => CowValue::Int64(i),
+            ValueRef::UInt64(u) => CowValue::UInt64(u),
+            ValueRef::Float64(f) => CowValue::Float64(f),
+            ValueRef::Null => CowValue::Null,
+            ValueRef::List(items) => {
+                CowValue::List(items.into_iter().map(CowValue::from).collect())
+            }
+            ValueRef::Map(entries) => CowValue::Map(
+                entries.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+            ),
+            ValueRef::Timestamp(ts) => CowValue::Timestamp(ts),
+            ValueRef::Uuid(u) => CowValue::Uuid(u),
+            ValueRef::Decimal(d) => CowValue::Decimal(d),
+            ValueRef::Enum { variant, name, payload } => CowValue::Enum {
+                variant,
+                name: name.map(Cow::Borrowed),
+                payload: payload.map(|p| Box::new((*p).into())),
+            },
+            ValueRef::Int8(i) => CowValue::Int8(i),
+            ValueRef::Int16(i) => CowValue::Int16(i),
+            ValueRef::UInt8(u) => CowValue::UInt8(u),
+            ValueRef::UInt16(u) => CowValue::UInt16(u),
+            ValueRef::UInt32(u) => CowValue::UInt32(u),
+            ValueRef::Unknown { type_code, raw } => {
+                CowValue::Unknown { type_code, raw: Cow::Borrowed(raw) }
+            }
+        }
+    }
+}
+
+impl<'a> From<FieldRef<'a>> for CowField<'a> {
+    fn from(field: FieldRef<'a>) -> Self {
+        CowField { key: Cow::Borrowed(field.key), value: field.value.into() }
+    }
+}
+
+/// Декодирование одного поля со ссылками на `data` вместо копий
+pub fn decode_field_cow(data: &[u8]) -> Result<CowField<'_>, DecodeError> {
+    decode_field_cow_with(data, &UNLIMITED)
+}
+
+/// То же, что [`decode_field_cow`], с ограничениями и профилем
+pub fn decode_field_cow_with<'a>(
+    data: &'a [u8],
+    opts: &DecodeOptions,
+) -> Result<CowField<'a>, DecodeError> {
+    Decoder::new(Reader::new(data), opts).read_tree(&Cowed)
+}
+
+/// Декодирование сообщения со ссылками на `data` вместо копий
+pub fn decode_message_cow<'a>(
+    data: &'a [u8],
+    opts: &DecodeOptions,
+) -> Result<Vec<CowField<'a>>, DecodeError> {
+    let mut dec = Decoder::new(Reader::new(data), opts);
+    let mut fields = Vec::new();
+    while dec.input.pos() < data.len() {
+        fields.push(dec.read_tree(&Cowed)?);
+    }
+    Ok(fields)
+}
+
+/// Сборка `CowField`: срезы входа заимствуются, прочитанное из потока
+/// остаётся во владении
+struct Cowed;
+
+impl<'a> Tree<'a> for Cowed {
+    type Str = Cow<'a, str>;
+    type Value = CowValue<'a>;
+    type Field = CowField<'a>;
+    type Fields = Vec<CowField<'a>>;
+    type Items = Vec<CowValue<'a>>;
+    type Entries = Vec<(CowValue<'a>, CowValue<'a>)>;
+
+    fn str(&self, bytes: Cow<'a, [u8]>, offset: usize) -> Result<Cow<'a, str>, DecodeError> {
+        utf8::cow(bytes).ok_or(DecodeError::InvalidUtf8 { offset })
+    }
+
+    fn string(&self, s: Cow<'a, str>) -> CowValue<'a> {
+        CowValue::String(s)
+    }
+
+    fn bytes(&self, bytes: Cow<'a, [u8]>) -> CowValue<'a> {
+        CowValue::Bytes(bytes)
+    }
+
+    fn unknown(&self, type_code: u8, raw: Cow<'a, [u8]>) -> CowValue<'a> {
+        CowValue::Unknown { type_code, raw }
+    }
+
+    fn scalar(&self, value: Value) -> CowValue<'a> {
+        value.into()
+    }
+
+    fn fields(&self) -> Self::Fields {
+        Vec::new()
+    }
+
+    fn items(&self) -> Self::Items {
+        Vec::new()
+    }
+
+    fn entries(&self) -> Self::Entries {
+        Vec::new()
+    }
+
+    fn message(&self, fields: Vec<CowField<'a>>) -> CowValue<'a> {
+        CowValue::Message(fields)
+    }
+
+    fn list(&self, items: Vec<CowValue<'a>>) -> CowValue<'a> {
+        CowValue::List(items)
+    }
+
+    fn map(&self, entries: Vec<(CowValue<'a>, CowValue<'a>)>) -> CowValue<'a> {
+        CowValue::Map(entries)
+    }
+
+    fn enumeration(
+        &self,
+        variant: u32,
+        name: Option<Cow<'a, str>>,
+        payload: Option<CowValue<'a>>,
+    ) -> CowValue<'a> {
+        CowValue::Enum { variant, name, payload: payload.map(Box::new) }
+    }
+
+    fn field(&self, key: Cow<'a, str>, value: CowValue<'a>) -> CowField<'a> {
+        CowField { key, value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_field, decode_field_ref, encode_field};
+
+    #[test]
+    fn borrowed_until_modified() {
+        let f = Field {
+            key: "m".into(),
+            value: Value::List(vec![Value::String("a".into()), Value::Bytes(vec![1, 2])]),
+        };
+        let enc = encode_field(&f).unwrap();
+        let mut cow = decode_field_cow(&enc).unwrap();
+        assert!(matches!(cow.key, Cow::Borrowed("m")));
+        assert_eq!(cow, decode_field_ref(&enc).unwrap().into());
+
+        let CowValue::List(items) = &mut cow.value else { panic!("ожидался список") };
+        let CowValue::String(s) = &mut items[0] else { panic!("ожидалась строка") };
+        s.to_mut().push('b');
+        assert!(matches!(items[1], CowValue::Bytes(Cow::Borrowed(_))));
+
+        let mut expected = decode_field(&enc).unwrap();
+        if let Value::List(items) = &mut expected.value {
+            items[0] = Value::String("ab".into());
+        }
+        assert_eq!(cow.clone().into_owned(), expected);
+        assert_eq!(CowField::from(expected.clone()).into_owned(), expected);
+    }
+}
@@ -0,0 +1,364 @@
+//! Плоские сообщения в CSV (RFC 4180) и обратно
+//!
+//! Заголовок — ключи в порядке первого появления, строка — одно сообщение.
+//! Значения записываются текстом: числа и `Bool` как в Rust, `Bytes` —
+//! base64, `Uuid` — `xxxxxxxx-xxxx-…`, `Timestamp` — секунды от эпохи с
+//! девятью знаками дроби, `Null` — пустая ячейка. Пустая строка
+//! записывается как `""` и отличается от пустой ячейки, строки вида
+//! `42` или `true` тоже в кавычках и читаются обратно строками. Вложенные
+//! сообщения, списки, отображения и перечисления не поддерживаются.
+//!
+//! При чтении целые становятся `Int64` (или `UInt64`, если не помещаются),
+//! дробные — `Float64`, `true`/`false` — `Bool`, остальное — `String`.
+
+use std::fmt;
+
+use crate::typed::{type_name, Hyphenated};
+use crate::{base64, Field, Value};
+
+/// Как обходиться с полями, которых нет в сообщении
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingFields {
+    /// Пустая ячейка; при чтении пустая ячейка не даёт поля
+    #[default]
+    Skip,
+    /// Пустая ячейка; при чтении пустая ячейка даёт `Value::Null`
+    Null,
+    /// Отсутствующее поле и пустая ячейка — ошибка
+    Error,
+}
+
+/// Настройки записи и чтения CSV
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub missing: MissingFields,
+    /// Все ячейки читаются как `String`, без распознавания чисел
+    pub strings: bool,
+}
+
+/// Сообщения не переводятся в CSV или обратно
+///
+/// `row` — номер сообщения с нуля, `line` — номер строки текста с единицы.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsvError {
+    /// Значение поля не записывается в ячейку
+    Nested { row: usize, key: String, found: &'static str },
+    /// Ключ встречается в сообщении дважды
+    DuplicateKey { row: usize, key: String },
+    /// Нет поля или ячейка пуста при `MissingFields::Error`
+    MissingField { line: usize, key: String },
+    /// Число ячеек в строке не совпадает с заголовком
+    RowLength { line: usize, expected: usize, found: usize },
+    /// Незакрытая кавычка или текст после закрывающей кавычки
+    Syntax { line: usize },
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::Nested { row, key, found } => {
+                write!(f, "сообщение {row}, поле {key:?}: значение {found} не записывается в CSV")
+            }
+            CsvError::DuplicateKey { row, key } => {
+                write!(f, "сообщение {row}: ключ {key:?} повторяется")
+            }
+            CsvError::MissingField { line, key } => write!(f, "строка {line}: нет поля {key:?}"),
+            CsvError::RowLength { line, expected, found } => {
+                write!(f, "строка {line}: {found} ячеек вместо {expected}")
+            }
+            CsvError::Syntax { line } => write!(f, "строка {line}: неверные кавычки"),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+/// Сообщения как CSV с заголовком; строки разделяются `\n`
+///
+/// ```
+/// use custom_codec::{to_csv, CsvOptions, Field, Value};
+///
+/// let rows = [
+///     vec![Field { key: "id".into(), value: Value::Int32(1) }],
+///     vec![Field { key: "name".into(), value: Value::String("a, b".into()) }],
+/// ];
+/// assert_eq!(to_csv(&rows, &CsvOptions::default()).unwrap(), "id,name\n1,\n,\"a, b\"\n");
+/// ```
+pub fn to_csv(messages: &[Vec<Field>], options: &CsvOptions) -> Result<String, CsvError> {
+    let mut header: Vec<&str> = Vec::new();
+    for (row, fields) in messages.iter().enumerate() {
+        for (i, f) in fields.iter().enumerate() {
+            if fields[..i].iter().any(|g| g.key == f.key) {
+                return Err(CsvError::DuplicateKey { row, key: f.key.clone() });
+            }
+            if !header.contains(&f.key.as_str()) {
+                header.push(&f.key);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    if header.is_empty() {
+        return Ok(out);
+    }
+    push_record(&mut out, header.iter().map(|k| ((*k).to_owned(), false)));
+    for (row, fields) in messages.iter().enumerate() {
+        let mut cells = Vec::with_capacity(header.len());
+        for key in &header {
+            let cell = match fields.iter().find(|f| f.key == *key) {
+                Some(f) => {
+                    let text = scalar_text(&f.value).ok_or_else(|| CsvError::Nested {
+                        row,
+                        key: f.key.clone(),
+                        found: type_name(&f.value),
+                    })?;
+                    // строка в кавычках, если без них она прочиталась бы иначе:
+                    // пустая — как пустая ячейка, `42` или `true` — как число и `Bool`
+                    let quote = match &f.value {
+                        Value::String(s) => s.is_empty() || infer(s.clone()) != f.value,
+                        _ => false,
+                    };
+                    (text, quote)
+                }
+                None if options.missing == MissingFields::Error => {
+                    let key = (*key).to_owned();
+                    return Err(CsvError::MissingField { line: row + 2, key });
+                }
+                None => (String::new(), false),
+            };
+            cells.push(cell);
+        }
+        push_record(&mut out, cells.into_iter());
+    }
+    Ok(out)
+}
+
+fn push_record(out: &mut String, cells: impl Iterator<Item = (String, bool)>) {
+    for (i, (cell, quote)) in cells.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if quote || cell.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&cell.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(&cell);
+        }
+    }
+    out.push('\n');
+}
+
+/// Текстовая запись скалярного значения; `None` для вложенных
+pub(crate) fn scalar_text(v: &Value) -> Option<String> {
+    Some(match v {
+        Value::Int8(x) => x.to_string(),
+        Value::Int16(x) => x.to_string(),
+        Value::Int32(x) => x.to_string(),
+        Value::Int64(x) => x.to_string(),
+        Value::UInt8(x) => x.to_string(),
+        Value::UInt16(x) => x.to_string(),
+        Value::UInt32(x) => x.to_string(),
+        Value::UInt64(x) => x.to_string(),
+        Value::Float32(x) => x.to_string(),
+        Value::Float64(x) => x.to_string(),
+        Value::Bool(x) => x.to_string(),
+        Value::String(s) => s.clone(),
+        Value::Bytes(b) | Value::Unknown { raw: b, .. } => base64::encode(b),
+        Value::Timestamp(ts) => {
+            let nanos = ts.unix_nanos();
+            let sign = if nanos < 0 { "-" } else { "" };
+            let nanos = nanos.unsigned_abs();
+            format!("{sign}{}.{:09}", nanos / 1_000_000_000, nanos % 1_000_000_000)
+        }
+        Value::Uuid(u) => Hyphenated(u).to_string(),
+        Value::Decimal(d) => d.to_string(),
+        Value::Null => String::new(),
+        Value::Message(_) | Value::List(_) | Value::Map(_) | Value::Enum { .. } => return None,
+    })
+}
+
+/// Сообщения из CSV с заголовком; допускаются концы строк `\n` и `\r\n`
+pub fn from_csv(text: &str, options: &CsvOptions) -> Result<Vec<Vec<Field>>, CsvError> {
+    let mut records = Records { rest: text, line: 1 };
+    let Some((_, header)) = records.next().transpose()? else {
+        return Ok(Vec::new());
+    };
+    let header: Vec<String> = header.into_iter().map(|(cell, _)| cell).collect();
+
+    let mut messages = Vec::new();
+    for record in records {
+        let (line, cells) = record?;
+        if cells.len() != header.len() {
+            let (expected, found) = (header.len(), cells.len());
+            return Err(CsvError::RowLength { line, expected, found });
+        }
+        let mut fields = Vec::with_capacity(cells.len());
+        for (key, (cell, quoted)) in header.iter().zip(cells) {
+            let value = if cell.is_empty() && !quoted {
+                match options.missing {
+                    MissingFields::Skip => continue,
+                    MissingFields::Null => Value::Null,
+                    MissingFields::Error => {
+                        return Err(CsvError::MissingField { line, key: key.clone() })
+                    }
+                }
+            } else if options.strings || quoted {
+                Value::String(cell)
+            } else {
+                infer(cell)
+            };
+            fields.push(Field { key: key.clone(), value });
+        }
+        messages.push(fields);
+    }
+    Ok(messages)
+}
+
+fn infer(cell: String) -> Value {
+    if let Ok(x) = cell.parse::<i64>() {
+        return Value::Int64(x);
+    }
+    if let Ok(x) = cell.parse::<u64>() {
+        return Value::UInt64(x);
+    }
+    // parse::<f64> принимает и `inf`/`NaN`, такие ячейки остаются строками
+    let numeric = cell.bytes().all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b));
+    if numeric && cell.bytes().any(|b| b.is_ascii_digit()) {
+        if let Ok(x) = cell.parse::<f64>() {
+            return Value::Float64(x);
+        }
+    }
+    match cell.as_str() {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::String(cell),
+    }
+}
+
+/// Записи CSV: номер первой строки и ячейки с признаком кавычек
+struct Records<'a> {
+    rest: &'a str,
+    line: usize,
+}
+
+type Record = (usize, Vec<(String, bool)>);
+
+impl Iterator for Records<'_> {
+    type Item = Result<Record, CsvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let start = self.line;
+        let mut cells = Vec::new();
+        let mut chars = self.rest.char_indices().peekable();
+        let mut cell = String::new();
+        let mut quoted = false;
+        let mut end = self.rest.len();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' if cell.is_empty() && !quoted => {
+                    quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some((_, '"')) if chars.peek().map(|p| p.1) == Some('"') => {
+                                chars.next();
+                                cell.push('"');
+                            }
+                            Some((_, '"')) => break,
+                            Some((_, c)) => {
+                                self.line += usize::from(c == '\n');
+                                cell.push(c);
+                            }
+                            None => return Some(Err(CsvError::Syntax { line: start })),
+                        }
+                    }
+                    if !matches!(chars.peek().map(|p| p.1), None | Some(',' | '\n' | '\r')) {
+                        return Some(Err(CsvError::Syntax { line: self.line }));
+                    }
+                }
+                ',' => cells.push((std::mem::take(&mut cell), std::mem::take(&mut quoted))),
+                '\n' => {
+                    end = i + 1;
+                    break;
+                }
+                '\r' if chars.peek().map(|p| p.1) == Some('\n') => {}
+                _ if quoted => return Some(Err(CsvError::Syntax { line: self.line })),
+                c => cell.push(c),
+            }
+        }
+        cells.push((cell, quoted));
+        self.rest = &self.rest[end..];
+        self.line += 1;
+        Some(Ok((start, cells)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Timestamp;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn csv_roundtrip() {
+        let messages = vec![
+            vec![
+                field("id", Value::Int64(1)),
+                field("name", Value::String("say \"hi\",\nbye".into())),
+                field("ok", Value::Bool(true)),
+            ],
+            vec![
+                field("id", Value::UInt64(u64::MAX)),
+                field("name", Value::String(String::new())),
+                field("ratio", Value::Float64(-0.25)),
+            ],
+        ];
+        let text = to_csv(&messages, &CsvOptions::default()).unwrap();
+        let expected = "id,name,ok,ratio\n1,\"say \"\"hi\"\",\nbye\",true,\n\
+                        18446744073709551615,\"\",,-0.25\n";
+        assert_eq!(text, expected);
+        assert_eq!(from_csv(&text, &CsvOptions::default()).unwrap(), messages);
+
+        // строки, похожие на числа и `Bool`, не меняют тип
+        let lookalikes: Vec<_> = ["42", "true", "-0.5", "1e3", "inf", " 1"]
+            .into_iter()
+            .map(|s| vec![field("s", Value::String(s.into()))])
+            .collect();
+        let text = to_csv(&lookalikes, &CsvOptions::default()).unwrap();
+        assert_eq!(text, "s\n\"42\"\n\"true\"\n\"-0.5\"\n\"1e3\"\ninf\n 1\n");
+        assert_eq!(from_csv(&text, &CsvOptions::default()).unwrap(), lookalikes);
+
+        let ts = Value::Timestamp(Timestamp { secs: -2, nanos: 500_000_000 });
+        assert_eq!(scalar_text(&ts).unwrap(), "-1.500000000");
+
+        let strings = CsvOptions { strings: true, ..CsvOptions::default() };
+        let rows = from_csv("a\r\n007\r\n", &strings).unwrap();
+        assert_eq!(rows, vec![vec![field("a", Value::String("007".into()))]]);
+    }
+
+    #[test]
+    fn csv_missing_fields() {
+        let messages = vec![vec![field("a", Value::Int64(1))], vec![field("b", Value::Null)]];
+        let text = to_csv(&messages, &CsvOptions::default()).unwrap();
+        assert_eq!(text, "a,b\n1,\n,\n");
+
+        let null = CsvOptions { missing: MissingFields::Null, ..CsvOptions::default() };
+        let rows = from_csv(&text, &null).unwrap();
+        assert_eq!(rows[1], vec![field("a", Value::Null), field("b", Value::Null)]);
+
+        let error = CsvOptions { missing: MissingFields::Error, ..CsvOptions::default() };
+        let err = CsvError::MissingField { line: 2, key: "b".into() };
+        assert_eq!(to_csv(&messages, &error).unwrap_err(), err);
+        assert_eq!(from_csv(&text, &error).unwrap_err(), err);
+
+        let err = CsvError::RowLength { line: 2, expected: 2, found: 1 };
+        assert_eq!(from_csv("a,b\n1\n", &CsvOptions::default()).unwrap_err(), err);
+        assert_eq!(from_csv("a\n\"x\n", &CsvOptions::default()), Err(CsvError::Syntax { line: 2 }));
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+
+use crate::endian::Number;
+use crate::Endianness;
+
+/// Максимальный масштаб: i128 вмещает 38 десятичных знаков
+pub const MAX_SCALE: u8 = 38;
+
+/// Точное десятичное число `mantissa × 10^-scale`
+///
+/// На проводе занимает 17 байт: `scale` (u8) и `mantissa` (i128).
+/// Числа с разным масштабом различаются: `1.0` и `1.00` — разные значения.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    pub mantissa: i128,
+    pub scale: u8,
+}
+
+impl Decimal {
+    /// Число с заданным масштабом; `None`, если масштаб больше [`MAX_SCALE`]
+    pub const fn new(mantissa: i128, scale: u8) -> Option<Self> {
+        if scale <= MAX_SCALE {
+            Some(Decimal { mantissa, scale })
+        } else {
+            None
+        }
+    }
+
+    /// Из записи вида `-12.50`, как её выводит `Display`; масштаб — число
+    /// знаков после точки
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", text),
+        };
+        let (int, frac) = match digits.split_once('.') {
+            Some((_, "")) => return None,
+            Some(parts) => parts,
+            None => (digits, ""),
+        };
+        let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if int.is_empty() || !all_digits(int) || !all_digits(frac) {
+            return None;
+        }
+        let mantissa = format!("{sign}{int}{frac}").parse().ok()?;
+        Decimal::new(mantissa, u8::try_from(frac.len()).ok()?)
+    }
+
+    pub(crate) fn to_bytes(self, endian: Endianness) -> [u8; 17] {
+        let mut out = [0u8; 17];
+        out[0] = self.scale;
+        out[1..].copy_from_slice(&self.mantissa.to_bytes(endian));
+        out
+    }
+
+    pub(crate) fn from_bytes(bytes: [u8; 17], endian: Endianness) -> Option<Self> {
+        Decimal::new(i128::from_bytes(bytes[1..].try_into().unwrap(), endian), bytes[0])
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if self.mantissa < 0 {
+            f.write_str("-")?;
+        }
+        if scale == 0 {
+            return f.write_str(&digits);
+        }
+        // ведущие нули для чисел меньше единицы
+        let digits = format!("{digits:0>width$}", width = scale + 1);
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{int}.{frac}")
+    }
+}
+
+/// Число не представимо в целевом десятичном типе
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimalOutOfRange;
+
+impl fmt::Display for DecimalOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("десятичное число вне допустимого диапазона")
+    }
+}
+
+impl std::error::Error for DecimalOutOfRange {}
+
+#[cfg(feature = "rust_decimal")]
+mod rust_decimal_impls {
+    use super::*;
+
+    impl From<rust_decimal::Decimal> for Decimal {
+        fn from(d: rust_decimal::Decimal) -> Self {
+            // масштаб rust_decimal не превышает 28
+            Decimal { mantissa: d.mantissa(), scale: d.scale() as u8 }
+        }
+    }
+
+    impl TryFrom<Decimal> for rust_decimal::Decimal {
+        type Error = DecimalOutOfRange;
+
+        fn try_from(d: Decimal) -> Result<Self, Self::Error> {
+            rust_decimal::Decimal::try_from_i128_with_scale(d.mantissa, d.scale as u32)
+                .map_err(|_| DecimalOutOfRange)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let d = |m, s| Decimal::new(m, s).unwrap().to_string();
+        assert_eq!(d(12345, 2), "123.45");
+        assert_eq!(d(-5, 3), "-0.005");
+        assert_eq!(d(7, 0), "7");
+        assert_eq!(d(0, 1), "0.0");
+        assert_eq!(Decimal::new(1, MAX_SCALE + 1), None);
+    }
+
+    #[test]
+    fn parse() {
+        for text in ["123.45", "-0.005", "7", "0.0"] {
+            assert_eq!(Decimal::parse(text).unwrap().to_string(), text);
+        }
+        for text in ["", "-", ".5", "1.", "1.2.3", "+1", "1e3"] {
+            assert_eq!(Decimal::parse(text), None, "{text}");
+        }
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn rust_decimal_conversion() {
+        let rd = rust_decimal::Decimal::new(-1999, 2);
+        let d = Decimal::from(rd);
+        assert_eq!(d, Decimal { mantissa: -1999, scale: 2 });
+        assert_eq!(rust_decimal::Decimal::try_from(d), Ok(rd));
+        assert_eq!(
+            rust_decimal::Decimal::try_from(Decimal { mantissa: i128::MAX, scale: 0 }),
+            Err(DecimalOutOfRange)
+        );
+    }
+}
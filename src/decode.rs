@@ -0,0 +1,1276 @@
+use std::borrow::Cow;
+use std::ops::DerefMut;
+
+use crate::endian::Number;
+use crate::options::{INLINE_FALSE, INLINE_INT_BASE, INLINE_TRUE, KEY_LEN_ESCAPE};
+use crate::tooling::Node;
+
+use crate::{
+    utf8, varint, Decimal, DecodeError, DecodeOptions, DuplicateKeys, Field, IntEncoding,
+    KeyLength, KeyTable, LengthEncoding, Limit, Timestamp, UnknownTypes, Value,
+};
+
+pub(crate) static UNLIMITED: DecodeOptions = DecodeOptions::unlimited();
+
+/// Декодирование одного поля
+///
+/// Ограничена только глубина вложенности — [`MAX_VALUE_DEPTH`](crate::MAX_VALUE_DEPTH).
+pub fn decode_field(data: &[u8]) -> Result<Field, DecodeError> {
+    decode_field_consumed(data).map(|(field, _)| field)
+}
+
+/// Декодирование одного поля с возвратом числа прочитанных байт
+///
+/// Позволяет последовательно читать несколько полей из одного буфера.
+pub fn decode_field_consumed(data: &[u8]) -> Result<(Field, usize), DecodeError> {
+    let mut dec = Decoder::new(Reader::new(data), &UNLIMITED);
+    let field = dec.read_field()?;
+    Ok((field, dec.input.pos()))
+}
+
+/// Декодирование одного поля с ограничениями для недоверенных данных
+pub fn decode_field_with(data: &[u8], opts: &DecodeOptions) -> Result<Field, DecodeError> {
+    Decoder::new(Reader::new(data), opts).read_field()
+}
+
+/// Декодирование сообщения
+///
+/// Буфер должен целиком состоять из полей: неполное или испорченное
+/// поле в конце приводит к ошибке, а не отбрасывается молча. Глубина
+/// ограничена, как у `decode_field`.
+pub fn decode_message(data: &[u8]) -> Result<Vec<Field>, DecodeError> {
+    decode_message_with(data, &UNLIMITED)
+}
+
+/// Декодирование сообщения с ограничениями для недоверенных данных
+pub fn decode_message_with(data: &[u8], opts: &DecodeOptions) -> Result<Vec<Field>, DecodeError> {
+    decode_message_at(data, 0, opts)
+}
+
+/// Декодирование полей от позиции `pos` до конца `data`
+pub(crate) fn decode_message_at(
+    data: &[u8],
+    pos: usize,
+    opts: &DecodeOptions,
+) -> Result<Vec<Field>, DecodeError> {
+    let mut dec = Decoder::new(Reader::at(data, pos), opts);
+    let mut fields = Vec::new();
+    while dec.input.pos() < data.len() {
+        fields.push(dec.read_field()?);
+    }
+    Ok(fields)
+}
+
+/// Ленивый обход полей, записанных подряд в `data`
+///
+/// ```
+/// use custom_codec::{encode_message, iter_fields, Field, Value};
+///
+/// let fields = vec![
+///     Field { key: "a".into(), value: Value::Int32(1) },
+///     Field { key: "b".into(), value: Value::Null },
+/// ];
+/// let enc = encode_message(&fields).unwrap();
+/// let keys: Vec<String> = iter_fields(&enc).map(|f| f.unwrap().key).collect();
+/// assert_eq!(keys, ["a", "b"]);
+/// ```
+pub fn iter_fields(data: &[u8]) -> FieldIter<'_> {
+    iter_fields_with(data, &UNLIMITED)
+}
+
+/// То же, что [`iter_fields`], с ограничениями и профилем
+pub fn iter_fields_with<'a>(data: &'a [u8], opts: &'a DecodeOptions) -> FieldIter<'a> {
+    FieldIter { dec: Decoder::new(Reader::new(data), opts), len: data.len(), done: false }
+}
+
+/// Итератор [`iter_fields`]
+///
+/// После первой ошибки итерация заканчивается: граница следующего
+/// поля неизвестна.
+pub struct FieldIter<'a> {
+    dec: Decoder<'a, Reader<'a>>,
+    len: usize,
+    done: bool,
+}
+
+impl FieldIter<'_> {
+    /// Смещение начала следующего поля
+    pub fn offset(&self) -> usize {
+        self.dec.input.pos()
+    }
+}
+
+impl Iterator for FieldIter<'_> {
+    type Item = Result<Field, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.offset() >= self.len {
+            return None;
+        }
+        let field = self.dec.read_field();
+        self.done = field.is_err();
+        Some(field)
+    }
+}
+
+impl std::iter::FusedIterator for FieldIter<'_> {}
+
+/// Источник байт для декодера: срез в памяти или поток
+pub(crate) trait Input<'a> {
+    /// Смещение от начала данных
+    fn pos(&self) -> usize;
+
+    /// Следующие `len` байт; при нехватке данных — `UnexpectedEof`
+    /// со смещением начала запрошенного участка
+    fn take(&mut self, len: usize) -> Result<Cow<'a, [u8]>, DecodeError>;
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let mut arr = [0u8; N];
+        arr.copy_from_slice(&self.take(N)?);
+        Ok(arr)
+    }
+}
+
+/// Чтение из среза без копирования
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// сколько байт не хватило последнему неудачному чтению
+    missing: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Reader::at(data, 0)
+    }
+
+    /// Чтение с позиции `pos`, смещения в ошибках остаются абсолютными
+    pub(crate) fn at(data: &'a [u8], pos: usize) -> Self {
+        Reader { data, pos, missing: 0 }
+    }
+
+    /// Недостаток данных после `UnexpectedEof`
+    #[cfg_attr(not(any(feature = "tokio", feature = "tokio-util")), allow(dead_code))]
+    pub(crate) fn missing(&self) -> usize {
+        self.missing
+    }
+}
+
+impl<'a> Input<'a> for Reader<'a> {
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn take(&mut self, len: usize) -> Result<Cow<'a, [u8]>, DecodeError> {
+        let end = self.pos.saturating_add(len);
+        if end > self.data.len() {
+            self.missing = end - self.data.len();
+            return Err(DecodeError::UnexpectedEof { offset: self.pos });
+        }
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(Cow::Borrowed(slice))
+    }
+}
+
+/// Во что собираются прочитанные значения: владеющие `Field`,
+/// заимствующие вход `FieldRef` или размещённые в арене
+pub(crate) trait Tree<'a> {
+    /// Ключ поля и имя варианта перечисления
+    type Str: Default;
+    type Value: PartialEq;
+    type Field;
+    /// Поля сообщения, элементы списка и пары отображения
+    type Fields: Seq<Self::Field>;
+    type Items: Seq<Self::Value>;
+    type Entries: Seq<(Self::Value, Self::Value)>;
+
+    /// Проверка UTF-8; `offset` — начало строки во входе
+    fn str(&self, bytes: Cow<'a, [u8]>, offset: usize) -> Result<Self::Str, DecodeError>;
+    fn string(&self, s: Self::Str) -> Self::Value;
+    fn bytes(&self, bytes: Cow<'a, [u8]>) -> Self::Value;
+    /// Значение неизвестного типа при `UnknownTypes::Preserve`
+    fn unknown(&self, type_code: u8, raw: Cow<'a, [u8]>) -> Self::Value;
+    /// Значение без строк, байт и вложенных значений
+    fn scalar(&self, value: Value) -> Self::Value;
+    fn fields(&self) -> Self::Fields;
+    fn items(&self) -> Self::Items;
+    fn entries(&self) -> Self::Entries;
+    fn message(&self, fields: Self::Fields) -> Self::Value;
+    fn list(&self, items: Self::Items) -> Self::Value;
+    fn map(&self, entries: Self::Entries) -> Self::Value;
+    fn enumeration(
+        &self,
+        variant: u32,
+        name: Option<Self::Str>,
+        payload: Option<Self::Value>,
+    ) -> Self::Value;
+    fn field(&self, key: Self::Str, value: Self::Value) -> Self::Field;
+}
+
+/// Последовательность, в которую дописываются элементы контейнера
+pub(crate) trait Seq<E>: DerefMut<Target = [E]> {
+    fn push(&mut self, e: E);
+}
+
+impl<E> Seq<E> for Vec<E> {
+    fn push(&mut self, e: E) {
+        Vec::push(self, e)
+    }
+}
+
+/// Декодирование в `Field` и `Value`
+pub(crate) struct Owned;
+
+impl<'a> Tree<'a> for Owned {
+    type Str = String;
+    type Value = Value;
+    type Field = Field;
+    type Fields = Vec<Field>;
+    type Items = Vec<Value>;
+    type Entries = Vec<(Value, Value)>;
+
+    fn str(&self, bytes: Cow<'a, [u8]>, offset: usize) -> Result<String, DecodeError> {
+        let s = match bytes {
+            Cow::Borrowed(b) => utf8::str(b).map(str::to_owned),
+            Cow::Owned(v) => utf8::string(v),
+        };
+        s.ok_or(DecodeError::InvalidUtf8 { offset })
+    }
+
+    fn string(&self, s: String) -> Value {
+        Value::String(s)
+    }
+
+    fn bytes(&self, bytes: Cow<'a, [u8]>) -> Value {
+        Value::Bytes(bytes.into_owned())
+    }
+
+    fn unknown(&self, type_code: u8, raw: Cow<'a, [u8]>) -> Value {
+        Value::Unknown { type_code, raw: raw.into_owned() }
+    }
+
+    fn scalar(&self, value: Value) -> Value {
+        value
+    }
+
+    fn fields(&self) -> Self::Fields {
+        Vec::new()
+    }
+
+    fn items(&self) -> Self::Items {
+        Vec::new()
+    }
+
+    fn entries(&self) -> Self::Entries {
+        Vec::new()
+    }
+
+    fn message(&self, fields: Vec<Field>) -> Value {
+        Value::Message(fields)
+    }
+
+    fn list(&self, items: Vec<Value>) -> Value {
+        Value::List(items)
+    }
+
+    fn map(&self, entries: Vec<(Value, Value)>) -> Value {
+        Value::Map(entries)
+    }
+
+    fn enumeration(&self, variant: u32, name: Option<String>, payload: Option<Value>) -> Value {
+        Value::Enum { variant, name, payload: payload.map(Box::new) }
+    }
+
+    fn field(&self, key: String, value: Value) -> Field {
+        Field { key, value }
+    }
+}
+
+/// Вложенный контейнер, содержимое которого ещё читается
+struct Frame<'a, T: Tree<'a>> {
+    /// ключ поля, значением которого является контейнер
+    /// (у элементов списков и отображений ключей нет)
+    key: Option<T::Str>,
+    body: Body<'a, T>,
+    start: usize,
+    end: usize,
+}
+
+enum Body<'a, T: Tree<'a>> {
+    Message(T::Fields),
+    List(T::Items),
+    /// прочитанные пары и ключ, ожидающий своего значения
+    Map(T::Entries, Option<(T::Value, usize)>),
+    /// `has_payload` — флаг из заголовка: ожидается ли элемент-содержимое
+    Enum { variant: u32, name: Option<T::Str>, payload: Option<T::Value>, has_payload: bool },
+}
+
+/// Флаги заголовка `Value::Enum`
+pub(crate) const ENUM_HAS_NAME: u8 = 0b01;
+pub(crate) const ENUM_HAS_PAYLOAD: u8 = 0b10;
+
+impl<'a, T: Tree<'a>> Body<'a, T> {
+    /// Значение закрытого контейнера; у отображения не должно оставаться
+    /// ключа без значения, а у перечисления — обещанного содержимого
+    fn into_value(self, tree: &T, end: usize) -> Result<T::Value, DecodeError> {
+        Ok(match self {
+            Body::Message(fields) => tree.message(fields),
+            Body::List(items) => tree.list(items),
+            Body::Map(entries, None) => tree.map(entries),
+            Body::Enum { variant, name, payload, has_payload } if payload.is_some() == has_payload => {
+                tree.enumeration(variant, name, payload)
+            }
+            Body::Map(_, Some(_)) | Body::Enum { .. } => {
+                return Err(DecodeError::UnexpectedEof { offset: end })
+            }
+        })
+    }
+
+    /// Может ли контейнер принять ещё один элемент
+    fn accepts_more(&self) -> bool {
+        match self {
+            Body::Enum { payload, has_payload, .. } => *has_payload && payload.is_none(),
+            _ => true,
+        }
+    }
+}
+
+/// Добавление пары в отображение по политике `DuplicateKeys`
+fn push_entry<V: PartialEq>(
+    entries: &mut impl Seq<(V, V)>,
+    key: V,
+    value: V,
+    key_offset: usize,
+    policy: DuplicateKeys,
+) -> Result<(), DecodeError> {
+    if policy != DuplicateKeys::Allow {
+        if let Some(slot) = entries.iter_mut().find(|(k, _)| *k == key) {
+            match policy {
+                DuplicateKeys::Reject => {
+                    return Err(DecodeError::DuplicateKey { offset: key_offset })
+                }
+                DuplicateKeys::KeepLast => slot.1 = value,
+                DuplicateKeys::KeepFirst | DuplicateKeys::Allow => {}
+            }
+            return Ok(());
+        }
+    }
+    entries.push((key, value));
+    Ok(())
+}
+
+/// Однопроходный декодер поверх `Input`
+///
+/// Каждый байт входа читается ровно один раз, а вложенные сообщения
+/// разбираются по мере чтения, без промежуточных буферов.
+pub(crate) struct Decoder<'o, I> {
+    pub(crate) input: I,
+    opts: &'o DecodeOptions,
+    /// граница текущего контейнера: поле не может выходить
+    /// за пределы сообщения или списка, которому принадлежит
+    limit: usize,
+    /// число уже прочитанных полей
+    fields: usize,
+    /// таблица ключей, если поля несут числовые теги вместо ключей
+    tags: Option<&'o KeyTable>,
+}
+
+impl<'a, 'o, I: Input<'a>> Decoder<'o, I> {
+    pub(crate) fn new(input: I, opts: &'o DecodeOptions) -> Self {
+        Decoder { input, opts, limit: usize::MAX, fields: 0, tags: None }
+    }
+
+    /// Декодер полей с числовыми тегами
+    pub(crate) fn with_tags(mut self, table: &'o KeyTable) -> Self {
+        self.tags = Some(table);
+        self
+    }
+
+    pub(crate) fn opts(&self) -> &'o DecodeOptions {
+        self.opts
+    }
+
+    /// Ошибка `LimitExceeded`, если ограничение `limit` нарушено
+    fn check(&self, exceeded: bool, offset: usize, limit: Limit) -> Result<(), DecodeError> {
+        match exceeded {
+            true => Err(DecodeError::LimitExceeded { offset, limit }),
+            false => Ok(()),
+        }
+    }
+
+    /// Проверка, что `len` байт помещаются в текущий контейнер;
+    /// возвращает позицию их конца
+    fn ensure(&self, len: usize) -> Result<usize, DecodeError> {
+        let pos = self.input.pos();
+        pos.checked_add(len)
+            .filter(|&end| end <= self.limit)
+            .ok_or(DecodeError::UnexpectedEof { offset: pos })
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<Cow<'a, [u8]>, DecodeError> {
+        self.ensure(len)?;
+        self.input.take(len)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        self.ensure(1)?;
+        Ok(self.input.take_array::<1>()?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, DecodeError> {
+        self.ensure(4)?;
+        Ok(u32::from_bytes(self.input.take_array()?, self.opts.profile.endianness))
+    }
+
+    pub(crate) fn read_varint(&mut self) -> Result<u64, DecodeError> {
+        let offset = self.input.pos();
+        let mut value = 0u64;
+        for i in 0..varint::MAX_LEN {
+            let byte = self.read_u8()?;
+            let bits = (byte & 0x7f) as u64;
+            // десятый байт может нести лишь один значащий бит
+            if i == varint::MAX_LEN - 1 && bits > 1 {
+                break;
+            }
+            value |= bits << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::InvalidVarint { offset })
+    }
+
+    /// Префикс длины в формате профиля
+    fn read_len(&mut self) -> Result<usize, DecodeError> {
+        match self.opts.profile.lengths {
+            LengthEncoding::Fixed32 => Ok(self.read_u32()? as usize),
+            LengthEncoding::Fixed64 => {
+                self.ensure(8)?;
+                let len = u64::from_bytes(self.input.take_array()?, self.opts.profile.endianness);
+                // длина больше адресного пространства всё равно не поместится во вход
+                Ok(usize::try_from(len).unwrap_or(usize::MAX))
+            }
+            LengthEncoding::Varint => {
+                let offset = self.input.pos();
+                usize::try_from(self.read_varint()?)
+                    .map_err(|_| DecodeError::InvalidVarint { offset })
+            }
+        }
+    }
+
+    /// Префикс длины ключа поля
+    fn read_key_len(&mut self) -> Result<usize, DecodeError> {
+        match self.opts.profile.keys {
+            KeyLength::AsValues => self.read_len(),
+            KeyLength::Short => match self.read_u8()? {
+                KEY_LEN_ESCAPE => Ok(self.read_u32()? as usize),
+                len => Ok(len as usize),
+            },
+        }
+    }
+
+    /// Ключ поля или его числовой тег
+    fn read_key<T: Tree<'a>>(&mut self, tree: &T) -> Result<T::Str, DecodeError> {
+        if let Some(table) = self.tags {
+            let offset = self.input.pos();
+            let tag = self.read_varint()?;
+            let key = u32::try_from(tag).ok().and_then(|t| table.key(t));
+            let key = key.ok_or(DecodeError::UnknownTag { offset, tag })?;
+            return tree.str(Cow::Owned(key.as_bytes().to_vec()), offset);
+        }
+        let key_len = self.read_key_len()?;
+        let key_offset = self.input.pos();
+        tree.str(self.take(key_len)?, key_offset)
+    }
+
+    /// Начало `Value::Enum`: номер варианта, флаги и необязательное имя
+    fn read_enum_header<T: Tree<'a>>(&mut self, tree: &T) -> Result<Body<'a, T>, DecodeError> {
+        let variant = self.read_u32()?;
+        let flags_offset = self.input.pos();
+        let flags = self.read_u8()?;
+        if flags & !(ENUM_HAS_NAME | ENUM_HAS_PAYLOAD) != 0 {
+            return Err(DecodeError::InvalidValue { offset: flags_offset, type_code: 16 });
+        }
+        let name = match flags & ENUM_HAS_NAME {
+            0 => None,
+            _ => {
+                let len = self.read_len()?;
+                let offset = self.input.pos();
+                Some(tree.str(self.take(len)?, offset)?)
+            }
+        };
+        let has_payload = flags & ENUM_HAS_PAYLOAD != 0;
+        Ok(Body::Enum { variant, name, payload: None, has_payload })
+    }
+
+    /// Длина и значение элемента; контейнер открывается на `stack`
+    fn read_value<T: Tree<'a>>(
+        &mut self,
+        tree: &T,
+        type_code: u8,
+        key: Option<T::Str>,
+        type_offset: usize,
+        stack: &mut Vec<Frame<'a, T>>,
+    ) -> Result<Option<Done<'a, T>>, DecodeError> {
+        // длина значения
+        let len_offset = self.input.pos();
+        let val_len = self.read_len()?;
+        let val_offset = self.input.pos();
+        self.check(val_len > self.opts.max_value_len, len_offset, Limit::ValueLength)?;
+        self.check(
+            val_offset.saturating_add(val_len) > self.opts.max_total_len,
+            len_offset,
+            Limit::TotalLength,
+        )?;
+
+        match type_code {
+            6 | 11 | 12 | 16 => {
+                // содержимое контейнера читается следующими итерациями
+                self.check(stack.len() >= self.opts.max_depth, type_offset, Limit::Depth)?;
+                let end = self.ensure(val_len)?;
+                self.limit = end;
+                let body = match type_code {
+                    6 => Body::Message(tree.fields()),
+                    11 => Body::List(tree.items()),
+                    12 => Body::Map(tree.entries(), None),
+                    _ => self.read_enum_header(tree)?,
+                };
+                stack.push(Frame { key, body, start: type_offset, end });
+                Ok(None)
+            }
+            _ => {
+                let val_bytes = self.take(val_len)?;
+                let offsets = (type_offset, len_offset, val_offset);
+                let value =
+                    decode_scalar(tree, type_code, val_bytes, offsets, self.opts)?;
+                Ok(Some((key, value, type_offset)))
+            }
+        }
+    }
+
+    /// Позиция конца поля по его заголовку, без чтения значения
+    ///
+    /// Нужна, чтобы дождаться поля целиком; `UnexpectedEof` означает,
+    /// что не хватает самого заголовка.
+    pub(crate) fn field_end(&mut self) -> Result<usize, DecodeError> {
+        let type_offset = self.input.pos();
+        let type_code = self.read_u8()?;
+        let key_len = self.read_key_len()?;
+        let key_end = self.input.pos().saturating_add(key_len);
+        self.check(key_end > self.opts.max_total_len, type_offset, Limit::TotalLength)?;
+        self.take(key_len)?;
+        if self.opts.profile.inline_scalars && inline_value(type_code).is_some() {
+            return Ok(self.input.pos());
+        }
+        let len_offset = self.input.pos();
+        let val_len = self.read_len()?;
+        let end = self.input.pos().saturating_add(val_len);
+        self.check(val_len > self.opts.max_value_len, len_offset, Limit::ValueLength)?;
+        self.check(end > self.opts.max_total_len, len_offset, Limit::TotalLength)?;
+        Ok(end)
+    }
+
+    /// Заголовок поля `Value::Bytes`: ключ и длина данных, которые
+    /// остаются непрочитанными
+    pub(crate) fn read_bytes_header(&mut self) -> Result<(String, usize), DecodeError> {
+        let type_offset = self.input.pos();
+        let code = self.read_u8()?;
+        if code != 5 {
+            return Err(DecodeError::InvalidTypeCode { offset: type_offset, code });
+        }
+        let key_len = self.read_key_len()?;
+        let key_offset = self.input.pos();
+        let key = Owned.str(self.take(key_len)?, key_offset)?;
+        let len_offset = self.input.pos();
+        let len = self.read_len()?;
+        self.check(len > self.opts.max_value_len, len_offset, Limit::ValueLength)?;
+        Ok((key, len))
+    }
+
+    /// Чтение одного поля без рекурсии
+    ///
+    /// Незавершённые контейнеры хранятся в явном стеке, поэтому глубина
+    /// вложенности входных данных расходует кучу, а не стек потока.
+    pub(crate) fn read_field(&mut self) -> Result<Field, DecodeError> {
+        self.read_tree(&Owned)
+    }
+
+    /// Чтение одного поля в представление `T`
+    pub(crate) fn read_tree<T: Tree<'a>>(&mut self, tree: &T) -> Result<T::Field, DecodeError> {
+        let outer_limit = self.limit;
+        let mut stack: Vec<Frame<'a, T>> = Vec::new();
+
+        loop {
+            let type_offset = self.input.pos();
+            if stack.last().is_some_and(|f| !f.body.accepts_more()) {
+                // лишние байты в перечислении без содержимого
+                return Err(DecodeError::InvalidValue { offset: type_offset, type_code: 16 });
+            }
+            self.fields += 1;
+            self.check(self.fields > self.opts.max_fields, type_offset, Limit::FieldCount)?;
+            let type_code = self.read_u8()?;
+
+            // ключ есть только у полей сообщений, у элементов контейнеров его нет
+            let key = match stack.last() {
+                Some(Frame { body: Body::List(_) | Body::Map(..) | Body::Enum { .. }, .. }) => None,
+                _ => Some(self.read_key(tree)?),
+            };
+
+            // значения, целиком записанные в коде типа, не имеют длины
+            let inline = match self.opts.profile.inline_scalars {
+                true => inline_value(type_code),
+                false => None,
+            };
+            let mut done = match inline {
+                Some(value) => Some((key, tree.scalar(value), type_offset)),
+                None => self.read_value(tree, type_code, key, type_offset, &mut stack)?,
+            };
+
+            // закрытие контейнеров, дочитанных до конца
+            loop {
+                if let Some((key, value, offset)) = done.take() {
+                    match stack.last_mut() {
+                        Some(Frame { body: Body::Message(fields), .. }) => {
+                            fields.push(tree.field(key.unwrap_or_default(), value))
+                        }
+                        Some(Frame { body: Body::List(items), .. }) => items.push(value),
+                        Some(Frame { body: Body::Map(entries, pending), .. }) => match pending.take() {
+                            None => *pending = Some((value, offset)),
+                            Some((k, key_offset)) => {
+                                let policy = self.opts.duplicate_map_keys;
+                                push_entry(entries, k, value, key_offset, policy)?
+                            }
+                        },
+                        Some(Frame { body: Body::Enum { payload, .. }, .. }) => *payload = Some(value),
+                        None => {
+                            self.limit = outer_limit;
+                            return Ok(tree.field(key.unwrap_or_default(), value));
+                        }
+                    }
+                }
+                match stack.last() {
+                    Some(top) if self.input.pos() == top.end => {
+                        let frame = stack.pop().unwrap();
+                        self.limit = stack.last().map_or(outer_limit, |f| f.end);
+                        let value = frame.body.into_value(tree, frame.end)?;
+                        done = Some((frame.key, value, frame.start));
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+}
+
+/// Чтение по одному элементу для потребителей, которые сами обходят
+/// вложенность (потоковый `Deserializer` serde)
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+impl<'a, 'o, I: Input<'a>> Decoder<'o, I> {
+    /// Следующий элемент; содержимое контейнера не читается, а остаётся
+    /// следующим вызовам до `Scope::end`
+    ///
+    /// `keyed` — элемент является полем сообщения; `depth` — число уже
+    /// открытых контейнеров. Применяются все ограничения, кроме
+    /// `duplicate_map_keys`: пары отображения здесь не накапливаются.
+    pub(crate) fn next_item<T: Tree<'a>>(
+        &mut self,
+        tree: &T,
+        keyed: bool,
+        depth: usize,
+    ) -> Result<(Option<T::Str>, Item<'a, T>), DecodeError> {
+        let type_offset = self.input.pos();
+        self.fields += 1;
+        self.check(self.fields > self.opts.max_fields, type_offset, Limit::FieldCount)?;
+        let type_code = self.read_u8()?;
+        let key = match keyed {
+            true => Some(self.read_key(tree)?),
+            false => None,
+        };
+        if self.opts.profile.inline_scalars {
+            if let Some(value) = inline_value(type_code) {
+                return Ok((key, Item::Scalar(tree.scalar(value))));
+            }
+        }
+        let len_offset = self.input.pos();
+        let val_len = self.read_len()?;
+        let val_offset = self.input.pos();
+        self.check(val_len > self.opts.max_value_len, len_offset, Limit::ValueLength)?;
+        self.check(
+            val_offset.saturating_add(val_len) > self.opts.max_total_len,
+            len_offset,
+            Limit::TotalLength,
+        )?;
+        if !matches!(type_code, 6 | 11 | 12 | 16) {
+            let val_bytes = self.take(val_len)?;
+            let offsets = (type_offset, len_offset, val_offset);
+            let value = decode_scalar(tree, type_code, val_bytes, offsets, self.opts)?;
+            return Ok((key, Item::Scalar(value)));
+        }
+        self.check(depth >= self.opts.max_depth, type_offset, Limit::Depth)?;
+        let end = self.ensure(val_len)?;
+        let scope = Scope { type_code, end, outer: self.limit };
+        self.limit = end;
+        if type_code != 16 {
+            return Ok((key, Item::Open(scope)));
+        }
+        let Body::Enum { variant, name, has_payload, .. } = self.read_enum_header(tree)? else {
+            unreachable!("read_enum_header возвращает Body::Enum")
+        };
+        Ok((key, Item::Enum { variant, name, has_payload, scope }))
+    }
+
+    /// Дочитано ли содержимое контейнера
+    pub(crate) fn scope_done(&self, scope: &Scope) -> bool {
+        self.input.pos() >= scope.end
+    }
+
+    /// Выход из контейнера; непрочитанное содержимое пропускается без разбора
+    pub(crate) fn close(&mut self, scope: &Scope) -> Result<(), DecodeError> {
+        let rest = scope.end.saturating_sub(self.input.pos());
+        self.take(rest)?;
+        self.limit = scope.outer;
+        Ok(())
+    }
+}
+
+/// Разбор с положением частей элементов во входе (`tooling::inspect`)
+impl<'a, 'o, I: Input<'a>> Decoder<'o, I> {
+    /// Следующий элемент и его смещения, как `next_item`
+    ///
+    /// У контейнера возвращается открытый `Scope`, его содержимое читают
+    /// следующие вызовы; у перечисления прочитан заголовок, а `Node::value`
+    /// несёт номер и имя варианта без содержимого.
+    pub(crate) fn next_node(
+        &mut self,
+        keyed: bool,
+        depth: usize,
+    ) -> Result<(Node, Option<Scope>), DecodeError> {
+        let offset = self.input.pos();
+        self.fields += 1;
+        self.check(self.fields > self.opts.max_fields, offset, Limit::FieldCount)?;
+        let type_code = self.read_u8()?;
+        let mut key_offset = self.input.pos();
+        let key = match (keyed, self.tags) {
+            (false, _) => None,
+            (true, Some(_)) => Some(self.read_key(&Owned)?),
+            (true, None) => {
+                let key_len = self.read_key_len()?;
+                key_offset = self.input.pos();
+                Some(Owned.str(self.take(key_len)?, key_offset)?)
+            }
+        };
+        let len_offset = self.input.pos();
+        let mut node = Node {
+            key,
+            type_code,
+            offset,
+            key_offset,
+            len_offset,
+            value_offset: len_offset,
+            end: len_offset,
+            value: None,
+            children: Vec::new(),
+        };
+        if self.opts.profile.inline_scalars {
+            if let Some(value) = inline_value(type_code) {
+                node.value = Some(value);
+                return Ok((node, None));
+            }
+        }
+        let val_len = self.read_len()?;
+        node.value_offset = self.input.pos();
+        self.check(val_len > self.opts.max_value_len, len_offset, Limit::ValueLength)?;
+        self.check(
+            node.value_offset.saturating_add(val_len) > self.opts.max_total_len,
+            len_offset,
+            Limit::TotalLength,
+        )?;
+        node.end = self.ensure(val_len)?;
+        if !matches!(type_code, 6 | 11 | 12 | 16) {
+            let val_bytes = self.take(val_len)?;
+            let offsets = (offset, len_offset, node.value_offset);
+            node.value = Some(decode_scalar(&Owned, type_code, val_bytes, offsets, self.opts)?);
+            return Ok((node, None));
+        }
+        self.check(depth >= self.opts.max_depth, offset, Limit::Depth)?;
+        let scope = Scope { type_code, end: node.end, outer: self.limit };
+        self.limit = node.end;
+        if type_code == 16 {
+            let Body::Enum { variant, name, has_payload, .. } = self.read_enum_header(&Owned)?
+            else {
+                unreachable!("read_enum_header возвращает Body::Enum")
+            };
+            // содержимое есть ровно тогда, когда за заголовком остались байты
+            let pos = self.input.pos();
+            match (has_payload, pos == node.end) {
+                (true, true) => return Err(DecodeError::UnexpectedEof { offset: node.end }),
+                (false, false) => {
+                    return Err(DecodeError::InvalidValue { offset: pos, type_code: 16 })
+                }
+                _ => {}
+            }
+            node.value = Some(Value::Enum { variant, name, payload: None });
+        }
+        Ok((node, Some(scope)))
+    }
+
+    /// Ключ следующего поля и конец поля; значение пропускается без
+    /// разбора и не проверяется
+    pub(crate) fn skip_field(&mut self) -> Result<(Cow<'a, [u8]>, usize), DecodeError> {
+        let type_code = self.read_u8()?;
+        let key_len = self.read_key_len()?;
+        let key = self.take(key_len)?;
+        if self.opts.profile.inline_scalars && inline_value(type_code).is_some() {
+            return Ok((key, self.input.pos()));
+        }
+        let val_len = self.read_len()?;
+        let end = self.ensure(val_len)?;
+        self.take(val_len)?;
+        Ok((key, end))
+    }
+}
+
+/// Элемент, прочитанный `Decoder::next_item`
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub(crate) enum Item<'a, T: Tree<'a>> {
+    /// значение, не являющееся контейнером
+    Scalar(T::Value),
+    /// сообщение, список или отображение
+    Open(Scope),
+    /// заголовок перечисления; содержимое, если есть, — следующий элемент
+    Enum { variant: u32, name: Option<T::Str>, has_payload: bool, scope: Scope },
+}
+
+/// Открытый контейнер: код типа и границы
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+#[derive(Debug)]
+pub(crate) struct Scope {
+    pub(crate) type_code: u8,
+    pub(crate) end: usize,
+    /// граница объемлющего контейнера
+    outer: usize,
+}
+
+/// Прочитанное значение: ключ, значение и смещение его кода типа
+type Done<'a, T> = (Option<<T as Tree<'a>>::Str>, <T as Tree<'a>>::Value, usize);
+
+/// Значение, целиком записанное в коде типа (`Profile::inline_scalars`)
+fn inline_value(type_code: u8) -> Option<Value> {
+    match type_code {
+        INLINE_TRUE => Some(Value::Bool(true)),
+        INLINE_FALSE => Some(Value::Bool(false)),
+        INLINE_INT_BASE.. => Some(Value::Int32((type_code - INLINE_INT_BASE) as i32)),
+        _ => None,
+    }
+}
+
+/// Проверка длины значения фиксированного размера
+fn fixed<const N: usize>(bytes: &[u8], offset: usize) -> Result<[u8; N], DecodeError> {
+    bytes.try_into().map_err(|_| DecodeError::LengthMismatch {
+        offset,
+        expected: N,
+        actual: bytes.len(),
+    })
+}
+
+/// Разбор значения, не являющегося контейнером
+///
+/// `offsets` — смещения кода типа, префикса длины и начала значения,
+/// используются в сообщениях об ошибках. Ошибки длины указывают на префикс длины.
+fn decode_scalar<'a, T: Tree<'a>>(
+    tree: &T,
+    type_code: u8,
+    val_bytes: Cow<'a, [u8]>,
+    (type_offset, len_offset, val_offset): (usize, usize, usize),
+    opts: &DecodeOptions,
+) -> Result<T::Value, DecodeError> {
+    let profile = &opts.profile;
+    let endian = profile.endianness;
+    // zig-zag varint занимает всё значение целиком
+    let zigzag = || {
+        varint::decode(&val_bytes)
+            .map(varint::unzigzag)
+            .ok_or(DecodeError::InvalidValue { offset: val_offset, type_code })
+    };
+    let value = match type_code {
+        1 => Value::Int32(match profile.integers {
+            IntEncoding::Fixed => i32::from_bytes(fixed(&val_bytes, len_offset)?, endian),
+            IntEncoding::ZigZag => i32::try_from(zigzag()?)
+                .map_err(|_| DecodeError::InvalidValue { offset: val_offset, type_code })?,
+        }),
+        2 => Value::Float32(f32::from_bytes(fixed(&val_bytes, len_offset)?, endian)),
+        3 => {
+            let [b] = fixed(&val_bytes, len_offset)?;
+            Value::Bool(b != 0)
+        }
+        4 => return Ok(tree.string(tree.str(val_bytes, val_offset)?)),
+        5 => return Ok(tree.bytes(val_bytes)),
+        7 => Value::Int64(match profile.integers {
+            IntEncoding::Fixed => i64::from_bytes(fixed(&val_bytes, len_offset)?, endian),
+            IntEncoding::ZigZag => zigzag()?,
+        }),
+        8 => Value::UInt64(u64::from_bytes(fixed(&val_bytes, len_offset)?, endian)),
+        9 => Value::Float64(f64::from_bytes(fixed(&val_bytes, len_offset)?, endian)),
+        10 => {
+            let [] = fixed(&val_bytes, len_offset)?;
+            Value::Null
+        }
+        13 => Value::Timestamp(
+            Timestamp::from_bytes(fixed(&val_bytes, len_offset)?, endian)
+                .ok_or(DecodeError::InvalidValue { offset: val_offset, type_code })?,
+        ),
+        14 => Value::Uuid(fixed(&val_bytes, len_offset)?),
+        15 => Value::Decimal(
+            Decimal::from_bytes(fixed(&val_bytes, len_offset)?, endian)
+                .ok_or(DecodeError::InvalidValue { offset: val_offset, type_code })?,
+        ),
+        17 => Value::Int8(i8::from_bytes(fixed(&val_bytes, len_offset)?, endian)),
+        18 => Value::Int16(i16::from_bytes(fixed(&val_bytes, len_offset)?, endian)),
+        19 => Value::UInt8(u8::from_bytes(fixed(&val_bytes, len_offset)?, endian)),
+        20 => Value::UInt16(u16::from_bytes(fixed(&val_bytes, len_offset)?, endian)),
+        21 => Value::UInt32(u32::from_bytes(fixed(&val_bytes, len_offset)?, endian)),
+        code if opts.unknown_types == UnknownTypes::Preserve => {
+            return Ok(tree.unknown(code, val_bytes))
+        }
+        code => return Err(DecodeError::InvalidTypeCode { offset: type_offset, code }),
+    };
+    Ok(tree.scalar(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encode_field, encode_field_with, EncodeOptions, Endianness, Profile, MAX_VALUE_DEPTH,
+    };
+
+    /// Цепочка из `depth` вложенных сообщений, собранная без рекурсии
+    fn nested_chain(depth: usize) -> Vec<u8> {
+        let leaf = [1, 0, 0, 0, 1, b'x', 0, 0, 0, 4, 0, 0, 0, 9];
+        // заголовок уровня: код типа, длина ключа, ключ "m", длина значения
+        let header = 1 + 4 + 1 + 4;
+        let mut out = Vec::with_capacity(depth * header + leaf.len());
+        for level in 0..depth {
+            let payload = (depth - level - 1) * header + leaf.len();
+            out.extend_from_slice(&[6, 0, 0, 0, 1, b'm']);
+            out.extend_from_slice(&(payload as u32).to_be_bytes());
+        }
+        out.extend_from_slice(&leaf);
+        out
+    }
+
+    #[test]
+    fn deep_nesting_does_not_overflow_stack() {
+        // предел глубины проверяется без рекурсии на любой глубине
+        let err = decode_field(&nested_chain(200_000)).unwrap_err();
+        assert!(matches!(err, DecodeError::LimitExceeded { limit: Limit::Depth, .. }));
+
+        // результат на пределе глубины удаляется обычным образом
+        let field = decode_field(&nested_chain(MAX_VALUE_DEPTH)).unwrap();
+        let mut value = &field.value;
+        for _ in 0..MAX_VALUE_DEPTH {
+            let Value::Message(fields) = value else { panic!("ожидалось сообщение") };
+            value = &fields[0].value;
+        }
+        assert_eq!(value, &Value::Int32(9));
+        assert!(decode_field(&nested_chain(MAX_VALUE_DEPTH + 1)).is_err());
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let chain = nested_chain(3);
+        let err = |o: DecodeOptions| match decode_field_with(&chain, &o).unwrap_err() {
+            DecodeError::LimitExceeded { offset, limit } => (offset, limit),
+            e => panic!("unexpected error {e:?}"),
+        };
+        let d = DecodeOptions::default;
+
+        assert!(decode_field_with(&chain, &d()).is_ok());
+        assert_eq!(err(DecodeOptions { max_depth: 2, ..d() }), (20, Limit::Depth));
+        assert_eq!(err(DecodeOptions { max_fields: 3, ..d() }), (30, Limit::FieldCount));
+        assert_eq!(err(DecodeOptions { max_value_len: 20, ..d() }), (6, Limit::ValueLength));
+        let max_total_len = chain.len() - 1;
+        assert_eq!(err(DecodeOptions { max_total_len, ..d() }), (6, Limit::TotalLength));
+
+        let field = Field { key: "a".into(), value: Value::Int32(1) };
+        let enc = crate::encode_message(&[field.clone(), field]).unwrap();
+        assert_eq!(
+            decode_message_with(&enc, &DecodeOptions { max_fields: 1, ..d() }).unwrap_err(),
+            DecodeError::LimitExceeded { offset: 14, limit: Limit::FieldCount }
+        );
+    }
+
+    #[test]
+    fn inner_field_cannot_overrun_parent() {
+        let mut enc = nested_chain(2);
+        // внутреннее сообщение объявляет длину больше родительского
+        enc[16..20].copy_from_slice(&100u32.to_be_bytes());
+        assert_eq!(decode_field(&enc).unwrap_err(), DecodeError::UnexpectedEof { offset: 20 });
+    }
+
+    #[test]
+    fn list_limits_apply_to_elements() {
+        let list = Value::List(vec![Value::List(vec![Value::Int32(1), Value::Int32(2)])]);
+        let enc = crate::encode_field(&Field { key: "l".into(), value: list.clone() }).unwrap();
+        assert_eq!(decode_field(&enc).unwrap().value, list);
+
+        let d = DecodeOptions::default;
+        let err = |o| decode_field_with(&enc, &o).unwrap_err();
+        assert_eq!(
+            err(DecodeOptions { max_depth: 1, ..d() }),
+            DecodeError::LimitExceeded { offset: 10, limit: Limit::Depth }
+        );
+        assert_eq!(
+            err(DecodeOptions { max_fields: 3, ..d() }),
+            DecodeError::LimitExceeded { offset: 24, limit: Limit::FieldCount }
+        );
+    }
+
+    #[test]
+    fn map_duplicate_key_policies() {
+        let one = |v| (Value::Int32(1), Value::Int32(v));
+        let map = Value::Map(vec![one(10), (Value::Null, Value::Null), one(20)]);
+        let enc = crate::encode_field(&Field { key: "m".into(), value: map.clone() }).unwrap();
+        let decode = |duplicate_map_keys| {
+            let opts = DecodeOptions { duplicate_map_keys, ..DecodeOptions::default() };
+            decode_field_with(&enc, &opts).map(|f| f.value)
+        };
+
+        assert_eq!(decode(DuplicateKeys::Allow).unwrap(), map);
+        let kept = |v| Value::Map(vec![one(v), (Value::Null, Value::Null)]);
+        assert_eq!(decode(DuplicateKeys::KeepFirst).unwrap(), kept(10));
+        assert_eq!(decode(DuplicateKeys::KeepLast).unwrap(), kept(20));
+        assert_eq!(decode(DuplicateKeys::Reject).unwrap_err(), DecodeError::DuplicateKey { offset: 38 });
+    }
+
+    #[test]
+    fn map_with_dangling_key() {
+        // отображение из одного ключа без значения
+        let enc = [12, 0, 0, 0, 1, b'm', 0, 0, 0, 5, 10, 0, 0, 0, 0];
+        assert_eq!(decode_field(&enc).unwrap_err(), DecodeError::UnexpectedEof { offset: 15 });
+    }
+
+    #[test]
+    fn enum_framing_errors() {
+        // вариант 1, флаг содержимого, но самого содержимого нет
+        let mut enc = vec![16, 0, 0, 0, 1, b'e', 0, 0, 0, 5, 0, 0, 0, 1, ENUM_HAS_PAYLOAD];
+        assert_eq!(decode_field(&enc).unwrap_err(), DecodeError::UnexpectedEof { offset: 15 });
+
+        // без флага содержимого, но с лишним элементом
+        enc[14] = 0;
+        enc[9] = 10;
+        enc.extend_from_slice(&[10, 0, 0, 0, 0]);
+        assert_eq!(
+            decode_field(&enc).unwrap_err(),
+            DecodeError::InvalidValue { offset: 15, type_code: 16 }
+        );
+
+        // неизвестные флаги
+        enc[14] = 0x80;
+        assert_eq!(
+            decode_field(&enc).unwrap_err(),
+            DecodeError::InvalidValue { offset: 14, type_code: 16 }
+        );
+    }
+
+    #[test]
+    fn varint_profile() {
+        let f = Field {
+            key: "m".into(),
+            value: Value::Message(vec![
+                Field { key: "n".into(), value: Value::Int32(7) },
+                Field { key: "s".into(), value: Value::String("x".repeat(200)) },
+                Field {
+                    key: "e".into(),
+                    value: Value::Enum {
+                        variant: 2,
+                        name: Some("Two".into()),
+                        payload: Some(Box::new(Value::List(vec![Value::Null]))),
+                    },
+                },
+            ]),
+        };
+        let enc_opts = EncodeOptions { profile: Profile::compact(), ..EncodeOptions::default() };
+        let opts = DecodeOptions { profile: Profile::compact(), ..DecodeOptions::default() };
+        let enc = encode_field_with(&f, &enc_opts).unwrap();
+        assert_eq!(enc.len(), f.encoded_len_with(&Profile::compact()));
+        assert!(enc.len() < encode_field(&f).unwrap().len());
+        assert_eq!(&enc[..3], [6, 1, b'm']);
+        assert_eq!(decode_field_with(&enc, &opts).unwrap(), f);
+
+        // исходный профиль не меняется
+        let standard = encode_field_with(&f, &EncodeOptions::default()).unwrap();
+        assert_eq!(standard, encode_field(&f).unwrap());
+    }
+
+    #[test]
+    fn invalid_varint() {
+        let opts = DecodeOptions { profile: Profile::compact(), ..DecodeOptions::default() };
+        // одиннадцать байт продолжения
+        let mut enc = vec![5];
+        enc.extend_from_slice(&[0x80; 11]);
+        assert_eq!(
+            decode_field_with(&enc, &opts).unwrap_err(),
+            DecodeError::InvalidVarint { offset: 1 }
+        );
+        // значение больше u64
+        let enc = [5, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02];
+        assert_eq!(
+            decode_field_with(&enc, &opts).unwrap_err(),
+            DecodeError::InvalidVarint { offset: 1 }
+        );
+        // обрыв посреди длины
+        assert_eq!(
+            decode_field_with(&[5, 0x81], &opts).unwrap_err(),
+            DecodeError::UnexpectedEof { offset: 2 }
+        );
+    }
+
+    #[test]
+    fn zigzag_integers() {
+        let profile = Profile { integers: IntEncoding::ZigZag, ..Profile::STANDARD };
+        let enc_opts = EncodeOptions { profile, ..EncodeOptions::default() };
+        let opts = DecodeOptions { profile, ..DecodeOptions::default() };
+        let values =
+            [Value::Int32(-1), Value::Int32(i32::MIN), Value::Int64(300), Value::Int64(i64::MAX)];
+        for value in values {
+            let f = Field { key: "i".into(), value };
+            let enc = encode_field_with(&f, &enc_opts).unwrap();
+            assert_eq!(enc.len(), f.encoded_len_with(&profile));
+            assert_eq!(decode_field_with(&enc, &opts).unwrap(), f);
+        }
+        let minus_one = Field { key: "i".into(), value: Value::Int32(-1) };
+        let enc = encode_field_with(&minus_one, &enc_opts).unwrap();
+        assert_eq!(enc, [1, 0, 0, 0, 1, b'i', 0, 0, 0, 1, 0x01]);
+
+        // значение за пределами i32
+        let mut enc = vec![1, 0, 0, 0, 1, b'i', 0, 0, 0, 5];
+        varint::write(&mut enc, varint::zigzag(i64::MAX)).unwrap();
+        enc.truncate(15);
+        enc[14] &= 0x7f;
+        assert_eq!(
+            decode_field_with(&enc, &opts).unwrap_err(),
+            DecodeError::InvalidValue { offset: 10, type_code: 1 }
+        );
+        // незавершённый varint
+        let enc = [7, 0, 0, 0, 1, b'i', 0, 0, 0, 1, 0x80];
+        assert_eq!(
+            decode_field_with(&enc, &opts).unwrap_err(),
+            DecodeError::InvalidValue { offset: 10, type_code: 7 }
+        );
+    }
+
+    #[test]
+    fn little_endian() {
+        let profile = Profile { endianness: Endianness::Little, ..Profile::STANDARD };
+        let opts = DecodeOptions { profile, ..DecodeOptions::default() };
+        let enc_opts = EncodeOptions { profile, ..EncodeOptions::default() };
+        let f = Field { key: "n".into(), value: Value::UInt16(0x0102) };
+        let enc = encode_field_with(&f, &enc_opts).unwrap();
+        assert_eq!(enc, [20, 1, 0, 0, 0, b'n', 2, 0, 0, 0, 0x02, 0x01]);
+        assert_eq!(decode_field_with(&enc, &opts).unwrap(), f);
+
+        let f = Field {
+            key: "m".into(),
+            value: Value::Message(vec![
+                Field { key: "t".into(), value: Value::Timestamp(Timestamp { secs: -5, nanos: 7 }) },
+                Field { key: "d".into(), value: Value::Decimal(Decimal { mantissa: -12, scale: 1 }) },
+                Field { key: "f".into(), value: Value::Float64(0.5) },
+                Field { key: "e".into(), value: Value::Enum { variant: 3, name: None, payload: None } },
+            ]),
+        };
+        let enc = encode_field_with(&f, &enc_opts).unwrap();
+        assert_ne!(enc, encode_field(&f).unwrap());
+        assert_eq!(decode_field_with(&enc, &opts).unwrap(), f);
+    }
+
+    #[test]
+    fn short_key_lengths() {
+        let profile = Profile { keys: KeyLength::Short, ..Profile::STANDARD };
+        let opts = DecodeOptions { profile, ..DecodeOptions::default() };
+        let enc_opts = EncodeOptions { profile, ..EncodeOptions::default() };
+        let f = Field { key: "k".into(), value: Value::Bool(true) };
+        let enc = encode_field_with(&f, &enc_opts).unwrap();
+        assert_eq!(enc, [3, 1, b'k', 0, 0, 0, 1, 1]);
+        assert_eq!(decode_field_with(&enc, &opts).unwrap(), f);
+
+        // длинный ключ после escape-байта
+        for len in [254, 255, 300] {
+            let f = Field { key: "k".repeat(len), value: Value::Null };
+            let enc = encode_field_with(&f, &enc_opts).unwrap();
+            assert_eq!(enc.len(), f.encoded_len_with(&profile));
+            assert_eq!(enc[1] == KEY_LEN_ESCAPE, len >= 255);
+            assert_eq!(decode_field_with(&enc, &opts).unwrap(), f);
+        }
+    }
+
+    #[test]
+    fn inline_scalars() {
+        let profile = Profile::compact();
+        let enc_opts = EncodeOptions { profile, ..EncodeOptions::default() };
+        let opts = DecodeOptions { profile, ..DecodeOptions::default() };
+        let f = Field {
+            key: "m".into(),
+            value: Value::Message(vec![
+                Field { key: "on".into(), value: Value::Bool(true) },
+                Field { key: "n".into(), value: Value::Int32(5) },
+                Field { key: "big".into(), value: Value::Int32(128) },
+                Field { key: "l".into(), value: Value::List(vec![Value::Bool(false), Value::Null]) },
+            ]),
+        };
+        let enc = encode_field_with(&f, &enc_opts).unwrap();
+        assert_eq!(enc.len(), f.encoded_len_with(&profile));
+        assert_eq!(&enc[4..7], [INLINE_TRUE, 2, b'o']);
+        assert_eq!(decode_field_with(&enc, &opts).unwrap(), f);
+
+        let flag = Field { key: "k".into(), value: Value::Bool(false) };
+        assert_eq!(encode_field_with(&flag, &enc_opts).unwrap(), [INLINE_FALSE, 1, b'k']);
+
+        // без флага профиля такие коды неизвестны
+        let profile = Profile { inline_scalars: false, ..profile };
+        let plain = DecodeOptions { profile, ..DecodeOptions::default() };
+        assert_eq!(
+            decode_field_with(&[INLINE_FALSE, 1, b'k', 0], &plain).unwrap_err(),
+            DecodeError::InvalidTypeCode { offset: 0, code: INLINE_FALSE }
+        );
+    }
+
+    #[test]
+    fn field_iter() {
+        let fields = vec![
+            Field { key: "a".into(), value: Value::String("x".into()) },
+            Field { key: "b".into(), value: Value::List(vec![Value::Int32(1)]) },
+        ];
+        let enc = crate::encode_message(&fields).unwrap();
+        let mut it = iter_fields(&enc);
+        assert_eq!(it.next(), Some(Ok(fields[0].clone())));
+        assert_eq!(it.offset(), fields[0].encoded_len());
+        assert_eq!(it.collect::<Result<Vec<_>, _>>().unwrap(), fields[1..]);
+
+        let cut = &enc[..enc.len() - 1];
+        let items: Vec<_> = iter_fields(cut).collect();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1], Err(decode_message(cut).unwrap_err()));
+    }
+
+    #[test]
+    fn unknown_types_preserved() {
+        // поле с кодом 0x55, ключ "n", три байта данных
+        let data = [0x55, 0, 0, 0, 1, b'n', 0, 0, 0, 3, 7, 8, 9];
+        assert_eq!(
+            decode_field(&data).unwrap_err(),
+            DecodeError::InvalidTypeCode { offset: 0, code: 0x55 }
+        );
+
+        let opts = DecodeOptions { unknown_types: UnknownTypes::Preserve, ..UNLIMITED };
+        let field = decode_field_with(&data, &opts).unwrap();
+        assert_eq!(field.value, Value::Unknown { type_code: 0x55, raw: vec![7, 8, 9] });
+        assert_eq!(encode_field(&field).unwrap(), data);
+
+        // внутри списка неизвестное значение тоже сохраняется
+        let list = Field { key: "l".into(), value: Value::List(vec![field.value.clone()]) };
+        let enc = encode_field(&list).unwrap();
+        assert_eq!(decode_field_with(&enc, &opts).unwrap(), list);
+    }
+}
@@ -0,0 +1,297 @@
+//! Самоописывающие сообщения: схема записывается перед полями
+//!
+//! ```text
+//! "CCDS" | версия (u8) | длина дескриптора (u32 BE) | дескриптор | поля
+//! ```
+//!
+//! Дескриптор — схема, закодированная как сообщение с короткими ключами,
+//! поля — обычное сообщение в стандартном профиле. Получателю не нужно
+//! знать схему заранее: `decode_described` возвращает её вместе с полями,
+//! уже дополненными значениями по умолчанию и проверенными по ней.
+//!
+//! Схема в дескрипторе — `Message` с полями `n` (имя, если есть), `u`
+//! (`true`, если допускаются неизвестные поля) и `f` — список полей схемы.
+//! Поле схемы — `Message` с `k` (ключ), `t` (тип), `r` (`true` для
+//! обязательного), `d` (значение по умолчанию) и `x` (`true` для
+//! устаревшего). Тип — имя встроенного типа строкой или `Message` с одним
+//! полем: `s` — схема вложенного сообщения, `l` — тип элементов списка,
+//! `m` — список из типов ключа и значения.
+
+use crate::crc32;
+use crate::decode::{decode_message_at, UNLIMITED};
+use crate::schema_dsl::builtin;
+use crate::typed::{find_field, message_fields, required_field};
+use crate::{
+    encode_message, Decode, DecodeError, Encode, EncodeError, Field, Schema, SchemaError,
+    SchemaField, SchemaType, Value, ValueError,
+};
+
+/// Сигнатура самоописывающего сообщения
+pub const DESCRIBED_MAGIC: [u8; 4] = *b"CCDS";
+
+/// Версия раскладки дескриптора
+const DESCRIBED_VERSION: u8 = 1;
+
+/// Сигнатура, версия и длина дескриптора
+const DESCRIBED_HEADER_LEN: usize = 4 + 1 + 4;
+
+fn field(key: &str, value: Value) -> Field {
+    Field { key: key.to_owned(), value }
+}
+
+impl Encode for SchemaType {
+    fn encode_value(&self) -> Value {
+        let (key, value) = match self {
+            SchemaType::Message(schema) => ("s", schema.encode_value()),
+            SchemaType::List(item) => ("l", item.encode_value()),
+            SchemaType::Map(k, v) => ("m", Value::List(vec![k.encode_value(), v.encode_value()])),
+            scalar => return Value::String(scalar.to_string()),
+        };
+        Value::Message(vec![field(key, value)])
+    }
+}
+
+impl Decode for SchemaType {
+    fn decode_value(v: &Value) -> Result<Self, ValueError> {
+        if let Value::String(name) = v {
+            return builtin(name).ok_or_else(|| ValueError::UnknownVariant { name: name.clone() });
+        }
+        let f = match message_fields(v)? {
+            [f] => f,
+            fields => return Err(ValueError::InvalidLength { expected: 1, found: fields.len() }),
+        };
+        let ty = match f.key.as_str() {
+            "s" => Schema::decode_value(&f.value).map(SchemaType::Message),
+            "l" => Self::decode_value(&f.value).map(|item| SchemaType::List(Box::new(item))),
+            "m" => <(SchemaType, SchemaType)>::decode_value(&f.value)
+                .map(|(k, v)| SchemaType::Map(Box::new(k), Box::new(v))),
+            _ => return Err(ValueError::UnknownVariant { name: f.key.clone() }),
+        };
+        ty.map_err(|e| e.in_field(&f.key))
+    }
+}
+
+impl Encode for SchemaField {
+    fn encode_value(&self) -> Value {
+        let mut fields = vec![
+            field("k", Value::String(self.key.clone())),
+            field("t", self.ty.encode_value()),
+        ];
+        if self.required {
+            fields.push(field("r", Value::Bool(true)));
+        }
+        if let Some(default) = &self.default {
+            fields.push(field("d", default.clone()));
+        }
+        if self.deprecated {
+            fields.push(field("x", Value::Bool(true)));
+        }
+        Value::Message(fields)
+    }
+}
+
+/// Флаг `key`: отсутствие или `Null` — `false`
+fn flag(fields: &[Field], key: &str) -> Result<bool, ValueError> {
+    let flag = find_field(fields, key).map(Option::<bool>::decode_value).transpose();
+    Ok(flag.map_err(|e| e.in_field(key))?.flatten().unwrap_or(false))
+}
+
+impl Decode for SchemaField {
+    fn decode_value(v: &Value) -> Result<Self, ValueError> {
+        let fields = message_fields(v)?;
+        let key = String::decode_value(required_field(fields, "k")?);
+        let ty = SchemaType::decode_value(required_field(fields, "t")?);
+        let default = find_field(fields, "d").filter(|d| **d != Value::Null).cloned();
+        Ok(SchemaField {
+            key: key.map_err(|e| e.in_field("k"))?,
+            ty: ty.map_err(|e| e.in_field("t"))?,
+            required: flag(fields, "r")?,
+            default,
+            deprecated: flag(fields, "x")?,
+        })
+    }
+}
+
+impl Encode for Schema {
+    fn encode_value(&self) -> Value {
+        let mut fields = Vec::with_capacity(3);
+        if let Some(name) = self.name() {
+            fields.push(field("n", Value::String(name.to_owned())));
+        }
+        if self.allow_unknown() {
+            fields.push(field("u", Value::Bool(true)));
+        }
+        fields.push(field("f", self.fields().encode_value()));
+        Value::Message(fields)
+    }
+}
+
+impl Decode for Schema {
+    fn decode_value(v: &Value) -> Result<Self, ValueError> {
+        let fields = message_fields(v)?;
+        let name = find_field(fields, "n").map(Option::<String>::decode_value).transpose();
+        let mut schema = match name.map_err(|e| e.in_field("n"))?.flatten() {
+            Some(name) => Schema::named(&name),
+            None => Schema::new(),
+        };
+        schema.set_allow_unknown(flag(fields, "u")?);
+        let specs = Vec::<SchemaField>::decode_value(required_field(fields, "f")?);
+        specs.map_err(|e| e.in_field("f"))?.into_iter().for_each(|f| schema.insert(f));
+        Ok(schema)
+    }
+}
+
+/// Сообщение с дескриптором схемы перед полями
+///
+/// Поля по схеме не проверяются; проверку выполняет получатель.
+///
+/// ```
+/// use custom_codec::{decode_described, encode_described, Field, Schema, SchemaType, Value};
+///
+/// let mut schema = Schema::named("Point");
+/// schema.required("x", SchemaType::Int32);
+/// let fields = vec![Field { key: "x".into(), value: Value::Int32(3) }];
+///
+/// let data = encode_described(&fields, &schema).unwrap();
+/// let (received, decoded) = decode_described(&data).unwrap();
+/// assert_eq!(received, schema);
+/// assert_eq!(decoded, fields);
+/// ```
+pub fn encode_described(fields: &[Field], schema: &Schema) -> Result<Vec<u8>, EncodeError> {
+    let descriptor = match schema.encode_value() {
+        Value::Message(fields) => encode_message(&fields)?,
+        _ => unreachable!("схема кодируется как Message"),
+    };
+    let len = u32::try_from(descriptor.len())
+        .map_err(|_| EncodeError::LengthOverflow { len: descriptor.len() })?;
+    let body = encode_message(fields)?;
+    let mut out = Vec::with_capacity(DESCRIBED_HEADER_LEN + descriptor.len() + body.len());
+    out.extend_from_slice(&DESCRIBED_MAGIC);
+    out.push(DESCRIBED_VERSION);
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(&descriptor);
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+impl Schema {
+    /// CRC-32 дескриптора схемы: номер для `SchemaId` без общего реестра
+    ///
+    /// Одинаковые схемы дают одинаковый номер; порядок полей схемы важен.
+    pub fn fingerprint(&self) -> u32 {
+        let Value::Message(fields) = self.encode_value() else {
+            unreachable!("схема кодируется как Message");
+        };
+        let descriptor = encode_message(&fields).expect("дескриптор схемы короче 4 GiB");
+        crc32::checksum(&descriptor)
+    }
+}
+
+/// Схема и поля самоописывающего сообщения
+///
+/// Поля дополняются значениями по умолчанию и проверяются по схеме, как в
+/// `decode_with_schema`. Дескриптор, который не описывает схему, даёт
+/// `SchemaError::Descriptor`.
+pub fn decode_described(data: &[u8]) -> Result<(Schema, Vec<Field>), SchemaError> {
+    if data.len() < DESCRIBED_HEADER_LEN {
+        return Err(DecodeError::UnexpectedEof { offset: data.len() }.into());
+    }
+    if data[..4] != DESCRIBED_MAGIC {
+        return Err(DecodeError::InvalidMagic { offset: 0 }.into());
+    }
+    if data[4] != DESCRIBED_VERSION {
+        return Err(DecodeError::UnsupportedVersion { offset: 4, version: data[4] }.into());
+    }
+    let len = u32::from_be_bytes(data[5..9].try_into().unwrap()) as usize;
+    let end = DESCRIBED_HEADER_LEN.saturating_add(len);
+    if end > data.len() {
+        let actual = data.len() - DESCRIBED_HEADER_LEN;
+        return Err(DecodeError::LengthMismatch { offset: 5, expected: len, actual }.into());
+    }
+    let descriptor = decode_message_at(&data[..end], DESCRIBED_HEADER_LEN, &UNLIMITED)?;
+    let schema =
+        Schema::decode_value(&Value::Message(descriptor)).map_err(SchemaError::Descriptor)?;
+    let mut fields = decode_message_at(data, end, &UNLIMITED)?;
+    schema.apply_defaults(&mut fields);
+    schema.validate_message(&fields).map_err(SchemaError::Invalid)?;
+    Ok((schema, fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_schemas;
+
+    #[test]
+    fn described_roundtrip() {
+        let text = r#"
+            message User {
+              required Int64 id;
+              optional Map<String, List<Bytes>> keys;
+              optional String lang [default = "ru", deprecated];
+              required Address address;
+              option allow_unknown = true;
+            }
+            message Address { optional Uuid city; }
+        "#;
+        let schema = parse_schemas(text).unwrap().get("User").unwrap().clone();
+        assert_eq!(Schema::decode_value(&schema.encode_value()), Ok(schema.clone()));
+
+        let fields = vec![
+            field("id", Value::Int64(1)),
+            field("address", Value::Message(vec![])),
+            field("extra", Value::Bool(true)),
+        ];
+        let data = encode_described(&fields, &schema).unwrap();
+        assert_eq!(&data[..5], b"CCDS\x01");
+        let (received, decoded) = decode_described(&data).unwrap();
+        assert_eq!(received, schema);
+        let mut expected = fields.clone();
+        expected.push(field("lang", Value::String("ru".into())));
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn described_errors() {
+        let mut schema = Schema::new();
+        schema.required("id", SchemaType::Int64);
+        let data = encode_described(&[], &schema).unwrap();
+        assert!(matches!(decode_described(&data), Err(SchemaError::Invalid(_))));
+        assert_eq!(
+            decode_described(&data[..data.len() - 1]),
+            Err(SchemaError::Decode(DecodeError::LengthMismatch {
+                offset: 5,
+                expected: data.len() - DESCRIBED_HEADER_LEN,
+                actual: data.len() - DESCRIBED_HEADER_LEN - 1,
+            }))
+        );
+        assert_eq!(
+            decode_described(b"CCDC\x01\x00\x00\x00\x00"),
+            Err(SchemaError::Decode(DecodeError::InvalidMagic { offset: 0 }))
+        );
+
+        let bogus = vec![field("f", Value::List(vec![Value::Message(vec![
+            field("k", Value::String("id".into())),
+            field("t", Value::String("Int128".into())),
+        ])]))];
+        let descriptor = encode_message(&bogus).unwrap();
+        let mut data = b"CCDS\x01".to_vec();
+        data.extend_from_slice(&(descriptor.len() as u32).to_be_bytes());
+        data.extend_from_slice(&descriptor);
+        let unknown = ValueError::UnknownVariant { name: "Int128".into() };
+        assert_eq!(
+            decode_described(&data),
+            Err(SchemaError::Descriptor(unknown.in_field("t").in_field("f")))
+        );
+    }
+
+    #[test]
+    fn fingerprint_follows_schema() {
+        let mut a = Schema::named("Point");
+        a.required("x", SchemaType::Int32);
+        let b = a.clone();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        a.optional("y", SchemaType::Int32);
+        assert_ne!(a.fingerprint(), b.fingerprint());
+    }
+}
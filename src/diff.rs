@@ -0,0 +1,271 @@
+//! Структурное сравнение сообщений
+//!
+//! Поля сопоставляются по ключу, повторы ключа — по порядку следования.
+//! Вложенные сообщения и списки сравниваются поэлементно, остальные
+//! значения — целиком. Изменения перечислены в порядке полей первого
+//! сообщения; добавленные поля идут после изменений своего уровня.
+//!
+//! Порядок полей тоже учитывается: если поля уровня во втором сообщении
+//! идут не так, как оставшиеся поля первого с добавленными в конце,
+//! после изменений уровня идёт [`Change::Reordered`] с новым порядком
+//! ключей.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{Field, Message, Value};
+
+/// Шаг пути к значению
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// Поле сообщения; `nth` — номер повтора ключа, у первого поля 0
+    Key { key: String, nth: usize },
+    /// Элемент списка
+    Index(usize),
+}
+
+/// Путь к значению от корня сообщения
+///
+/// Печатается как путь `Message::get_path` с `[i]` для элемента списка и
+/// `[#n]` для повтора ключа; точка, обратная черта, `[` и `*` в ключе
+/// экранируются, так что печатный путь — ещё и селектор
+/// [`Message::select`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FieldPath(pub Vec<PathSegment>);
+
+impl FieldPath {
+    pub(crate) fn with(&self, segment: PathSegment) -> FieldPath {
+        let mut path = self.clone();
+        path.0.push(segment);
+        path
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key { key, nth } => {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    for c in key.chars() {
+                        if matches!(c, '.' | '\\' | '[' | '*') {
+                            f.write_str("\\")?;
+                        }
+                        write!(f, "{c}")?;
+                    }
+                    if *nth > 0 {
+                        write!(f, "[#{nth}]")?;
+                    }
+                }
+                PathSegment::Index(i) => write!(f, "[{i}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Различие в значении по пути
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added { path: FieldPath, value: Value },
+    Removed { path: FieldPath, value: Value },
+    Changed { path: FieldPath, before: Value, after: Value },
+    /// Поля сообщения по пути `path` (пустой путь — корень) идут в
+    /// порядке `keys`; повторы ключа между собой не переставляются
+    Reordered { path: FieldPath, keys: Vec<String> },
+}
+
+impl Change {
+    pub fn path(&self) -> &FieldPath {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. }
+            | Change::Reordered { path, .. } => path,
+        }
+    }
+}
+
+/// Результат [`diff`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    /// Сообщения совпадают
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Изменения, превращающие `a` в `b`
+///
+/// ```
+/// use custom_codec::{diff, Change, Message, Value};
+///
+/// let mut a = Message::new();
+/// a.insert("age", Value::Int32(42));
+/// let mut b = a.clone();
+/// b.insert("age", Value::Int32(43));
+/// let changes = diff(&a, &b).changes;
+/// assert_eq!(changes[0].path().to_string(), "age");
+/// assert!(matches!(changes[0], Change::Changed { after: Value::Int32(43), .. }));
+/// ```
+pub fn diff(a: &Message, b: &Message) -> Diff {
+    let mut changes = Vec::new();
+    diff_fields(&FieldPath::default(), a.fields(), b.fields(), &mut changes);
+    Diff { changes }
+}
+
+fn diff_fields(path: &FieldPath, a: &[Field], b: &[Field], changes: &mut Vec<Change>) {
+    let key = |key: &str, nth| PathSegment::Key { key: key.to_owned(), nth };
+    // позиции повторов каждого ключа в `b` и номер каждого поля `b` среди них
+    let mut positions: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut nth_in_b = Vec::with_capacity(b.len());
+    for (j, field) in b.iter().enumerate() {
+        let same = positions.entry(&field.key).or_default();
+        nth_in_b.push(same.len());
+        same.push(j);
+    }
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let mut matched = vec![false; b.len()];
+    let mut kept = Vec::new();
+    for field in a {
+        // n-й повтор ключа в `a` сопоставляется с n-м повтором в `b`
+        let count = seen.entry(&field.key).or_default();
+        let nth = *count;
+        *count += 1;
+        let path = path.with(key(&field.key, nth));
+        let other = positions.get(field.key.as_str()).and_then(|same| same.get(nth));
+        match other {
+            Some(&j) => {
+                matched[j] = true;
+                kept.push(field.key.as_str());
+                diff_values(path, &field.value, &b[j].value, changes);
+            }
+            None => changes.push(Change::Removed { path, value: field.value.clone() }),
+        }
+    }
+    for (j, field) in b.iter().enumerate().filter(|&(j, _)| !matched[j]) {
+        let path = path.with(key(&field.key, nth_in_b[j]));
+        changes.push(Change::Added { path, value: field.value.clone() });
+    }
+
+    // после удалений и добавлений в конец поля шли бы как `kept` и `added`
+    let added = b.iter().zip(&matched).filter(|&(_, &m)| !m).map(|(f, _)| f.key.as_str());
+    if !kept.into_iter().chain(added).eq(b.iter().map(|f| f.key.as_str())) {
+        let keys = b.iter().map(|f| f.key.clone()).collect();
+        changes.push(Change::Reordered { path: path.clone(), keys });
+    }
+}
+
+fn diff_values(path: FieldPath, a: &Value, b: &Value, changes: &mut Vec<Change>) {
+    match (a, b) {
+        (Value::Message(a), Value::Message(b)) => diff_fields(&path, a, b, changes),
+        (Value::List(a), Value::List(b)) => {
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                diff_values(path.with(PathSegment::Index(i)), x, y, changes);
+            }
+            for (i, x) in a.iter().enumerate().skip(b.len()) {
+                let path = path.with(PathSegment::Index(i));
+                changes.push(Change::Removed { path, value: x.clone() });
+            }
+            for (i, y) in b.iter().enumerate().skip(a.len()) {
+                let path = path.with(PathSegment::Index(i));
+                changes.push(Change::Added { path, value: y.clone() });
+            }
+        }
+        _ if a != b => {
+            changes.push(Change::Changed { path, before: a.clone(), after: b.clone() })
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn nested_changes() {
+        let a = Message::from(vec![
+            field("id", Value::Int32(1)),
+            field("user", Value::Message(vec![
+                field("age", Value::Int32(42)),
+                field("email", Value::String("a@example.com".into())),
+            ])),
+            field("tags", Value::List(vec![Value::Int32(1), Value::Int32(2)])),
+        ]);
+        let b = Message::from(vec![
+            field("id", Value::Int32(1)),
+            field("user", Value::Message(vec![
+                field("age", Value::Int32(43)),
+                field("a.b", Value::Null),
+            ])),
+            field("tags", Value::List(vec![Value::Int32(1)])),
+        ]);
+        let changes = diff(&a, &b).changes;
+        let paths: Vec<_> = changes.iter().map(|c| c.path().to_string()).collect();
+        assert_eq!(paths, ["user.age", "user.email", r"user.a\.b", "tags[1]"]);
+        assert_eq!(changes[0], Change::Changed {
+            path: FieldPath(vec![
+                PathSegment::Key { key: "user".into(), nth: 0 },
+                PathSegment::Key { key: "age".into(), nth: 0 },
+            ]),
+            before: Value::Int32(42),
+            after: Value::Int32(43),
+        });
+        assert!(matches!(&changes[2], Change::Added { value: Value::Null, .. }));
+        assert!(matches!(&changes[3], Change::Removed { value: Value::Int32(2), .. }));
+        assert!(diff(&a, &a).is_empty());
+    }
+
+    #[test]
+    fn repeated_keys() {
+        let a = Message::from(vec![field("x", Value::Int32(1)), field("x", Value::Int32(2))]);
+        let b = Message::from(vec![
+            field("x", Value::Int32(1)),
+            field("x", Value::Int32(3)),
+            field("x", Value::Int32(4)),
+        ]);
+        let changes = diff(&a, &b).changes;
+        let paths: Vec<_> = changes.iter().map(|c| c.path().to_string()).collect();
+        assert_eq!(paths, ["x[#1]", "x[#2]"]);
+        assert!(matches!(&changes[1], Change::Added { value: Value::Int32(4), .. }));
+
+        // повторы сопоставляются за линейное время, а не перебором `b` на каждое поле
+        let many = |n| (0..n).map(|i| field("x", Value::Int32(i))).collect::<Vec<_>>();
+        let many = |n| Message::from(many(n));
+        let changes = diff(&many(50_000), &many(50_001)).changes;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path().to_string(), "x[#50000]");
+    }
+
+    #[test]
+    fn reordered_fields() {
+        let a = Message::from(vec![field("id", Value::Int32(1)), field("name", Value::Null)]);
+        let b = Message::from(vec![field("name", Value::Null), field("id", Value::Int32(1))]);
+        let keys = vec!["name".to_owned(), "id".to_owned()];
+        assert_eq!(diff(&a, &b).changes, [Change::Reordered { path: FieldPath::default(), keys }]);
+
+        // новое поле не в конце тоже меняет порядок
+        let a = Message::from(vec![field("id", Value::Int32(1))]);
+        let b = Message::from(vec![field("new", Value::Null), field("id", Value::Int32(1))]);
+        let changes = diff(&a, &b).changes;
+        assert!(matches!(&changes[..], [Change::Added { .. }, Change::Reordered { .. }]));
+        let b = Message::from(vec![field("id", Value::Int32(1)), field("new", Value::Null)]);
+        assert!(matches!(&diff(&a, &b).changes[..], [Change::Added { .. }]));
+
+        let nested = |fields| Message::from(vec![field("user", Value::Message(fields))]);
+        let x = nested(vec![field("a", Value::Null), field("b", Value::Null)]);
+        let y = nested(vec![field("b", Value::Null), field("a", Value::Null)]);
+        assert_eq!(diff(&x, &y).changes[0].path().to_string(), "user");
+    }
+}
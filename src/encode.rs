@@ -0,0 +1,724 @@
+use std::io::Write;
+
+use crate::decode::{ENUM_HAS_NAME, ENUM_HAS_PAYLOAD};
+use crate::endian::Number;
+use crate::options::{INLINE_TRUE, KEY_LEN_ESCAPE};
+use crate::{
+    varint, EncodeError, EncodeOptions, Field, IntEncoding, KeyLength, KeyTable, LengthEncoding,
+    Profile, Value,
+};
+
+/// Код типа значения
+pub(crate) fn type_code(value: &Value) -> u8 {
+    match value {
+        Value::Int32(_) => 1,
+        Value::Float32(_) => 2,
+        Value::Bool(_) => 3,
+        Value::String(_) => 4,
+        Value::Bytes(_) => 5,
+        Value::Message(_) => 6,
+        Value::Int64(_) => 7,
+        Value::UInt64(_) => 8,
+        Value::Float64(_) => 9,
+        Value::Null => 10,
+        Value::List(_) => 11,
+        Value::Map(_) => 12,
+        Value::Timestamp(_) => 13,
+        Value::Uuid(_) => 14,
+        Value::Decimal(_) => 15,
+        Value::Enum { .. } => 16,
+        Value::Int8(_) => 17,
+        Value::Int16(_) => 18,
+        Value::UInt8(_) => 19,
+        Value::UInt16(_) => 20,
+        Value::UInt32(_) => 21,
+        Value::Unknown { type_code, .. } => *type_code,
+    }
+}
+
+/// Кодирование одного поля
+///
+/// Ошибка возможна, только если длина значения не помещается
+/// в префикс длины: в исходном профиле это значения больше 4 GiB.
+pub fn encode_field(field: &Field) -> Result<Vec<u8>, EncodeError> {
+    encode_field_with(field, &EncodeOptions::default())
+}
+
+/// Кодирование одного поля с настройками
+pub fn encode_field_with(field: &Field, opts: &EncodeOptions) -> Result<Vec<u8>, EncodeError> {
+    let mut out = Vec::with_capacity(field.encoded_len_with(&opts.profile));
+    encode_field_into_with(field, &mut out, opts)?;
+    Ok(out)
+}
+
+/// Кодирование одного поля с дописыванием в конец существующего буфера
+///
+/// При ошибке в буфере может остаться начало поля.
+pub fn encode_field_into(field: &Field, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    encode_field_into_with(field, out, &EncodeOptions::default())
+}
+
+/// То же, что [`encode_field_into`], с настройками
+pub fn encode_field_into_with(
+    field: &Field,
+    out: &mut Vec<u8>,
+    opts: &EncodeOptions,
+) -> Result<(), EncodeError> {
+    Writer::vec(out, opts.profile).field(field)
+}
+
+/// Кодирование поля в начало `buf` без выделения памяти
+///
+/// Возвращает число записанных байт. Если поле не помещается, `buf`
+/// не изменяется и возвращается `EncodeError::BufferTooSmall`.
+pub fn encode_field_to_slice(field: &Field, buf: &mut [u8]) -> Result<usize, EncodeError> {
+    encode_field_to_slice_with(field, buf, &EncodeOptions::default())
+}
+
+/// То же, что [`encode_field_to_slice`], с настройками
+pub fn encode_field_to_slice_with(
+    field: &Field,
+    buf: &mut [u8],
+    opts: &EncodeOptions,
+) -> Result<usize, EncodeError> {
+    let needed = field.encoded_len_with(&opts.profile);
+    if needed > buf.len() {
+        return Err(EncodeError::BufferTooSmall { needed, available: buf.len() });
+    }
+    Writer::new(&mut &mut buf[..needed], opts.profile).field(field)?;
+    Ok(needed)
+}
+
+/// Буфер `encode_field_small`: до `SMALL_FIELD_LEN` байт хранятся на стеке
+#[cfg(feature = "smallvec")]
+pub type SmallBytes = smallvec::SmallVec<[u8; SMALL_FIELD_LEN]>;
+
+/// Размер поля, до которого `encode_field_small` не выделяет память
+#[cfg(feature = "smallvec")]
+pub const SMALL_FIELD_LEN: usize = 64;
+
+/// Кодирование поля без выделения памяти, если оно занимает
+/// не больше `SMALL_FIELD_LEN` байт
+#[cfg(feature = "smallvec")]
+pub fn encode_field_small(field: &Field) -> Result<SmallBytes, EncodeError> {
+    let mut stack = StackBuf { buf: [0; SMALL_FIELD_LEN], len: 0, full: false };
+    match Writer::new(&mut stack, Profile::STANDARD).field(field) {
+        Ok(()) => Ok(SmallBytes::from_buf_and_len(stack.buf, stack.len)),
+        // размер заранее не считается: поле, не поместившееся на стек,
+        // кодируется заново в куче
+        Err(_) if stack.full => encode_field(field).map(SmallBytes::from_vec),
+        Err(e) => Err(e),
+    }
+}
+
+/// Буфер фиксированного размера для `encode_field_small`
+#[cfg(feature = "smallvec")]
+struct StackBuf {
+    buf: [u8; SMALL_FIELD_LEN],
+    len: usize,
+    full: bool,
+}
+
+#[cfg(feature = "smallvec")]
+impl Write for StackBuf {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.write_all(data).map(|_| data.len())
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let end = self.len + data.len();
+        if end > SMALL_FIELD_LEN {
+            self.full = true;
+            return Err(std::io::ErrorKind::WriteZero.into());
+        }
+        self.buf[self.len..end].copy_from_slice(data);
+        self.len = end;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Кодирование сообщения — нескольких полей подряд
+pub fn encode_message(fields: &[Field]) -> Result<Vec<u8>, EncodeError> {
+    encode_message_with(fields, &EncodeOptions::default())
+}
+
+/// Кодирование сообщения с настройками
+pub fn encode_message_with(fields: &[Field], opts: &EncodeOptions) -> Result<Vec<u8>, EncodeError> {
+    let profile = &opts.profile;
+    let mut out = Vec::with_capacity(fields.iter().map(|f| f.encoded_len_with(profile)).sum());
+    for f in fields {
+        encode_field_into_with(f, &mut out, opts)?;
+    }
+    Ok(out)
+}
+
+/// Кодировщик с переиспользуемым буфером
+///
+/// Буфер очищается перед каждым вызовом, но не освобождается, так что
+/// после первых сообщений кодирование перестаёт выделять память.
+///
+/// ```
+/// use custom_codec::{Encoder, Field, Value};
+///
+/// let mut enc = Encoder::new();
+/// for i in 0..3 {
+///     let bytes = enc.encode(&Field { key: "n".into(), value: Value::Int32(i) }).unwrap();
+///     assert_eq!(bytes.len(), 1 + 4 + 1 + 4 + 4);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+    opts: EncodeOptions,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Encoder::default()
+    }
+
+    pub fn with_options(opts: EncodeOptions) -> Self {
+        Encoder { buf: Vec::new(), opts }
+    }
+
+    /// Кодировщик с заранее выделенным буфером на `capacity` байт
+    pub fn with_capacity(capacity: usize) -> Self {
+        Encoder { buf: Vec::with_capacity(capacity), opts: EncodeOptions::default() }
+    }
+
+    /// Кодирование поля; результат действителен до следующего вызова
+    pub fn encode(&mut self, field: &Field) -> Result<&[u8], EncodeError> {
+        self.buf.clear();
+        encode_field_into_with(field, &mut self.buf, &self.opts)?;
+        Ok(&self.buf)
+    }
+
+    /// Кодирование сообщения; результат действителен до следующего вызова
+    pub fn encode_message(&mut self, fields: &[Field]) -> Result<&[u8], EncodeError> {
+        self.buf.clear();
+        for f in fields {
+            encode_field_into_with(f, &mut self.buf, &self.opts)?;
+        }
+        Ok(&self.buf)
+    }
+
+    /// Выделенная под буфер память в байтах
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Освобождение памяти сверх `capacity`, например после редкого
+    /// большого сообщения
+    pub fn shrink_to(&mut self, capacity: usize) {
+        self.buf.clear();
+        self.buf.shrink_to(capacity);
+    }
+}
+
+/// Элемент контейнера: код типа, длина и данные
+///
+/// Для сравнения ключей: 8-байтовые длины big-endian упорядочивают
+/// элементы так же, как 4-байтовые, но не переполняются.
+pub(crate) fn encode_element(value: &Value) -> Vec<u8> {
+    let wide = Profile { lengths: LengthEncoding::Fixed64, ..Profile::STANDARD };
+    let mut out = Vec::with_capacity(1 + value.encoded_len_with(&wide));
+    Writer::vec(&mut out, wide).element(value).expect("8-байтовые длины не переполняются");
+    out
+}
+
+/// Запись полей в любой `Write` в заданном профиле
+///
+/// Как и декодер, без рекурсии: содержимое контейнеров откладывается в
+/// стек шагов в куче, так что глубина значения не расходует стек.
+pub(crate) struct Writer<'w, W: ?Sized> {
+    w: &'w mut W,
+    profile: Profile,
+    /// таблица ключей: ключи полей заменяются числовыми тегами
+    tags: Option<&'w KeyTable>,
+    /// доступ к выходу как к `Vec`: фиксированные длины контейнеров
+    /// дописываются после их содержимого, а не считаются заранее
+    vec: Option<fn(&mut W) -> &mut Vec<u8>>,
+    /// посчитанные заранее длины ещё не открытых контейнеров, последняя —
+    /// следующего
+    lens: Vec<usize>,
+}
+
+/// Что осталось записать
+enum Step<'v> {
+    Field(&'v Field),
+    /// элемент списка или отображения, содержимое перечисления
+    Element(&'v Value),
+    /// подстановка длины контейнера, открытого на позиции `start`
+    Close { start: usize },
+}
+
+impl<'w> Writer<'w, Vec<u8>> {
+    /// Запись с подстановкой длин контейнеров задним числом
+    ///
+    /// В буфере под фиксированную длину оставляется место, а сама длина
+    /// записывается, когда содержимое готово, так что размеры не
+    /// считаются вовсе.
+    pub(crate) fn vec(w: &'w mut Vec<u8>, profile: Profile) -> Self {
+        Writer { w, profile, tags: None, vec: Some(|w| w), lens: Vec::new() }
+    }
+}
+
+/// Запись по одному элементу для сериализатора serde: контейнер
+/// открывается раньше, чем известно его содержимое
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+impl Writer<'_, Vec<u8>> {
+    /// Элемент, не являющийся контейнером; `key` — ключ поля сообщения
+    pub(crate) fn item(&mut self, key: Option<&str>, value: &Value) -> Result<(), EncodeError> {
+        self.w.push(self.code(value)?);
+        if let Some(key) = key {
+            self.key(key)?;
+        }
+        let mut steps = Vec::new();
+        self.value(value, &mut steps)?;
+        self.run(steps)
+    }
+
+    /// Код типа, ключ и место под длину контейнера; возвращает позицию
+    /// длины для `close`
+    pub(crate) fn open(&mut self, type_code: u8, key: Option<&str>) -> Result<usize, EncodeError> {
+        self.w.push(type_code);
+        if let Some(key) = key {
+            self.key(key)?;
+        }
+        let start = self.w.len();
+        let width = match self.profile.lengths {
+            LengthEncoding::Fixed32 => 4,
+            LengthEncoding::Fixed64 => 8,
+            // длина varint неизвестна заранее и вставляется при закрытии
+            LengthEncoding::Varint => 0,
+        };
+        self.w.resize(start + width, 0);
+        Ok(start)
+    }
+
+    /// Запись длины контейнера, открытого на позиции `start`
+    pub(crate) fn close(&mut self, start: usize) -> Result<(), EncodeError> {
+        let endian = self.profile.endianness;
+        match self.profile.lengths {
+            LengthEncoding::Fixed32 => {
+                let len = self.w.len() - start - 4;
+                let len = u32::try_from(len).map_err(|_| EncodeError::LengthOverflow { len })?;
+                self.w[start..start + 4].copy_from_slice(&len.to_bytes(endian))
+            }
+            LengthEncoding::Fixed64 => {
+                let len = (self.w.len() - start - 8) as u64;
+                self.w[start..start + 8].copy_from_slice(&len.to_bytes(endian))
+            }
+            LengthEncoding::Varint => {
+                let mut prefix = Vec::with_capacity(varint::MAX_LEN);
+                varint::write(&mut prefix, (self.w.len() - start) as u64)?;
+                self.w.splice(start..start, prefix);
+            }
+        }
+        Ok(())
+    }
+
+    /// Номер варианта, флаги и имя в открытом контейнере `Value::Enum`
+    pub(crate) fn enum_header(
+        &mut self,
+        variant: u32,
+        name: &str,
+        has_payload: bool,
+    ) -> Result<(), EncodeError> {
+        self.w.extend_from_slice(&variant.to_bytes(self.profile.endianness));
+        let flags = ENUM_HAS_NAME | if has_payload { ENUM_HAS_PAYLOAD } else { 0 };
+        self.w.push(flags);
+        self.chunk(name.as_bytes())
+    }
+}
+
+impl<'w, W: Write + ?Sized> Writer<'w, W> {
+    pub(crate) fn new(w: &'w mut W, profile: Profile) -> Self {
+        Writer { w, profile, tags: None, vec: None, lens: Vec::new() }
+    }
+
+    /// Все ключи полей должны присутствовать в таблице
+    pub(crate) fn with_tags(mut self, table: &'w KeyTable) -> Self {
+        self.tags = Some(table);
+        self
+    }
+
+    pub(crate) fn field(&mut self, field: &Field) -> Result<(), EncodeError> {
+        self.run(vec![Step::Field(field)])
+    }
+
+    /// Запись шагов, пока стек не опустеет
+    fn run(&mut self, mut steps: Vec<Step<'_>>) -> Result<(), EncodeError> {
+        while let Some(step) = steps.pop() {
+            let value = match step {
+                Step::Field(field) => {
+                    // 1 байт type_code
+                    self.w.write_all(&[self.code(&field.value)?])?;
+                    match self.tags {
+                        Some(table) => {
+                            let tag = table.tag(&field.key).expect("ключ проверен заранее");
+                            varint::write(self.w, tag as u64)?
+                        }
+                        None => self.key(&field.key)?,
+                    }
+                    &field.value
+                }
+                Step::Element(value) => {
+                    self.w.write_all(&[self.code(value)?])?;
+                    value
+                }
+                Step::Close { start } => {
+                    self.patch(start)?;
+                    continue;
+                }
+            };
+            self.value(value, &mut steps)?;
+        }
+        Ok(())
+    }
+
+    /// Заголовок поля `Value::Bytes` длиной `len` без самих данных:
+    /// они дописываются вызывающим
+    pub(crate) fn bytes_header(&mut self, key: &str, len: usize) -> Result<(), EncodeError> {
+        self.w.write_all(&[type_code(&Value::Bytes(Vec::new()))])?;
+        self.key(key)?;
+        self.len(len)
+    }
+
+    /// Длина ключа и ключ
+    fn key(&mut self, key: &str) -> Result<(), EncodeError> {
+        let key = key.as_bytes();
+        match self.profile.keys {
+            KeyLength::AsValues => self.len(key.len())?,
+            KeyLength::Short if key.len() < KEY_LEN_ESCAPE as usize => {
+                self.w.write_all(&[key.len() as u8])?
+            }
+            KeyLength::Short => {
+                let len = u32::try_from(key.len())
+                    .map_err(|_| EncodeError::LengthOverflow { len: key.len() })?;
+                self.w.write_all(&[KEY_LEN_ESCAPE])?;
+                self.w.write_all(&len.to_bytes(self.profile.endianness))?
+            }
+        }
+        Ok(self.w.write_all(key)?)
+    }
+
+    /// Код типа с учётом значений, записанных в нём целиком
+    fn code(&self, value: &Value) -> Result<u8, EncodeError> {
+        if let Value::Unknown { type_code: code, .. } = value {
+            // иначе значение прочиталось бы как значение другого типа
+            if !(*code == 0 || (22..INLINE_TRUE).contains(code)) {
+                return Err(EncodeError::InvalidTypeCode { code: *code });
+            }
+        }
+        Ok(self.profile.inline_code(value).unwrap_or_else(|| type_code(value)))
+    }
+
+    /// Запись длины и данных значения; содержимое контейнера добавляется
+    /// в `steps`
+    fn value<'v>(
+        &mut self,
+        value: &'v Value,
+        steps: &mut Vec<Step<'v>>,
+    ) -> Result<(), EncodeError> {
+        if self.profile.inline_code(value).is_some() {
+            return Ok(());
+        }
+        match value {
+            Value::Int32(i) => match self.profile.integers {
+                IntEncoding::Fixed => self.chunk(&i.to_bytes(self.profile.endianness)),
+                IntEncoding::ZigZag => self.zigzag(*i as i64),
+            },
+            Value::Float32(f) => self.chunk(&f.to_bytes(self.profile.endianness)),
+            Value::Bool(b) => self.chunk(&[*b as u8]),
+            Value::String(s) => self.chunk(s.as_bytes()),
+            Value::Bytes(bts) | Value::Unknown { raw: bts, .. } => self.chunk(bts),
+            Value::Message(fields) => {
+                self.container(value, steps)?;
+                steps.extend(fields.iter().rev().map(Step::Field));
+                Ok(())
+            }
+            Value::List(items) => {
+                self.container(value, steps)?;
+                steps.extend(items.iter().rev().map(Step::Element));
+                Ok(())
+            }
+            Value::Map(entries) => {
+                self.container(value, steps)?;
+                let pairs = entries.iter().rev();
+                steps.extend(pairs.flat_map(|(k, v)| [Step::Element(v), Step::Element(k)]));
+                Ok(())
+            }
+            Value::Int64(i) => match self.profile.integers {
+                IntEncoding::Fixed => self.chunk(&i.to_bytes(self.profile.endianness)),
+                IntEncoding::ZigZag => self.zigzag(*i),
+            },
+            Value::UInt64(u) => self.chunk(&u.to_bytes(self.profile.endianness)),
+            Value::Float64(f) => self.chunk(&f.to_bytes(self.profile.endianness)),
+            Value::Null => self.chunk(&[]),
+            Value::Timestamp(ts) => self.chunk(&ts.to_bytes(self.profile.endianness)),
+            Value::Uuid(bytes) => self.chunk(bytes),
+            Value::Decimal(d) => self.chunk(&d.to_bytes(self.profile.endianness)),
+            Value::Enum { variant, name, payload } => {
+                self.container(value, steps)?;
+                self.w.write_all(&variant.to_bytes(self.profile.endianness))?;
+                let mut flags = 0;
+                if name.is_some() {
+                    flags |= ENUM_HAS_NAME;
+                }
+                if payload.is_some() {
+                    flags |= ENUM_HAS_PAYLOAD;
+                }
+                self.w.write_all(&[flags])?;
+                if let Some(name) = name {
+                    self.chunk(name.as_bytes())?;
+                }
+                steps.extend(payload.as_deref().map(Step::Element));
+                Ok(())
+            }
+            Value::Int8(i) => self.chunk(&i.to_bytes(self.profile.endianness)),
+            Value::Int16(i) => self.chunk(&i.to_bytes(self.profile.endianness)),
+            Value::UInt8(u) => self.chunk(&[*u]),
+            Value::UInt16(u) => self.chunk(&u.to_bytes(self.profile.endianness)),
+            Value::UInt32(u) => self.chunk(&u.to_bytes(self.profile.endianness)),
+        }
+    }
+
+    /// Префикс длины контейнера перед содержимым; если длина
+    /// подставляется задним числом, в `steps` добавляется `Step::Close`,
+    /// который выполнится после содержимого
+    fn container(&mut self, value: &Value, steps: &mut Vec<Step<'_>>) -> Result<(), EncodeError> {
+        let fixed = self.profile.lengths != LengthEncoding::Varint;
+        let Some(vec) = self.vec.filter(|_| fixed) else {
+            // длины нужны заранее: они считаются одним проходом для всех
+            // вложенных контейнеров, а не заново на каждом уровне
+            if self.lens.is_empty() {
+                self.lens = self.container_lens(value);
+                self.lens.reverse();
+            }
+            let len = self.lens.pop().expect("длины посчитаны для всех контейнеров");
+            return self.len(len);
+        };
+        steps.push(Step::Close { start: vec(self.w).len() });
+        self.len(0)
+    }
+
+    /// Длина содержимого контейнера, открытого на позиции `start`
+    fn patch(&mut self, start: usize) -> Result<(), EncodeError> {
+        let vec = self.vec.expect("длина подставляется только в `Vec`");
+        let endian = self.profile.endianness;
+        let out = vec(self.w);
+        match self.profile.lengths {
+            LengthEncoding::Fixed32 => {
+                let len = out.len() - start - 4;
+                let len = u32::try_from(len).map_err(|_| EncodeError::LengthOverflow { len })?;
+                out[start..start + 4].copy_from_slice(&len.to_bytes(endian))
+            }
+            _ => {
+                let len = (out.len() - start - 8) as u64;
+                out[start..start + 8].copy_from_slice(&len.to_bytes(endian))
+            }
+        }
+        Ok(())
+    }
+
+    /// Размеры данных контейнера и вложенных контейнеров в порядке записи
+    fn container_lens(&self, value: &Value) -> Vec<usize> {
+        match self.tags {
+            Some(table) => value.container_lens(&self.profile, &|key| {
+                varint::encoded_len(table.tag(key).expect("ключ проверен заранее") as u64)
+            }),
+            None => value.container_lens(&self.profile, &|key| {
+                self.profile.key_len_size(key.len()) + key.len()
+            }),
+        }
+    }
+
+    /// Элемент списка или отображения: код типа и значение без ключа
+    fn element(&mut self, value: &Value) -> Result<(), EncodeError> {
+        self.run(vec![Step::Element(value)])
+    }
+
+    /// Префикс длины в формате профиля
+    fn len(&mut self, len: usize) -> Result<(), EncodeError> {
+        match self.profile.lengths {
+            LengthEncoding::Fixed32 => {
+                let len = u32::try_from(len).map_err(|_| EncodeError::LengthOverflow { len })?;
+                self.w.write_all(&len.to_bytes(self.profile.endianness))?
+            }
+            LengthEncoding::Fixed64 => {
+                self.w.write_all(&(len as u64).to_bytes(self.profile.endianness))?
+            }
+            LengthEncoding::Varint => varint::write(self.w, len as u64)?,
+        }
+        Ok(())
+    }
+
+    /// Целое в zig-zag varint с префиксом длины
+    fn zigzag(&mut self, v: i64) -> Result<(), EncodeError> {
+        let v = varint::zigzag(v);
+        self.len(varint::encoded_len(v))?;
+        Ok(varint::write(self.w, v)?)
+    }
+
+    /// Длина и сами данные
+    fn chunk(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        self.len(bytes.len())?;
+        Ok(self.w.write_all(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_field_with, DecodeOptions};
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn length_overflow_is_an_error() {
+        let mut out = Vec::new();
+        let mut w = Writer::new(&mut out, Profile::STANDARD);
+        assert_eq!(w.len(1 << 32), Err(EncodeError::LengthOverflow { len: 1 << 32 }));
+        let wide = Profile { lengths: LengthEncoding::Fixed64, ..Profile::STANDARD };
+        assert_eq!(Writer::new(&mut out, wide).len(1 << 32), Ok(()));
+        assert_eq!(out, [0, 0, 0, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn backpatched_lengths_match_streaming() {
+        let leaf = Value::Enum {
+            variant: 2,
+            name: Some("v".into()),
+            payload: Some(Box::new(Value::Map(vec![(Value::Int32(1), Value::Bytes(vec![9]))]))),
+        };
+        let mut value = Value::List(vec![leaf, Value::Null]);
+        for i in 0..50 {
+            value = Value::Message(vec![Field { key: format!("m{i}"), value }]);
+        }
+        let f = Field { key: "root".into(), value };
+        for profile in [
+            Profile::STANDARD,
+            Profile::compact(),
+            Profile { lengths: LengthEncoding::Fixed64, ..Profile::STANDARD },
+            Profile { endianness: crate::Endianness::Little, ..Profile::STANDARD },
+        ] {
+            let opts = EncodeOptions { profile, ..EncodeOptions::default() };
+            let mut streamed = Vec::new();
+            Writer::new(&mut streamed, profile).field(&f).unwrap();
+            let enc = encode_field_with(&f, &opts).unwrap();
+            assert_eq!(enc, streamed);
+            assert_eq!(enc.len(), f.encoded_len_with(&profile));
+        }
+    }
+
+    #[test]
+    fn deep_values_do_not_overflow_stack() {
+        let depth = 200_000;
+        let mut value = Value::Int32(9);
+        for _ in 0..depth {
+            value = Value::Message(vec![Field { key: "m".into(), value }]);
+        }
+        let f = Field { key: "m".into(), value };
+        let enc = encode_field(&f).unwrap();
+        assert_eq!(enc.len(), f.encoded_len());
+        let mut streamed = Vec::new();
+        Writer::new(&mut streamed, Profile::STANDARD).field(&f).unwrap();
+        assert_eq!(enc, streamed);
+        assert_eq!(enc[enc.len() - 14..], [1, 0, 0, 0, 1, b'm', 0, 0, 0, 4, 0, 0, 0, 9]);
+
+        // varint-длины считаются заранее, но один раз на всё значение:
+        // подсчёт на каждом уровне при такой глубине не уложился бы в тест
+        let profile = Profile::compact();
+        let opts = EncodeOptions { profile, ..EncodeOptions::default() };
+        let enc = encode_field_with(&f, &opts).unwrap();
+        assert_eq!(enc.len(), f.encoded_len_with(&profile));
+        let mut streamed = Vec::new();
+        Writer::new(&mut streamed, profile).field(&f).unwrap();
+        assert_eq!(enc, streamed);
+
+        // удаление такого значения рекурсивно, поэтому оно разбирается вручную
+        let mut value = f.value;
+        while let Value::Message(mut fields) = value {
+            value = fields.pop().unwrap().value;
+        }
+    }
+
+    #[test]
+    fn unknown_type_codes() {
+        let unknown = |type_code| Value::Unknown { type_code, raw: vec![1] };
+        let field = |code| Field { key: "x".into(), value: unknown(code) };
+        for code in [1, 6, 11, 12, 16, 21, INLINE_TRUE, 0x7f, 0x80, 0xff] {
+            assert_eq!(encode_field(&field(code)), Err(EncodeError::InvalidTypeCode { code }));
+        }
+        let list = Field { key: "l".into(), value: Value::List(vec![unknown(6)]) };
+        assert_eq!(encode_field(&list), Err(EncodeError::InvalidTypeCode { code: 6 }));
+
+        let preserve = crate::UnknownTypes::Preserve;
+        let opts = DecodeOptions { unknown_types: preserve, ..DecodeOptions::default() };
+        for code in [0, 22, 0x7d] {
+            let enc = encode_field(&field(code)).unwrap();
+            assert_eq!(decode_field_with(&enc, &opts).unwrap(), field(code));
+        }
+    }
+
+    #[test]
+    fn wide_lengths() {
+        let profile = Profile { lengths: LengthEncoding::Fixed64, ..Profile::STANDARD };
+        let f = Field { key: "b".into(), value: Value::Bytes(vec![1, 2, 3]) };
+        let enc = encode_field_with(&f, &EncodeOptions { profile, ..EncodeOptions::default() })
+            .unwrap();
+        assert_eq!(enc, [5, 0, 0, 0, 0, 0, 0, 0, 1, b'b', 0, 0, 0, 0, 0, 0, 0, 3, 1, 2, 3]);
+        let opts = DecodeOptions { profile, ..DecodeOptions::default() };
+        assert_eq!(decode_field_with(&enc, &opts).unwrap(), f);
+    }
+
+    #[test]
+    fn encode_into_slice() {
+        let f = Field { key: "s".into(), value: Value::String("slice".into()) };
+        let enc = encode_field(&f).unwrap();
+        let mut buf = [0xaa; 32];
+        assert_eq!(encode_field_to_slice(&f, &mut buf), Ok(enc.len()));
+        assert_eq!(buf[..enc.len()], enc);
+        assert_eq!(buf[enc.len()], 0xaa);
+
+        let mut small = [0; 8];
+        assert_eq!(
+            encode_field_to_slice(&f, &mut small),
+            Err(EncodeError::BufferTooSmall { needed: enc.len(), available: 8 })
+        );
+        assert_eq!(small, [0; 8]);
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn small_fields_stay_inline() {
+        let f = Field { key: "n".into(), value: Value::Int64(-1) };
+        let small = encode_field_small(&f).unwrap();
+        assert!(!small.spilled());
+        assert_eq!(small[..], encode_field(&f).unwrap());
+
+        let big = Field { key: "b".into(), value: Value::Bytes(vec![1; SMALL_FIELD_LEN]) };
+        let small = encode_field_small(&big).unwrap();
+        assert!(small.spilled());
+        assert_eq!(small[..], encode_field(&big).unwrap());
+    }
+
+    #[test]
+    fn encoder_reuses_buffer() {
+        let big = Field { key: "b".into(), value: Value::Bytes(vec![0; 100]) };
+        let small = Field { key: "s".into(), value: Value::Bool(true) };
+        let mut enc = Encoder::new();
+        assert_eq!(enc.encode(&big).unwrap(), encode_field(&big).unwrap());
+        let capacity = enc.capacity();
+        assert_eq!(enc.encode(&small).unwrap(), encode_field(&small).unwrap());
+        assert_eq!(enc.capacity(), capacity);
+        assert_eq!(
+            enc.encode_message(&[small.clone(), big.clone()]).unwrap(),
+            encode_message(&[small, big]).unwrap()
+        );
+    }
+}
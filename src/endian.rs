@@ -0,0 +1,35 @@
+//! Числа в порядке байт, выбранном профилем
+
+use crate::Endianness;
+
+/// Число фиксированной ширины
+pub(crate) trait Number: Sized {
+    type Bytes;
+
+    fn to_bytes(self, endian: Endianness) -> Self::Bytes;
+    fn from_bytes(bytes: Self::Bytes, endian: Endianness) -> Self;
+}
+
+macro_rules! number {
+    ($($t:ty),*) => {$(
+        impl Number for $t {
+            type Bytes = [u8; std::mem::size_of::<$t>()];
+
+            fn to_bytes(self, endian: Endianness) -> Self::Bytes {
+                match endian {
+                    Endianness::Big => self.to_be_bytes(),
+                    Endianness::Little => self.to_le_bytes(),
+                }
+            }
+
+            fn from_bytes(bytes: Self::Bytes, endian: Endianness) -> Self {
+                match endian {
+                    Endianness::Big => <$t>::from_be_bytes(bytes),
+                    Endianness::Little => <$t>::from_le_bytes(bytes),
+                }
+            }
+        }
+    )*};
+}
+
+number!(i8, i16, i32, i64, i128, u8, u16, u32, u64, f32, f64);
@@ -0,0 +1,347 @@
+//! Конверт: сигнатура, версия формата и длина сообщения
+//!
+//! ```text
+//! "CCDC" | версия (u8) | флаги (u8) | профиль (u8) | длина (u32 BE)
+//!        | [номер схемы (u32 BE) | версия схемы (u32 BE)] | поля | [CRC-32]
+//! ```
+//!
+//! При флаге `FLAG_CRC32` за телом следует CRC-32 заголовка и тела
+//! (u32 BE); она проверяется до разбора полей. При флаге `FLAG_SCHEMA_ID`
+//! перед телом записан `SchemaId` из `EncodeOptions::schema_id`; длина
+//! тела его не включает.
+//!
+//! Профиль кодирования записывается в конверт, поэтому декодеру его
+//! знать не нужно. Неизвестные версии и биты флагов отвергаются, так что
+//! будущие изменения формата получат новый номер версии.
+
+use std::ops::Range;
+
+use crate::decode::{decode_message_at, UNLIMITED};
+use crate::crc32;
+use crate::{
+    encode_message_with, Checksum, DecodeError, DecodeOptions, EncodeError, EncodeOptions,
+    Endianness, Field, IntEncoding, KeyLength, LengthEncoding, Limit, Profile, SchemaId,
+};
+
+/// Сигнатура в начале конверта
+pub const MAGIC: [u8; 4] = *b"CCDC";
+
+/// Текущая версия формата
+pub const VERSION: u8 = 1;
+
+/// Размер заголовка конверта
+pub const HEADER_LEN: usize = 4 + 1 + 1 + 1 + 4;
+
+/// За телом следует CRC-32
+const FLAG_CRC32: u8 = 0b0001;
+
+/// Перед телом записан идентификатор схемы
+const FLAG_SCHEMA_ID: u8 = 0b0010;
+
+/// Размер идентификатора схемы после заголовка
+const SCHEMA_ID_LEN: usize = 4 + 4;
+
+const PROFILE_VARINT_LENGTHS: u8 = 0b0001;
+const PROFILE_SHORT_KEYS: u8 = 0b0010;
+const PROFILE_ZIGZAG: u8 = 0b0100;
+const PROFILE_LITTLE_ENDIAN: u8 = 0b1000;
+const PROFILE_WIDE_LENGTHS: u8 = 0b1_0000;
+const PROFILE_INLINE_SCALARS: u8 = 0b10_0000;
+
+impl Profile {
+    pub(crate) fn to_bits(self) -> u8 {
+        let mut bits = 0;
+        match self.lengths {
+            LengthEncoding::Fixed32 => {}
+            LengthEncoding::Varint => bits |= PROFILE_VARINT_LENGTHS,
+            LengthEncoding::Fixed64 => bits |= PROFILE_WIDE_LENGTHS,
+        }
+        if self.keys == KeyLength::Short {
+            bits |= PROFILE_SHORT_KEYS;
+        }
+        if self.integers == IntEncoding::ZigZag {
+            bits |= PROFILE_ZIGZAG;
+        }
+        if self.endianness == Endianness::Little {
+            bits |= PROFILE_LITTLE_ENDIAN;
+        }
+        if self.inline_scalars {
+            bits |= PROFILE_INLINE_SCALARS;
+        }
+        bits
+    }
+
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        let known = PROFILE_VARINT_LENGTHS
+            | PROFILE_SHORT_KEYS
+            | PROFILE_ZIGZAG
+            | PROFILE_LITTLE_ENDIAN
+            | PROFILE_WIDE_LENGTHS
+            | PROFILE_INLINE_SCALARS;
+        if bits & !known != 0 {
+            return None;
+        }
+        let mut profile = Profile::STANDARD;
+        profile.lengths = match bits & (PROFILE_VARINT_LENGTHS | PROFILE_WIDE_LENGTHS) {
+            0 => LengthEncoding::Fixed32,
+            PROFILE_VARINT_LENGTHS => LengthEncoding::Varint,
+            PROFILE_WIDE_LENGTHS => LengthEncoding::Fixed64,
+            _ => return None,
+        };
+        if bits & PROFILE_SHORT_KEYS != 0 {
+            profile.keys = KeyLength::Short;
+        }
+        if bits & PROFILE_ZIGZAG != 0 {
+            profile.integers = IntEncoding::ZigZag;
+        }
+        if bits & PROFILE_LITTLE_ENDIAN != 0 {
+            profile.endianness = Endianness::Little;
+        }
+        profile.inline_scalars = bits & PROFILE_INLINE_SCALARS != 0;
+        Some(profile)
+    }
+}
+
+/// Кодирование сообщения в конверте
+pub fn encode_enveloped(fields: &[Field]) -> Result<Vec<u8>, EncodeError> {
+    encode_enveloped_with(fields, &EncodeOptions::default())
+}
+
+/// Кодирование сообщения в конверте с настройками
+///
+/// Тело конверта ограничено 4 GiB независимо от профиля.
+pub fn encode_enveloped_with(
+    fields: &[Field],
+    opts: &EncodeOptions,
+) -> Result<Vec<u8>, EncodeError> {
+    let body = encode_message_with(fields, opts)?;
+    let len = u32::try_from(body.len())
+        .map_err(|_| EncodeError::LengthOverflow { len: body.len() })?;
+    let mut flags = match opts.checksum {
+        Checksum::None => 0,
+        Checksum::Crc32 => FLAG_CRC32,
+    };
+    if opts.schema_id.is_some() {
+        flags |= FLAG_SCHEMA_ID;
+    }
+    let mut out = Vec::with_capacity(HEADER_LEN + SCHEMA_ID_LEN + body.len() + 4);
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&[VERSION, flags, opts.profile.to_bits()]);
+    out.extend_from_slice(&len.to_be_bytes());
+    if let Some(id) = opts.schema_id {
+        out.extend_from_slice(&id.id.to_be_bytes());
+        out.extend_from_slice(&id.version.to_be_bytes());
+    }
+    out.extend_from_slice(&body);
+    if flags & FLAG_CRC32 != 0 {
+        let crc = crc32::checksum(&out);
+        out.extend_from_slice(&crc.to_be_bytes());
+    }
+    Ok(out)
+}
+
+/// Разобранный заголовок конверта
+struct Header {
+    /// Длина тела
+    len: usize,
+    /// Начало тела: после заголовка и идентификатора схемы
+    body: usize,
+    /// Длина контрольной суммы за телом
+    trailer: usize,
+    profile: Profile,
+}
+
+/// Заголовок конверта; идентификатор схемы не читается, только учитывается
+fn read_header(data: &[u8]) -> Result<Header, DecodeError> {
+    if data.len() < HEADER_LEN {
+        return Err(DecodeError::UnexpectedEof { offset: data.len() });
+    }
+    if data[..4] != MAGIC {
+        return Err(DecodeError::InvalidMagic { offset: 0 });
+    }
+    if data[4] != VERSION {
+        return Err(DecodeError::UnsupportedVersion { offset: 4, version: data[4] });
+    }
+    let flags = data[5];
+    if flags & !(FLAG_CRC32 | FLAG_SCHEMA_ID) != 0 {
+        return Err(DecodeError::UnsupportedFlags { offset: 5, flags });
+    }
+    let profile = Profile::from_bits(data[6])
+        .ok_or(DecodeError::UnsupportedFlags { offset: 6, flags: data[6] })?;
+    let len = u32::from_be_bytes(data[7..11].try_into().unwrap()) as usize;
+    let body = if flags & FLAG_SCHEMA_ID != 0 { HEADER_LEN + SCHEMA_ID_LEN } else { HEADER_LEN };
+    let trailer = if flags & FLAG_CRC32 != 0 { 4 } else { 0 };
+    Ok(Header { len, body, trailer, profile })
+}
+
+/// Полная длина конверта по его заголовку, ограниченная `max_total_len`
+///
+/// Позволяет дождаться конверта целиком при чтении из потока.
+#[cfg_attr(not(feature = "futures"), allow(dead_code))]
+pub(crate) fn enveloped_len(header: &[u8], opts: &DecodeOptions) -> Result<usize, DecodeError> {
+    let header = read_header(header)?;
+    let total = header.body + header.len + header.trailer;
+    if total > opts.max_total_len {
+        return Err(DecodeError::LimitExceeded { offset: 7, limit: Limit::TotalLength });
+    }
+    Ok(total)
+}
+
+/// Декодирование сообщения из конверта
+pub fn decode_enveloped(data: &[u8]) -> Result<Vec<Field>, DecodeError> {
+    decode_enveloped_with(data, &UNLIMITED)
+}
+
+/// Декодирование сообщения из конверта с ограничениями
+///
+/// Профиль берётся из конверта, `opts.profile` не используется.
+pub fn decode_enveloped_with(
+    data: &[u8],
+    opts: &DecodeOptions,
+) -> Result<Vec<Field>, DecodeError> {
+    let (body, profile) = envelope_body(data)?;
+    // смещения ошибок внутри тела отсчитываются от начала конверта
+    decode_message_at(&data[..body.end], body.start, &DecodeOptions { profile, ..opts.clone() })
+}
+
+/// Границы тела конверта и его профиль после проверки длины и контрольной суммы
+pub(crate) fn envelope_body(data: &[u8]) -> Result<(Range<usize>, Profile), DecodeError> {
+    let Header { len, body, trailer, profile } = read_header(data)?;
+    let actual = data.len().saturating_sub(body + trailer);
+    if actual != len || data.len() < body + trailer {
+        return Err(DecodeError::LengthMismatch { offset: 7, expected: len, actual });
+    }
+    let end = body + len;
+    if trailer != 0 {
+        let expected = u32::from_be_bytes(data[end..].try_into().unwrap());
+        let actual = crc32::checksum(&data[..end]);
+        if expected != actual {
+            return Err(DecodeError::ChecksumMismatch { offset: end, expected, actual });
+        }
+    }
+    Ok((body..end, profile))
+}
+
+/// Идентификатор схемы из заголовка конверта; тело не проверяется
+pub fn envelope_schema_id(data: &[u8]) -> Result<Option<SchemaId>, DecodeError> {
+    let header = read_header(data)?;
+    if header.body == HEADER_LEN {
+        return Ok(None);
+    }
+    let id = data.get(HEADER_LEN..header.body).ok_or(DecodeError::UnexpectedEof {
+        offset: data.len(),
+    })?;
+    let word = |i: usize| u32::from_be_bytes(id[i..i + 4].try_into().unwrap());
+    Ok(Some(SchemaId { id: word(0), version: word(4) }))
+}
+
+/// Настройки, с которыми записан конверт: профиль, контрольная сумма и
+/// схема; тело не проверяется
+///
+/// Позволяет перезаписать изменённое сообщение в том же виде.
+pub fn envelope_options(data: &[u8]) -> Result<EncodeOptions, DecodeError> {
+    let header = read_header(data)?;
+    let checksum = match header.trailer {
+        0 => Checksum::None,
+        _ => Checksum::Crc32,
+    };
+    let schema_id = envelope_schema_id(data)?;
+    Ok(EncodeOptions { profile: header.profile, checksum, schema_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    fn sample() -> Vec<Field> {
+        vec![
+            Field { key: "id".into(), value: Value::Int64(-5) },
+            Field { key: "name".into(), value: Value::String("probe".into()) },
+        ]
+    }
+
+    #[test]
+    fn envelope_roundtrip() {
+        let enc = encode_enveloped(&sample()).unwrap();
+        assert_eq!(&enc[..HEADER_LEN], b"CCDC\x01\x00\x00\x00\x00\x00\x25");
+        assert_eq!(decode_enveloped(&enc).unwrap(), sample());
+
+        // профиль записан в конверт
+        let opts = EncodeOptions { profile: Profile::compact(), ..EncodeOptions::default() };
+        let enc = encode_enveloped_with(&sample(), &opts).unwrap();
+        assert_eq!(enc[6], PROFILE_VARINT_LENGTHS | PROFILE_ZIGZAG | PROFILE_INLINE_SCALARS);
+        assert_eq!(decode_enveloped(&enc).unwrap(), sample());
+    }
+
+    #[test]
+    fn envelope_errors() {
+        let enc = encode_enveloped(&sample()).unwrap();
+        let with = |i: usize, b: u8| {
+            let mut e = enc.clone();
+            e[i] = b;
+            decode_enveloped(&e).unwrap_err()
+        };
+        let short = decode_enveloped(&[1, 2, 3]).unwrap_err();
+        assert_eq!(short, DecodeError::UnexpectedEof { offset: 3 });
+        assert_eq!(with(0, b'X'), DecodeError::InvalidMagic { offset: 0 });
+        assert_eq!(with(4, 2), DecodeError::UnsupportedVersion { offset: 4, version: 2 });
+        assert_eq!(with(5, 0x80), DecodeError::UnsupportedFlags { offset: 5, flags: 0x80 });
+        assert_eq!(with(6, 0x80), DecodeError::UnsupportedFlags { offset: 6, flags: 0x80 });
+        assert_eq!(
+            decode_enveloped(&enc[..enc.len() - 1]).unwrap_err(),
+            DecodeError::LengthMismatch { offset: 7, expected: 37, actual: 36 }
+        );
+        // ошибка в теле указывает на смещение во всём конверте
+        let body = DecodeError::InvalidTypeCode { offset: HEADER_LEN, code: 99 };
+        assert_eq!(with(HEADER_LEN, 99), body);
+    }
+
+    #[test]
+    fn checksum_trailer() {
+        let opts = EncodeOptions { checksum: Checksum::Crc32, ..EncodeOptions::default() };
+        let enc = encode_enveloped_with(&sample(), &opts).unwrap();
+        assert_eq!(enc[5], FLAG_CRC32);
+        assert_eq!(enc.len(), encode_enveloped(&sample()).unwrap().len() + 4);
+        assert_eq!(decode_enveloped(&enc).unwrap(), sample());
+
+        // испорченный байт в теле обнаруживается до разбора полей
+        let mut bad = enc.clone();
+        bad[HEADER_LEN + 3] ^= 0x01;
+        let end = enc.len() - 4;
+        assert!(matches!(
+            decode_enveloped(&bad).unwrap_err(),
+            DecodeError::ChecksumMismatch { offset, .. } if offset == end
+        ));
+        assert_eq!(
+            decode_enveloped(&enc[..end + 2]).unwrap_err(),
+            DecodeError::LengthMismatch { offset: 7, expected: 37, actual: 35 }
+        );
+    }
+
+    #[test]
+    fn schema_id_header() {
+        let id = SchemaId { id: 0xdead_beef, version: 3 };
+        let opts = EncodeOptions {
+            checksum: Checksum::Crc32,
+            schema_id: Some(id),
+            ..EncodeOptions::default()
+        };
+        let enc = encode_enveloped_with(&sample(), &opts).unwrap();
+        assert_eq!(enc[5], FLAG_CRC32 | FLAG_SCHEMA_ID);
+        assert_eq!(&enc[HEADER_LEN..HEADER_LEN + SCHEMA_ID_LEN], b"\xde\xad\xbe\xef\0\0\0\x03");
+        assert_eq!(envelope_schema_id(&enc), Ok(Some(id)));
+        assert_eq!(envelope_options(&enc).as_ref(), Ok(&opts));
+        assert_eq!(decode_enveloped(&enc).unwrap(), sample());
+        let opts = DecodeOptions::default();
+        assert_eq!(enveloped_len(&enc[..HEADER_LEN], &opts), Ok(enc.len()));
+
+        let plain = encode_enveloped(&sample()).unwrap();
+        assert_eq!(envelope_schema_id(&plain), Ok(None));
+        assert_eq!(envelope_options(&plain), Ok(EncodeOptions::default()));
+        assert_eq!(
+            envelope_schema_id(&enc[..HEADER_LEN + 2]),
+            Err(DecodeError::UnexpectedEof { offset: HEADER_LEN + 2 })
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use std::path::PathBuf;
+use std::{fmt, io};
+
+use crate::Value;
+
+/// Ошибка декодирования.
+///
+/// Каждый вариант несёт смещение (в байтах от начала входного буфера),
+/// на котором обнаружена проблема.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Данные закончились раньше, чем ожидалось
+    UnexpectedEof { offset: usize },
+    /// Неизвестный код типа
+    InvalidTypeCode { offset: usize, code: u8 },
+    /// Ключ или строковое значение не является корректным UTF-8
+    InvalidUtf8 { offset: usize },
+    /// Длина значения не соответствует его типу
+    LengthMismatch { offset: usize, expected: usize, actual: usize },
+    /// Ошибка ввода-вывода при чтении из потока
+    Io { offset: usize, kind: io::ErrorKind },
+    /// Превышено ограничение из `DecodeOptions`
+    LimitExceeded { offset: usize, limit: Limit },
+    /// Повторяющийся ключ в `Value::Map` при политике `DuplicateKeys::Reject`
+    /// или в таблице ключей
+    DuplicateKey { offset: usize },
+    /// Данные значения недопустимы для его типа
+    InvalidValue { offset: usize, type_code: u8 },
+    /// Varint длиннее 10 байт или не помещается в `usize`
+    InvalidVarint { offset: usize },
+    /// Тег поля отсутствует в таблице ключей
+    UnknownTag { offset: usize, tag: u64 },
+    /// Данные отличаются от канонической формы начиная с этого смещения
+    NonCanonical { offset: usize },
+    /// Данные не начинаются с сигнатуры конверта
+    InvalidMagic { offset: usize },
+    /// Неизвестная версия формата в конверте
+    UnsupportedVersion { offset: usize, version: u8 },
+    /// Неизвестные биты флагов или профиля в конверте
+    UnsupportedFlags { offset: usize, flags: u8 },
+    /// Контрольная сумма не совпала; `offset` — начало контрольной суммы
+    ChecksumMismatch { offset: usize, expected: u32, actual: u32 },
+}
+
+/// Ограничение декодирования, которое было превышено
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Depth,
+    ValueLength,
+    TotalLength,
+    FieldCount,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Limit::Depth => "глубина вложенности",
+            Limit::ValueLength => "длина значения",
+            Limit::TotalLength => "общий размер",
+            Limit::FieldCount => "число полей",
+        })
+    }
+}
+
+impl DecodeError {
+    /// Смещение, на котором произошла ошибка
+    pub fn offset(&self) -> usize {
+        match *self {
+            DecodeError::UnexpectedEof { offset }
+            | DecodeError::InvalidTypeCode { offset, .. }
+            | DecodeError::InvalidUtf8 { offset }
+            | DecodeError::LengthMismatch { offset, .. }
+            | DecodeError::Io { offset, .. }
+            | DecodeError::LimitExceeded { offset, .. }
+            | DecodeError::DuplicateKey { offset }
+            | DecodeError::InvalidValue { offset, .. }
+            | DecodeError::InvalidVarint { offset }
+            | DecodeError::UnknownTag { offset, .. }
+            | DecodeError::NonCanonical { offset }
+            | DecodeError::InvalidMagic { offset }
+            | DecodeError::UnsupportedVersion { offset, .. }
+            | DecodeError::UnsupportedFlags { offset, .. }
+            | DecodeError::ChecksumMismatch { offset, .. } => offset,
+        }
+    }
+
+    /// Та же ошибка со смещением, сдвинутым на `by`: для участка,
+    /// декодированного отдельно от начала данных
+    pub(crate) fn shifted(mut self, by: usize) -> Self {
+        match &mut self {
+            DecodeError::UnexpectedEof { offset }
+            | DecodeError::InvalidTypeCode { offset, .. }
+            | DecodeError::InvalidUtf8 { offset }
+            | DecodeError::LengthMismatch { offset, .. }
+            | DecodeError::Io { offset, .. }
+            | DecodeError::LimitExceeded { offset, .. }
+            | DecodeError::DuplicateKey { offset }
+            | DecodeError::InvalidValue { offset, .. }
+            | DecodeError::InvalidVarint { offset }
+            | DecodeError::UnknownTag { offset, .. }
+            | DecodeError::NonCanonical { offset }
+            | DecodeError::InvalidMagic { offset }
+            | DecodeError::UnsupportedVersion { offset, .. }
+            | DecodeError::UnsupportedFlags { offset, .. }
+            | DecodeError::ChecksumMismatch { offset, .. } => *offset += by,
+        }
+        self
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof { offset } => {
+                write!(f, "неожиданный конец данных (смещение {offset})")
+            }
+            DecodeError::InvalidTypeCode { offset, code } => {
+                write!(f, "неизвестный код типа {code} (смещение {offset})")
+            }
+            DecodeError::InvalidUtf8 { offset } => {
+                write!(f, "некорректный UTF-8 (смещение {offset})")
+            }
+            DecodeError::LengthMismatch { offset, expected, actual } => write!(
+                f,
+                "неверная длина значения: ожидалось {expected}, получено {actual} (смещение {offset})"
+            ),
+            DecodeError::Io { offset, kind } => {
+                write!(f, "ошибка ввода-вывода: {kind} (смещение {offset})")
+            }
+            DecodeError::LimitExceeded { offset, limit } => {
+                write!(f, "превышено ограничение: {limit} (смещение {offset})")
+            }
+            DecodeError::DuplicateKey { offset } => {
+                write!(f, "повторяющийся ключ отображения (смещение {offset})")
+            }
+            DecodeError::InvalidValue { offset, type_code } => {
+                write!(f, "недопустимое значение типа {type_code} (смещение {offset})")
+            }
+            DecodeError::InvalidVarint { offset } => {
+                write!(f, "некорректный varint (смещение {offset})")
+            }
+            DecodeError::UnknownTag { offset, tag } => {
+                write!(f, "неизвестный тег поля {tag} (смещение {offset})")
+            }
+            DecodeError::NonCanonical { offset } => {
+                write!(f, "данные не в канонической форме (смещение {offset})")
+            }
+            DecodeError::InvalidMagic { offset } => {
+                write!(f, "нет сигнатуры конверта (смещение {offset})")
+            }
+            DecodeError::UnsupportedVersion { offset, version } => {
+                write!(f, "неподдерживаемая версия формата {version} (смещение {offset})")
+            }
+            DecodeError::UnsupportedFlags { offset, flags } => {
+                write!(f, "неизвестные флаги конверта {flags:#04x} (смещение {offset})")
+            }
+            DecodeError::ChecksumMismatch { offset, expected, actual } => write!(
+                f,
+                "контрольная сумма не совпала: ожидалось {expected:#010x}, получено {actual:#010x} (смещение {offset})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Ошибка кодирования
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodeError {
+    /// Длина не помещается в префикс длины выбранного профиля
+    LengthOverflow { len: usize },
+    /// Ключ поля отсутствует в таблице ключей
+    UnknownKey { key: String },
+    /// Повторяющийся ключ поля не допускается канонической формой
+    DuplicateField { key: String },
+    /// Повторяющийся ключ `Value::Map` (после нормализации) не допускается
+    /// канонической формой
+    DuplicateMapKey { key: Value },
+    /// Ошибка записи в `Write`
+    Io { kind: io::ErrorKind },
+    /// Закодированное поле не помещается в переданный срез
+    BufferTooSmall { needed: usize, available: usize },
+    /// Код `Value::Unknown` занят известным типом или значением,
+    /// записанным в коде типа целиком
+    InvalidTypeCode { code: u8 },
+}
+
+/// Ошибка чтения транспорта в `FramedRead`; смещение в потоке неизвестно
+#[cfg(feature = "tokio-util")]
+impl From<io::Error> for DecodeError {
+    fn from(e: io::Error) -> Self {
+        DecodeError::Io { offset: 0, kind: e.kind() }
+    }
+}
+
+impl From<io::Error> for EncodeError {
+    fn from(e: io::Error) -> Self {
+        EncodeError::Io { kind: e.kind() }
+    }
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::LengthOverflow { len } => {
+                write!(f, "длина {len} не помещается в префикс длины")
+            }
+            EncodeError::UnknownKey { key } => write!(f, "ключ «{key}» отсутствует в таблице"),
+            EncodeError::DuplicateField { key } => write!(f, "повторяющийся ключ поля «{key}»"),
+            EncodeError::DuplicateMapKey { key } => {
+                write!(f, "повторяющийся ключ отображения {key:?}")
+            }
+            EncodeError::Io { kind } => write!(f, "ошибка ввода-вывода: {kind}"),
+            EncodeError::BufferTooSmall { needed, available } => {
+                write!(f, "нужно {needed} байт, в буфере {available}")
+            }
+            EncodeError::InvalidTypeCode { code } => {
+                write!(f, "код типа {code} нельзя записать как неизвестный")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Ошибка операции, которая и декодирует, и кодирует
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Decode(DecodeError),
+    Encode(EncodeError),
+    /// Файл не удалось открыть, прочитать или записать
+    File { path: PathBuf, kind: io::ErrorKind },
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+impl From<EncodeError> for Error {
+    fn from(e: EncodeError) -> Self {
+        Error::Encode(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Decode(e) => e.fmt(f),
+            Error::Encode(e) => e.fmt(f),
+            Error::File { path, kind } => write!(f, "{}: {kind}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Decode(e) => Some(e),
+            Error::Encode(e) => Some(e),
+            Error::File { .. } => None,
+        }
+    }
+}
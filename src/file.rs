@@ -0,0 +1,104 @@
+//! Сохранение сообщений в файлы и чтение из них
+//!
+//! Сообщение записывается в конверте: профиль хранится в самом файле,
+//! а обрезанный файл распознаётся по длине в заголовке.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::{
+    decode_enveloped_with, encode_enveloped_with, DecodeError, DecodeOptions, EncodeOptions,
+    Error, Field, Limit,
+};
+
+/// Запись сообщения в файл `path` с заменой прежнего содержимого
+pub fn encode_to_file(path: impl AsRef<Path>, fields: &[Field]) -> Result<(), Error> {
+    encode_to_file_with(path, fields, &EncodeOptions::default())
+}
+
+/// Запись сообщения в файл с настройками кодирования
+pub fn encode_to_file_with(
+    path: impl AsRef<Path>,
+    fields: &[Field],
+    opts: &EncodeOptions,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    let data = encode_enveloped_with(fields, opts)?;
+    fs::write(path, data).map_err(|e| file_error(path, e))
+}
+
+/// Чтение сообщения из файла `path` с ограничениями `DecodeOptions::default()`
+pub fn decode_from_file(path: impl AsRef<Path>) -> Result<Vec<Field>, Error> {
+    decode_from_file_with(path, &DecodeOptions::default())
+}
+
+/// Чтение сообщения из файла с ограничениями
+///
+/// Файл больше `max_total_len` отвергается, не будучи прочитанным целиком.
+pub fn decode_from_file_with(
+    path: impl AsRef<Path>,
+    opts: &DecodeOptions,
+) -> Result<Vec<Field>, Error> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| file_error(path, e))?;
+    let mut data = Vec::new();
+    let max = opts.max_total_len;
+    file.take((max as u64).saturating_add(1))
+        .read_to_end(&mut data)
+        .map_err(|e| file_error(path, e))?;
+    if data.len() > max {
+        return Err(DecodeError::LimitExceeded { offset: max, limit: Limit::TotalLength }.into());
+    }
+    Ok(decode_enveloped_with(&data, opts)?)
+}
+
+fn file_error(path: &Path, e: io::Error) -> Error {
+    Error::File { path: path.to_owned(), kind: e.kind() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Checksum, Profile, Value};
+
+    fn sample() -> Vec<Field> {
+        vec![
+            Field { key: "id".into(), value: Value::Int64(42) },
+            Field { key: "blob".into(), value: Value::Bytes(vec![1, 2, 3]) },
+        ]
+    }
+
+    #[test]
+    fn file_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("custom_codec_file_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("msg.ccdc");
+
+        encode_to_file(&path, &sample()).unwrap();
+        assert_eq!(decode_from_file(&path).unwrap(), sample());
+
+        // профиль записан в файл
+        let opts = EncodeOptions {
+            profile: Profile::compact(),
+            checksum: Checksum::Crc32,
+            ..EncodeOptions::default()
+        };
+        encode_to_file_with(&path, &sample(), &opts).unwrap();
+        assert_eq!(decode_from_file(&path).unwrap(), sample());
+
+        let small = DecodeOptions { max_total_len: 8, ..DecodeOptions::default() };
+        let err = DecodeError::LimitExceeded { offset: 8, limit: Limit::TotalLength };
+        assert_eq!(decode_from_file_with(&path, &small), Err(Error::Decode(err)));
+
+        // обрезанный файл
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        assert!(matches!(decode_from_file(&path), Err(Error::Decode(_))));
+
+        let missing = dir.join("missing.ccdc");
+        let err = Error::File { path: missing.clone(), kind: io::ErrorKind::NotFound };
+        assert_eq!(decode_from_file(&missing), Err(err));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+//! Рамки `[длина u32 BE][содержимое]` для передачи сообщений потоком
+//!
+//! В отличие от конверта рамка не несёт ни сигнатуры, ни профиля: это
+//! минимальный слой, отделяющий одно сообщение от другого.
+
+use crate::{
+    decode_message_with, encode_message_with, DecodeError, DecodeOptions, EncodeError,
+    EncodeOptions, Field, Limit,
+};
+
+/// Размер префикса длины рамки
+pub const FRAME_HEADER_LEN: usize = 4;
+
+/// Дописывание рамки с содержимым `payload` в конец `out`
+pub fn write_frame(payload: &[u8], out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    let len = u32::try_from(payload.len())
+        .map_err(|_| EncodeError::LengthOverflow { len: payload.len() })?;
+    out.reserve(FRAME_HEADER_LEN + payload.len());
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(payload);
+    Ok(())
+}
+
+/// Кодирование сообщения в рамку
+pub fn encode_framed(fields: &[Field]) -> Result<Vec<u8>, EncodeError> {
+    encode_framed_with(fields, &EncodeOptions::default())
+}
+
+/// Кодирование сообщения в рамку с настройками
+pub fn encode_framed_with(
+    fields: &[Field],
+    opts: &EncodeOptions,
+) -> Result<Vec<u8>, EncodeError> {
+    let body = encode_message_with(fields, opts)?;
+    let mut out = Vec::new();
+    write_frame(&body, &mut out)?;
+    Ok(out)
+}
+
+/// Сборка рамок из кусков данных произвольного размера
+///
+/// ```
+/// use custom_codec::{encode_framed, Field, FrameReader, Value};
+///
+/// let fields = vec![Field { key: "n".into(), value: Value::Int32(7) }];
+/// let enc = encode_framed(&fields).unwrap();
+/// let mut reader = FrameReader::new(1024);
+/// reader.feed(&enc[..3]);
+/// assert!(reader.next_frame().is_none());
+/// reader.feed(&enc[3..]);
+/// assert_eq!(reader.next_frame(), Some(Ok(enc[4..].to_vec())));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameReader {
+    /// полученные данные; выданные рамки занимают `buf[..start]`
+    buf: Vec<u8>,
+    /// начало ещё не выданных данных в `buf`
+    start: usize,
+    max_frame_len: usize,
+    /// смещение `buf[start]` от начала потока
+    consumed: usize,
+    /// после ошибки поток не читается: граница следующей рамки неизвестна
+    failed: bool,
+}
+
+impl Default for FrameReader {
+    /// Рамки не длиннее `DecodeOptions::default().max_total_len`
+    fn default() -> Self {
+        FrameReader::new(DecodeOptions::default().max_total_len)
+    }
+}
+
+impl FrameReader {
+    /// Рамки длиннее `max_frame_len` отвергаются по префиксу длины,
+    /// до накопления их содержимого
+    pub fn new(max_frame_len: usize) -> Self {
+        FrameReader { buf: Vec::new(), start: 0, max_frame_len, consumed: 0, failed: false }
+    }
+
+    /// Добавление очередной порции данных
+    pub fn feed(&mut self, data: &[u8]) {
+        if !self.failed {
+            self.buf.extend_from_slice(data);
+        }
+    }
+
+    /// Содержимое следующей рамки, если она получена целиком
+    ///
+    /// Смещения в ошибках отсчитываются от начала потока; после ошибки
+    /// рамки больше не выдаются.
+    pub fn next_frame(&mut self) -> Option<Result<Vec<u8>, DecodeError>> {
+        let pending = self.pending();
+        if self.failed || pending.len() < FRAME_HEADER_LEN {
+            return None;
+        }
+        let len = u32::from_be_bytes(pending[..FRAME_HEADER_LEN].try_into().unwrap()) as usize;
+        if len > self.max_frame_len {
+            self.failed = true;
+            self.buf = Vec::new();
+            self.start = 0;
+            let offset = self.consumed;
+            return Some(Err(DecodeError::LimitExceeded { offset, limit: Limit::TotalLength }));
+        }
+        let end = FRAME_HEADER_LEN + len;
+        if pending.len() < end {
+            return None;
+        }
+        let frame = pending[FRAME_HEADER_LEN..end].to_vec();
+        self.start += end;
+        self.consumed += end;
+        // как в `StreamingDecoder`: сдвиг только после выдачи половины буфера
+        if self.start > self.buf.len() / 2 {
+            self.buf.drain(..self.start);
+            self.start = 0;
+        }
+        Some(Ok(frame))
+    }
+
+    /// Полученные, но ещё не выданные данные
+    fn pending(&self) -> &[u8] {
+        &self.buf[self.start..]
+    }
+
+    /// Следующая рамка, декодированная как сообщение
+    ///
+    /// Ошибка в содержимом рамки не мешает читать следующие рамки.
+    pub fn next_message(
+        &mut self,
+        opts: &DecodeOptions,
+    ) -> Option<Result<Vec<Field>, DecodeError>> {
+        let start = self.consumed + FRAME_HEADER_LEN;
+        let body = match self.next_frame()? {
+            Ok(body) => body,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(decode_message_with(&body, opts).map_err(|e| e.shifted(start)))
+    }
+
+    /// Число полученных, но ещё не выданных байт
+    pub fn buffered(&self) -> usize {
+        self.pending().len()
+    }
+
+    /// Завершение потока: ошибка, если последняя рамка пришла не целиком
+    pub fn finish(self) -> Result<(), DecodeError> {
+        match self.buffered() {
+            0 => Ok(()),
+            len => Err(DecodeError::UnexpectedEof { offset: self.consumed + len }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Profile, Value};
+
+    fn sample(n: i64) -> Vec<Field> {
+        vec![
+            Field { key: "id".into(), value: Value::Int64(n) },
+            Field { key: "tags".into(), value: Value::List(vec![Value::String("x".into())]) },
+        ]
+    }
+
+    #[test]
+    fn frames_from_chunks() {
+        let opts = EncodeOptions { profile: Profile::compact(), ..EncodeOptions::default() };
+        let mut wire = Vec::new();
+        for n in 0..4 {
+            wire.extend(encode_framed_with(&sample(n), &opts).unwrap());
+        }
+        write_frame(&[], &mut wire).unwrap();
+
+        let dec = DecodeOptions { profile: Profile::compact(), ..DecodeOptions::default() };
+        let mut reader = FrameReader::default();
+        let mut messages = Vec::new();
+        for chunk in wire.chunks(5) {
+            reader.feed(chunk);
+            while let Some(msg) = reader.next_message(&dec) {
+                messages.push(msg.unwrap());
+            }
+        }
+        let mut expected: Vec<Vec<Field>> = (0..4).map(sample).collect();
+        expected.push(Vec::new());
+        assert_eq!(messages, expected);
+        reader.finish().unwrap();
+    }
+
+    #[test]
+    fn many_frames_in_one_feed() {
+        let mut wire = Vec::new();
+        for n in 0..10_000 {
+            write_frame(&[n as u8], &mut wire).unwrap();
+        }
+        let mut reader = FrameReader::new(1024);
+        reader.feed(&wire);
+        for n in 0..10_000 {
+            assert_eq!(reader.next_frame(), Some(Ok(vec![n as u8])));
+            assert_eq!(reader.buffered(), (FRAME_HEADER_LEN + 1) * (10_000 - n - 1));
+            // выданное начало буфера сдвигается, только когда оно больше половины
+            assert!(reader.start <= reader.buf.len() / 2);
+        }
+        assert!(reader.buf.is_empty());
+        reader.finish().unwrap();
+    }
+
+    #[test]
+    fn frame_errors() {
+        let one = encode_framed(&sample(1)).unwrap();
+        let mut wire = one.clone();
+        wire.extend_from_slice(&[0, 0, 0, 9, 0x55, 0, 0, 0, 0, 0, 0, 0, 0]);
+        wire.extend_from_slice(&one);
+
+        // испорченное содержимое рамки не ломает поток
+        let mut reader = FrameReader::new(1024);
+        reader.feed(&wire);
+        assert!(reader.next_message(&DecodeOptions::default()).unwrap().is_ok());
+        let bad = reader.next_message(&DecodeOptions::default()).unwrap();
+        let offset = one.len() + FRAME_HEADER_LEN;
+        assert_eq!(bad, Err(DecodeError::InvalidTypeCode { offset, code: 0x55 }));
+        assert_eq!(reader.next_message(&DecodeOptions::default()), Some(Ok(sample(1))));
+
+        // слишком длинная рамка отвергается по префиксу
+        let mut reader = FrameReader::new(8);
+        reader.feed(&wire[..FRAME_HEADER_LEN]);
+        let err = DecodeError::LimitExceeded { offset: 0, limit: Limit::TotalLength };
+        assert_eq!(reader.next_frame(), Some(Err(err)));
+        assert_eq!(reader.next_frame(), None);
+
+        let mut reader = FrameReader::new(1024);
+        reader.feed(&one[..one.len() - 1]);
+        assert_eq!(reader.next_frame(), None);
+        assert_eq!(reader.finish(), Err(DecodeError::UnexpectedEof { offset: one.len() - 1 }));
+    }
+}
@@ -0,0 +1,346 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead, Read, Write};
+
+use crate::decode::{Decoder, Input};
+use crate::encode::Writer;
+use crate::{DecodeError, DecodeOptions, EncodeError, EncodeOptions, Field, Profile};
+
+/// Кодирование поля напрямую в `Write` (файл, сокет и т.п.)
+pub fn encode_field_to<W: Write>(field: &Field, w: &mut W) -> Result<(), EncodeError> {
+    Writer::new(w, Profile::STANDARD).field(field)
+}
+
+/// Кодирование поля в `Write` с настройками
+pub fn encode_field_to_with<W: Write>(
+    field: &Field,
+    w: &mut W,
+    opts: &EncodeOptions,
+) -> Result<(), EncodeError> {
+    Writer::new(w, opts.profile).field(field)
+}
+
+/// Декодирование одного поля из `Read`
+///
+/// Читается ровно одно поле, поэтому функцию можно вызывать повторно
+/// для последовательного чтения полей из потока.
+pub fn decode_field_from<R: Read>(r: &mut R) -> Result<Field, DecodeError> {
+    decode_field_from_with(r, &DecodeOptions::unlimited())
+}
+
+/// Декодирование одного поля из `Read` с ограничениями
+///
+/// Длины проверяются до чтения, так что недоверенный поток не может
+/// заставить читать и хранить больше разрешённого. Вложенные сообщения
+/// разбираются прямо из потока, без буферизации их содержимого.
+pub fn decode_field_from_with<R: Read>(
+    r: &mut R,
+    opts: &DecodeOptions,
+) -> Result<Field, DecodeError> {
+    Decoder::new(Source { inner: r, pos: 0 }, opts).read_field()
+}
+
+/// Декодирование полей из `BufRead` до конца потока
+///
+/// Конец потока определяется по пустому буферу `fill_buf`, поэтому
+/// обрыв посреди поля отличается от конца данных.
+pub fn decode_message_from<R: BufRead>(r: &mut R) -> Result<Vec<Field>, DecodeError> {
+    decode_message_from_with(r, &DecodeOptions::unlimited())
+}
+
+/// Декодирование полей из `BufRead` с ограничениями
+///
+/// Ограничения общего размера и числа полей действуют на весь поток,
+/// как при декодировании одного сообщения.
+pub fn decode_message_from_with<R: BufRead>(
+    r: &mut R,
+    opts: &DecodeOptions,
+) -> Result<Vec<Field>, DecodeError> {
+    let mut dec = Decoder::new(Source { inner: r, pos: 0 }, opts);
+    let mut fields = Vec::new();
+    loop {
+        let source = &mut dec.input;
+        match source.inner.fill_buf() {
+            Ok([]) => return Ok(fields),
+            Ok(_) => fields.push(dec.read_field()?),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(source.io_error(e)),
+        }
+    }
+}
+
+/// Кодирование поля `Value::Bytes` с данными из `r`, без загрузки их в память
+///
+/// Из `r` копируется ровно `len` байт; если данных меньше, возвращается
+/// `EncodeError::Io` с `UnexpectedEof`, а в `w` остаётся неполное поле.
+pub fn encode_bytes_to<R: Read, W: Write>(
+    key: &str,
+    len: u64,
+    r: &mut R,
+    w: &mut W,
+) -> Result<(), EncodeError> {
+    encode_bytes_to_with(key, len, r, w, &EncodeOptions::default())
+}
+
+/// То же, что [`encode_bytes_to`], с настройками
+pub fn encode_bytes_to_with<R: Read, W: Write>(
+    key: &str,
+    len: u64,
+    r: &mut R,
+    w: &mut W,
+    opts: &EncodeOptions,
+) -> Result<(), EncodeError> {
+    let size =
+        usize::try_from(len).map_err(|_| EncodeError::LengthOverflow { len: usize::MAX })?;
+    Writer::new(w, opts.profile).bytes_header(key, size)?;
+    let copied = io::copy(&mut r.take(len), w)?;
+    if copied < len {
+        return Err(EncodeError::Io { kind: io::ErrorKind::UnexpectedEof });
+    }
+    Ok(())
+}
+
+/// Чтение поля `Value::Bytes` из `Read` без загрузки данных в память
+///
+/// Возвращает ключ и `Read` над данными значения. Чтобы продолжить чтение
+/// полей из `r`, данные нужно дочитать до конца. Поле другого типа
+/// отвергается как `InvalidTypeCode`.
+pub fn decode_bytes_from<R: Read>(
+    r: &mut R,
+) -> Result<(String, BytesReader<'_, R>), DecodeError> {
+    decode_bytes_from_with(r, &DecodeOptions::unlimited())
+}
+
+/// То же, что [`decode_bytes_from`], с ограничениями
+pub fn decode_bytes_from_with<'r, R: Read>(
+    r: &'r mut R,
+    opts: &DecodeOptions,
+) -> Result<(String, BytesReader<'r, R>), DecodeError> {
+    let mut dec = Decoder::new(Source { inner: r, pos: 0 }, opts);
+    let (key, len) = dec.read_bytes_header()?;
+    Ok((key, BytesReader { inner: dec.input.inner, remaining: len as u64 }))
+}
+
+/// Данные поля `Value::Bytes`, читаемые прямо из потока
+///
+/// Обрыв потока раньше конца данных — ошибка `UnexpectedEof`.
+#[derive(Debug)]
+pub struct BytesReader<'r, R> {
+    inner: &'r mut R,
+    remaining: u64,
+}
+
+impl<R> BytesReader<'_, R> {
+    /// Сколько байт значения ещё не прочитано
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+}
+
+impl<R: Read> Read for BytesReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let max = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Обёртка над `Read`, считающая прочитанные байты
+struct Source<'r, R> {
+    inner: &'r mut R,
+    pos: usize,
+}
+
+impl<R: Read> Source<'_, R> {
+    fn io_error(&self, e: io::Error) -> DecodeError {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => DecodeError::UnexpectedEof { offset: self.pos },
+            kind => DecodeError::Io { offset: self.pos, kind },
+        }
+    }
+}
+
+impl<R: Read> Input<'static> for Source<'_, R> {
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Буфер растёт по мере поступления данных, так что испорченная
+    /// длина не приводит к огромной аллокации.
+    fn take(&mut self, len: usize) -> Result<Cow<'static, [u8]>, DecodeError> {
+        let mut buf = Vec::new();
+        (&mut *self.inner)
+            .take(len as u64)
+            .read_to_end(&mut buf)
+            .map_err(|e| self.io_error(e))?;
+        if buf.len() < len {
+            return Err(DecodeError::UnexpectedEof { offset: self.pos });
+        }
+        self.pos += len;
+        Ok(Cow::Owned(buf))
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let mut arr = [0u8; N];
+        self.inner.read_exact(&mut arr).map_err(|e| self.io_error(e))?;
+        self.pos += N;
+        Ok(arr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_field, encode_field, Limit, Value};
+
+    fn sample() -> Field {
+        Field {
+            key: "user".into(),
+            value: Value::Message(vec![
+                Field { key: "name".into(), value: Value::String("Rust".into()) },
+                Field { key: "raw".into(), value: Value::Bytes(vec![0, 1, 2]) },
+            ]),
+        }
+    }
+
+    #[test]
+    fn writer_matches_encode_field() {
+        let f = sample();
+        let mut out = Vec::new();
+        encode_field_to(&f, &mut out).unwrap();
+        assert_eq!(out, encode_field(&f).unwrap());
+    }
+
+    #[test]
+    fn read_sequential_fields() {
+        let a = sample();
+        let b = Field { key: "n".into(), value: Value::Int32(-5) };
+        let mut buf = Vec::new();
+        encode_field_to(&a, &mut buf).unwrap();
+        encode_field_to(&b, &mut buf).unwrap();
+
+        let mut r = &buf[..];
+        assert_eq!(decode_field_from(&mut r).unwrap(), a);
+        assert_eq!(decode_field_from(&mut r).unwrap(), b);
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn stream_errors_match_slice_errors() {
+        let enc = encode_field(&sample()).unwrap();
+        let cut = &enc[..enc.len() - 3];
+        assert_eq!(decode_field_from(&mut &cut[..]).unwrap_err(), decode_field(cut).unwrap_err());
+
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::ConnectionReset.into())
+            }
+        }
+        assert_eq!(
+            decode_field_from(&mut Broken).unwrap_err(),
+            DecodeError::Io { offset: 0, kind: io::ErrorKind::ConnectionReset }
+        );
+    }
+
+    #[test]
+    fn nested_message_decoded_from_stream() {
+        // вложенное сообщение обрывается посреди потока
+        let enc = encode_field(&sample()).unwrap();
+        let cut = &enc[..20];
+        assert_eq!(decode_field_from(&mut &cut[..]).unwrap_err(), decode_field(cut).unwrap_err());
+        assert_eq!(decode_field_from(&mut &enc[..]).unwrap(), sample());
+    }
+
+    #[test]
+    fn stream_limits() {
+        // объявленная длина 1 GiB отвергается до чтения данных
+        let mut enc = vec![5, 0, 0, 0, 1, b'b'];
+        enc.extend_from_slice(&(1u32 << 30).to_be_bytes());
+        assert_eq!(
+            decode_field_from_with(&mut &enc[..], &DecodeOptions::default()).unwrap_err(),
+            DecodeError::LimitExceeded { offset: 6, limit: Limit::ValueLength }
+        );
+
+        let opts = DecodeOptions { max_depth: 0, ..DecodeOptions::default() };
+        assert_eq!(
+            decode_field_from_with(&mut &encode_field(&sample()).unwrap()[..], &opts).unwrap_err(),
+            DecodeError::LimitExceeded { offset: 0, limit: Limit::Depth }
+        );
+    }
+
+    #[test]
+    fn message_from_buf_read() {
+        let fields = vec![sample(), Field { key: "n".into(), value: Value::Int32(1) }];
+        let enc = crate::encode_message(&fields).unwrap();
+        let mut r = io::BufReader::with_capacity(3, &enc[..]);
+        assert_eq!(decode_message_from(&mut r).unwrap(), fields);
+        assert_eq!(decode_message_from(&mut &[][..]).unwrap(), []);
+
+        let cut = &enc[..enc.len() - 2];
+        let mut r = io::BufReader::with_capacity(3, cut);
+        let err = decode_message_from(&mut r).unwrap_err();
+        assert_eq!(err, crate::decode_message(cut).unwrap_err());
+    }
+
+    #[test]
+    fn bytes_streamed_in_chunks() {
+        let blob: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        for profile in [Profile::STANDARD, Profile::compact()] {
+            let opts = EncodeOptions { profile, ..EncodeOptions::default() };
+            let mut out = Vec::new();
+            encode_bytes_to_with("blob", blob.len() as u64, &mut &blob[..], &mut out, &opts)
+                .unwrap();
+            let f = Field { key: "blob".into(), value: Value::Bytes(blob.clone()) };
+            assert_eq!(out, crate::encode_field_with(&f, &opts).unwrap());
+
+            let dec = DecodeOptions { profile, ..DecodeOptions::unlimited() };
+            out.extend(crate::encode_field_with(&sample(), &opts).unwrap());
+            let mut r = &out[..];
+            let (key, mut data) = decode_bytes_from_with(&mut r, &dec).unwrap();
+            assert_eq!((key.as_str(), data.remaining()), ("blob", blob.len() as u64));
+            let mut read = Vec::new();
+            io::copy(&mut data, &mut read).unwrap();
+            assert_eq!(read, blob);
+            // поток стоит на следующем поле
+            assert_eq!(decode_field_from_with(&mut r, &dec).unwrap(), sample());
+        }
+    }
+
+    #[test]
+    fn bytes_stream_errors() {
+        let mut out = Vec::new();
+        let err = encode_bytes_to("b", 10, &mut &[1u8, 2, 3][..], &mut out).unwrap_err();
+        assert_eq!(err, EncodeError::Io { kind: io::ErrorKind::UnexpectedEof });
+
+        let mut r = &out[..];
+        let (_, mut data) = decode_bytes_from(&mut r).unwrap();
+        let err = io::copy(&mut data, &mut io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let enc = encode_field(&sample()).unwrap();
+        let err = decode_bytes_from(&mut &enc[..]).unwrap_err();
+        assert_eq!(err, DecodeError::InvalidTypeCode { offset: 0, code: 6 });
+        let opts = DecodeOptions { max_value_len: 2, ..DecodeOptions::default() };
+        let err = decode_bytes_from_with(&mut &out[..], &opts).unwrap_err();
+        assert_eq!(err, DecodeError::LimitExceeded { offset: 6, limit: Limit::ValueLength });
+    }
+
+    #[test]
+    fn stream_with_profile() {
+        let f = sample();
+        let opts = EncodeOptions { profile: Profile::compact(), ..EncodeOptions::default() };
+        let mut out = Vec::new();
+        encode_field_to_with(&f, &mut out, &opts).unwrap();
+        assert_eq!(out, crate::encode_field_with(&f, &opts).unwrap());
+
+        let dec = DecodeOptions { profile: Profile::compact(), ..DecodeOptions::default() };
+        assert_eq!(decode_field_from_with(&mut &out[..], &dec).unwrap(), f);
+    }
+}
@@ -0,0 +1,395 @@
+//! Преобразование полей в `serde_json::Value` и обратно
+//!
+//! Поле записывается объектом из одного элемента `{ключ: значение}`,
+//! сообщение — объектом из всех своих полей.
+//! Правила для значений:
+//!
+//! - целые и числа с плавающей точкой — числа JSON; бесконечности и NaN — `null`;
+//! - `Bytes` — строка base64 со стандартным алфавитом и дополнением `=`;
+//! - `Message` — объект; `List` — массив; `Map` — массив пар `[ключ, значение]`;
+//! - `Timestamp` — объект `{secs, nanos}`, `Uuid` и `Decimal` — строки,
+//!   вариант перечисления — объект `{имя: содержимое}`, как в `Serialize`.
+//!
+//! При чтении целые становятся `Int64`, а не помещающиеся в него — `UInt64`;
+//! дробные числа — `Float64` или, с `JsonOptions::float32`, `Float32`.
+//! Строки остаются строками, кроме полей из `JsonOptions::bytes_keys`.
+//! Порядок ключей объекта сохраняется (у `serde_json` включён признак
+//! `preserve_order`); при повторе ключа остаётся последнее значение.
+//! Признак действует на всё дерево зависимостей: с `json` объекты
+//! `serde_json` в других крейтах сборки тоже хранят порядок вставки.
+//!
+//! `Schema::to_json_schema` описывает JSON-запись сообщения по этим же
+//! правилам, чтобы её проверяли обычные валидаторы JSON Schema.
+
+use std::fmt;
+
+use serde_json::{json, Map, Number, Value as Json};
+
+use crate::typed::Hyphenated;
+use crate::{base64, Field, Schema, SchemaType, Value};
+
+/// Версия JSON Schema в `$schema`
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Настройки чтения JSON
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JsonOptions {
+    /// Дробные числа читаются как `Float32`
+    pub float32: bool,
+    /// Ключи полей на любой глубине, строки в которых — base64 `Bytes`;
+    /// в массивах декодируется каждый строковый элемент
+    pub bytes_keys: Vec<String>,
+}
+
+/// JSON не переводится в поле
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonError {
+    /// Поле должно быть объектом ровно из одного элемента
+    NotAField,
+    /// Сообщение должно быть объектом
+    NotAMessage,
+    /// Строка в поле из `bytes_keys` — не base64
+    InvalidBase64 { key: String },
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::NotAField => f.write_str("поле должно быть объектом из одного элемента"),
+            JsonError::NotAMessage => f.write_str("сообщение должно быть объектом"),
+            JsonError::InvalidBase64 { key } => write!(f, "поле {key:?}: строка не в base64"),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// Поле как объект `{ключ: значение}`
+///
+/// ```
+/// use custom_codec::{to_json, Field, Value};
+///
+/// let f = Field { key: "blob".into(), value: Value::Bytes(vec![1, 2, 3]) };
+/// assert_eq!(to_json(&f), serde_json::json!({ "blob": "AQID" }));
+/// ```
+pub fn to_json(field: &Field) -> Json {
+    let mut map = Map::new();
+    map.insert(field.key.clone(), value_to_json(&field.value));
+    Json::Object(map)
+}
+
+/// Значение без ключа
+pub fn value_to_json(value: &Value) -> Json {
+    let float = |f: f64| Number::from_f64(f).map_or(Json::Null, Json::Number);
+    match value {
+        Value::Int8(i) => Json::from(*i),
+        Value::Int16(i) => Json::from(*i),
+        Value::Int32(i) => Json::from(*i),
+        Value::Int64(i) => Json::from(*i),
+        Value::UInt8(u) => Json::from(*u),
+        Value::UInt16(u) => Json::from(*u),
+        Value::UInt32(u) => Json::from(*u),
+        Value::UInt64(u) => Json::from(*u),
+        Value::Float32(f) => float(*f as f64),
+        Value::Float64(f) => float(*f),
+        Value::Bool(b) => Json::Bool(*b),
+        Value::String(s) => Json::String(s.clone()),
+        Value::Bytes(b) => Json::String(base64::encode(b)),
+        Value::Null => Json::Null,
+        Value::Message(fields) => message_to_json(fields),
+        Value::List(items) => Json::Array(items.iter().map(value_to_json).collect()),
+        Value::Map(entries) => Json::Array(
+            entries
+                .iter()
+                .map(|(k, v)| Json::Array(vec![value_to_json(k), value_to_json(v)]))
+                .collect(),
+        ),
+        Value::Timestamp(ts) => serde_json::json!({ "secs": ts.secs, "nanos": ts.nanos }),
+        Value::Uuid(u) => Json::String(Hyphenated(u).to_string()),
+        Value::Decimal(d) => Json::String(d.to_string()),
+        Value::Enum { variant, name, payload } => {
+            let name = name.clone().unwrap_or_else(|| variant.to_string());
+            let payload = payload.as_deref().map_or(Json::Null, value_to_json);
+            Json::Object(Map::from_iter([(name, payload)]))
+        }
+        Value::Unknown { type_code, raw } => {
+            serde_json::json!({ "type_code": type_code, "raw": base64::encode(raw) })
+        }
+    }
+}
+
+/// Поле из объекта `{ключ: значение}`
+pub fn from_json(json: &Json, opts: &JsonOptions) -> Result<Field, JsonError> {
+    let (key, value) = match json {
+        Json::Object(map) if map.len() == 1 => map.iter().next().unwrap(),
+        _ => return Err(JsonError::NotAField),
+    };
+    Ok(Field { key: key.clone(), value: read(value, opts, key)? })
+}
+
+/// Сообщение как объект, в котором каждое поле — элемент
+pub fn message_to_json(fields: &[Field]) -> Json {
+    Json::Object(fields.iter().map(|f| (f.key.clone(), value_to_json(&f.value))).collect())
+}
+
+/// Сообщение из объекта `{ключ: значение, …}`
+pub fn message_from_json(json: &Json, opts: &JsonOptions) -> Result<Vec<Field>, JsonError> {
+    match json {
+        Json::Object(map) => map
+            .iter()
+            .map(|(k, v)| Ok(Field { key: k.clone(), value: read(v, opts, k)? }))
+            .collect(),
+        _ => Err(JsonError::NotAMessage),
+    }
+}
+
+/// `key` — ключ ближайшего объемлющего поля
+fn read(json: &Json, opts: &JsonOptions, key: &str) -> Result<Value, JsonError> {
+    Ok(match json {
+        Json::Null => Value::Null,
+        Json::Bool(b) => Value::Bool(*b),
+        Json::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => Value::Int64(i),
+            (None, Some(u)) => Value::UInt64(u),
+            _ => {
+                let f = n.as_f64().expect("число JSON без arbitrary_precision");
+                if opts.float32 {
+                    Value::Float32(f as f32)
+                } else {
+                    Value::Float64(f)
+                }
+            }
+        },
+        Json::String(s) if opts.bytes_keys.iter().any(|k| k == key) => {
+            let bytes = base64::decode(s);
+            Value::Bytes(bytes.ok_or_else(|| JsonError::InvalidBase64 { key: key.to_owned() })?)
+        }
+        Json::String(s) => Value::String(s.clone()),
+        Json::Array(items) => {
+            Value::List(items.iter().map(|v| read(v, opts, key)).collect::<Result<_, _>>()?)
+        }
+        Json::Object(_) => Value::Message(message_from_json(json, opts)?),
+    })
+}
+
+impl Schema {
+    /// JSON Schema (draft 2020-12) для `value_to_json` сообщения этой схемы
+    ///
+    /// Необязательное поле может быть `null`, устаревшее помечено
+    /// `deprecated`, значение по умолчанию записано в `default`.
+    ///
+    /// ```
+    /// use custom_codec::{Schema, SchemaType};
+    ///
+    /// let mut schema = Schema::named("Probe");
+    /// schema.required("id", SchemaType::UInt8);
+    ///
+    /// let json = schema.to_json_schema();
+    /// assert_eq!(json["title"], "Probe");
+    /// assert_eq!(json["required"][0], "id");
+    /// assert_eq!(json["properties"]["id"]["maximum"], 255);
+    /// ```
+    pub fn to_json_schema(&self) -> Json {
+        let mut out = object_schema(self);
+        out.insert("$schema".into(), JSON_SCHEMA_DIALECT.into());
+        Json::Object(out)
+    }
+}
+
+fn object_schema(schema: &Schema) -> Map<String, Json> {
+    let mut properties = Map::new();
+    for f in schema.fields() {
+        let mut ty = type_schema(&f.ty);
+        if !f.required {
+            ty = json!({ "anyOf": [ty, { "type": "null" }] });
+        }
+        if let Json::Object(map) = &mut ty {
+            if let Some(default) = &f.default {
+                map.insert("default".into(), value_to_json(default));
+            }
+            if f.deprecated {
+                map.insert("deprecated".into(), true.into());
+            }
+        }
+        properties.insert(f.key.clone(), ty);
+    }
+    let required = schema.fields().iter().filter(|f| f.required).map(|f| Json::from(&*f.key));
+    let mut out = Map::new();
+    if let Some(name) = schema.name() {
+        out.insert("title".into(), name.into());
+    }
+    out.insert("type".into(), "object".into());
+    out.insert("properties".into(), Json::Object(properties));
+    out.insert("required".into(), Json::Array(required.collect()));
+    out.insert("additionalProperties".into(), schema.allow_unknown().into());
+    out
+}
+
+fn integer(min: impl Into<Json>, max: impl Into<Json>) -> Json {
+    json!({ "type": "integer", "minimum": min.into(), "maximum": max.into() })
+}
+
+fn type_schema(ty: &SchemaType) -> Json {
+    match ty {
+        SchemaType::Any => json!({}),
+        SchemaType::Bool => json!({ "type": "boolean" }),
+        SchemaType::Int8 => integer(i8::MIN, i8::MAX),
+        SchemaType::Int16 => integer(i16::MIN, i16::MAX),
+        SchemaType::Int32 => integer(i32::MIN, i32::MAX),
+        SchemaType::Int64 => integer(i64::MIN, i64::MAX),
+        SchemaType::UInt8 => integer(0, u8::MAX),
+        SchemaType::UInt16 => integer(0, u16::MAX),
+        SchemaType::UInt32 => integer(0, u32::MAX),
+        SchemaType::UInt64 => integer(0, u64::MAX),
+        // бесконечности и NaN записываются как null
+        SchemaType::Float32 | SchemaType::Float64 => json!({ "type": ["number", "null"] }),
+        SchemaType::String => json!({ "type": "string" }),
+        SchemaType::Bytes => json!({ "type": "string", "contentEncoding": "base64" }),
+        SchemaType::Timestamp => json!({
+            "type": "object",
+            "properties": {
+                "secs": { "type": "integer" },
+                "nanos": { "type": "integer", "minimum": 0, "maximum": 999_999_999 },
+            },
+            "required": ["secs", "nanos"],
+            "additionalProperties": false,
+        }),
+        SchemaType::Uuid => json!({ "type": "string", "format": "uuid" }),
+        SchemaType::Decimal => json!({ "type": "string", "pattern": "^-?[0-9]+(\\.[0-9]+)?$" }),
+        SchemaType::Enum => json!({ "type": "object", "minProperties": 1, "maxProperties": 1 }),
+        SchemaType::Message(schema) => Json::Object(object_schema(schema)),
+        SchemaType::List(item) => json!({ "type": "array", "items": type_schema(item) }),
+        SchemaType::Map(k, v) => json!({
+            "type": "array",
+            "items": {
+                "type": "array",
+                "prefixItems": [type_schema(k), type_schema(v)],
+                "minItems": 2,
+                "maxItems": 2,
+            },
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SchemaField;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let f = field(
+            "probe",
+            Value::Message(vec![
+                field("id", Value::Int64(-7)),
+                field("big", Value::UInt64(u64::MAX)),
+                field("ratio", Value::Float64(0.25)),
+                field("blob", Value::Bytes(vec![0, 255])),
+                field("tags", Value::List(vec![Value::String("a".into()), Value::Null])),
+            ]),
+        );
+        let json = json!({ "probe": {
+            "id": -7,
+            "big": u64::MAX,
+            "ratio": 0.25,
+            "blob": "AP8=",
+            "tags": ["a", null],
+        }});
+        assert_eq!(to_json(&f), json);
+
+        let opts = JsonOptions { bytes_keys: vec!["blob".into()], ..JsonOptions::default() };
+        assert_eq!(from_json(&json, &opts).unwrap(), f);
+
+        let opts = JsonOptions { float32: true, ..JsonOptions::default() };
+        let f32_field = from_json(&json!({ "x": 1.5 }), &opts).unwrap();
+        assert_eq!(f32_field, field("x", Value::Float32(1.5)));
+    }
+
+    #[test]
+    fn json_errors_and_lossy_values() {
+        let opts = JsonOptions { bytes_keys: vec!["b".into()], ..JsonOptions::default() };
+        assert_eq!(from_json(&json!({ "b": "*" }), &opts), Err(JsonError::InvalidBase64 {
+            key: "b".into()
+        }));
+        assert_eq!(from_json(&json!([1]), &opts), Err(JsonError::NotAField));
+        assert_eq!(from_json(&json!({ "a": 1, "b": 2 }), &opts), Err(JsonError::NotAField));
+
+        assert_eq!(value_to_json(&Value::Float32(f32::NAN)), Json::Null);
+        let map = Value::Map(vec![(Value::Int32(1), Value::Bool(true))]);
+        assert_eq!(value_to_json(&map), json!([[1, true]]));
+        let e = Value::Enum { variant: 2, name: None, payload: None };
+        assert_eq!(value_to_json(&e), json!({ "2": null }));
+    }
+
+    #[test]
+    fn message_json() {
+        let fields = vec![field("ok", Value::Bool(true)), field("id", Value::Int64(1))];
+        let json = message_to_json(&fields);
+        assert_eq!(json.to_string(), r#"{"ok":true,"id":1}"#);
+        assert_eq!(message_from_json(&json, &JsonOptions::default()).unwrap(), fields);
+        let err = message_from_json(&json!([1]), &JsonOptions::default());
+        assert_eq!(err, Err(JsonError::NotAMessage));
+    }
+
+    #[test]
+    fn json_schema_follows_json_mapping() {
+        let mut inner = Schema::named("Inner");
+        inner.required("ok", SchemaType::Bool);
+        let mut schema = Schema::named("Probe");
+        schema.required("id", SchemaType::Int64);
+        schema.insert(
+            SchemaField::optional("lang", SchemaType::String)
+                .with_default(Value::String("ru".into()))
+                .deprecate(),
+        );
+        schema.optional("lookup", SchemaType::Map(
+            Box::new(SchemaType::Int32),
+            Box::new(SchemaType::Message(inner)),
+        ));
+        schema.set_allow_unknown(true);
+
+        let expected = json!({
+            "$schema": JSON_SCHEMA_DIALECT,
+            "title": "Probe",
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer", "minimum": i64::MIN, "maximum": i64::MAX },
+                "lang": {
+                    "anyOf": [{ "type": "string" }, { "type": "null" }],
+                    "default": "ru",
+                    "deprecated": true,
+                },
+                "lookup": {
+                    "anyOf": [
+                        {
+                            "type": "array",
+                            "items": {
+                                "type": "array",
+                                "prefixItems": [
+                                    { "type": "integer", "minimum": i32::MIN, "maximum": i32::MAX },
+                                    {
+                                        "title": "Inner",
+                                        "type": "object",
+                                        "properties": { "ok": { "type": "boolean" } },
+                                        "required": ["ok"],
+                                        "additionalProperties": false,
+                                    },
+                                ],
+                                "minItems": 2,
+                                "maxItems": 2,
+                            },
+                        },
+                        { "type": "null" },
+                    ],
+                },
+            },
+            "required": ["id"],
+            "additionalProperties": true,
+        });
+        assert_eq!(schema.to_json_schema(), expected);
+    }
+}
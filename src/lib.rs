@@ -1,7 +1,206 @@
-use std::io::{Cursor, Read};
+mod access;
+#[cfg(feature = "tokio")]
+pub mod aio;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "bumpalo")]
+mod arena;
+mod armor;
+#[cfg(feature = "arrow")]
+mod arrow;
+mod base64;
+mod builder;
+mod canonical;
+#[cfg(feature = "cbor")]
+mod cbor;
+mod connection;
+mod convert;
+#[cfg(feature = "tokio-util")]
+mod codec;
+pub mod codegen;
+mod cow;
+mod crc32;
+mod csv;
+mod decimal;
+mod decode;
+mod described;
+mod diff;
+mod encode;
+mod envelope;
+mod endian;
+mod error;
+mod file;
+mod framing;
+mod io;
+#[cfg(feature = "json")]
+mod json;
+mod macros;
+mod message;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+mod options;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod patch;
+mod protobuf;
+mod registry;
+mod schema;
+mod schema_dsl;
+mod select;
+#[cfg(feature = "serde")]
+mod serde;
+#[cfg(feature = "serde")]
+mod serde_stream;
+#[cfg(feature = "bytes")]
+mod shared;
+mod splice;
+#[cfg(feature = "futures")]
+mod stream;
+mod streaming;
+mod tagged;
+#[cfg(feature = "proptest")]
+pub mod testing;
+mod text;
+mod timestamp;
+#[cfg(feature = "toml")]
+mod toml;
+pub mod tooling;
+mod typed;
+mod utf8;
+mod value_ref;
+#[cfg(feature = "serde")]
+mod value_serde;
+mod varint;
+#[cfg(feature = "xml")]
+mod xml;
+#[cfg(feature = "yaml")]
+mod yaml;
+
+#[cfg(feature = "bumpalo")]
+pub use arena::{
+    decode_field_in, decode_field_in_with, decode_message_in, ArenaField, ArenaValue,
+};
+pub use armor::{
+    decode_armored, decode_armored_with, encode_armored, encode_armored_with, ArmorError,
+    ARMOR_FOOTER, ARMOR_HEADER,
+};
+#[cfg(feature = "arrow")]
+pub use arrow::{from_record_batch, to_record_batch, RecordBatchError};
+pub use builder::MessageBuilder;
+pub use canonical::{
+    canonicalize, decode_canonical, decode_canonical_with, encode_canonical,
+};
+#[cfg(feature = "cbor")]
+pub use cbor::{decode_cbor, encode_cbor, value_from_cbor, value_to_cbor, CborError, CBOR_ENUM_TAG};
+#[cfg(feature = "tokio-util")]
+pub use codec::CustomCodec;
+pub use connection::{Connection, Event};
+pub use convert::ConversionError;
+pub use csv::{from_csv, to_csv, CsvError, CsvOptions, MissingFields};
+pub use cow::{
+    decode_field_cow, decode_field_cow_with, decode_message_cow, CowField, CowValue,
+};
+pub use decimal::{Decimal, DecimalOutOfRange};
+pub use decode::{
+    decode_field, decode_field_consumed, decode_field_with, decode_message, decode_message_with,
+    iter_fields, iter_fields_with, FieldIter,
+};
+pub use diff::{diff, Change, Diff, FieldPath, PathSegment};
+pub use described::{decode_described, encode_described, DESCRIBED_MAGIC};
+pub use encode::{
+    encode_field, encode_field_into, encode_field_into_with, encode_field_to_slice,
+    encode_field_to_slice_with, encode_field_with, encode_message, encode_message_with, Encoder,
+};
+#[cfg(feature = "smallvec")]
+pub use encode::{encode_field_small, SmallBytes, SMALL_FIELD_LEN};
+pub use envelope::{
+    decode_enveloped, decode_enveloped_with, encode_enveloped, encode_enveloped_with,
+    envelope_options, envelope_schema_id, HEADER_LEN, MAGIC, VERSION,
+};
+pub use error::{DecodeError, EncodeError, Error, Limit};
+pub use file::{decode_from_file, decode_from_file_with, encode_to_file, encode_to_file_with};
+pub use framing::{
+    encode_framed, encode_framed_with, write_frame, FrameReader, FRAME_HEADER_LEN,
+};
+pub use io::{
+    decode_bytes_from, decode_bytes_from_with, decode_field_from, decode_field_from_with,
+    decode_message_from, decode_message_from_with, encode_bytes_to, encode_bytes_to_with,
+    encode_field_to, encode_field_to_with, BytesReader,
+};
+#[cfg(feature = "json")]
+pub use json::{
+    from_json, message_from_json, message_to_json, to_json, value_to_json, JsonError, JsonOptions,
+};
+pub use message::{Message, MergeStrategy};
+#[cfg(feature = "msgpack")]
+pub use msgpack::{
+    from_msgpack, to_msgpack, value_from_msgpack, value_to_msgpack, MsgpackError,
+    MSGPACK_EXT_DECIMAL, MSGPACK_EXT_ENUM, MSGPACK_EXT_UUID,
+};
+pub use options::{
+    Checksum, DecodeOptions, DuplicateKeys, EncodeOptions, Endianness, IntEncoding, KeyLength,
+    LengthEncoding, Profile, SchemaId, UnknownTypes, MAX_VALUE_DEPTH,
+};
+#[cfg(feature = "rayon")]
+pub use parallel::{encode_message_parallel, encode_message_parallel_with};
+pub use patch::{apply_patch, Patch, PatchError, PatchOp};
+pub use protobuf::{decode_protobuf, encode_protobuf, ProtoError, ProtoSchema, ProtoType};
+pub use registry::{decode_resolved, SchemaResolver};
+pub use schema::{
+    decode_with_schema, encode_checked, encode_with_schema, CheckedEncodeError, Schema,
+    SchemaError, SchemaField, SchemaType, Violation, ViolationKind,
+};
+pub use schema_dsl::{parse_schemas, SchemaParseError, Schemas};
+pub use select::{SelectError, Selected, Selector};
+#[cfg(feature = "serde")]
+pub use serde::{
+    from_slice, from_slice_with, from_value, to_value, to_vec, to_vec_with, SerdeError,
+};
+#[cfg(feature = "serde")]
+pub use serde_stream::{Deserializer, Serializer};
+#[cfg(feature = "bytes")]
+pub use shared::{
+    decode_field_shared, decode_field_shared_with, decode_message_shared, encode_field_to_bytes,
+    encode_field_to_bytes_with, SharedField, SharedValue,
+};
+pub use splice::{append_field, append_field_with, remove_field, remove_field_with};
+#[cfg(feature = "futures")]
+pub use stream::MessageStream;
+pub use streaming::StreamingDecoder;
+pub use tagged::{
+    decode_tagged, decode_with_table, encode_tagged, encode_with_table, keyed_to_tagged,
+    tagged_to_keyed, KeyTable,
+};
+pub use text::{from_text, to_text, value_to_text, TextError, MAX_TEXT_DEPTH};
+pub use timestamp::{Timestamp, TimestampOutOfRange};
+#[cfg(feature = "toml")]
+pub use toml::{from_toml, to_toml, value_from_toml, value_to_toml, TomlError};
+pub use typed::{Decode, Encode, ValueError};
+#[cfg(feature = "derive")]
+pub use custom_codec_derive::{Decode, Encode};
+pub use value_ref::{
+    decode_field_ref, decode_field_ref_with, decode_message_ref, FieldRef, ValueRef,
+};
+#[cfg(feature = "xml")]
+pub use xml::{from_xml, to_xml, XmlError, XmlOptions, XML_LIST_ITEM};
+#[cfg(feature = "yaml")]
+pub use yaml::{from_yaml, to_yaml, value_from_yaml, value_to_yaml, YamlError};
+
+// код `#[derive(Encode, Decode)]` ссылается на `::custom_codec`, в том числе в тестах крейта
+extern crate self as custom_codec;
+
+/// Вспомогательные функции для кода `#[derive(Encode, Decode)]`, `codegen` и `ccodec`
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::message::path_keys;
+    pub use crate::typed::{
+        decode_bytes, decode_entries, decode_list, decode_uuid, find_field, message_fields,
+        required_field, schema_default, tuple_items, variant_name,
+    };
+}
 
 /// Типы поддерживаемых значений
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Int32(i32),
     Float32(f32),
@@ -9,127 +208,189 @@ pub enum Value {
     String(String),
     Bytes(Vec<u8>),
     Message(Vec<Field>), // вложенное сообщение
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    Null, // значение присутствует, но пусто
+    List(Vec<Value>), // элементы записываются без ключей
+    Map(Vec<(Value, Value)>), // ключи произвольного типа
+    Timestamp(Timestamp),
+    Uuid([u8; 16]),
+    Decimal(Decimal),
+    /// Вариант размеченного объединения: номер, необязательное имя и содержимое
+    Enum { variant: u32, name: Option<String>, payload: Option<Box<Value>> },
+    Int8(i8),
+    Int16(i16),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    /// Значение типа, неизвестного этой версии: код типа и данные без
+    /// префикса длины; декодируется при `UnknownTypes::Preserve`.
+    /// Кодируется только с кодом 0 или от 22 до 0x7d, не занятым другими
+    /// типами
+    Unknown { type_code: u8, raw: Vec<u8> },
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Field {
     pub key: String,
     pub value: Value,
 }
 
-/// Кодирование одного поля
-pub fn encode_field(field: &Field) -> Vec<u8> {
-    let mut out = Vec::new();
-
-    // 1 байт type_code
-    let type_code: u8 = match field.value {
-        Value::Int32(_) => 1,
-        Value::Float32(_) => 2,
-        Value::Bool(_) => 3,
-        Value::String(_) => 4,
-        Value::Bytes(_) => 5,
-        Value::Message(_) => 6,
-    };
-    out.push(type_code);
-
-    // длина ключа (4 байта big-endian)
-    let key_bytes = field.key.as_bytes();
-    out.extend_from_slice(&(key_bytes.len() as u32).to_be_bytes());
-    out.extend_from_slice(key_bytes);
-
-    // значение
-    match &field.value {
-        Value::Int32(i) => {
-            out.extend_from_slice(&(4u32).to_be_bytes());
-            out.extend_from_slice(&i.to_be_bytes());
-        }
-        Value::Float32(f) => {
-            out.extend_from_slice(&(4u32).to_be_bytes());
-            out.extend_from_slice(&f.to_be_bytes());
-        }
-        Value::Bool(b) => {
-            out.extend_from_slice(&(1u32).to_be_bytes());
-            out.push(*b as u8);
+impl Value {
+    /// Размер значения на проводе: префикс длины и данные
+    pub fn encoded_len(&self) -> usize {
+        self.encoded_len_with(&Profile::STANDARD)
+    }
+
+    /// Размер значения на проводе в заданном профиле
+    pub fn encoded_len_with(&self, profile: &Profile) -> usize {
+        if profile.inline_code(self).is_some() {
+            return 0;
         }
-        Value::String(s) => {
-            let bytes = s.as_bytes();
-            out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
-            out.extend_from_slice(bytes);
+        let len = self.payload_len(profile);
+        profile.len_size(len) + len
+    }
+
+    /// Размер данных значения без префикса длины
+    pub(crate) fn payload_len(&self, profile: &Profile) -> usize {
+        self.payload_len_by(profile, &|key| profile.key_len_size(key.len()) + key.len())
+    }
+
+    /// Размер данных значения, где `key_size` — размер ключа вложенного поля
+    /// вместе с его префиксом
+    pub(crate) fn payload_len_by(
+        &self,
+        profile: &Profile,
+        key_size: &dyn Fn(&str) -> usize,
+    ) -> usize {
+        match self.scalar_len(profile) {
+            Some(len) => len,
+            None => self.container_lens(profile, key_size)[0],
         }
-        Value::Bytes(bts) => {
-            out.extend_from_slice(&(bts.len() as u32).to_be_bytes());
-            out.extend_from_slice(bts);
+    }
+
+    /// Размер данных скалярного значения; `None` для контейнеров
+    fn scalar_len(&self, profile: &Profile) -> Option<usize> {
+        Some(match self {
+            Value::Int8(_) | Value::UInt8(_) => 1,
+            Value::Int16(_) | Value::UInt16(_) => 2,
+            Value::Int32(i) => profile.int_size(*i as i64, 4),
+            Value::Int64(i) => profile.int_size(*i, 8),
+            Value::Float32(_) | Value::UInt32(_) => 4,
+            Value::UInt64(_) | Value::Float64(_) => 8,
+            Value::Bool(_) => 1,
+            Value::Null => 0,
+            Value::Timestamp(_) => 12,
+            Value::Uuid(_) => 16,
+            Value::Decimal(_) => 17,
+            Value::String(s) => s.len(),
+            Value::Bytes(b) | Value::Unknown { raw: b, .. } => b.len(),
+            Value::Message(_) | Value::List(_) | Value::Map(_) | Value::Enum { .. } => {
+                return None
+            }
+        })
+    }
+
+    /// Размеры данных контейнера и всех вложенных контейнеров в порядке
+    /// их записи
+    ///
+    /// Обход без рекурсии, как у кодировщика: размер вложенного
+    /// контейнера прибавляется к родителю, когда посчитано всё его
+    /// содержимое.
+    pub(crate) fn container_lens(
+        &self,
+        profile: &Profile,
+        key_size: &dyn Fn(&str) -> usize,
+    ) -> Vec<usize> {
+        enum Visit<'v> {
+            /// значение и байты перед ним в родителе: код типа и ключ
+            Enter { value: &'v Value, parent: Option<usize>, head: usize },
+            Exit { slot: usize, parent: Option<usize>, head: usize },
         }
-        Value::Message(fields) => {
-            let mut inner = Vec::new();
-            for f in fields {
-                inner.extend_from_slice(&encode_field(f));
+        let mut lens = Vec::new();
+        let mut stack = vec![Visit::Enter { value: self, parent: None, head: 0 }];
+        while let Some(visit) = stack.pop() {
+            let (parent, size) = match visit {
+                Visit::Enter { value, parent, head } if profile.inline_code(value).is_some() => {
+                    (parent, head)
+                }
+                Visit::Enter { value, parent, head } => match value.scalar_len(profile) {
+                    Some(len) => (parent, head + profile.len_size(len) + len),
+                    None => {
+                        let slot = lens.len();
+                        stack.push(Visit::Exit { slot, parent, head });
+                        // у каждого элемента свой код типа
+                        let child = |value, head| Visit::Enter { value, parent: Some(slot), head };
+                        lens.push(match value {
+                            Value::Message(fields) => {
+                                let key = |f: &Field| 1 + key_size(&f.key);
+                                stack.extend(fields.iter().rev().map(|f| child(&f.value, key(f))));
+                                0
+                            }
+                            Value::List(items) => {
+                                stack.extend(items.iter().rev().map(|v| child(v, 1)));
+                                0
+                            }
+                            Value::Map(entries) => {
+                                let pairs = entries.iter().rev();
+                                stack.extend(pairs.flat_map(|(k, v)| [child(v, 1), child(k, 1)]));
+                                0
+                            }
+                            Value::Enum { name, payload, .. } => {
+                                stack.extend(payload.as_deref().map(|p| child(p, 1)));
+                                let name = name.as_ref().map(String::len);
+                                4 + 1 + name.map_or(0, |n| profile.len_size(n) + n)
+                            }
+                            _ => unreachable!("у скаляров есть scalar_len"),
+                        });
+                        continue;
+                    }
+                },
+                Visit::Exit { slot, parent, head } => {
+                    let len = lens[slot];
+                    (parent, head + profile.len_size(len) + len)
+                }
+            };
+            if let Some(parent) = parent {
+                lens[parent] += size;
             }
-            out.extend_from_slice(&(inner.len() as u32).to_be_bytes());
-            out.extend_from_slice(&inner);
         }
+        lens
     }
-
-    out
 }
 
-/// Декодирование одного поля
-pub fn decode_field(data: &[u8]) -> Option<Field> {
-    let mut cur = Cursor::new(data);
-
-    let mut type_code = [0u8; 1];
-    cur.read_exact(&mut type_code).ok()?;
-
-    let mut len_buf = [0u8; 4];
-
-    // длина ключа
-    cur.read_exact(&mut len_buf).ok()?;
-    let key_len = u32::from_be_bytes(len_buf) as usize;
-
-    let mut key_bytes = vec![0u8; key_len];
-    cur.read_exact(&mut key_bytes).ok()?;
-    let key = String::from_utf8(key_bytes).ok()?;
-
-    // длина значения
-    cur.read_exact(&mut len_buf).ok()?;
-    let val_len = u32::from_be_bytes(len_buf) as usize;
+#[cfg(feature = "uuid")]
+impl From<::uuid::Uuid> for Value {
+    fn from(u: ::uuid::Uuid) -> Self {
+        Value::Uuid(u.into_bytes())
+    }
+}
 
-    let mut val_bytes = vec![0u8; val_len];
-    cur.read_exact(&mut val_bytes).ok()?;
+/// При несовпадении типа возвращается исходное значение
+#[cfg(feature = "uuid")]
+impl TryFrom<Value> for ::uuid::Uuid {
+    type Error = Value;
 
-    let value = match type_code[0] {
-        1 => {
-            let mut arr = [0u8;4];
-            arr.copy_from_slice(&val_bytes);
-            Value::Int32(i32::from_be_bytes(arr))
+    fn try_from(value: Value) -> Result<Self, Value> {
+        match value {
+            Value::Uuid(bytes) => Ok(::uuid::Uuid::from_bytes(bytes)),
+            other => Err(other),
         }
-        2 => {
-            let mut arr = [0u8;4];
-            arr.copy_from_slice(&val_bytes);
-            Value::Float32(f32::from_be_bytes(arr))
-        }
-        3 => Value::Bool(val_bytes[0] != 0),
-        4 => Value::String(String::from_utf8(val_bytes).ok()?),
-        5 => Value::Bytes(val_bytes),
-        6 => {
-            let mut inner = Vec::new();
-            let mut slice = &val_bytes[..];
-            while !slice.is_empty() {
-                if let Some(f) = decode_field(slice) {
-                    let encoded = encode_field(&f);
-                    let take = encoded.len();
-                    inner.push(f);
-                    slice = &slice[take..];
-                } else {
-                    break;
-                }
-            }
-            Value::Message(inner)
-        }
-        _ => return None,
-    };
+    }
+}
+
+impl Field {
+    /// Точный размер закодированного поля, без кодирования
+    pub fn encoded_len(&self) -> usize {
+        self.encoded_len_with(&Profile::STANDARD)
+    }
 
-    Some(Field { key, value })
+    /// Точный размер закодированного поля в заданном профиле
+    pub fn encoded_len_with(&self, profile: &Profile) -> usize {
+        let key = profile.key_len_size(self.key.len()) + self.key.len();
+        1 + key + self.value.encoded_len_with(profile)
+    }
 }
 
 #[cfg(test)]
@@ -139,7 +400,7 @@ mod tests {
     #[test]
     fn int_roundtrip() {
         let f = Field { key: "age".into(), value: Value::Int32(42) };
-        let enc = encode_field(&f);
+        let enc = encode_field(&f).unwrap();
         let dec = decode_field(&enc).unwrap();
         assert_eq!(f, dec);
     }
@@ -147,8 +408,262 @@ mod tests {
     #[test]
     fn string_roundtrip() {
         let f = Field { key: "name".into(), value: Value::String("Rust".into()) };
-        let enc = encode_field(&f);
+        let enc = encode_field(&f).unwrap();
         let dec = decode_field(&enc).unwrap();
         assert_eq!(f, dec);
     }
+
+    #[test]
+    fn truncated_input() {
+        let f = Field { key: "age".into(), value: Value::Int32(42) };
+        let enc = encode_field(&f).unwrap();
+        let err = decode_field(&enc[..enc.len() - 1]).unwrap_err();
+        assert_eq!(err, DecodeError::UnexpectedEof { offset: 12 });
+    }
+
+    #[test]
+    fn invalid_type_code_and_utf8() {
+        let mut enc = encode_field(&Field { key: "k".into(), value: Value::Bool(true) }).unwrap();
+        enc[0] = 0xEE;
+        assert_eq!(
+            decode_field(&enc).unwrap_err(),
+            DecodeError::InvalidTypeCode { offset: 0, code: 0xEE }
+        );
+
+        let mut enc = encode_field(&Field { key: "k".into(), value: Value::Bool(true) }).unwrap();
+        enc[5] = 0xFF;
+        assert_eq!(decode_field(&enc).unwrap_err(), DecodeError::InvalidUtf8 { offset: 5 });
+    }
+
+    #[test]
+    fn length_mismatch_in_nested_message() {
+        // Int32 с длиной значения 2 внутри вложенного сообщения
+        let mut inner = vec![1, 0, 0, 0, 1, b'x', 0, 0, 0, 2, 0, 7];
+        let mut enc = vec![6, 0, 0, 0, 1, b'm'];
+        enc.extend_from_slice(&(inner.len() as u32).to_be_bytes());
+        enc.append(&mut inner);
+        assert_eq!(
+            decode_field(&enc).unwrap_err(),
+            DecodeError::LengthMismatch { offset: 16, expected: 4, actual: 2 }
+        );
+    }
+
+    #[test]
+    fn consumed_walks_concatenated_fields() {
+        let a = Field { key: "a".into(), value: Value::Int32(1) };
+        let b = Field { key: "b".into(), value: Value::String("two".into()) };
+        let mut buf = encode_field(&a).unwrap();
+        buf.extend_from_slice(&encode_field(&b).unwrap());
+
+        let (first, used) = decode_field_consumed(&buf).unwrap();
+        assert_eq!(first, a);
+        let (second, rest) = decode_field_consumed(&buf[used..]).unwrap();
+        assert_eq!(second, b);
+        assert_eq!(used + rest, buf.len());
+    }
+
+    #[test]
+    fn message_roundtrip() {
+        let fields = vec![
+            Field { key: "flag".into(), value: Value::Bool(false) },
+            Field { key: "data".into(), value: Value::Bytes(vec![1, 2, 3]) },
+            Field {
+                key: "inner".into(),
+                value: Value::Message(vec![Field { key: "ratio".into(), value: Value::Float32(0.5) }]),
+            },
+        ];
+        let enc = encode_message(&fields).unwrap();
+        assert_eq!(decode_message(&enc).unwrap(), fields);
+        assert_eq!(decode_message(&[]).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn message_trailing_garbage() {
+        let mut enc = encode_message(&[Field { key: "a".into(), value: Value::Int32(1) }]).unwrap();
+        let end = enc.len();
+        enc.extend_from_slice(&[4, 0, 0]);
+        assert_eq!(decode_message(&enc).unwrap_err(), DecodeError::UnexpectedEof { offset: end + 1 });
+    }
+
+    #[test]
+    fn encode_into_appends() {
+        let f = Field { key: "b".into(), value: Value::Bool(true) };
+        let mut buf = vec![0xAA];
+        encode_field_into(&f, &mut buf).unwrap();
+        assert_eq!(buf[0], 0xAA);
+        assert_eq!(&buf[1..], &encode_field(&f).unwrap()[..]);
+    }
+
+    #[test]
+    fn encoded_len_is_exact() {
+        let f = Field {
+            key: "root".into(),
+            value: Value::Message(vec![
+                Field { key: "a".into(), value: Value::Bool(true) },
+                Field { key: "b".into(), value: Value::String("hello".into()) },
+                Field { key: "c".into(), value: Value::Message(vec![]) },
+            ]),
+        };
+        assert_eq!(f.encoded_len(), encode_field(&f).unwrap().len());
+        assert_eq!(f.value.encoded_len(), f.encoded_len() - 1 - 4 - f.key.len());
+    }
+
+    #[test]
+    fn int64_roundtrip() {
+        for value in [Value::Int64(i64::MIN), Value::Int64(-1), Value::UInt64(u64::MAX)] {
+            let f = Field { key: "id".into(), value };
+            let enc = encode_field(&f).unwrap();
+            assert_eq!(enc.len(), 1 + 4 + 2 + 4 + 8);
+            assert_eq!(decode_field(&enc).unwrap(), f);
+        }
+        let epoch = Field { key: "ts".into(), value: Value::Int64(1_700_000_000_000) };
+        assert_eq!(&encode_field(&epoch).unwrap()[7..], &[0, 0, 0, 8, 0, 0, 1, 0x8b, 0xcf, 0xe5, 0x68, 0]);
+    }
+
+    #[test]
+    fn float64_roundtrip() {
+        let f = Field { key: "price".into(), value: Value::Float64(0.1 + 0.2) };
+        let enc = encode_field(&f).unwrap();
+        assert_eq!(&enc[0..1], &[9]);
+        assert_eq!(&enc[10..14], &[0, 0, 0, 8]);
+        assert_eq!(decode_field(&enc).unwrap(), f);
+    }
+
+    #[test]
+    fn null_roundtrip() {
+        let f = Field { key: "middle_name".into(), value: Value::Null };
+        let enc = encode_field(&f).unwrap();
+        assert_eq!(enc.len(), 1 + 4 + 11 + 4);
+        assert_eq!(decode_field(&enc).unwrap(), f);
+
+        // Null с непустым значением
+        let bad = [10, 0, 0, 0, 1, b'n', 0, 0, 0, 1, 0];
+        assert_eq!(
+            decode_field(&bad).unwrap_err(),
+            DecodeError::LengthMismatch { offset: 6, expected: 0, actual: 1 }
+        );
+    }
+
+    #[test]
+    fn list_roundtrip() {
+        let f = Field {
+            key: "tags".into(),
+            value: Value::List(vec![
+                Value::String("a".into()),
+                Value::String("bc".into()),
+                Value::List(vec![]),
+                Value::Message(vec![Field { key: "x".into(), value: Value::Null }]),
+            ]),
+        };
+        let enc = encode_field(&f).unwrap();
+        assert_eq!(enc.len(), f.encoded_len());
+        // первый элемент: код типа, длина, данные — без ключа
+        assert_eq!(&enc[13..19], &[4, 0, 0, 0, 1, b'a']);
+        assert_eq!(decode_field(&enc).unwrap(), f);
+    }
+
+    #[test]
+    fn map_roundtrip() {
+        let f = Field {
+            key: "by_id".into(),
+            value: Value::Map(vec![
+                (Value::Int32(1), Value::String("one".into())),
+                (Value::Bytes(vec![0xff]), Value::List(vec![Value::Bool(true)])),
+                (Value::Null, Value::Map(vec![])),
+            ]),
+        };
+        let enc = encode_field(&f).unwrap();
+        assert_eq!(enc.len(), f.encoded_len());
+        assert_eq!(decode_field(&enc).unwrap(), f);
+    }
+
+    #[test]
+    fn timestamp_roundtrip() {
+        let ts = Timestamp::new(-86_400, 123_456_789).unwrap();
+        let f = Field { key: "at".into(), value: Value::Timestamp(ts) };
+        let mut enc = encode_field(&f).unwrap();
+        assert_eq!(enc.len(), 1 + 4 + 2 + 4 + 12);
+        assert_eq!(decode_field(&enc).unwrap(), f);
+
+        // наносекунды за пределами секунды
+        let nanos = enc.len() - 4;
+        enc[nanos..].copy_from_slice(&1_000_000_000u32.to_be_bytes());
+        assert_eq!(
+            decode_field(&enc).unwrap_err(),
+            DecodeError::InvalidValue { offset: 11, type_code: 13 }
+        );
+    }
+
+    #[test]
+    fn uuid_roundtrip() {
+        let f = Field { key: "id".into(), value: Value::Uuid(*b"0123456789abcdef") };
+        let enc = encode_field(&f).unwrap();
+        assert_eq!(enc.len(), 1 + 4 + 2 + 4 + 16);
+        assert_eq!(decode_field(&enc).unwrap(), f);
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_crate_conversion() {
+        let u = ::uuid::Uuid::from_u128(0x67e5_5044_10b1_426f_9247_bb68_0e5f_e0c8);
+        let v = Value::from(u);
+        assert_eq!(v, Value::Uuid(u.into_bytes()));
+        assert_eq!(::uuid::Uuid::try_from(v), Ok(u));
+        assert_eq!(::uuid::Uuid::try_from(Value::Null), Err(Value::Null));
+    }
+
+    #[test]
+    fn decimal_roundtrip() {
+        let d = Decimal::new(-123_456_789_012_345_678_901_234_567, 10).unwrap();
+        let f = Field { key: "amount".into(), value: Value::Decimal(d) };
+        let mut enc = encode_field(&f).unwrap();
+        assert_eq!(enc.len(), 1 + 4 + 6 + 4 + 17);
+        assert_eq!(decode_field(&enc).unwrap(), f);
+
+        // масштаб больше 38
+        enc[15] = 39;
+        assert_eq!(
+            decode_field(&enc).unwrap_err(),
+            DecodeError::InvalidValue { offset: 15, type_code: 15 }
+        );
+    }
+
+    #[test]
+    fn enum_roundtrip() {
+        let variants = [
+            Value::Enum { variant: 0, name: None, payload: None },
+            Value::Enum { variant: 7, name: Some("Circle".into()), payload: None },
+            Value::Enum {
+                variant: 99,
+                name: None,
+                payload: Some(Box::new(Value::Message(vec![Field {
+                    key: "r".into(),
+                    value: Value::Float64(1.5),
+                }]))),
+            },
+        ];
+        for value in variants {
+            let f = Field { key: "shape".into(), value };
+            let enc = encode_field(&f).unwrap();
+            assert_eq!(enc.len(), f.encoded_len());
+            assert_eq!(decode_field(&enc).unwrap(), f);
+        }
+    }
+
+    #[test]
+    fn small_int_roundtrip() {
+        let cases = [
+            (Value::Int8(-128), 1),
+            (Value::Int16(-300), 2),
+            (Value::UInt8(255), 1),
+            (Value::UInt16(65_535), 2),
+            (Value::UInt32(4_000_000_000), 4),
+        ];
+        for (value, width) in cases {
+            let f = Field { key: "t".into(), value };
+            let enc = encode_field(&f).unwrap();
+            assert_eq!(enc.len(), 1 + 4 + 1 + 4 + width);
+            assert_eq!(decode_field(&enc).unwrap(), f);
+        }
+    }
 }
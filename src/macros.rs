@@ -0,0 +1,91 @@
+//! Макросы `msg!` и `field!`
+//!
+//! Значения приводятся к `Value` через `Into`, поэтому вариант
+//! определяется типом выражения: `42` — `Int32`, `2.5` — `Float64`,
+//! `"Rust"` — `String`, вложенный `msg!` — `Message`. Нужный вариант
+//! задаётся суффиксом литерала (`42u8`) или явным `Value`.
+
+/// Сообщение из пар `ключ => значение`
+///
+/// ```
+/// use custom_codec::{msg, Value};
+///
+/// let msg = msg! {
+///     "age" => 42,
+///     "name" => "Rust",
+///     "addr" => msg! { "city" => "Oslo", "zip" => 150u16 },
+/// };
+/// assert_eq!(msg.get("age"), Some(&Value::Int32(42)));
+/// assert_eq!(msg.get_path("addr.zip"), Some(&Value::UInt16(150)));
+/// ```
+#[macro_export]
+macro_rules! msg {
+    () => {
+        $crate::Message::new()
+    };
+    ($($key:expr => $value:expr),+ $(,)?) => {
+        $crate::Message::from(::std::vec![$($crate::field!($key => $value)),+])
+    };
+}
+
+/// Поле `ключ => значение`
+///
+/// ```
+/// use custom_codec::{field, Field, Value};
+///
+/// let f = field!("id" => 7u64);
+/// assert_eq!(f, Field { key: "id".into(), value: Value::UInt64(7) });
+/// ```
+#[macro_export]
+macro_rules! field {
+    ($key:expr => $value:expr) => {
+        $crate::Field {
+            key: ::std::convert::Into::<::std::string::String>::into($key),
+            value: ::std::convert::Into::<$crate::Value>::into($value),
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Field, Message, Value};
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn infers_variants() {
+        let name = String::from("Rust");
+        let msg = msg! {
+            "age" => 42,
+            "ratio" => 2.5,
+            "ok" => true,
+            "name" => name,
+            "raw" => vec![1u8, 2],
+            "tags" => vec![Value::from("a"), Value::from(1i64)],
+            "none" => None::<i32>,
+            "addr" => msg! { "city" => "Oslo" },
+        };
+        let expected = vec![
+            field("age", Value::Int32(42)),
+            field("ratio", Value::Float64(2.5)),
+            field("ok", Value::Bool(true)),
+            field("name", Value::String("Rust".into())),
+            field("raw", Value::Bytes(vec![1, 2])),
+            field("tags", Value::List(vec![Value::String("a".into()), Value::Int64(1)])),
+            field("none", Value::Null),
+            field("addr", Value::Message(vec![field("city", Value::String("Oslo".into()))])),
+        ];
+        assert_eq!(msg.into_fields(), expected);
+    }
+
+    #[test]
+    fn empty_and_repeated_keys() {
+        assert_eq!(msg! {}, Message::new());
+        let key = String::from("n");
+        let msg = msg! { key.clone() => 1u8, key => -1i8 };
+        assert_eq!(msg.fields(), [field("n", Value::UInt8(1)), field("n", Value::Int8(-1))]);
+        assert_eq!(field!("n" => 1u8), field("n", Value::UInt8(1)));
+    }
+}
@@ -0,0 +1,397 @@
+use std::ops::Index;
+
+use crate::{decode_message, encode_message, DecodeError, EncodeError, Field, Value};
+
+/// Сообщение — упорядоченный набор полей с доступом по ключу.
+///
+/// Формат допускает повторяющиеся ключи; методы доступа по ключу
+/// работают с первым подходящим полем.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Message {
+    fields: Vec<Field>,
+}
+
+impl Message {
+    /// Пустое сообщение
+    pub fn new() -> Self {
+        Message { fields: Vec::new() }
+    }
+
+    /// Количество полей
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Значение по ключу
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.fields.iter().find(|f| f.key == key).map(|f| &f.value)
+    }
+
+    /// Изменяемое значение по ключу
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.fields.iter_mut().find(|f| f.key == key).map(|f| &mut f.value)
+    }
+
+    /// Значение по пути ключей через точку: `user.address.city`
+    ///
+    /// Каждый следующий ключ ищется во вложенном `Value::Message`, как в
+    /// `get`. Обратная черта экранирует `.`, `=` и саму себя: путь `a\.b` —
+    /// ключ `a.b`, `a\\b` — ключ `a\b`. Путь с другим экранированием не
+    /// находит значения.
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        let keys = path_keys(path)?;
+        let (last, parents) = keys.split_last()?;
+        let mut fields = &self.fields[..];
+        for key in parents {
+            match &fields.iter().find(|f| &f.key == key)?.value {
+                Value::Message(inner) => fields = inner,
+                _ => return None,
+            }
+        }
+        fields.iter().find(|f| &f.key == last).map(|f| &f.value)
+    }
+
+    /// Изменяемое значение по пути, как в `get_path`
+    pub fn get_path_mut(&mut self, path: &str) -> Option<&mut Value> {
+        let keys = path_keys(path)?;
+        let (last, parents) = keys.split_last()?;
+        let mut fields = &mut self.fields[..];
+        for key in parents {
+            match &mut fields.iter_mut().find(|f| &f.key == key)?.value {
+                Value::Message(inner) => fields = inner,
+                _ => return None,
+            }
+        }
+        fields.iter_mut().find(|f| &f.key == last).map(|f| &mut f.value)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.fields.iter().any(|f| f.key == key)
+    }
+
+    /// Вставка значения. Если ключ уже есть, значение заменяется,
+    /// а прежнее возвращается; иначе поле добавляется в конец.
+    pub fn insert(&mut self, key: impl Into<String>, value: Value) -> Option<Value> {
+        let key = key.into();
+        match self.get_mut(&key) {
+            Some(slot) => Some(std::mem::replace(slot, value)),
+            None => {
+                self.fields.push(Field { key, value });
+                None
+            }
+        }
+    }
+
+    /// Удаление поля по ключу с сохранением порядка остальных полей
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        let idx = self.fields.iter().position(|f| f.key == key)?;
+        Some(self.fields.remove(idx).value)
+    }
+
+    /// Наложение полей `other` на сообщение
+    ///
+    /// Поля, ключей которых ещё нет, дописываются в конец в порядке `other`;
+    /// одноимённые объединяются по `strategy`.
+    pub fn merge(&mut self, other: &Message, strategy: MergeStrategy) {
+        merge_fields(&mut self.fields, &other.fields, strategy);
+    }
+
+    /// Обход полей в порядке их следования
+    pub fn iter(&self) -> std::slice::Iter<'_, Field> {
+        self.fields.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Field> {
+        self.fields.iter_mut()
+    }
+
+    /// Обход ключей
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.fields.iter().map(|f| f.key.as_str())
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub fn into_fields(self) -> Vec<Field> {
+        self.fields
+    }
+
+    /// Кодирование всех полей подряд (как содержимое `Value::Message`)
+    pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
+        encode_message(&self.fields)
+    }
+
+    /// Декодирование буфера, содержащего поля подряд
+    pub fn decode(data: &[u8]) -> Result<Message, DecodeError> {
+        decode_message(data).map(Message::from)
+    }
+}
+
+/// Объединение одноимённых полей в `Message::merge`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum MergeStrategy {
+    /// Значение из `other` заменяет прежнее
+    #[default]
+    Overwrite,
+    /// Прежнее значение остаётся; добавляются только новые ключи
+    KeepExisting,
+    /// Вложенные сообщения объединяются рекурсивно, прочие значения
+    /// заменяются
+    Deep,
+    /// Как `Deep`, но `Bytes` и `List` дописываются к прежним
+    Concat,
+}
+
+fn merge_fields(fields: &mut Vec<Field>, other: &[Field], strategy: MergeStrategy) {
+    use MergeStrategy::*;
+    for field in other {
+        let Some(slot) = fields.iter_mut().find(|f| f.key == field.key) else {
+            fields.push(field.clone());
+            continue;
+        };
+        match (strategy, &mut slot.value, &field.value) {
+            (KeepExisting, ..) => {}
+            (Deep | Concat, Value::Message(a), Value::Message(b)) => merge_fields(a, b, strategy),
+            (Concat, Value::Bytes(a), Value::Bytes(b)) => a.extend_from_slice(b),
+            (Concat, Value::List(a), Value::List(b)) => a.extend(b.iter().cloned()),
+            (_, value, new) => *value = new.clone(),
+        }
+    }
+}
+
+/// Ключи пути `Message::get_path`; `None`, если после `\` не `.`, `=` или `\`
+pub fn path_keys(path: &str) -> Option<Vec<String>> {
+    let mut keys = vec![String::new()];
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => keys.push(String::new()),
+            '\\' => match chars.next() {
+                Some(c @ ('.' | '=' | '\\')) => keys.last_mut().unwrap().push(c),
+                _ => return None,
+            },
+            c => keys.last_mut().unwrap().push(c),
+        }
+    }
+    Some(keys)
+}
+
+impl From<Vec<Field>> for Message {
+    fn from(fields: Vec<Field>) -> Self {
+        Message { fields }
+    }
+}
+
+impl From<Message> for Vec<Field> {
+    fn from(msg: Message) -> Self {
+        msg.fields
+    }
+}
+
+impl From<Message> for Value {
+    fn from(msg: Message) -> Self {
+        Value::Message(msg.fields)
+    }
+}
+
+/// Значение по ключу; паникует, если поля нет
+impl Index<&str> for Message {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        self.get(key).unwrap_or_else(|| panic!("нет поля `{key}`"))
+    }
+}
+
+impl IntoIterator for Message {
+    type Item = Field;
+    type IntoIter = std::vec::IntoIter<Field>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Message {
+    type Item = &'a Field;
+    type IntoIter = std::slice::Iter<'a, Field>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Message {
+    type Item = &'a mut Field;
+    type IntoIter = std::slice::IterMut<'a, Field>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.iter_mut()
+    }
+}
+
+/// Поля берутся по порядку; повторы ключа сохраняются, как при
+/// декодировании
+impl FromIterator<Field> for Message {
+    fn from_iter<I: IntoIterator<Item = Field>>(iter: I) -> Self {
+        Message { fields: iter.into_iter().collect() }
+    }
+}
+
+impl FromIterator<(String, Value)> for Message {
+    fn from_iter<I: IntoIterator<Item = (String, Value)>>(iter: I) -> Self {
+        iter.into_iter().map(|(key, value)| Field { key, value }).collect()
+    }
+}
+
+/// Поля дописываются в конец, как `Vec::extend`; в отличие от
+/// `insert`, одноимённое поле не заменяется
+impl Extend<Field> for Message {
+    fn extend<I: IntoIterator<Item = Field>>(&mut self, iter: I) {
+        self.fields.extend(iter);
+    }
+}
+
+impl Extend<(String, Value)> for Message {
+    fn extend<I: IntoIterator<Item = (String, Value)>>(&mut self, iter: I) {
+        self.fields.extend(iter.into_iter().map(|(key, value)| Field { key, value }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_like_access() {
+        let mut msg = Message::new();
+        assert_eq!(msg.insert("age", Value::Int32(42)), None);
+        assert_eq!(msg.insert("name", Value::String("Rust".into())), None);
+        assert_eq!(msg.insert("age", Value::Int32(43)), Some(Value::Int32(42)));
+
+        assert_eq!(msg.len(), 2);
+        assert_eq!(msg.get("age"), Some(&Value::Int32(43)));
+        assert!(msg.contains_key("name"));
+        assert_eq!(msg.keys().collect::<Vec<_>>(), ["age", "name"]);
+
+        assert_eq!(msg.remove("age"), Some(Value::Int32(43)));
+        assert!(!msg.contains_key("age"));
+        assert_eq!(msg.remove("age"), None);
+    }
+
+    #[test]
+    fn path_access() {
+        let mut address = Message::new();
+        address.insert("city", Value::String("Oslo".into()));
+        address.insert("a.b", Value::Null);
+        let mut user = Message::new();
+        user.insert("address", address.into());
+        user.insert("age", Value::Int32(42));
+        let mut msg = Message::new();
+        msg.insert("user", user.into());
+
+        assert_eq!(msg.get_path("user.address.city"), Some(&Value::String("Oslo".into())));
+        assert_eq!(msg.get_path(r"user.address.a\.b"), Some(&Value::Null));
+        assert!(matches!(msg.get_path("user"), Some(Value::Message(_))));
+        assert_eq!(msg.get_path("user.address.zip"), None);
+        assert_eq!(msg.get_path("user.age.x"), None);
+        assert_eq!(path_keys(r"a\\b.c\=").unwrap(), [r"a\b", "c="]);
+        assert_eq!(path_keys(r"a\b"), None);
+        assert_eq!(path_keys(r"a\"), None);
+        assert_eq!(msg.get_path(r"user\age"), None);
+
+        *msg.get_path_mut("user.age").unwrap() = Value::Int32(43);
+        assert_eq!(msg.get_path("user.age"), Some(&Value::Int32(43)));
+        assert_eq!(msg.get_path_mut("user.address.city.x"), None);
+    }
+
+    #[test]
+    fn merge_strategies() {
+        let msg = |fields: &[(&str, Value)]| {
+            let mut msg = Message::new();
+            for (key, value) in fields {
+                msg.insert(*key, value.clone());
+            }
+            msg
+        };
+        let tags = |tags: &[i32]| Value::List(tags.iter().map(|&t| Value::Int32(t)).collect());
+        let db = msg(&[("host", Value::String("localhost".into())), ("port", Value::Int32(1))]);
+        let defaults = msg(&[
+            ("db", db.into()),
+            ("tags", tags(&[1])),
+            ("raw", Value::Bytes(vec![1])),
+        ]);
+        let env = msg(&[
+            ("db", msg(&[("port", Value::Int32(2))]).into()),
+            ("tags", tags(&[2])),
+            ("raw", Value::Bytes(vec![2])),
+            ("debug", Value::Bool(true)),
+        ]);
+        let merged = |strategy| {
+            let mut merged = defaults.clone();
+            merged.merge(&env, strategy);
+            merged
+        };
+
+        let overwrite = merged(MergeStrategy::Overwrite);
+        assert_eq!(overwrite.get("db"), env.get("db"));
+        assert_eq!(overwrite.keys().collect::<Vec<_>>(), ["db", "tags", "raw", "debug"]);
+        let keep = merged(MergeStrategy::KeepExisting);
+        assert_eq!(keep.get("tags"), Some(&tags(&[1])));
+        assert_eq!(keep.get("debug"), Some(&Value::Bool(true)));
+
+        let deep = merged(MergeStrategy::Deep);
+        assert_eq!(deep.get_path("db.host"), Some(&Value::String("localhost".into())));
+        assert_eq!(deep.get_path("db.port"), Some(&Value::Int32(2)));
+        assert_eq!(deep.get("tags"), Some(&tags(&[2])));
+        let concat = merged(MergeStrategy::Concat);
+        assert_eq!(concat.get_path("db.port"), Some(&Value::Int32(2)));
+        assert_eq!(concat.get("tags"), Some(&tags(&[1, 2])));
+        assert_eq!(concat.get("raw"), Some(&Value::Bytes(vec![1, 2])));
+    }
+
+    #[test]
+    fn collection_traits() {
+        let mut msg: Message =
+            [("age".to_owned(), Value::Int32(42)), ("name".to_owned(), "Rust".into())]
+                .into_iter()
+                .collect();
+        assert_eq!(msg["age"], Value::Int32(42));
+        msg.extend([("age".to_owned(), Value::Int32(43))]);
+        msg.extend([Field { key: "id".into(), value: Value::UInt8(1) }]);
+        assert_eq!(msg.keys().collect::<Vec<_>>(), ["age", "name", "age", "id"]);
+        assert_eq!(msg["age"], Value::Int32(42));
+
+        for field in &mut msg {
+            field.key.make_ascii_uppercase();
+        }
+        let keys: Vec<_> = (&msg).into_iter().map(|f| f.key.as_str()).collect();
+        assert_eq!(keys, ["AGE", "NAME", "AGE", "ID"]);
+        let copy: Message = msg.clone().into_iter().collect();
+        assert_eq!(copy, msg);
+    }
+
+    #[test]
+    #[should_panic(expected = "нет поля `missing`")]
+    fn index_missing_key() {
+        let _ = &Message::new()["missing"];
+    }
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let mut inner = Message::new();
+        inner.insert("city", Value::String("Oslo".into()));
+        let mut msg = Message::new();
+        msg.insert("id", Value::Int32(7));
+        msg.insert("addr", inner.into());
+
+        let enc = msg.encode().unwrap();
+        assert_eq!(Message::decode(&enc).unwrap(), msg);
+    }
+}
@@ -0,0 +1,295 @@
+//! Преобразование между `Value` и MessagePack через `rmpv`
+//!
+//! У MessagePack есть целые, `F32` и `F64`, строки, двоичные данные,
+//! массивы и отображения с любыми ключами, так что эти значения переносятся
+//! без потерь, только целые читаются как `Int64` (или `UInt64`, если не
+//! помещаются). Отображение, все ключи которого строки, становится
+//! `Value::Message`. Остальные типы записываются расширениями:
+//!
+//! - `Timestamp` — стандартное расширение -1 в кратчайшей из форм 32, 64 и 96 бит;
+//! - `Uuid` — [`MSGPACK_EXT_UUID`] над 16 байтами;
+//! - `Decimal` — [`MSGPACK_EXT_DECIMAL`]: байт `scale` и мантисса i128, старшие
+//!   байты первыми;
+//! - вариант перечисления — [`MSGPACK_EXT_ENUM`] над массивом MessagePack
+//!   `[номер, имя или nil]` с содержимым третьим элементом, если оно есть.
+
+use std::fmt;
+
+use rmpv::Value as Msgpack;
+
+use crate::decimal::MAX_SCALE;
+use crate::{Decimal, Field, Timestamp, Value};
+
+/// Расширение UUID
+pub const MSGPACK_EXT_UUID: i8 = 1;
+/// Расширение десятичного числа
+pub const MSGPACK_EXT_DECIMAL: i8 = 2;
+/// Расширение варианта перечисления
+pub const MSGPACK_EXT_ENUM: i8 = 3;
+
+const EXT_TIMESTAMP: i8 = -1;
+
+/// Данные MessagePack не переводятся в `Value`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MsgpackError {
+    /// Неверная кодировка MessagePack или лишние байты после значения
+    Syntax(String),
+    /// Значение без аналога в формате: `what` — что именно
+    Unsupported { what: &'static str },
+    /// На верхнем уровне ожидалось отображение со строковыми ключами
+    NotAMessage,
+}
+
+impl fmt::Display for MsgpackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MsgpackError::Syntax(msg) => write!(f, "неверный MessagePack: {msg}"),
+            MsgpackError::Unsupported { what } => write!(f, "не поддерживается: {what}"),
+            MsgpackError::NotAMessage => {
+                f.write_str("на верхнем уровне ожидалось отображение со строковыми ключами")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MsgpackError {}
+
+/// Сообщение как отображение MessagePack
+pub fn to_msgpack(fields: &[Field]) -> Vec<u8> {
+    write(&message(fields))
+}
+
+/// Сообщение из отображения MessagePack со строковыми ключами
+pub fn from_msgpack(data: &[u8]) -> Result<Vec<Field>, MsgpackError> {
+    match value_from_msgpack(&read(data)?)? {
+        Value::Message(fields) => Ok(fields),
+        _ => Err(MsgpackError::NotAMessage),
+    }
+}
+
+fn write(value: &Msgpack) -> Vec<u8> {
+    let mut out = Vec::new();
+    rmpv::encode::write_value(&mut out, value).expect("запись в Vec не завершается ошибкой");
+    out
+}
+
+/// Ровно одно значение, занимающее весь срез
+fn read(mut data: &[u8]) -> Result<Msgpack, MsgpackError> {
+    let value = rmpv::decode::read_value(&mut data)
+        .map_err(|e| MsgpackError::Syntax(e.to_string()))?;
+    match data.len() {
+        0 => Ok(value),
+        rest => Err(MsgpackError::Syntax(format!("лишние байты после значения: {rest}"))),
+    }
+}
+
+fn message(fields: &[Field]) -> Msgpack {
+    let entry = |f: &Field| (Msgpack::from(f.key.as_str()), value_to_msgpack(&f.value));
+    Msgpack::Map(fields.iter().map(entry).collect())
+}
+
+/// Расширение -1: 32 бита секунд, 64 бита (30 бит наносекунд и 34 бита
+/// секунд) или 96 бит (32 бита наносекунд и 64 бита секунд)
+fn timestamp(ts: &Timestamp) -> Vec<u8> {
+    match (u32::try_from(ts.secs), ts.secs >> 34) {
+        (Ok(secs), _) if ts.nanos == 0 => secs.to_be_bytes().to_vec(),
+        (_, 0) => ((ts.nanos as u64) << 34 | ts.secs as u64).to_be_bytes().to_vec(),
+        _ => [&ts.nanos.to_be_bytes()[..], &ts.secs.to_be_bytes()].concat(),
+    }
+}
+
+fn read_timestamp(data: &[u8]) -> Option<Timestamp> {
+    match data.len() {
+        4 => Timestamp::new(u32::from_be_bytes(data.try_into().ok()?) as i64, 0),
+        8 => {
+            let n = u64::from_be_bytes(data.try_into().ok()?);
+            Timestamp::new((n & ((1 << 34) - 1)) as i64, (n >> 34) as u32)
+        }
+        12 => {
+            let nanos = u32::from_be_bytes(data[..4].try_into().ok()?);
+            Timestamp::new(i64::from_be_bytes(data[4..].try_into().ok()?), nanos)
+        }
+        _ => None,
+    }
+}
+
+pub fn value_to_msgpack(value: &Value) -> Msgpack {
+    match value {
+        Value::Int8(i) => Msgpack::from(*i),
+        Value::Int16(i) => Msgpack::from(*i),
+        Value::Int32(i) => Msgpack::from(*i),
+        Value::Int64(i) => Msgpack::from(*i),
+        Value::UInt8(u) => Msgpack::from(*u),
+        Value::UInt16(u) => Msgpack::from(*u),
+        Value::UInt32(u) => Msgpack::from(*u),
+        Value::UInt64(u) => Msgpack::from(*u),
+        Value::Float32(f) => Msgpack::F32(*f),
+        Value::Float64(f) => Msgpack::F64(*f),
+        Value::Bool(b) => Msgpack::Boolean(*b),
+        Value::String(s) => Msgpack::from(s.as_str()),
+        Value::Bytes(b) => Msgpack::Binary(b.clone()),
+        Value::Null => Msgpack::Nil,
+        Value::Message(fields) => message(fields),
+        Value::List(items) => Msgpack::Array(items.iter().map(value_to_msgpack).collect()),
+        Value::Map(entries) => Msgpack::Map(
+            entries.iter().map(|(k, v)| (value_to_msgpack(k), value_to_msgpack(v))).collect(),
+        ),
+        Value::Timestamp(ts) => Msgpack::Ext(EXT_TIMESTAMP, timestamp(ts)),
+        Value::Uuid(u) => Msgpack::Ext(MSGPACK_EXT_UUID, u.to_vec()),
+        Value::Decimal(d) => {
+            let data = [&[d.scale][..], &d.mantissa.to_be_bytes()].concat();
+            Msgpack::Ext(MSGPACK_EXT_DECIMAL, data)
+        }
+        Value::Enum { variant, name, payload } => {
+            let mut items =
+                vec![Msgpack::from(*variant), name.as_deref().map_or(Msgpack::Nil, Msgpack::from)];
+            items.extend(payload.as_deref().map(value_to_msgpack));
+            Msgpack::Ext(MSGPACK_EXT_ENUM, write(&Msgpack::Array(items)))
+        }
+        // расширения для неизвестного типа нет: код и данные как сообщение
+        Value::Unknown { type_code, raw } => Msgpack::Map(vec![
+            (Msgpack::from("type_code"), Msgpack::from(*type_code)),
+            (Msgpack::from("raw"), Msgpack::Binary(raw.clone())),
+        ]),
+    }
+}
+
+fn unsupported<T>(what: &'static str) -> Result<T, MsgpackError> {
+    Err(MsgpackError::Unsupported { what })
+}
+
+pub fn value_from_msgpack(value: &Msgpack) -> Result<Value, MsgpackError> {
+    Ok(match value {
+        Msgpack::Nil => Value::Null,
+        Msgpack::Boolean(b) => Value::Bool(*b),
+        Msgpack::Integer(i) => match (i.as_i64(), i.as_u64()) {
+            (Some(i), _) => Value::Int64(i),
+            (None, Some(u)) => Value::UInt64(u),
+            _ => unreachable!("целое MessagePack помещается в i64 или u64"),
+        },
+        Msgpack::F32(f) => Value::Float32(*f),
+        Msgpack::F64(f) => Value::Float64(*f),
+        Msgpack::String(s) => match s.as_str() {
+            Some(s) => Value::String(s.to_owned()),
+            None => return unsupported("строка не в UTF-8"),
+        },
+        Msgpack::Binary(b) => Value::Bytes(b.clone()),
+        Msgpack::Array(items) => {
+            Value::List(items.iter().map(value_from_msgpack).collect::<Result<_, _>>()?)
+        }
+        Msgpack::Map(entries) if entries.iter().all(|(k, _)| k.as_str().is_some()) => {
+            Value::Message(
+                entries
+                    .iter()
+                    .map(|(k, v)| {
+                        let key = k.as_str().unwrap_or_default().to_owned();
+                        Ok(Field { key, value: value_from_msgpack(v)? })
+                    })
+                    .collect::<Result<_, MsgpackError>>()?,
+            )
+        }
+        Msgpack::Map(entries) => Value::Map(
+            entries
+                .iter()
+                .map(|(k, v)| Ok((value_from_msgpack(k)?, value_from_msgpack(v)?)))
+                .collect::<Result<_, MsgpackError>>()?,
+        ),
+        Msgpack::Ext(ty, data) => ext(*ty, data)?,
+    })
+}
+
+fn ext(ty: i8, data: &[u8]) -> Result<Value, MsgpackError> {
+    match ty {
+        EXT_TIMESTAMP => match read_timestamp(data) {
+            Some(ts) => Ok(Value::Timestamp(ts)),
+            None => unsupported("неверная временная метка"),
+        },
+        MSGPACK_EXT_UUID => match <[u8; 16]>::try_from(data) {
+            Ok(u) => Ok(Value::Uuid(u)),
+            Err(_) => unsupported("UUID не из 16 байт"),
+        },
+        MSGPACK_EXT_DECIMAL => {
+            let decimal = match data {
+                [scale, mantissa @ ..] if *scale <= MAX_SCALE && mantissa.len() == 16 => {
+                    let mantissa = i128::from_be_bytes(mantissa.try_into().unwrap());
+                    Decimal { mantissa, scale: *scale }
+                }
+                _ => return unsupported("неверное десятичное число"),
+            };
+            Ok(Value::Decimal(decimal))
+        }
+        MSGPACK_EXT_ENUM => {
+            let parts = match read(data)? {
+                Msgpack::Array(parts) if matches!(parts.len(), 2 | 3) => parts,
+                _ => return unsupported("неверный вариант перечисления"),
+            };
+            let variant = parts[0].as_u64().and_then(|v| u32::try_from(v).ok());
+            let name = match &parts[1] {
+                Msgpack::Nil => None,
+                other => match other.as_str() {
+                    Some(name) => Some(name.to_owned()),
+                    None => return unsupported("имя варианта не строка"),
+                },
+            };
+            let payload = parts.get(2).map(value_from_msgpack).transpose()?.map(Box::new);
+            match variant {
+                Some(variant) => Ok(Value::Enum { variant, name, payload }),
+                None => unsupported("номер варианта вне u32"),
+            }
+        }
+        _ => unsupported("тип расширения MessagePack"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn msgpack_roundtrip() {
+        let fields = vec![
+            field("id", Value::Int64(-7)),
+            field("big", Value::UInt64(u64::MAX)),
+            field("f", Value::Float32(0.5)),
+            field("d", Value::Float64(0.25)),
+            field("blob", Value::Bytes(vec![0, 255])),
+            field("by_id", Value::Map(vec![(Value::Int64(1), Value::String("a".into()))])),
+            field("inner", Value::Message(vec![field("ok", Value::Bool(true))])),
+            field("items", Value::List(vec![Value::Null])),
+            field("t32", Value::Timestamp(Timestamp { secs: 5, nanos: 0 })),
+            field("t64", Value::Timestamp(Timestamp { secs: 5, nanos: 7 })),
+            field("t96", Value::Timestamp(Timestamp { secs: -5, nanos: 7 })),
+            field("id2", Value::Uuid([7; 16])),
+            field("sum", Value::Decimal(Decimal { mantissa: i128::MIN, scale: 38 })),
+            field(
+                "e",
+                Value::Enum { variant: 2, name: Some("Circle".into()), payload: Some(Box::new(
+                    Value::Float64(1.5),
+                )) },
+            ),
+            field("unit", Value::Enum { variant: 0, name: None, payload: None }),
+        ];
+        let enc = to_msgpack(&fields);
+        assert_eq!(from_msgpack(&enc).unwrap(), fields);
+
+        // длины стандартных форм временной метки
+        let ts = |secs, nanos| timestamp(&Timestamp { secs, nanos }).len();
+        assert_eq!((ts(5, 0), ts(5, 7), ts(-5, 0), ts(1 << 34, 0)), (4, 8, 12, 12));
+    }
+
+    #[test]
+    fn msgpack_errors() {
+        assert_eq!(from_msgpack(&write(&Msgpack::Nil)), Err(MsgpackError::NotAMessage));
+        assert!(matches!(from_msgpack(&[0x80, 0xc0]), Err(MsgpackError::Syntax(_))));
+        assert!(matches!(from_msgpack(&[0x81, 0xa1]), Err(MsgpackError::Syntax(_))));
+
+        let err = MsgpackError::Unsupported { what: "тип расширения MessagePack" };
+        assert_eq!(value_from_msgpack(&Msgpack::Ext(42, vec![])), Err(err));
+        let err = MsgpackError::Unsupported { what: "UUID не из 16 байт" };
+        assert_eq!(value_from_msgpack(&Msgpack::Ext(MSGPACK_EXT_UUID, vec![1])), Err(err));
+    }
+}
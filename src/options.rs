@@ -0,0 +1,267 @@
+use std::fmt;
+
+use crate::Value;
+
+/// Вариант формата на проводе
+///
+/// Профиль не записывается в данные: кодирующая и декодирующая стороны
+/// должны использовать один и тот же. По умолчанию — исходный формат
+/// с 4-байтовыми длинами.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Profile {
+    /// Кодирование длин значений и, по умолчанию, ключей
+    pub lengths: LengthEncoding,
+    /// Кодирование длин ключей полей
+    pub keys: KeyLength,
+    /// Кодирование `Int32` и `Int64`
+    pub integers: IntEncoding,
+    /// Порядок байт чисел и фиксированных длин
+    pub endianness: Endianness,
+    /// `Bool` и `Int32` от 0 до 127 записываются одним кодом типа,
+    /// без длины и данных
+    pub inline_scalars: bool,
+}
+
+impl Profile {
+    /// Исходный формат
+    pub const STANDARD: Profile = Profile {
+        lengths: LengthEncoding::Fixed32,
+        keys: KeyLength::AsValues,
+        integers: IntEncoding::Fixed,
+        endianness: Endianness::Big,
+        inline_scalars: false,
+    };
+
+    /// Компактный формат для мелких полей: все размеры переменные
+    pub const fn compact() -> Self {
+        Profile {
+            lengths: LengthEncoding::Varint,
+            keys: KeyLength::AsValues,
+            integers: IntEncoding::ZigZag,
+            endianness: Endianness::Big,
+            inline_scalars: true,
+        }
+    }
+
+    /// Код типа, в котором целиком записано значение
+    pub(crate) fn inline_code(&self, value: &Value) -> Option<u8> {
+        if !self.inline_scalars {
+            return None;
+        }
+        match *value {
+            Value::Bool(true) => Some(INLINE_TRUE),
+            Value::Bool(false) => Some(INLINE_FALSE),
+            Value::Int32(i @ 0..=127) => Some(INLINE_INT_BASE + i as u8),
+            _ => None,
+        }
+    }
+
+    /// Размер префикса длины `len`
+    pub(crate) fn len_size(&self, len: usize) -> usize {
+        match self.lengths {
+            LengthEncoding::Fixed32 => 4,
+            LengthEncoding::Fixed64 => 8,
+            LengthEncoding::Varint => crate::varint::encoded_len(len as u64),
+        }
+    }
+
+    /// Размер префикса длины ключа `len`
+    pub(crate) fn key_len_size(&self, len: usize) -> usize {
+        match self.keys {
+            KeyLength::AsValues => self.len_size(len),
+            KeyLength::Short if len < KEY_LEN_ESCAPE as usize => 1,
+            KeyLength::Short => 1 + 4,
+        }
+    }
+
+    /// Размер знакового целого шириной `width` байт
+    pub(crate) fn int_size(&self, v: i64, width: usize) -> usize {
+        match self.integers {
+            IntEncoding::Fixed => width,
+            IntEncoding::ZigZag => crate::varint::encoded_len(crate::varint::zigzag(v)),
+        }
+    }
+}
+
+/// Способ записи длин
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LengthEncoding {
+    /// 4 байта big-endian
+    #[default]
+    Fixed32,
+    /// LEB128: 1 байт для длин до 127
+    Varint,
+    /// 8 байт: для значений больше 4 GiB
+    Fixed64,
+}
+
+/// Коды типов `Profile::inline_scalars`: `true`, `false`
+/// и `Int32` от 0 до 127 (`INLINE_INT_BASE + n`)
+pub(crate) const INLINE_TRUE: u8 = 0x7e;
+pub(crate) const INLINE_FALSE: u8 = 0x7f;
+pub(crate) const INLINE_INT_BASE: u8 = 0x80;
+
+/// Байт `KeyLength::Short`, за которым следует полная 4-байтовая длина
+pub(crate) const KEY_LEN_ESCAPE: u8 = 0xff;
+
+/// Способ записи длин ключей
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum KeyLength {
+    /// Так же, как длины значений
+    #[default]
+    AsValues,
+    /// Один байт для ключей короче 255 байт; для длинных — байт 0xFF
+    /// и 4-байтовая длина
+    Short,
+}
+
+/// Способ записи знаковых целых `Int32` и `Int64`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum IntEncoding {
+    /// Фиксированная ширина: 4 и 8 байт
+    #[default]
+    Fixed,
+    /// Zig-zag и LEB128: числа от -64 до 63 занимают 1 байт
+    ZigZag,
+}
+
+/// Порядок байт многобайтовых чисел
+///
+/// Не влияет на varint, UUID и строки.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    #[default]
+    Big,
+    Little,
+}
+
+/// Настройки кодирования
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncodeOptions {
+    pub profile: Profile,
+    /// Контрольная сумма в конце конверта; вне конверта не пишется
+    pub checksum: Checksum,
+    /// Идентификатор схемы в заголовке конверта; вне конверта не пишется
+    pub schema_id: Option<SchemaId>,
+}
+
+/// Схема сообщения в реестре: номер и версия
+///
+/// Номер выдаёт общий реестр схем или `Schema::fingerprint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SchemaId {
+    pub id: u32,
+    pub version: u32,
+}
+
+impl fmt::Display for SchemaId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#010x} v{}", self.id, self.version)
+    }
+}
+
+/// Контрольная сумма сообщения в конверте
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Checksum {
+    #[default]
+    None,
+    /// CRC-32 (IEEE 802.3) заголовка и тела, 4 байта big-endian
+    Crc32,
+}
+
+/// Настройки декодирования
+///
+/// Значения по умолчанию рассчитаны на недоверенные данные из сети.
+/// Поля публичные, так что удобно менять только нужные:
+///
+/// ```
+/// use custom_codec::DecodeOptions;
+///
+/// let opts = DecodeOptions { max_depth: 8, ..DecodeOptions::default() };
+/// # let _ = opts;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Максимальная глубина вложенности сообщений, списков и отображений
+    /// (0 — вложенные контейнеры запрещены)
+    pub max_depth: usize,
+    /// Максимальная длина одного значения в байтах
+    pub max_value_len: usize,
+    /// Максимальный общий размер декодируемых данных в байтах
+    pub max_total_len: usize,
+    /// Максимальное число полей с учётом вложенных полей и элементов
+    pub max_fields: usize,
+    /// Что делать с повторяющимися ключами в `Value::Map`
+    pub duplicate_map_keys: DuplicateKeys,
+    /// Что делать со значениями неизвестных типов
+    pub unknown_types: UnknownTypes,
+    /// Формат, в котором закодированы данные
+    pub profile: Profile,
+}
+
+/// Политика для повторяющихся ключей в `Value::Map`
+///
+/// Ключи сравниваются попарно, поэтому для больших отображений любая
+/// политика, кроме `Allow`, стоит квадратичного времени.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Сохранять все пары в порядке следования
+    #[default]
+    Allow,
+    /// Ошибка `DecodeError::DuplicateKey`
+    Reject,
+    /// Оставлять первое значение
+    KeepFirst,
+    /// Оставлять последнее значение (на месте первого вхождения)
+    KeepLast,
+}
+
+/// Политика для значений с неизвестным кодом типа
+///
+/// Сохранённое значение записывается обратно байт в байт, если профиль
+/// кодирования тот же, что у исходных данных.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownTypes {
+    /// Ошибка `DecodeError::InvalidTypeCode`
+    #[default]
+    Reject,
+    /// `Value::Unknown` с кодом типа и данными значения
+    Preserve,
+}
+
+/// Глубина вложенности у `DecodeOptions::unlimited()`
+///
+/// Декодирование не рекурсивно, но удаление, клонирование и сравнение
+/// `Value` рекурсивны: без предела глубокие данные переполнили бы стек
+/// уже при удалении результата.
+pub const MAX_VALUE_DEPTH: usize = 256;
+
+impl DecodeOptions {
+    /// Без ограничений, кроме глубины [`MAX_VALUE_DEPTH`], — поведение
+    /// `decode_field` и `decode_message`
+    pub const fn unlimited() -> Self {
+        DecodeOptions {
+            max_depth: MAX_VALUE_DEPTH,
+            max_value_len: usize::MAX,
+            max_total_len: usize::MAX,
+            max_fields: usize::MAX,
+            duplicate_map_keys: DuplicateKeys::Allow,
+            unknown_types: UnknownTypes::Reject,
+            profile: Profile::STANDARD,
+        }
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions {
+            max_depth: 64,
+            max_value_len: 16 << 20,
+            max_total_len: 64 << 20,
+            max_fields: 65_536,
+            duplicate_map_keys: DuplicateKeys::Allow,
+            unknown_types: UnknownTypes::Reject,
+            profile: Profile::STANDARD,
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Параллельное кодирование сообщений с большим числом полей
+
+use rayon::prelude::*;
+
+use crate::encode::Writer;
+use crate::{EncodeError, EncodeOptions, Field};
+
+/// Сколько полей подряд кодирует один поток: мелкие поля не окупают
+/// передачу задачи между потоками
+const MIN_FIELDS_PER_TASK: usize = 32;
+
+/// Кодирование сообщения в пуле потоков rayon
+///
+/// Результат совпадает с `encode_message`. Сначала считаются размеры
+/// полей, затем каждое поле пишется в свой участок общего буфера,
+/// так что данные не копируются повторно.
+pub fn encode_message_parallel(fields: &[Field]) -> Result<Vec<u8>, EncodeError> {
+    encode_message_parallel_with(fields, &EncodeOptions::default())
+}
+
+/// То же, что [`encode_message_parallel`], с настройками
+pub fn encode_message_parallel_with(
+    fields: &[Field],
+    opts: &EncodeOptions,
+) -> Result<Vec<u8>, EncodeError> {
+    let profile = opts.profile;
+    let lens: Vec<usize> = fields
+        .par_iter()
+        .with_min_len(MIN_FIELDS_PER_TASK)
+        .map(|f| f.encoded_len_with(&profile))
+        .collect();
+
+    let mut out = vec![0; lens.iter().sum()];
+    let mut parts = Vec::with_capacity(fields.len());
+    let mut rest = &mut out[..];
+    for &len in &lens {
+        let (part, tail) = rest.split_at_mut(len);
+        parts.push(part);
+        rest = tail;
+    }
+
+    fields
+        .par_iter()
+        .zip(parts)
+        .with_min_len(MIN_FIELDS_PER_TASK)
+        .try_for_each(|(f, mut part)| Writer::new(&mut part, profile).field(f))?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode_message, encode_message_with, Profile, Value};
+
+    #[test]
+    fn matches_sequential_encoding() {
+        let fields: Vec<Field> = (0..1000)
+            .map(|i| Field {
+                key: format!("f{i}"),
+                value: match i % 3 {
+                    0 => Value::Int32(i),
+                    1 => Value::String("x".repeat(i as usize % 50)),
+                    _ => Value::List(vec![Value::Int64(i as i64); 3]),
+                },
+            })
+            .collect();
+        assert_eq!(encode_message_parallel(&fields).unwrap(), encode_message(&fields).unwrap());
+
+        let opts = EncodeOptions { profile: Profile::compact(), ..EncodeOptions::default() };
+        assert_eq!(
+            encode_message_parallel_with(&fields, &opts).unwrap(),
+            encode_message_with(&fields, &opts).unwrap()
+        );
+        assert_eq!(encode_message_parallel(&[]).unwrap(), [0u8; 0]);
+    }
+}
@@ -0,0 +1,395 @@
+//! Патч: изменения сообщения для передачи вместо сообщения целиком
+//!
+//! Патч строится из [`diff`](crate::diff) и кодируется самим форматом:
+//! каждая операция — поле с ключом `set`, `add`, `remove` или `reorder`.
+//! Значение `set` и `add` — список `[путь, значение]`, значение `remove` —
+//! путь, `reorder` — `[путь, [ключи]]`. Путь — список шагов: строка — ключ
+//! поля, `UInt32` — номер элемента списка, `[ключ, UInt32]` — повтор ключа
+//! с этим номером.
+
+use std::fmt;
+
+use crate::{
+    decode_message_with, encode_message, Change, DecodeError, DecodeOptions, Diff, EncodeError,
+    Field, FieldPath, Message, PathSegment, Value,
+};
+
+/// Операция патча
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchOp {
+    /// Замена значения по пути
+    Set { path: FieldPath, value: Value },
+    /// Новое поле в конце сообщения или новый элемент в конце списка;
+    /// номер элемента равен длине списка
+    Add { path: FieldPath, value: Value },
+    /// Удаление поля или элемента списка
+    Remove { path: FieldPath },
+    /// Перестановка полей сообщения по пути в порядке `keys`; повторы
+    /// ключа сохраняют порядок между собой
+    Reorder { path: FieldPath, keys: Vec<String> },
+}
+
+impl PatchOp {
+    pub fn path(&self) -> &FieldPath {
+        match self {
+            PatchOp::Set { path, .. } | PatchOp::Add { path, .. } => path,
+            PatchOp::Remove { path } | PatchOp::Reorder { path, .. } => path,
+        }
+    }
+}
+
+/// Последовательность операций над сообщением
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch {
+    pub ops: Vec<PatchOp>,
+}
+
+/// Патч не декодируется или не применяется
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchError {
+    /// Байты патча не декодируются
+    Decode(DecodeError),
+    /// Сообщение не является патчем
+    Malformed,
+    /// Путь операции с номером `op` не найден в сообщении или операция к
+    /// нему не применима
+    PathNotFound { op: usize, path: FieldPath },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Decode(e) => write!(f, "патч не декодируется: {e}"),
+            PatchError::Malformed => f.write_str("сообщение не является патчем"),
+            PatchError::PathNotFound { op, path } => {
+                write!(f, "операция {op} патча: нет пути `{path}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PatchError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for PatchError {
+    fn from(e: DecodeError) -> Self {
+        PatchError::Decode(e)
+    }
+}
+
+impl From<Diff> for Patch {
+    /// Сначала замены и добавления, затем удаления в обратном порядке,
+    /// чтобы удаление не сдвигало номера элементов и повторов ключа в
+    /// путях остальных операций. Перестановки идут последними: добавленные
+    /// поля к ним уже дописаны в конец, а номера повторов ключа
+    /// перестановка не меняет.
+    fn from(diff: Diff) -> Self {
+        let mut ops = Vec::new();
+        let mut removed = Vec::new();
+        let mut reordered = Vec::new();
+        for change in diff.changes {
+            match change {
+                Change::Changed { path, after, .. } => {
+                    ops.push(PatchOp::Set { path, value: after })
+                }
+                Change::Added { path, value } => ops.push(PatchOp::Add { path, value }),
+                Change::Removed { path, .. } => removed.push(PatchOp::Remove { path }),
+                Change::Reordered { path, keys } => {
+                    reordered.push(PatchOp::Reorder { path, keys })
+                }
+            }
+        }
+        ops.extend(removed.into_iter().rev());
+        ops.extend(reordered);
+        Patch { ops }
+    }
+}
+
+impl Patch {
+    pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
+        let fields: Vec<_> = self
+            .ops
+            .iter()
+            .map(|op| match op {
+                PatchOp::Set { path, value } => field("set", path, Some(value)),
+                PatchOp::Add { path, value } => field("add", path, Some(value)),
+                PatchOp::Remove { path } => field("remove", path, None),
+                PatchOp::Reorder { path, keys } => {
+                    let keys = keys.iter().map(|k| Value::String(k.clone())).collect();
+                    field("reorder", path, Some(&Value::List(keys)))
+                }
+            })
+            .collect();
+        encode_message(&fields)
+    }
+
+    /// Декодирование с ограничениями `DecodeOptions::default()`, как для
+    /// данных из сети
+    pub fn decode(data: &[u8]) -> Result<Patch, PatchError> {
+        let fields = decode_message_with(data, &DecodeOptions::default())?;
+        let ops = fields.into_iter().map(op).collect::<Option<_>>();
+        Ok(Patch { ops: ops.ok_or(PatchError::Malformed)? })
+    }
+}
+
+fn field(key: &str, path: &FieldPath, value: Option<&Value>) -> Field {
+    let path = Value::List(
+        path.0
+            .iter()
+            .map(|segment| match segment {
+                PathSegment::Key { key, nth: 0 } => Value::String(key.clone()),
+                PathSegment::Key { key, nth } => {
+                    Value::List(vec![Value::String(key.clone()), Value::UInt32(*nth as u32)])
+                }
+                PathSegment::Index(i) => Value::UInt32(*i as u32),
+            })
+            .collect(),
+    );
+    let value = match value {
+        Some(value) => Value::List(vec![path, value.clone()]),
+        None => path,
+    };
+    Field { key: key.to_owned(), value }
+}
+
+fn op(field: Field) -> Option<PatchOp> {
+    match (field.key.as_str(), field.value) {
+        ("remove", path) => Some(PatchOp::Remove { path: path_from(path)? }),
+        (key @ ("set" | "add" | "reorder"), Value::List(items)) => {
+            let [path, value]: [Value; 2] = items.try_into().ok()?;
+            let path = path_from(path)?;
+            Some(match (key, value) {
+                ("set", value) => PatchOp::Set { path, value },
+                ("add", value) => PatchOp::Add { path, value },
+                (_, Value::List(keys)) => {
+                    let keys = keys.into_iter().map(|key| match key {
+                        Value::String(key) => Some(key),
+                        _ => None,
+                    });
+                    PatchOp::Reorder { path, keys: keys.collect::<Option<_>>()? }
+                }
+                _ => return None,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn path_from(path: Value) -> Option<FieldPath> {
+    let Value::List(segments) = path else { return None };
+    let segments = segments.into_iter().map(|segment| match segment {
+        Value::String(key) => Some(PathSegment::Key { key, nth: 0 }),
+        Value::UInt32(i) => Some(PathSegment::Index(i as usize)),
+        Value::List(pair) => match <[Value; 2]>::try_from(pair).ok()? {
+            [Value::String(key), Value::UInt32(nth)] => {
+                Some(PathSegment::Key { key, nth: nth as usize })
+            }
+            _ => None,
+        },
+        _ => None,
+    });
+    Some(FieldPath(segments.collect::<Option<_>>()?))
+}
+
+/// Применение операций патча по порядку
+///
+/// Если какой-то путь не найден, сообщение остаётся прежним.
+///
+/// ```
+/// use custom_codec::{apply_patch, diff, msg, Patch};
+///
+/// let mut a = msg! { "age" => 42 };
+/// let b = msg! { "name" => "Rust", "age" => 43 };
+///
+/// let patch = Patch::decode(&Patch::from(diff(&a, &b)).encode().unwrap()).unwrap();
+/// apply_patch(&mut a, &patch).unwrap();
+/// assert_eq!(a, b);
+/// ```
+pub fn apply_patch(msg: &mut Message, patch: &Patch) -> Result<(), PatchError> {
+    let mut fields = msg.fields().to_vec();
+    for (i, op) in patch.ops.iter().enumerate() {
+        if apply(&mut fields, op).is_none() {
+            return Err(PatchError::PathNotFound { op: i, path: op.path().clone() });
+        }
+    }
+    *msg = Message::from(fields);
+    Ok(())
+}
+
+/// Поля сообщения или элементы списка, в которых выполняется операция
+enum Target<'a> {
+    Fields(&'a mut Vec<Field>),
+    List(&'a mut Vec<Value>),
+}
+
+fn apply(fields: &mut Vec<Field>, op: &PatchOp) -> Option<()> {
+    if let PatchOp::Reorder { path, keys } = op {
+        let Target::Fields(fields) = target(fields, &path.0)? else { return None };
+        return reorder(fields, keys);
+    }
+    let (last, parents) = op.path().0.split_last()?;
+    let target = target(fields, parents)?;
+
+    match (op, target, last) {
+        (PatchOp::Set { value, .. }, Target::Fields(fields), PathSegment::Key { key, nth }) => {
+            fields.iter_mut().filter(|f| &f.key == key).nth(*nth)?.value = value.clone();
+        }
+        (PatchOp::Set { value, .. }, Target::List(items), PathSegment::Index(i)) => {
+            *items.get_mut(*i)? = value.clone();
+        }
+        (PatchOp::Add { value, .. }, Target::Fields(fields), PathSegment::Key { key, .. }) => {
+            fields.push(Field { key: key.clone(), value: value.clone() });
+        }
+        (PatchOp::Add { value, .. }, Target::List(items), PathSegment::Index(i)) => {
+            if *i != items.len() {
+                return None;
+            }
+            items.push(value.clone());
+        }
+        (PatchOp::Remove { .. }, Target::Fields(fields), PathSegment::Key { key, nth }) => {
+            let (i, _) = fields.iter().enumerate().filter(|(_, f)| &f.key == key).nth(*nth)?;
+            fields.remove(i);
+        }
+        (PatchOp::Remove { .. }, Target::List(items), PathSegment::Index(i)) => {
+            if *i >= items.len() {
+                return None;
+            }
+            items.remove(*i);
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Поля сообщения или элементы списка по пути `path`
+fn target<'a>(fields: &'a mut Vec<Field>, path: &[PathSegment]) -> Option<Target<'a>> {
+    let mut target = Target::Fields(fields);
+    for segment in path {
+        let value = match (target, segment) {
+            (Target::Fields(fields), PathSegment::Key { key, nth }) => {
+                &mut fields.iter_mut().filter(|f| &f.key == key).nth(*nth)?.value
+            }
+            (Target::List(items), PathSegment::Index(i)) => items.get_mut(*i)?,
+            _ => return None,
+        };
+        target = match value {
+            Value::Message(fields) => Target::Fields(fields),
+            Value::List(items) => Target::List(items),
+            _ => return None,
+        };
+    }
+    Some(target)
+}
+
+/// Поля в порядке `keys`: каждый ключ берёт первое ещё не взятое поле;
+/// ключи должны перечислять все поля
+fn reorder(fields: &mut Vec<Field>, keys: &[String]) -> Option<()> {
+    if keys.len() != fields.len() {
+        return None;
+    }
+    let mut rest: Vec<_> = std::mem::take(fields).into_iter().map(Some).collect();
+    for key in keys {
+        let slot = rest.iter_mut().find(|f| f.as_ref().is_some_and(|f| &f.key == key))?;
+        fields.extend(slot.take());
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn diff_roundtrip() {
+        let list = |items: &[i32]| Value::List(items.iter().map(|&i| Value::Int32(i)).collect());
+        let a = Message::from(vec![
+            field("id", Value::Int32(1)),
+            field("user", Value::Message(vec![
+                field("age", Value::Int32(42)),
+                field("email", Value::String("a@example.com".into())),
+            ])),
+            field("tags", list(&[1, 2, 3, 4])),
+            field("x", Value::Null),
+            field("x", Value::Int32(1)),
+            field("x", Value::Int32(2)),
+            field("gone", Value::Bool(true)),
+        ]);
+        let b = Message::from(vec![
+            field("id", Value::Int32(1)),
+            field("user", Value::Message(vec![
+                field("age", Value::Int32(43)),
+                field("a.b", Value::Null),
+            ])),
+            field("tags", list(&[1, 5])),
+            field("x", Value::Null),
+            field("grid", Value::List(vec![list(&[1])])),
+        ]);
+        let c = Message::from(vec![field("grid", Value::List(vec![list(&[1, 2]), list(&[])]))]);
+        // новое поле перед старым и переставленные повторы ключа
+        let d = Message::from(vec![
+            field("new", Value::Null),
+            field("x", Value::Null),
+            field("user", Value::Message(vec![
+                field("email", Value::String("a@example.com".into())),
+                field("age", Value::Int32(42)),
+            ])),
+            field("id", Value::Int32(1)),
+            field("x", Value::Int32(1)),
+        ]);
+
+        let pairs = [(&a, &b), (&b, &a), (&b, &c), (&c, &a), (&a, &d), (&d, &a), (&d, &b)];
+        for (from, to) in pairs {
+            let patch = Patch::from(diff(from, to));
+            let patch = Patch::decode(&patch.encode().unwrap()).unwrap();
+            let mut msg = from.clone();
+            apply_patch(&mut msg, &patch).unwrap();
+            assert_eq!(&msg, to);
+        }
+        let mut msg = a.clone();
+        apply_patch(&mut msg, &Patch::default()).unwrap();
+        assert_eq!(msg, a);
+    }
+
+    #[test]
+    fn errors() {
+        let key = |key: &str| PathSegment::Key { key: key.into(), nth: 0 };
+        let mut msg = Message::from(vec![field("n", Value::Int32(1))]);
+        let patch = Patch {
+            ops: vec![
+                PatchOp::Set { path: FieldPath(vec![key("n")]), value: Value::Int32(2) },
+                PatchOp::Remove { path: FieldPath(vec![key("n"), key("x")]) },
+            ],
+        };
+        let err = apply_patch(&mut msg, &patch).unwrap_err();
+        let path = FieldPath(vec![key("n"), key("x")]);
+        assert_eq!(err, PatchError::PathNotFound { op: 1, path });
+        assert_eq!(err.to_string(), "операция 1 патча: нет пути `n.x`");
+        // частично применённый патч не виден
+        assert_eq!(msg.get("n"), Some(&Value::Int32(1)));
+
+        let reorder = |keys: &[&str]| Patch {
+            ops: vec![PatchOp::Reorder {
+                path: FieldPath::default(),
+                keys: keys.iter().map(|&k| k.to_owned()).collect(),
+            }],
+        };
+        assert!(apply_patch(&mut msg, &reorder(&["x"])).is_err());
+        assert!(apply_patch(&mut msg, &reorder(&["n", "n"])).is_err());
+        apply_patch(&mut msg, &reorder(&["n"])).unwrap();
+
+        let other = encode_message(&[field("set", Value::Int32(1))]).unwrap();
+        assert_eq!(Patch::decode(&other), Err(PatchError::Malformed));
+        assert!(matches!(Patch::decode(&[4, 0]), Err(PatchError::Decode(_))));
+    }
+}
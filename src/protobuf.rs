@@ -0,0 +1,496 @@
+//! Перевод сообщений в проводной формат protobuf и обратно
+//!
+//! Проводной формат protobuf не описывает сам себя, поэтому тег и тип
+//! каждого поля задаются схемой [`ProtoSchema`] — обычно по `.proto` файлу
+//! сервиса. Поля со значением `Value::Null` не записываются. Повторяемые
+//! числовые поля пишутся упакованными, как в proto3, а читаются в обоих
+//! видах. При чтении неизвестные теги пропускаются, а для неповторяемого
+//! поля, встретившегося несколько раз, остаётся последнее значение.
+//!
+//! ```
+//! use custom_codec::{decode_protobuf, encode_protobuf, Field, ProtoSchema, ProtoType, Value};
+//!
+//! let mut schema = ProtoSchema::new();
+//! schema.insert("id", 1, ProtoType::Int32);
+//! schema.insert("name", 2, ProtoType::String);
+//!
+//! let fields = vec![
+//!     Field { key: "id".into(), value: Value::Int32(150) },
+//!     Field { key: "name".into(), value: Value::String("Rust".into()) },
+//! ];
+//! let wire = encode_protobuf(&fields, &schema).unwrap();
+//! assert_eq!(wire[..3], [0x08, 0x96, 0x01]);
+//! assert_eq!(decode_protobuf(&wire, &schema).unwrap(), fields);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{utf8, varint, Decode, Field, Value};
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_FIXED64: u8 = 1;
+const WIRE_LEN: u8 = 2;
+const WIRE_FIXED32: u8 = 5;
+
+/// Тип поля protobuf
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtoType {
+    Int32,
+    Int64,
+    UInt32,
+    UInt64,
+    /// zig-zag
+    SInt32,
+    SInt64,
+    Bool,
+    Fixed32,
+    Fixed64,
+    SFixed32,
+    SFixed64,
+    Float,
+    Double,
+    String,
+    Bytes,
+    /// Вложенное сообщение со своей схемой
+    Message(ProtoSchema),
+    /// `repeated`: значение поля — `Value::List`; вложенный `Repeated`
+    /// не записывается и не читается
+    Repeated(Box<ProtoType>),
+}
+
+impl ProtoType {
+    fn wire_type(&self) -> u8 {
+        match self {
+            ProtoType::Int32
+            | ProtoType::Int64
+            | ProtoType::UInt32
+            | ProtoType::UInt64
+            | ProtoType::SInt32
+            | ProtoType::SInt64
+            | ProtoType::Bool => WIRE_VARINT,
+            ProtoType::Fixed64 | ProtoType::SFixed64 | ProtoType::Double => WIRE_FIXED64,
+            ProtoType::Fixed32 | ProtoType::SFixed32 | ProtoType::Float => WIRE_FIXED32,
+            ProtoType::String | ProtoType::Bytes | ProtoType::Message(_) => WIRE_LEN,
+            ProtoType::Repeated(_) => WIRE_LEN,
+        }
+    }
+
+    /// Повторяемые поля этого типа можно упаковать
+    fn packable(&self) -> bool {
+        !matches!(self.wire_type(), WIRE_LEN)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ProtoField {
+    key: String,
+    tag: u32,
+    ty: ProtoType,
+}
+
+/// Соответствие ключей полей тегам и типам protobuf
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProtoSchema {
+    fields: Vec<ProtoField>,
+    by_key: HashMap<String, usize>,
+    by_tag: HashMap<u32, usize>,
+}
+
+impl ProtoSchema {
+    pub fn new() -> Self {
+        ProtoSchema::default()
+    }
+
+    /// Поле `key` с тегом `tag`; прежние описания того же ключа или тега удаляются
+    pub fn insert(&mut self, key: &str, tag: u32, ty: ProtoType) {
+        self.fields.retain(|f| f.key != key && f.tag != tag);
+        self.fields.push(ProtoField { key: key.to_owned(), tag, ty });
+        self.by_key = self.fields.iter().enumerate().map(|(i, f)| (f.key.clone(), i)).collect();
+        self.by_tag = self.fields.iter().enumerate().map(|(i, f)| (f.tag, i)).collect();
+    }
+
+    pub fn tag(&self, key: &str) -> Option<u32> {
+        self.by_key.get(key).map(|&i| self.fields[i].tag)
+    }
+
+    pub fn key(&self, tag: u32) -> Option<&str> {
+        self.by_tag.get(&tag).map(|&i| self.fields[i].key.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    fn lookup_key(&self, key: &str) -> Option<&ProtoField> {
+        self.by_key.get(key).map(|&i| &self.fields[i])
+    }
+
+    fn lookup_tag(&self, tag: u32) -> Option<&ProtoField> {
+        self.by_tag.get(&tag).map(|&i| &self.fields[i])
+    }
+}
+
+/// Ошибка перевода в protobuf и обратно
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtoError {
+    /// Ключа нет в схеме
+    UnknownKey { key: String },
+    /// Значение поля `key` не соответствует его типу в схеме
+    InvalidValue { key: String, expected: &'static str },
+    /// Обрезанные или испорченные данные на смещении `offset`
+    Malformed { offset: usize },
+    /// Поле `key` записано с другим проводным типом
+    WireTypeMismatch { key: String, offset: usize },
+    InvalidUtf8 { offset: usize },
+}
+
+impl fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtoError::UnknownKey { key } => write!(f, "ключа {key:?} нет в схеме protobuf"),
+            ProtoError::InvalidValue { key, expected } => {
+                write!(f, "поле {key:?}: ожидался {expected}")
+            }
+            ProtoError::Malformed { offset } => {
+                write!(f, "испорченные данные protobuf на смещении {offset}")
+            }
+            ProtoError::WireTypeMismatch { key, offset } => {
+                write!(f, "поле {key:?} на смещении {offset}: другой проводной тип")
+            }
+            ProtoError::InvalidUtf8 { offset } => {
+                write!(f, "строка не в UTF-8 на смещении {offset}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProtoError {}
+
+/// Сообщение в проводном формате protobuf
+pub fn encode_protobuf(fields: &[Field], schema: &ProtoSchema) -> Result<Vec<u8>, ProtoError> {
+    let mut out = Vec::new();
+    write_message(&mut out, fields, schema)?;
+    Ok(out)
+}
+
+/// Сообщение из проводного формата protobuf
+pub fn decode_protobuf(data: &[u8], schema: &ProtoSchema) -> Result<Vec<Field>, ProtoError> {
+    read_message(data, 0, schema)
+}
+
+fn put_varint(out: &mut Vec<u8>, v: u64) {
+    varint::write(out, v).expect("запись в Vec не завершается ошибкой");
+}
+
+fn put_key(out: &mut Vec<u8>, tag: u32, wire_type: u8) {
+    put_varint(out, (tag as u64) << 3 | wire_type as u64);
+}
+
+fn write_message(
+    out: &mut Vec<u8>,
+    fields: &[Field],
+    schema: &ProtoSchema,
+) -> Result<(), ProtoError> {
+    for f in fields {
+        let field = schema.lookup_key(&f.key).ok_or_else(|| ProtoError::UnknownKey {
+            key: f.key.clone(),
+        })?;
+        if f.value == Value::Null {
+            continue;
+        }
+        match (&field.ty, &f.value) {
+            (ProtoType::Repeated(item), Value::List(items)) if item.packable() => {
+                let mut packed = Vec::new();
+                for v in items {
+                    write_scalar(&mut packed, &f.key, item, v)?;
+                }
+                put_key(out, field.tag, WIRE_LEN);
+                put_varint(out, packed.len() as u64);
+                out.extend_from_slice(&packed);
+            }
+            (ProtoType::Repeated(item), Value::List(items)) => {
+                for v in items {
+                    put_key(out, field.tag, item.wire_type());
+                    write_scalar(out, &f.key, item, v)?;
+                }
+            }
+            (ty, v) => {
+                put_key(out, field.tag, ty.wire_type());
+                write_scalar(out, &f.key, ty, v)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Значение без ключа; длина перед строками и сообщениями пишется здесь же
+fn write_scalar(out: &mut Vec<u8>, key: &str, ty: &ProtoType, v: &Value) -> Result<(), ProtoError> {
+    fn get<T: Decode>(key: &str, v: &Value, expected: &'static str) -> Result<T, ProtoError> {
+        T::decode_value(v).map_err(|_| ProtoError::InvalidValue { key: key.to_owned(), expected })
+    }
+    match ty {
+        // отрицательные int32 расширяются до 64 бит, как в protobuf
+        ProtoType::Int32 => put_varint(out, get::<i32>(key, v, "int32")? as i64 as u64),
+        ProtoType::Int64 => put_varint(out, get::<i64>(key, v, "int64")? as u64),
+        ProtoType::UInt32 => put_varint(out, get::<u32>(key, v, "uint32")? as u64),
+        ProtoType::UInt64 => put_varint(out, get::<u64>(key, v, "uint64")?),
+        ProtoType::SInt32 => put_varint(out, varint::zigzag(get::<i32>(key, v, "sint32")? as i64)),
+        ProtoType::SInt64 => put_varint(out, varint::zigzag(get::<i64>(key, v, "sint64")?)),
+        ProtoType::Bool => put_varint(out, get::<bool>(key, v, "bool")? as u64),
+        ProtoType::Fixed32 => out.extend_from_slice(&get::<u32>(key, v, "fixed32")?.to_le_bytes()),
+        ProtoType::Fixed64 => out.extend_from_slice(&get::<u64>(key, v, "fixed64")?.to_le_bytes()),
+        ProtoType::SFixed32 => {
+            out.extend_from_slice(&get::<i32>(key, v, "sfixed32")?.to_le_bytes())
+        }
+        ProtoType::SFixed64 => {
+            out.extend_from_slice(&get::<i64>(key, v, "sfixed64")?.to_le_bytes())
+        }
+        ProtoType::Float => out.extend_from_slice(&get::<f32>(key, v, "float")?.to_le_bytes()),
+        ProtoType::Double => out.extend_from_slice(&get::<f64>(key, v, "double")?.to_le_bytes()),
+        ProtoType::String | ProtoType::Bytes | ProtoType::Message(_) => {
+            let mut body = Vec::new();
+            match (ty, v) {
+                (ProtoType::String, Value::String(s)) => body.extend_from_slice(s.as_bytes()),
+                (ProtoType::Bytes, Value::Bytes(b)) => body.extend_from_slice(b),
+                (ProtoType::Message(schema), Value::Message(fields)) => {
+                    write_message(&mut body, fields, schema)?
+                }
+                _ => {
+                    let expected = match ty {
+                        ProtoType::String => "string",
+                        ProtoType::Bytes => "bytes",
+                        _ => "message",
+                    };
+                    return Err(ProtoError::InvalidValue { key: key.to_owned(), expected });
+                }
+            }
+            put_varint(out, body.len() as u64);
+            out.extend_from_slice(&body);
+        }
+        ProtoType::Repeated(_) => {
+            return Err(ProtoError::InvalidValue { key: key.to_owned(), expected: "repeated" })
+        }
+    }
+    Ok(())
+}
+
+/// Чтение из среза; `base` — смещение среза во всём входе
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+    base: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn offset(&self) -> usize {
+        self.base + self.pos
+    }
+
+    fn malformed(&self) -> ProtoError {
+        ProtoError::Malformed { offset: self.offset() }
+    }
+
+    fn varint(&mut self) -> Result<u64, ProtoError> {
+        let rest = &self.data[self.pos..];
+        let len = rest.iter().take(varint::MAX_LEN).position(|b| b & 0x80 == 0);
+        let v = len.and_then(|len| varint::decode(&rest[..=len]));
+        let v = v.ok_or_else(|| self.malformed())?;
+        self.pos += len.unwrap_or(0) + 1;
+        Ok(v)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ProtoError> {
+        if self.data.len() - self.pos < n {
+            return Err(self.malformed());
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ProtoError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn skip(&mut self, wire_type: u8) -> Result<(), ProtoError> {
+        match wire_type {
+            WIRE_VARINT => self.varint().map(drop),
+            WIRE_FIXED64 => self.take(8).map(drop),
+            WIRE_FIXED32 => self.take(4).map(drop),
+            WIRE_LEN => {
+                let len = self.varint()?;
+                self.take(usize::try_from(len).map_err(|_| self.malformed())?).map(drop)
+            }
+            _ => Err(self.malformed()),
+        }
+    }
+}
+
+fn read_message(data: &[u8], base: usize, schema: &ProtoSchema) -> Result<Vec<Field>, ProtoError> {
+    let mut cur = Cursor { data, pos: 0, base };
+    let mut fields: Vec<Field> = Vec::new();
+    while cur.pos < data.len() {
+        let start = cur.offset();
+        let key = cur.varint()?;
+        let tag = u32::try_from(key >> 3).map_err(|_| cur.malformed())?;
+        let wire_type = key as u8 & 7;
+        let Some(field) = schema.lookup_tag(tag) else {
+            cur.skip(wire_type)?;
+            continue;
+        };
+        let mismatch = || ProtoError::WireTypeMismatch { key: field.key.clone(), offset: start };
+        let slot = fields.iter().position(|f| f.key == field.key);
+        match &field.ty {
+            ProtoType::Repeated(item) => {
+                let mut values = Vec::new();
+                if wire_type == WIRE_LEN && item.packable() {
+                    let len = cur.varint()?;
+                    let body = cur.take(usize::try_from(len).map_err(|_| cur.malformed())?)?;
+                    let base = cur.offset() - body.len();
+                    let mut packed = Cursor { data: body, pos: 0, base };
+                    while packed.pos < body.len() {
+                        values.push(read_scalar(&mut packed, item)?);
+                    }
+                } else if wire_type == item.wire_type() {
+                    values.push(read_scalar(&mut cur, item)?);
+                } else {
+                    return Err(mismatch());
+                }
+                match slot.map(|i| &mut fields[i].value) {
+                    Some(Value::List(items)) => items.extend(values),
+                    _ => fields.push(Field { key: field.key.clone(), value: Value::List(values) }),
+                }
+            }
+            ty if wire_type == ty.wire_type() => {
+                let value = read_scalar(&mut cur, ty)?;
+                match slot {
+                    Some(i) => fields[i].value = value,
+                    None => fields.push(Field { key: field.key.clone(), value }),
+                }
+            }
+            _ => return Err(mismatch()),
+        }
+    }
+    Ok(fields)
+}
+
+fn read_scalar(cur: &mut Cursor<'_>, ty: &ProtoType) -> Result<Value, ProtoError> {
+    Ok(match ty {
+        ProtoType::Int32 => Value::Int32(cur.varint()? as i32),
+        ProtoType::Int64 => Value::Int64(cur.varint()? as i64),
+        ProtoType::UInt32 => Value::UInt32(cur.varint()? as u32),
+        ProtoType::UInt64 => Value::UInt64(cur.varint()?),
+        ProtoType::SInt32 => Value::Int32(varint::unzigzag(cur.varint()?) as i32),
+        ProtoType::SInt64 => Value::Int64(varint::unzigzag(cur.varint()?)),
+        ProtoType::Bool => Value::Bool(cur.varint()? != 0),
+        ProtoType::Fixed32 => Value::UInt32(u32::from_le_bytes(cur.array()?)),
+        ProtoType::Fixed64 => Value::UInt64(u64::from_le_bytes(cur.array()?)),
+        ProtoType::SFixed32 => Value::Int32(i32::from_le_bytes(cur.array()?)),
+        ProtoType::SFixed64 => Value::Int64(i64::from_le_bytes(cur.array()?)),
+        ProtoType::Float => Value::Float32(f32::from_le_bytes(cur.array()?)),
+        ProtoType::Double => Value::Float64(f64::from_le_bytes(cur.array()?)),
+        ProtoType::String | ProtoType::Bytes | ProtoType::Message(_) => {
+            let len = cur.varint()?;
+            let offset = cur.offset();
+            let body = cur.take(usize::try_from(len).map_err(|_| cur.malformed())?)?;
+            match ty {
+                ProtoType::String => match utf8::str(body) {
+                    Some(s) => Value::String(s.to_owned()),
+                    None => return Err(ProtoError::InvalidUtf8 { offset }),
+                },
+                ProtoType::Bytes => Value::Bytes(body.to_vec()),
+                ProtoType::Message(schema) => Value::Message(read_message(body, offset, schema)?),
+                _ => unreachable!(),
+            }
+        }
+        // у protobuf нет повторяемых элементов повторяемого поля
+        ProtoType::Repeated(_) => return Err(cur.malformed()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    fn schema() -> ProtoSchema {
+        let mut inner = ProtoSchema::new();
+        inner.insert("ok", 1, ProtoType::Bool);
+        let mut schema = ProtoSchema::new();
+        schema.insert("id", 1, ProtoType::Int32);
+        schema.insert("delta", 2, ProtoType::SInt64);
+        schema.insert("name", 3, ProtoType::String);
+        schema.insert("blob", 4, ProtoType::Bytes);
+        schema.insert("ratio", 5, ProtoType::Double);
+        schema.insert("crc", 6, ProtoType::Fixed32);
+        schema.insert("inner", 7, ProtoType::Message(inner.clone()));
+        schema.insert("ids", 8, ProtoType::Repeated(Box::new(ProtoType::UInt64)));
+        schema.insert("names", 9, ProtoType::Repeated(Box::new(ProtoType::String)));
+        schema.insert("items", 10, ProtoType::Repeated(Box::new(ProtoType::Message(inner))));
+        schema
+    }
+
+    #[test]
+    fn protobuf_roundtrip() {
+        let ok = |b| Value::Message(vec![field("ok", Value::Bool(b))]);
+        let fields = vec![
+            field("id", Value::Int32(-1)),
+            field("delta", Value::Int64(-2)),
+            field("name", Value::String("Rust".into())),
+            field("blob", Value::Bytes(vec![0, 1])),
+            field("ratio", Value::Float64(0.5)),
+            field("crc", Value::UInt32(0xdead_beef)),
+            field("inner", ok(true)),
+            field("ids", Value::List(vec![Value::UInt64(1), Value::UInt64(300)])),
+            field("names", Value::List(vec![Value::String("a".into()), Value::String("b".into())])),
+            field("items", Value::List(vec![ok(true), ok(false)])),
+        ];
+        let wire = encode_protobuf(&fields, &schema()).unwrap();
+        assert_eq!(decode_protobuf(&wire, &schema()).unwrap(), fields);
+
+        // отрицательный int32 — десять байт, как у protoc
+        assert_eq!(wire[..11], [0x08, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        // упакованный repeated: тег 8, длина 3, значения 1 и 300
+        let packed = [0x42, 0x03, 0x01, 0xac, 0x02];
+        assert!(wire.windows(5).any(|w| w == packed));
+
+        // неупакованный repeated, неизвестный тег и повтор неповторяемого поля
+        let wire = [0x40, 0x01, 0x40, 0x02, 0x58, 0x05, 0x08, 0x01, 0x08, 0x02];
+        let expected = vec![
+            field("ids", Value::List(vec![Value::UInt64(1), Value::UInt64(2)])),
+            field("id", Value::Int32(2)),
+        ];
+        assert_eq!(decode_protobuf(&wire, &schema()).unwrap(), expected);
+    }
+
+    #[test]
+    fn protobuf_errors() {
+        let unknown = [field("nope", Value::Null)];
+        let err = ProtoError::UnknownKey { key: "nope".into() };
+        assert_eq!(encode_protobuf(&unknown, &schema()), Err(err));
+        let wrong = [field("name", Value::Int32(1))];
+        let err = ProtoError::InvalidValue { key: "name".into(), expected: "string" };
+        assert_eq!(encode_protobuf(&wrong, &schema()), Err(err));
+        let out_of_range = [field("id", Value::Int64(i64::MAX))];
+        assert!(matches!(encode_protobuf(&out_of_range, &schema()), Err(ProtoError::InvalidValue {
+            ..
+        })));
+
+        assert_eq!(decode_protobuf(&[0x1a, 0x05, b'a'], &schema()), Err(ProtoError::Malformed {
+            offset: 2
+        }));
+        let err = ProtoError::WireTypeMismatch { key: "id".into(), offset: 0 };
+        assert_eq!(decode_protobuf(&[0x0d, 0, 0, 0, 0], &schema()), Err(err));
+        assert_eq!(decode_protobuf(&[0x1a, 0x01, 0xff], &schema()), Err(ProtoError::InvalidUtf8 {
+            offset: 2
+        }));
+    }
+}
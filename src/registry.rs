@@ -0,0 +1,121 @@
+//! Реестр схем: конверт несёт `SchemaId`, схему даёт `SchemaResolver`
+//!
+//! Отправитель записывает идентификатор схемы в заголовок конверта через
+//! `EncodeOptions::schema_id`, получатель находит по нему схему в общем
+//! реестре, как в реестре схем Kafka. Номер схемы — из реестра или
+//! `Schema::fingerprint`, если реестра нет.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+
+use crate::{decode_enveloped, envelope_schema_id, Field, Schema, SchemaError, SchemaId};
+
+/// Источник схем по идентификатору из конверта
+pub trait SchemaResolver {
+    /// Схема с номером и версией `id`; `None`, если реестр её не знает
+    fn resolve(&self, id: SchemaId) -> Option<&Schema>;
+}
+
+impl<S: BuildHasher> SchemaResolver for HashMap<SchemaId, Schema, S> {
+    fn resolve(&self, id: SchemaId) -> Option<&Schema> {
+        self.get(&id)
+    }
+}
+
+impl SchemaResolver for BTreeMap<SchemaId, Schema> {
+    fn resolve(&self, id: SchemaId) -> Option<&Schema> {
+        self.get(&id)
+    }
+}
+
+impl<R: SchemaResolver + ?Sized> SchemaResolver for &R {
+    fn resolve(&self, id: SchemaId) -> Option<&Schema> {
+        (**self).resolve(id)
+    }
+}
+
+/// Сообщение из конверта с проверкой по схеме из реестра
+///
+/// Поля дополняются значениями по умолчанию и проверяются, как в
+/// `decode_with_schema`. Конверт без идентификатора схемы даёт
+/// `SchemaError::NoSchemaId`, неизвестный реестру —
+/// `SchemaError::UnknownSchema`.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use custom_codec::{
+///     decode_resolved, encode_enveloped_with, EncodeOptions, Field, Schema, SchemaId,
+///     SchemaType, Value,
+/// };
+///
+/// let mut schema = Schema::named("Point");
+/// schema.required("x", SchemaType::Int32);
+/// let id = SchemaId { id: schema.fingerprint(), version: 1 };
+/// let registry = HashMap::from([(id, schema)]);
+///
+/// let fields = vec![Field { key: "x".into(), value: Value::Int32(3) }];
+/// let opts = EncodeOptions { schema_id: Some(id), ..EncodeOptions::default() };
+/// let data = encode_enveloped_with(&fields, &opts).unwrap();
+/// assert_eq!(decode_resolved(&data, &registry).unwrap(), (id, fields));
+/// ```
+pub fn decode_resolved(
+    data: &[u8],
+    resolver: &impl SchemaResolver,
+) -> Result<(SchemaId, Vec<Field>), SchemaError> {
+    let id = envelope_schema_id(data)?.ok_or(SchemaError::NoSchemaId)?;
+    let schema = resolver.resolve(id).ok_or(SchemaError::UnknownSchema { id })?;
+    let mut fields = decode_enveloped(data)?;
+    schema.apply_defaults(&mut fields);
+    schema.validate_message(&fields).map_err(SchemaError::Invalid)?;
+    Ok((id, fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode_enveloped, encode_enveloped_with, EncodeOptions, SchemaType, Value};
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    fn registry() -> BTreeMap<SchemaId, Schema> {
+        let mut v1 = Schema::new();
+        v1.required("id", SchemaType::Int64);
+        let mut v2 = v1.clone();
+        v2.optional("name", SchemaType::String);
+        BTreeMap::from([
+            (SchemaId { id: 7, version: 1 }, v1),
+            (SchemaId { id: 7, version: 2 }, v2),
+        ])
+    }
+
+    fn enveloped(fields: &[Field], id: SchemaId) -> Vec<u8> {
+        let opts = EncodeOptions { schema_id: Some(id), ..EncodeOptions::default() };
+        encode_enveloped_with(fields, &opts).unwrap()
+    }
+
+    #[test]
+    fn resolves_by_version() {
+        let fields = vec![field("id", Value::Int64(1)), field("name", Value::String("x".into()))];
+        let v2 = SchemaId { id: 7, version: 2 };
+        assert_eq!(decode_resolved(&enveloped(&fields, v2), &registry()), Ok((v2, fields.clone())));
+
+        // в первой версии поля `name` нет
+        let v1 = SchemaId { id: 7, version: 1 };
+        let err = decode_resolved(&enveloped(&fields, v1), &registry()).unwrap_err();
+        assert!(matches!(err, SchemaError::Invalid(_)));
+    }
+
+    #[test]
+    fn resolve_errors() {
+        let fields = vec![field("id", Value::Int64(1))];
+        let unknown = SchemaId { id: 8, version: 1 };
+        let data = enveloped(&fields, unknown);
+        assert_eq!(decode_resolved(&data, &registry()), Err(SchemaError::UnknownSchema {
+            id: unknown
+        }));
+        let plain = encode_enveloped(&fields).unwrap();
+        assert_eq!(decode_resolved(&plain, &registry()), Err(SchemaError::NoSchemaId));
+    }
+}
@@ -0,0 +1,712 @@
+//! Схемы сообщений и проверка по ним
+//!
+//! [`Schema`] перечисляет поля сообщения: ключ, ожидаемый тип и
+//! обязательность. Типы сравниваются точно: `Int32` не подходит полю
+//! `Int64`. Обязательное поле должно присутствовать и не быть `Null`,
+//! необязательное может отсутствовать или быть `Null`. Поля, которых нет в
+//! схеме, по умолчанию считаются ошибкой.
+//!
+//! Проверка не останавливается на первой ошибке и возвращает все
+//! нарушения с путями вида `probe.tags[2]`.
+//!
+//! Для постепенной смены схемы у поля есть значение по умолчанию и пометка
+//! устаревшего. `decode_with_schema` подставляет значение по умолчанию
+//! вместо отсутствующего поля или `Null` до проверки, поэтому новое поле
+//! с ним можно сделать обязательным, не ломая старых отправителей.
+//! `encode_with_schema` сообщает о каждом записываемом устаревшем поле.
+//! `encode_checked` проверяет сообщение до кодирования, чтобы ошибку
+//! увидел отправитель, а не получатель.
+//!
+//! ```
+//! use custom_codec::{Field, Schema, SchemaType, Value, ViolationKind};
+//!
+//! let mut schema = Schema::new();
+//! schema.required("id", SchemaType::Int64);
+//! schema.optional("name", SchemaType::String);
+//!
+//! let ok = vec![Field { key: "id".into(), value: Value::Int64(1) }];
+//! assert!(schema.validate_message(&ok).is_ok());
+//!
+//! let bad = vec![Field { key: "name".into(), value: Value::Int32(1) }];
+//! let errors = schema.validate_message(&bad).unwrap_err();
+//! assert_eq!(errors[0].path, "name");
+//! assert_eq!(errors[1].kind, ViolationKind::Missing);
+//! ```
+
+use std::fmt;
+
+use crate::typed::type_name;
+use crate::{
+    decode_message, encode_message, DecodeError, EncodeError, Field, SchemaId, Value,
+    ValueError,
+};
+
+/// Ожидаемый тип значения
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaType {
+    /// Любое значение
+    Any,
+    Bool,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Float32,
+    Float64,
+    String,
+    Bytes,
+    Timestamp,
+    Uuid,
+    Decimal,
+    /// Вариант перечисления с любым содержимым
+    Enum,
+    /// Вложенное сообщение со своей схемой
+    Message(Schema),
+    /// Список из элементов одного типа
+    List(Box<SchemaType>),
+    /// Отображение с ключами и значениями заданных типов
+    Map(Box<SchemaType>, Box<SchemaType>),
+}
+
+impl fmt::Display for SchemaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SchemaType::Any => "Any",
+            SchemaType::Bool => "Bool",
+            SchemaType::Int8 => "Int8",
+            SchemaType::Int16 => "Int16",
+            SchemaType::Int32 => "Int32",
+            SchemaType::Int64 => "Int64",
+            SchemaType::UInt8 => "UInt8",
+            SchemaType::UInt16 => "UInt16",
+            SchemaType::UInt32 => "UInt32",
+            SchemaType::UInt64 => "UInt64",
+            SchemaType::Float32 => "Float32",
+            SchemaType::Float64 => "Float64",
+            SchemaType::String => "String",
+            SchemaType::Bytes => "Bytes",
+            SchemaType::Timestamp => "Timestamp",
+            SchemaType::Uuid => "Uuid",
+            SchemaType::Decimal => "Decimal",
+            SchemaType::Enum => "Enum",
+            SchemaType::Message(schema) => schema.name().unwrap_or("Message"),
+            SchemaType::List(item) => return write!(f, "List<{item}>"),
+            SchemaType::Map(k, v) => return write!(f, "Map<{k}, {v}>"),
+        };
+        f.write_str(name)
+    }
+}
+
+impl SchemaType {
+    /// Подходит ли вариант значения, без проверки вложенных значений
+    fn admits(&self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (SchemaType::Any, _)
+                | (SchemaType::Bool, Value::Bool(_))
+                | (SchemaType::Int8, Value::Int8(_))
+                | (SchemaType::Int16, Value::Int16(_))
+                | (SchemaType::Int32, Value::Int32(_))
+                | (SchemaType::Int64, Value::Int64(_))
+                | (SchemaType::UInt8, Value::UInt8(_))
+                | (SchemaType::UInt16, Value::UInt16(_))
+                | (SchemaType::UInt32, Value::UInt32(_))
+                | (SchemaType::UInt64, Value::UInt64(_))
+                | (SchemaType::Float32, Value::Float32(_))
+                | (SchemaType::Float64, Value::Float64(_))
+                | (SchemaType::String, Value::String(_))
+                | (SchemaType::Bytes, Value::Bytes(_))
+                | (SchemaType::Timestamp, Value::Timestamp(_))
+                | (SchemaType::Uuid, Value::Uuid(_))
+                | (SchemaType::Decimal, Value::Decimal(_))
+                | (SchemaType::Enum, Value::Enum { .. })
+                | (SchemaType::Message(_), Value::Message(_))
+                | (SchemaType::List(_), Value::List(_))
+                | (SchemaType::Map(..), Value::Map(_))
+        )
+    }
+}
+
+/// Описание одного поля схемы
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaField {
+    pub key: String,
+    pub ty: SchemaType,
+    pub required: bool,
+    /// Значение вместо отсутствующего поля или `Null`
+    pub default: Option<Value>,
+    /// Поле оставлено для старых получателей, новым записывать его не нужно
+    pub deprecated: bool,
+}
+
+impl SchemaField {
+    pub fn required(key: &str, ty: SchemaType) -> Self {
+        SchemaField { key: key.to_owned(), ty, required: true, default: None, deprecated: false }
+    }
+
+    pub fn optional(key: &str, ty: SchemaType) -> Self {
+        SchemaField { required: false, ..SchemaField::required(key, ty) }
+    }
+
+    /// Поле со значением по умолчанию
+    ///
+    /// Паникует, если значение не подходит к типу поля: иначе каждое
+    /// сообщение, получившее его, не прошло бы проверку.
+    pub fn with_default(self, value: Value) -> Self {
+        if let Some(v) = default_violations(&self.key, &self.ty, &value).first() {
+            panic!("значение по умолчанию не подходит к типу: {v}");
+        }
+        SchemaField { default: Some(value), ..self }
+    }
+
+    /// Поле с пометкой устаревшего
+    pub fn deprecate(self) -> Self {
+        SchemaField { deprecated: true, ..self }
+    }
+}
+
+/// Поля сообщения с типами
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    name: Option<String>,
+    fields: Vec<SchemaField>,
+    allow_unknown: bool,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Schema::default()
+    }
+
+    /// Схема с именем типа сообщения, как в DSL схем
+    pub fn named(name: &str) -> Self {
+        Schema { name: Some(name.to_owned()), ..Schema::default() }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Поле `key`; прежнее описание того же ключа заменяется
+    pub fn insert(&mut self, field: SchemaField) {
+        match self.fields.iter_mut().find(|f| f.key == field.key) {
+            Some(old) => *old = field,
+            None => self.fields.push(field),
+        }
+    }
+
+    /// Обязательное поле `key` типа `ty`
+    pub fn required(&mut self, key: &str, ty: SchemaType) {
+        self.insert(SchemaField::required(key, ty));
+    }
+
+    /// Необязательное поле `key` типа `ty`
+    pub fn optional(&mut self, key: &str, ty: SchemaType) {
+        self.insert(SchemaField::optional(key, ty));
+    }
+
+    /// Допускать ли поля, которых нет в схеме; по умолчанию нет
+    pub fn set_allow_unknown(&mut self, allow: bool) {
+        self.allow_unknown = allow;
+    }
+
+    pub fn allow_unknown(&self) -> bool {
+        self.allow_unknown
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SchemaField> {
+        self.fields.iter().find(|f| f.key == key)
+    }
+
+    /// Поля в порядке добавления
+    pub fn fields(&self) -> &[SchemaField] {
+        &self.fields
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Проверка поля, значение которого — сообщение этой схемы; пути
+    /// начинаются с ключа поля
+    pub fn validate(&self, field: &Field) -> Result<(), Vec<Violation>> {
+        let mut out = Vec::new();
+        match &field.value {
+            Value::Message(fields) => self.check_fields(fields, &field.key, &mut out),
+            other => out.push(Violation {
+                path: field.key.clone(),
+                kind: ViolationKind::TypeMismatch {
+                    expected: "Message".into(),
+                    found: type_name(other),
+                },
+            }),
+        }
+        finish(out)
+    }
+
+    /// Проверка полей сообщения
+    pub fn validate_message(&self, fields: &[Field]) -> Result<(), Vec<Violation>> {
+        let mut out = Vec::new();
+        self.check_fields(fields, "", &mut out);
+        finish(out)
+    }
+
+    /// Подстановка значений по умолчанию, в том числе во вложенные сообщения
+    pub fn apply_defaults(&self, fields: &mut Vec<Field>) {
+        for f in fields.iter_mut() {
+            if let Some(spec) = self.get(&f.key) {
+                fill_defaults(&spec.ty, &mut f.value);
+            }
+        }
+        for spec in &self.fields {
+            let Some(default) = &spec.default else { continue };
+            match fields.iter_mut().find(|f| f.key == spec.key) {
+                Some(f) if f.value == Value::Null => f.value = default.clone(),
+                Some(_) => {}
+                None => fields.push(Field { key: spec.key.clone(), value: default.clone() }),
+            }
+        }
+    }
+
+    /// Устаревшие поля в сообщении, но не `Null`; вид нарушения — `Deprecated`
+    pub fn deprecations(&self, fields: &[Field]) -> Vec<Violation> {
+        let mut out = Vec::new();
+        self.find_deprecated(fields, "", &mut out);
+        out
+    }
+
+    fn find_deprecated(&self, fields: &[Field], path: &str, out: &mut Vec<Violation>) {
+        for f in fields.iter().filter(|f| f.value != Value::Null) {
+            let Some(spec) = self.get(&f.key) else { continue };
+            let path = join(path, &f.key);
+            if spec.deprecated {
+                out.push(Violation { path: path.clone(), kind: ViolationKind::Deprecated });
+            }
+            deprecated_in(&spec.ty, &f.value, &path, out);
+        }
+    }
+
+    fn check_fields(&self, fields: &[Field], path: &str, out: &mut Vec<Violation>) {
+        let join = |key: &str| join(path, key);
+        for (i, f) in fields.iter().enumerate() {
+            let path = join(&f.key);
+            if fields[..i].iter().any(|g| g.key == f.key) {
+                out.push(Violation { path, kind: ViolationKind::Duplicate });
+                continue;
+            }
+            match self.get(&f.key) {
+                Some(_) if f.value == Value::Null => {}
+                Some(spec) => check(&spec.ty, &f.value, &path, out),
+                None if self.allow_unknown => {}
+                None => out.push(Violation { path, kind: ViolationKind::Unknown }),
+            }
+        }
+        for spec in self.fields.iter().filter(|s| s.required) {
+            let present = fields.iter().any(|f| f.key == spec.key && f.value != Value::Null);
+            if !present {
+                out.push(Violation { path: join(&spec.key), kind: ViolationKind::Missing });
+            }
+        }
+    }
+}
+
+/// Путь поля `key` внутри `path`
+fn join(path: &str, key: &str) -> String {
+    match path {
+        "" => key.to_owned(),
+        _ => format!("{path}.{key}"),
+    }
+}
+
+fn fill_defaults(ty: &SchemaType, value: &mut Value) {
+    match (ty, value) {
+        (SchemaType::Message(schema), Value::Message(fields)) => schema.apply_defaults(fields),
+        (SchemaType::List(item), Value::List(items)) => {
+            items.iter_mut().for_each(|v| fill_defaults(item, v))
+        }
+        (SchemaType::Map(k, v), Value::Map(entries)) => {
+            for (key, value) in entries {
+                fill_defaults(k, key);
+                fill_defaults(v, value);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn deprecated_in(ty: &SchemaType, value: &Value, path: &str, out: &mut Vec<Violation>) {
+    match (ty, value) {
+        (SchemaType::Message(schema), Value::Message(fields)) => {
+            schema.find_deprecated(fields, path, out)
+        }
+        (SchemaType::List(item), Value::List(items)) => {
+            for (i, v) in items.iter().enumerate() {
+                deprecated_in(item, v, &format!("{path}[{i}]"), out);
+            }
+        }
+        (SchemaType::Map(k, v), Value::Map(entries)) => {
+            for (i, (key, value)) in entries.iter().enumerate() {
+                deprecated_in(k, key, &format!("{path}[{i}].key"), out);
+                deprecated_in(v, value, &format!("{path}[{i}].value"), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn finish(out: Vec<Violation>) -> Result<(), Vec<Violation>> {
+    if out.is_empty() {
+        Ok(())
+    } else {
+        Err(out)
+    }
+}
+
+/// Нарушения типа `ty` значением по умолчанию поля `key`
+pub(crate) fn default_violations(key: &str, ty: &SchemaType, value: &Value) -> Vec<Violation> {
+    let mut out = Vec::new();
+    check(ty, value, key, &mut out);
+    out
+}
+
+fn check(ty: &SchemaType, value: &Value, path: &str, out: &mut Vec<Violation>) {
+    if !ty.admits(value) {
+        let expected = ty.to_string();
+        let kind = ViolationKind::TypeMismatch { expected, found: type_name(value) };
+        out.push(Violation { path: path.to_owned(), kind });
+        return;
+    }
+    match (ty, value) {
+        (SchemaType::Message(schema), Value::Message(fields)) => {
+            schema.check_fields(fields, path, out)
+        }
+        (SchemaType::List(item), Value::List(items)) => {
+            for (i, v) in items.iter().enumerate() {
+                check(item, v, &format!("{path}[{i}]"), out);
+            }
+        }
+        (SchemaType::Map(k, v), Value::Map(entries)) => {
+            for (i, (key, value)) in entries.iter().enumerate() {
+                let path = format!("{path}[{i}]");
+                check(k, key, &format!("{path}.key"), out);
+                check(v, value, &format!("{path}.value"), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Нарушение схемы в значении по пути `path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Ключи через точку и номера элементов в скобках: `probe.tags[2]`
+    pub path: String,
+    pub kind: ViolationKind,
+}
+
+/// Вид нарушения схемы
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// Нет обязательного поля или оно `Null`
+    Missing,
+    /// Поля нет в схеме
+    Unknown,
+    /// Ключ встречается в сообщении повторно
+    Duplicate,
+    /// Значение другого типа; `expected` — тип из схемы
+    TypeMismatch { expected: String, found: &'static str },
+    /// Поле устарело; не ошибка, а предупреждение `Schema::deprecations`
+    Deprecated,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = &self.path;
+        match &self.kind {
+            ViolationKind::Missing => write!(f, "{path}: нет обязательного поля"),
+            ViolationKind::Unknown => write!(f, "{path}: поля нет в схеме"),
+            ViolationKind::Duplicate => write!(f, "{path}: ключ повторяется"),
+            ViolationKind::Deprecated => write!(f, "{path}: поле устарело"),
+            ViolationKind::TypeMismatch { expected, found } => {
+                write!(f, "{path}: ожидалось {expected}, получено {found}")
+            }
+        }
+    }
+}
+
+/// Сообщение не декодируется или не соответствует схеме
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    Decode(DecodeError),
+    /// Все найденные нарушения схемы
+    Invalid(Vec<Violation>),
+    /// Дескриптор самоописывающего сообщения не описывает схему
+    Descriptor(ValueError),
+    /// В заголовке конверта нет идентификатора схемы
+    NoSchemaId,
+    /// Реестр не знает схему из заголовка конверта
+    UnknownSchema { id: SchemaId },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Decode(e) => e.fmt(f),
+            SchemaError::Invalid(violations) => {
+                f.write_str("сообщение не соответствует схеме")?;
+                for v in violations {
+                    write!(f, "; {v}")?;
+                }
+                Ok(())
+            }
+            SchemaError::Descriptor(e) => write!(f, "дескриптор схемы: {e}"),
+            SchemaError::NoSchemaId => f.write_str("в конверте нет идентификатора схемы"),
+            SchemaError::UnknownSchema { id } => write!(f, "схема {id} не найдена"),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SchemaError::Decode(e) => Some(e),
+            SchemaError::Invalid(_) | SchemaError::NoSchemaId => None,
+            SchemaError::UnknownSchema { .. } => None,
+            SchemaError::Descriptor(e) => Some(e),
+        }
+    }
+}
+
+impl From<DecodeError> for SchemaError {
+    fn from(e: DecodeError) -> Self {
+        SchemaError::Decode(e)
+    }
+}
+
+/// Декодирование сообщения с подстановкой значений по умолчанию и
+/// проверкой по схеме
+pub fn decode_with_schema(data: &[u8], schema: &Schema) -> Result<Vec<Field>, SchemaError> {
+    let mut fields = decode_message(data)?;
+    schema.apply_defaults(&mut fields);
+    schema.validate_message(&fields).map_err(SchemaError::Invalid)?;
+    Ok(fields)
+}
+
+/// Кодирование сообщения с вызовом `warn` для каждого устаревшего поля
+///
+/// ```
+/// use custom_codec::{encode_with_schema, Field, Schema, SchemaField, SchemaType, Value};
+///
+/// let mut schema = Schema::new();
+/// schema.insert(SchemaField::optional("login", SchemaType::String).deprecate());
+///
+/// let fields = vec![Field { key: "login".into(), value: Value::String("bob".into()) }];
+/// let mut warnings = Vec::new();
+/// encode_with_schema(&fields, &schema, |v| warnings.push(v.to_string())).unwrap();
+/// assert_eq!(warnings, ["login: поле устарело"]);
+/// ```
+pub fn encode_with_schema(
+    fields: &[Field],
+    schema: &Schema,
+    mut warn: impl FnMut(&Violation),
+) -> Result<Vec<u8>, EncodeError> {
+    schema.deprecations(fields).iter().for_each(&mut warn);
+    encode_message(fields)
+}
+
+/// Сообщение не прошло проверку перед кодированием или не кодируется
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckedEncodeError {
+    /// Все найденные нарушения схемы
+    Invalid(Vec<Violation>),
+    Encode(EncodeError),
+}
+
+impl fmt::Display for CheckedEncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckedEncodeError::Invalid(violations) => {
+                f.write_str("сообщение не соответствует схеме")?;
+                for v in violations {
+                    write!(f, "; {v}")?;
+                }
+                Ok(())
+            }
+            CheckedEncodeError::Encode(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CheckedEncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CheckedEncodeError::Invalid(_) => None,
+            CheckedEncodeError::Encode(e) => Some(e),
+        }
+    }
+}
+
+impl From<EncodeError> for CheckedEncodeError {
+    fn from(e: EncodeError) -> Self {
+        CheckedEncodeError::Encode(e)
+    }
+}
+
+/// Кодирование сообщения после проверки по схеме
+///
+/// Значения по умолчанию не подставляются: обязательное поле должно быть
+/// в сообщении, иначе получится `ViolationKind::Missing`.
+///
+/// ```
+/// use custom_codec::{encode_checked, CheckedEncodeError, Field, Schema, SchemaType, Value};
+///
+/// let mut schema = Schema::new();
+/// schema.required("id", SchemaType::Int64);
+///
+/// let bad = vec![Field { key: "id".into(), value: Value::Int32(1) }];
+/// let Err(CheckedEncodeError::Invalid(errors)) = encode_checked(&bad, &schema) else {
+///     panic!("сообщение не проверено");
+/// };
+/// assert_eq!(errors[0].to_string(), "id: ожидалось Int64, получено Int32");
+/// ```
+pub fn encode_checked(fields: &[Field], schema: &Schema) -> Result<Vec<u8>, CheckedEncodeError> {
+    schema.validate_message(fields).map_err(CheckedEncodeError::Invalid)?;
+    Ok(encode_message(fields)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    fn probe_schema() -> Schema {
+        let mut inner = Schema::new();
+        inner.required("ok", SchemaType::Bool);
+        let mut schema = Schema::new();
+        schema.required("id", SchemaType::Int64);
+        schema.optional("tags", SchemaType::List(Box::new(SchemaType::String)));
+        schema.optional("inner", SchemaType::Message(inner));
+        schema
+    }
+
+    #[test]
+    fn validate_collects_violations() {
+        let schema = probe_schema();
+        let good = field(
+            "probe",
+            Value::Message(vec![
+                field("id", Value::Int64(1)),
+                field("tags", Value::List(vec![Value::String("a".into())])),
+                field("inner", Value::Null),
+            ]),
+        );
+        assert_eq!(schema.validate(&good), Ok(()));
+
+        let bad = field(
+            "probe",
+            Value::Message(vec![
+                field("tags", Value::List(vec![Value::String("a".into()), Value::Int32(2)])),
+                field("inner", Value::Message(vec![field("extra", Value::Null)])),
+                field("tags", Value::Null),
+            ]),
+        );
+        let violation = |path: &str, kind| Violation { path: path.into(), kind };
+        let mismatch = |expected: &str, found| ViolationKind::TypeMismatch {
+            expected: expected.into(),
+            found,
+        };
+        assert_eq!(
+            schema.validate(&bad).unwrap_err(),
+            [
+                violation("probe.tags[1]", mismatch("String", "Int32")),
+                violation("probe.inner.extra", ViolationKind::Unknown),
+                violation("probe.inner.ok", ViolationKind::Missing),
+                violation("probe.tags", ViolationKind::Duplicate),
+                violation("probe.id", ViolationKind::Missing),
+            ]
+        );
+        let not_message = schema.validate(&field("probe", Value::Int64(1))).unwrap_err();
+        assert_eq!(not_message, [violation("probe", mismatch("Message", "Int64"))]);
+    }
+
+    #[test]
+    fn decode_with_schema_checks_message() {
+        let mut schema = probe_schema();
+        let fields = [field("id", Value::Int64(1)), field("x", Value::Null)];
+        let data = encode_message(&fields).unwrap();
+        assert!(matches!(decode_with_schema(&data, &schema), Err(SchemaError::Invalid(_))));
+        schema.set_allow_unknown(true);
+        assert_eq!(decode_with_schema(&data, &schema).unwrap().len(), 2);
+        assert!(matches!(decode_with_schema(&data[..3], &schema), Err(SchemaError::Decode(_))));
+    }
+
+    #[test]
+    fn defaults_and_deprecations() {
+        let mut inner = Schema::new();
+        inner.insert(SchemaField::required("ok", SchemaType::Bool).with_default(Value::Bool(true)));
+        inner.insert(SchemaField::optional("old", SchemaType::Int32).deprecate());
+        let mut schema = Schema::new();
+        schema.insert(SchemaField::required("lang", SchemaType::String).with_default(
+            Value::String("ru".into()),
+        ));
+        schema.optional("items", SchemaType::List(Box::new(SchemaType::Message(inner))));
+
+        let items = Value::List(vec![
+            Value::Message(vec![field("old", Value::Int32(1))]),
+            Value::Message(vec![field("ok", Value::Bool(false)), field("old", Value::Null)]),
+        ]);
+        let fields = vec![field("items", items)];
+        let data = encode_message(&fields).unwrap();
+        let decoded = decode_with_schema(&data, &schema).unwrap();
+        let items = Value::List(vec![
+            Value::Message(vec![field("old", Value::Int32(1)), field("ok", Value::Bool(true))]),
+            Value::Message(vec![field("ok", Value::Bool(false)), field("old", Value::Null)]),
+        ]);
+        assert_eq!(decoded, [field("items", items), field("lang", Value::String("ru".into()))]);
+
+        let mut warnings = Vec::new();
+        let encoded = encode_with_schema(&fields, &schema, |v| warnings.push(v.clone())).unwrap();
+        assert_eq!(encoded, data);
+        let deprecated = Violation { path: "items[0].old".into(), kind: ViolationKind::Deprecated };
+        assert_eq!(warnings, [deprecated]);
+    }
+
+    #[test]
+    #[should_panic(expected = "значение по умолчанию не подходит к типу: n: ожидалось Int64")]
+    fn default_must_match_type() {
+        let _ = SchemaField::required("n", SchemaType::Int64).with_default(Value::Int32(1));
+    }
+
+    #[test]
+    fn encode_checked_rejects_before_encoding() {
+        let schema = probe_schema();
+        let good = [field("id", Value::Int64(1)), field("tags", Value::Null)];
+        assert_eq!(encode_checked(&good, &schema).unwrap(), encode_message(&good).unwrap());
+
+        let bad = [field("tags", Value::String("a".into()))];
+        let err = encode_checked(&bad, &schema).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "сообщение не соответствует схеме; tags: ожидалось List<String>, получено String; \
+             id: нет обязательного поля"
+        );
+
+
+        // вложенное сообщение проверяется целиком
+        let nested = [field("id", Value::Int64(1)), field("inner", Value::Message(vec![]))];
+        let missing = Violation { path: "inner.ok".into(), kind: ViolationKind::Missing };
+        let err = encode_checked(&nested, &schema).unwrap_err();
+        assert_eq!(err, CheckedEncodeError::Invalid(vec![missing]));
+    }
+}
@@ -0,0 +1,543 @@
+//! Текстовый язык схем — маленький аналог `.proto`
+//!
+//! ```text
+//! // пользователь сервиса
+//! message User {
+//!   required Int64 id;
+//!   optional String "display name";
+//!   optional List<String> tags;
+//!   optional Map<String, Int64> scores;
+//!   required Address address;
+//!   optional String lang [default = "ru"];
+//!   optional String login [deprecated];
+//!   option allow_unknown = true;
+//! }
+//!
+//! message Address {
+//!   required String city;
+//! }
+//! ```
+//!
+//! Поле — `required` или `optional`, тип и ключ (идентификатор или строка в
+//! кавычках), за ключом в скобках — `default = значение` (значение в
+//! записи [`to_text`](crate::to_text)) и `deprecated`. Типы — имена
+//! вариантов [`SchemaType`] (`Int64`, `String`, `Any`, …), `List<T>`,
+//! `Map<K, V>` и имена сообщений из того же текста, в том числе объявленных
+//! ниже. Сообщение не может содержать само себя, а значение по умолчанию
+//! должно подходить к типу поля: `default = 1i32` у `Int64` — ошибка.
+//! Комментарии — от `//` до конца строки.
+
+use std::fmt;
+
+use crate::schema::default_violations;
+use crate::text::value_at;
+use crate::{Schema, SchemaField, SchemaType, TextError, Value, Violation};
+
+/// Схемы сообщений из текста, в порядке объявления
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schemas {
+    schemas: Vec<Schema>,
+}
+
+impl Schemas {
+    pub fn get(&self, name: &str) -> Option<&Schema> {
+        self.schemas.iter().find(|s| s.name() == Some(name))
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Schema> {
+        self.schemas.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.schemas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Schemas {
+    type Item = &'a Schema;
+    type IntoIter = std::slice::Iter<'a, Schema>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Текст схем не разбирается
+///
+/// `line` и `column` считаются с единицы.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaParseError {
+    /// На позиции ожидалось `expected`
+    Unexpected { line: usize, column: usize, expected: &'static str },
+    /// Тип `name` не встроенный и не объявлен
+    UnknownType { line: usize, column: usize, name: String },
+    /// Сообщение или поле `name` объявлено повторно
+    Duplicate { line: usize, column: usize, name: String },
+    /// Сообщение `name` содержит само себя
+    Recursive { name: String },
+    /// Значение по умолчанию не разбирается
+    Default(TextError),
+    /// Значение по умолчанию, записанное на позиции, не подходит к типу поля
+    DefaultType { line: usize, column: usize, violation: Violation },
+}
+
+impl fmt::Display for SchemaParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaParseError::Unexpected { line, column, expected } => {
+                write!(f, "{line}:{column}: ожидалось {expected}")
+            }
+            SchemaParseError::UnknownType { line, column, name } => {
+                write!(f, "{line}:{column}: неизвестный тип {name}")
+            }
+            SchemaParseError::Duplicate { line, column, name } => {
+                write!(f, "{line}:{column}: {name} объявлено повторно")
+            }
+            SchemaParseError::Recursive { name } => {
+                write!(f, "сообщение {name} содержит само себя")
+            }
+            SchemaParseError::Default(e) => write!(f, "значение по умолчанию: {e}"),
+            SchemaParseError::DefaultType { line, column, violation } => {
+                write!(f, "{line}:{column}: значение по умолчанию: {violation}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaParseError {}
+
+/// Тип поля до разрешения имён сообщений
+enum RawType {
+    Builtin(SchemaType),
+    List(Box<RawType>),
+    Map(Box<RawType>, Box<RawType>),
+    /// Имя сообщения и его позиция в тексте
+    Named(String, usize),
+}
+
+struct RawField {
+    key: String,
+    ty: RawType,
+    required: bool,
+    /// Значение по умолчанию и его позиция в тексте
+    default: Option<(Value, usize)>,
+    deprecated: bool,
+}
+
+struct RawMessage {
+    name: String,
+    fields: Vec<RawField>,
+    allow_unknown: bool,
+}
+
+/// Схемы из текста на языке схем
+///
+/// ```
+/// use custom_codec::{parse_schemas, SchemaType};
+///
+/// let schemas = parse_schemas("message Point { required Int32 x; required Int32 y; }").unwrap();
+/// let point = schemas.get("Point").unwrap();
+/// assert_eq!(point.get("x").unwrap().ty, SchemaType::Int32);
+/// ```
+pub fn parse_schemas(text: &str) -> Result<Schemas, SchemaParseError> {
+    let mut p = Parser { text, pos: 0 };
+    let mut raw: Vec<RawMessage> = Vec::new();
+    loop {
+        p.skip();
+        if p.pos == text.len() {
+            break;
+        }
+        if p.ident() != Some("message") {
+            return Err(p.unexpected("message"));
+        }
+        p.skip();
+        let at = p.pos;
+        let name = p.ident().ok_or_else(|| p.unexpected("имя сообщения"))?.to_owned();
+        if raw.iter().any(|m| m.name == name) {
+            return Err(p.error_at(at, |line, column| SchemaParseError::Duplicate {
+                line,
+                column,
+                name,
+            }));
+        }
+        raw.push(p.message(name)?);
+    }
+    let schemas = raw
+        .iter()
+        .map(|m| resolve(&raw, m, &mut Vec::new(), text))
+        .collect::<Result<_, _>>()?;
+    Ok(Schemas { schemas })
+}
+
+fn resolve(
+    raw: &[RawMessage],
+    message: &RawMessage,
+    stack: &mut Vec<String>,
+    text: &str,
+) -> Result<Schema, SchemaParseError> {
+    if stack.contains(&message.name) {
+        return Err(SchemaParseError::Recursive { name: message.name.clone() });
+    }
+    stack.push(message.name.clone());
+    let mut schema = Schema::named(&message.name);
+    schema.set_allow_unknown(message.allow_unknown);
+    for f in &message.fields {
+        let ty = resolve_type(raw, &f.ty, stack, text)?;
+        if let Some((value, at)) = &f.default {
+            if let Some(violation) = default_violations(&f.key, &ty, value).into_iter().next() {
+                let p = Parser { text, pos: *at };
+                return Err(p.error_at(*at, |line, column| SchemaParseError::DefaultType {
+                    line,
+                    column,
+                    violation,
+                }));
+            }
+        }
+        schema.insert(SchemaField {
+            key: f.key.clone(),
+            ty,
+            required: f.required,
+            default: f.default.as_ref().map(|(value, _)| value.clone()),
+            deprecated: f.deprecated,
+        });
+    }
+    stack.pop();
+    Ok(schema)
+}
+
+fn resolve_type(
+    raw: &[RawMessage],
+    ty: &RawType,
+    stack: &mut Vec<String>,
+    text: &str,
+) -> Result<SchemaType, SchemaParseError> {
+    Ok(match ty {
+        RawType::Builtin(ty) => ty.clone(),
+        RawType::List(item) => SchemaType::List(Box::new(resolve_type(raw, item, stack, text)?)),
+        RawType::Map(k, v) => SchemaType::Map(
+            Box::new(resolve_type(raw, k, stack, text)?),
+            Box::new(resolve_type(raw, v, stack, text)?),
+        ),
+        RawType::Named(name, at) => match raw.iter().find(|m| m.name == *name) {
+            Some(message) => SchemaType::Message(resolve(raw, message, stack, text)?),
+            None => {
+                let p = Parser { text, pos: *at };
+                let name = name.clone();
+                return Err(p.error_at(*at, |line, column| SchemaParseError::UnknownType {
+                    line,
+                    column,
+                    name,
+                }));
+            }
+        },
+    })
+}
+
+/// Встроенный тип по имени варианта `SchemaType`
+pub(crate) fn builtin(name: &str) -> Option<SchemaType> {
+    Some(match name {
+        "Any" => SchemaType::Any,
+        "Bool" => SchemaType::Bool,
+        "Int8" => SchemaType::Int8,
+        "Int16" => SchemaType::Int16,
+        "Int32" => SchemaType::Int32,
+        "Int64" => SchemaType::Int64,
+        "UInt8" => SchemaType::UInt8,
+        "UInt16" => SchemaType::UInt16,
+        "UInt32" => SchemaType::UInt32,
+        "UInt64" => SchemaType::UInt64,
+        "Float32" => SchemaType::Float32,
+        "Float64" => SchemaType::Float64,
+        "String" => SchemaType::String,
+        "Bytes" => SchemaType::Bytes,
+        "Timestamp" => SchemaType::Timestamp,
+        "Uuid" => SchemaType::Uuid,
+        "Decimal" => SchemaType::Decimal,
+        "Enum" => SchemaType::Enum,
+        _ => return None,
+    })
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error_at(
+        &self,
+        pos: usize,
+        make: impl FnOnce(usize, usize) -> SchemaParseError,
+    ) -> SchemaParseError {
+        let before = &self.text[..pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        make(line, column)
+    }
+
+    fn unexpected(&self, expected: &'static str) -> SchemaParseError {
+        self.error_at(self.pos, |line, column| SchemaParseError::Unexpected {
+            line,
+            column,
+            expected,
+        })
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    /// Пропуск пробелов и комментариев
+    fn skip(&mut self) {
+        loop {
+            let rest = self.rest();
+            self.pos += rest.len() - rest.trim_start().len();
+            if !self.rest().starts_with("//") {
+                return;
+            }
+            self.pos += self.rest().find('\n').unwrap_or(self.rest().len());
+        }
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        self.pos += len;
+        Some(&rest[..len])
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), SchemaParseError> {
+        self.skip();
+        if !self.rest().starts_with(c) {
+            return Err(self.unexpected(expected));
+        }
+        self.pos += c.len_utf8();
+        Ok(())
+    }
+
+    /// Тело сообщения; имя уже прочитано
+    fn message(&mut self, name: String) -> Result<RawMessage, SchemaParseError> {
+        self.expect('{', "`{`")?;
+        let mut message = RawMessage { name, fields: Vec::new(), allow_unknown: false };
+        loop {
+            self.skip();
+            if self.rest().starts_with('}') {
+                self.pos += 1;
+                return Ok(message);
+            }
+            let required = match self.ident() {
+                Some("required") => true,
+                Some("optional") => false,
+                Some("option") => {
+                    self.skip();
+                    if self.ident() != Some("allow_unknown") {
+                        return Err(self.unexpected("allow_unknown"));
+                    }
+                    self.expect('=', "`=`")?;
+                    self.skip();
+                    message.allow_unknown = match self.ident() {
+                        Some("true") => true,
+                        Some("false") => false,
+                        _ => return Err(self.unexpected("true или false")),
+                    };
+                    self.expect(';', "`;`")?;
+                    continue;
+                }
+                _ => return Err(self.unexpected("required, optional, option или `}`")),
+            };
+            let ty = self.ty()?;
+            self.skip();
+            let at = self.pos;
+            let key = self.key()?;
+            if message.fields.iter().any(|f| f.key == key) {
+                return Err(self.error_at(at, |line, column| SchemaParseError::Duplicate {
+                    line,
+                    column,
+                    name: key,
+                }));
+            }
+            let mut field = RawField { key, ty, required, default: None, deprecated: false };
+            self.skip();
+            if self.rest().starts_with('[') {
+                self.pos += 1;
+                self.options(&mut field)?;
+            }
+            self.expect(';', "`;`")?;
+            message.fields.push(field);
+        }
+    }
+
+    /// Настройки поля после `[` до `]` включительно
+    fn options(&mut self, field: &mut RawField) -> Result<(), SchemaParseError> {
+        loop {
+            self.skip();
+            match self.ident() {
+                Some("deprecated") => field.deprecated = true,
+                Some("default") => {
+                    self.expect('=', "`=`")?;
+                    self.skip();
+                    let (value, end) =
+                        value_at(self.text, self.pos).map_err(SchemaParseError::Default)?;
+                    field.default = Some((value, self.pos));
+                    self.pos = end;
+                }
+                _ => return Err(self.unexpected("default или deprecated")),
+            }
+            self.skip();
+            match self.rest().chars().next() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(self.unexpected("`,` или `]`")),
+            }
+        }
+    }
+
+    fn ty(&mut self) -> Result<RawType, SchemaParseError> {
+        self.skip();
+        let at = self.pos;
+        let name = self.ident().ok_or_else(|| self.unexpected("тип"))?;
+        Ok(match name {
+            "List" => {
+                self.expect('<', "`<`")?;
+                let item = self.ty()?;
+                self.expect('>', "`>`")?;
+                RawType::List(Box::new(item))
+            }
+            "Map" => {
+                self.expect('<', "`<`")?;
+                let k = self.ty()?;
+                self.expect(',', "`,`")?;
+                let v = self.ty()?;
+                self.expect('>', "`>`")?;
+                RawType::Map(Box::new(k), Box::new(v))
+            }
+            name => match builtin(name) {
+                Some(ty) => RawType::Builtin(ty),
+                None => RawType::Named(name.to_owned(), at),
+            },
+        })
+    }
+
+    /// Ключ поля: идентификатор или строка с экранированием `\"` и `\\`
+    fn key(&mut self) -> Result<String, SchemaParseError> {
+        if let Some(ident) = self.ident() {
+            return Ok(ident.to_owned());
+        }
+        if !self.rest().starts_with('"') {
+            return Err(self.unexpected("ключ"));
+        }
+        self.pos += 1;
+        let mut key = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(key);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c @ ('"' | '\\'))) => key.push(c),
+                    _ => break,
+                },
+                c => key.push(c),
+            }
+        }
+        self.pos = self.text.len();
+        Err(self.unexpected("`\"`"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ViolationKind;
+
+    #[test]
+    fn parse_schema_file() {
+        let text = r#"
+            // пользователь
+            message User {
+              required Int64 id;
+              optional String "display name";
+              optional List<String> tags;
+              optional Map<String, List<Int64>> scores;
+              required Address address;
+              optional String lang [default = "ru"];
+              optional Int32 age [deprecated, default = -1i32];
+              option allow_unknown = true;
+            }
+            message Address { required String city; }
+        "#;
+        let schemas = parse_schemas(text).unwrap();
+        let names: Vec<_> = schemas.iter().filter_map(Schema::name).collect();
+        assert_eq!(names, ["User", "Address"]);
+
+        let mut address = Schema::named("Address");
+        address.required("city", SchemaType::String);
+        let mut user = Schema::named("User");
+        user.required("id", SchemaType::Int64);
+        user.optional("display name", SchemaType::String);
+        user.optional("tags", SchemaType::List(Box::new(SchemaType::String)));
+        let scores = SchemaType::List(Box::new(SchemaType::Int64));
+        user.optional("scores", SchemaType::Map(Box::new(SchemaType::String), Box::new(scores)));
+        user.required("address", SchemaType::Message(address.clone()));
+        let lang = SchemaField::optional("lang", SchemaType::String);
+        user.insert(lang.with_default(Value::String("ru".into())));
+        let age = SchemaField::optional("age", SchemaType::Int32).deprecate();
+        user.insert(age.with_default(Value::Int32(-1)));
+        user.set_allow_unknown(true);
+        assert_eq!(schemas.get("User"), Some(&user));
+        assert_eq!(schemas.get("Address"), Some(&address));
+    }
+
+    #[test]
+    fn parse_schema_errors() {
+        let err = parse_schemas("message A {\n  required Strin x;\n}").unwrap_err();
+        let unknown = SchemaParseError::UnknownType { line: 2, column: 12, name: "Strin".into() };
+        assert_eq!(err, unknown);
+
+        let err = parse_schemas("message A { required Int32 x; optional Bool x; }").unwrap_err();
+        let duplicate = SchemaParseError::Duplicate { line: 1, column: 45, name: "x".into() };
+        assert_eq!(err, duplicate);
+
+        let err = parse_schemas("message A { required Int32 x }").unwrap_err();
+        let semicolon = SchemaParseError::Unexpected { line: 1, column: 30, expected: "`;`" };
+        assert_eq!(err, semicolon);
+
+        let err = parse_schemas("message A {\n  optional Int32 x [default = 1x];\n}").unwrap_err();
+        let what = "числовой суффикс";
+        let literal = TextError::InvalidLiteral { line: 2, column: 31, what };
+        assert_eq!(err, SchemaParseError::Default(literal));
+        let err = parse_schemas("message A { optional Int32 x [default = 1 2]; }").unwrap_err();
+        let bracket = SchemaParseError::Unexpected { line: 1, column: 43, expected: "`,` или `]`" };
+        assert_eq!(err, bracket);
+
+        let text = "message A {\n  required Int64 n [default = 1i32];\n}";
+        let err = parse_schemas(text).unwrap_err();
+        let violation = Violation {
+            path: "n".into(),
+            kind: ViolationKind::TypeMismatch { expected: "Int64".into(), found: "Int32" },
+        };
+        assert_eq!(err, SchemaParseError::DefaultType { line: 2, column: 31, violation });
+        let text = "message A { optional B b [default = { x: 1 }]; }
+                    message B { optional Int32 x; }";
+        let err = parse_schemas(text).unwrap_err();
+        assert!(matches!(err, SchemaParseError::DefaultType { line: 1, column: 37, .. }));
+        assert!(err.to_string().starts_with("1:37: значение по умолчанию: b.x: "), "{err}");
+
+        let text = "message A { optional B b; } message B { optional List<A> a; }";
+        assert_eq!(parse_schemas(text), Err(SchemaParseError::Recursive { name: "A".into() }));
+    }
+}
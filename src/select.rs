@@ -0,0 +1,306 @@
+//! Выборка значений по селектору
+//!
+//! Селектор — шаги через точку, как путь `Message::get_path`:
+//!
+//! - `ключ` — все поля с этим ключом, включая повторы;
+//! - `*` — все поля сообщения;
+//! - `**` — само значение и все вложенные на любой глубине;
+//! - `[n]` и `[*]` после шага — элемент списка с номером `n` и все элементы;
+//! - `[#n]` сразу после ключа — только повтор ключа с номером `n`.
+//!
+//! Точка, обратная черта, `[` и `*` в ключе экранируются обратной чертой,
+//! так что путь [`FieldPath`] в печатном виде — тоже селектор. Он выбирает
+//! значение по пути первым; у первого повтора ключа `[#0]` не печатается,
+//! поэтому за ним идут и остальные повторы. Значения возвращаются в
+//! порядке следования в сообщении, каждое один раз.
+
+use std::fmt;
+
+use crate::{Field, FieldPath, Message, PathSegment, Value};
+
+/// Разобранный селектор для многократного применения
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    /// Поля с ключом `key`; при `nth` — только этот повтор
+    Key { key: String, nth: Option<usize> },
+    AnyKey,
+    Descendants,
+    Index(usize),
+    AnyIndex,
+}
+
+/// Значение, найденное селектором
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selected<'a> {
+    pub path: FieldPath,
+    pub value: &'a Value,
+}
+
+/// Селектор не разбирается
+///
+/// `column` считается с единицы, в символах.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectError {
+    pub column: usize,
+    pub expected: &'static str,
+}
+
+impl fmt::Display for SelectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "селектор, столбец {}: ожидалось {}", self.column, self.expected)
+    }
+}
+
+impl std::error::Error for SelectError {}
+
+impl Selector {
+    pub fn parse(text: &str) -> Result<Selector, SelectError> {
+        let chars: Vec<char> = text.chars().collect();
+        let err = |i: usize, expected| SelectError { column: i + 1, expected };
+        let mut steps = Vec::new();
+        let mut i = 0;
+        loop {
+            // ключ до неэкранированной точки или `[`
+            let (start, mut key, mut escaped) = (i, String::new(), false);
+            while let Some(&c) = chars.get(i).filter(|&&c| c != '.' && c != '[') {
+                i += 1;
+                match c {
+                    '\\' => {
+                        key.push(*chars.get(i).ok_or(err(i, "символ после `\\`"))?);
+                        escaped = true;
+                        i += 1;
+                    }
+                    c => key.push(c),
+                }
+            }
+            steps.push(match (key.as_str(), escaped) {
+                ("", false) => return Err(err(start, "ключ")),
+                ("*", false) => Step::AnyKey,
+                ("**", false) => Step::Descendants,
+                _ => Step::Key { key, nth: None },
+            });
+
+            while chars.get(i) == Some(&'[') {
+                let open = i;
+                let close = chars[i..].iter().position(|&c| c == ']').ok_or(err(open, "`]`"))?;
+                let inner: String = chars[i + 1..i + close].iter().collect();
+                i += close + 1;
+                let index = |digits: &str| digits.parse().map_err(|_| err(open + 1, "номер"));
+                match (inner.strip_prefix('#'), steps.last_mut()) {
+                    (Some(nth), Some(Step::Key { nth: slot @ None, .. })) => {
+                        *slot = Some(index(nth)?)
+                    }
+                    (Some(_), _) => return Err(err(open, "`[#n]` сразу после ключа")),
+                    (None, _) if inner == "*" => steps.push(Step::AnyIndex),
+                    (None, _) => steps.push(Step::Index(index(&inner)?)),
+                }
+            }
+
+            match chars.get(i) {
+                None => return Ok(Selector { steps }),
+                Some('.') => i += 1,
+                Some(_) => return Err(err(i, "`.` или `[`")),
+            }
+        }
+    }
+
+    /// Значения `msg`, найденные селектором
+    pub fn select<'a>(&self, msg: &'a Message) -> Vec<Selected<'a>> {
+        let root = Node::Fields(msg.fields());
+        let mut hits = vec![Hit { path: FieldPath::default(), at: Vec::new(), node: root }];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            for hit in hits {
+                match step {
+                    Step::Key { key, nth } => {
+                        for (i, field, n) in repeats(hit.node.fields()) {
+                            if &field.key == key && nth.is_none_or(|nth| nth == n) {
+                                next.push(hit.field(i, field, n));
+                            }
+                        }
+                    }
+                    Step::AnyKey => {
+                        for (i, field, n) in repeats(hit.node.fields()) {
+                            next.push(hit.field(i, field, n));
+                        }
+                    }
+                    Step::Descendants => descend(hit, &mut next),
+                    Step::Index(i) => {
+                        if let Some(item) = hit.node.items().get(*i) {
+                            next.push(hit.item(*i, item));
+                        }
+                    }
+                    Step::AnyIndex => {
+                        for (i, item) in hit.node.items().iter().enumerate() {
+                            next.push(hit.item(i, item));
+                        }
+                    }
+                }
+            }
+            hits = next;
+        }
+        // после `**` значения идут не по порядку и могут повторяться
+        hits.sort_by(|a, b| a.at.cmp(&b.at));
+        hits.dedup_by(|a, b| a.at == b.at);
+        hits
+            .into_iter()
+            .filter_map(|hit| match hit.node {
+                Node::Value(value) => Some(Selected { path: hit.path, value }),
+                // `**` в конце селектора выбирает и корень, но он не значение
+                Node::Fields(_) => None,
+            })
+            .collect()
+    }
+}
+
+impl Message {
+    /// Значения, найденные селектором `query`, с путями к ним
+    ///
+    /// ```
+    /// use custom_codec::{msg, Value};
+    ///
+    /// let order = msg! {
+    ///     "items" => vec![
+    ///         Value::from(msg! { "name" => "tea", "price" => 3 }),
+    ///         Value::from(msg! { "name" => "cake", "price" => 5 }),
+    ///     ],
+    /// };
+    /// let prices = order.select("items[*].price").unwrap();
+    /// assert_eq!(prices[1].path.to_string(), "items[1].price");
+    /// assert_eq!(prices[1].value, &Value::Int32(5));
+    /// ```
+    pub fn select(&self, query: &str) -> Result<Vec<Selected<'_>>, SelectError> {
+        Ok(Selector::parse(query)?.select(self))
+    }
+}
+
+/// Корень выборки или найденное значение
+#[derive(Clone, Copy)]
+enum Node<'a> {
+    Fields(&'a [Field]),
+    Value(&'a Value),
+}
+
+impl<'a> Node<'a> {
+    fn fields(self) -> &'a [Field] {
+        match self {
+            Node::Fields(fields) => fields,
+            Node::Value(Value::Message(fields)) => fields,
+            Node::Value(_) => &[],
+        }
+    }
+
+    fn items(self) -> &'a [Value] {
+        match self {
+            Node::Value(Value::List(items)) => items,
+            _ => &[],
+        }
+    }
+}
+
+/// Узел, найденный частью селектора; `at` — номера полей и элементов от
+/// корня, по ним восстанавливается порядок следования
+struct Hit<'a> {
+    path: FieldPath,
+    at: Vec<usize>,
+    node: Node<'a>,
+}
+
+impl<'a> Hit<'a> {
+    fn child(&self, segment: PathSegment, i: usize, value: &'a Value) -> Hit<'a> {
+        let mut at = self.at.clone();
+        at.push(i);
+        Hit { path: self.path.with(segment), at, node: Node::Value(value) }
+    }
+
+    /// Поле с номером `i` и номером повтора ключа `nth`
+    fn field(&self, i: usize, field: &'a Field, nth: usize) -> Hit<'a> {
+        self.child(PathSegment::Key { key: field.key.clone(), nth }, i, &field.value)
+    }
+
+    fn item(&self, i: usize, item: &'a Value) -> Hit<'a> {
+        self.child(PathSegment::Index(i), i, item)
+    }
+}
+
+/// Поля с номером и номером повтора ключа
+fn repeats(fields: &[Field]) -> impl Iterator<Item = (usize, &Field, usize)> {
+    fields.iter().enumerate().map(|(i, field)| {
+        (i, field, fields[..i].iter().filter(|f| f.key == field.key).count())
+    })
+}
+
+/// Узел и все вложенные
+fn descend<'a>(hit: Hit<'a>, out: &mut Vec<Hit<'a>>) {
+    for (i, field, n) in repeats(hit.node.fields()) {
+        descend(hit.field(i, field, n), out);
+    }
+    for (i, item) in hit.node.items().iter().enumerate() {
+        descend(hit.item(i, item), out);
+    }
+    out.push(hit);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg;
+
+    fn paths(msg: &Message, query: &str) -> Vec<String> {
+        msg.select(query).unwrap().iter().map(|s| s.path.to_string()).collect()
+    }
+
+    #[test]
+    fn selects_across_levels() {
+        let item = |name: &str, price| msg! { "name" => name, "price" => price };
+        let msg = msg! {
+            "items" => vec![Value::from(item("tea", 3)), Value::from(item("cake", 5))],
+            "user" => msg! { "a.b" => 1, "tags" => vec![Value::from("x")], "tags" => true },
+            "price" => 0,
+            "*" => 2,
+            "**" => 3,
+        };
+        let prices: Vec<_> = msg.select("items[*].price").unwrap();
+        assert_eq!(prices.iter().map(|s| s.value).collect::<Vec<_>>(), [
+            &Value::Int32(3),
+            &Value::Int32(5)
+        ]);
+        assert_eq!(paths(&msg, "items[1].name"), ["items[1].name"]);
+        assert_eq!(paths(&msg, "items[2].name"), Vec::<String>::new());
+        assert_eq!(paths(&msg, "**.price"), ["items[0].price", "items[1].price", "price"]);
+        assert_eq!(paths(&msg, "user.*"), [r"user.a\.b", "user.tags", "user.tags[#1]"]);
+        assert_eq!(paths(&msg, "user.tags"), ["user.tags", "user.tags[#1]"]);
+        assert_eq!(paths(&msg, "user.tags[#1]"), ["user.tags[#1]"]);
+        assert_eq!(paths(&msg, "user.tags[0]"), ["user.tags[0]"]);
+        assert_eq!(paths(&msg, r"user.a\.b"), [r"user.a\.b"]);
+        assert_eq!(paths(&msg, "user.**").len(), 5);
+        assert_eq!(paths(&msg, "missing.**"), Vec::<String>::new());
+        assert_eq!(paths(&msg, "**.**"), paths(&msg, "**"));
+
+        assert_eq!(paths(&msg, r"\*"), [r"\*"]);
+        assert_eq!(paths(&msg, r"\*\*"), [r"\*\*"]);
+        // напечатанный путь выбирает своё значение первым
+        for selected in msg.select("**").unwrap() {
+            let found = msg.select(&selected.path.to_string()).unwrap();
+            assert_eq!(found[0], selected);
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = |query| Selector::parse(query).unwrap_err();
+        assert_eq!(err(""), SelectError { column: 1, expected: "ключ" });
+        assert_eq!(err("a..b"), SelectError { column: 3, expected: "ключ" });
+        assert_eq!(err("a[1"), SelectError { column: 2, expected: "`]`" });
+        assert_eq!(err("a[x]"), SelectError { column: 3, expected: "номер" });
+        assert_eq!(err("a[0]b"), SelectError { column: 5, expected: "`.` или `[`" });
+        assert_eq!(err("*[#1]").expected, "`[#n]` сразу после ключа");
+        assert_eq!(err(r"a\").to_string(), "селектор, столбец 3: ожидалось символ после `\\`");
+        assert!(Selector::parse(r"\*.\*\*").is_ok());
+    }
+}
@@ -0,0 +1,803 @@
+//! Формат данных serde
+//!
+//! Структуры и отображения со строковыми ключами становятся
+//! `Value::Message`, последовательности и кортежи — `Value::List`,
+//! прочие отображения — `Value::Map`, варианты перечислений —
+//! `Value::Enum` с номером и именем варианта. `Option` и `()`
+//! записываются как `Value::Null`.
+
+use std::fmt;
+
+use ::serde::de::value::{StringDeserializer, U32Deserializer};
+use ::serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Unexpected, Visitor};
+use ::serde::ser::{self, Serialize};
+
+use crate::{
+    DecodeError, DecodeOptions, Deserializer, EncodeError, EncodeOptions, Field, Serializer, Value,
+};
+
+/// Ошибка преобразования между типами serde и форматом
+#[derive(Debug, Clone, PartialEq)]
+pub enum SerdeError {
+    /// Сообщение реализации `Serialize` или `Deserialize`
+    Custom(String),
+    /// На верхнем уровне допускаются только структуры и отображения
+    /// со строковыми ключами
+    NotAMessage,
+    Encode(EncodeError),
+    Decode(DecodeError),
+}
+
+impl From<EncodeError> for SerdeError {
+    fn from(e: EncodeError) -> Self {
+        SerdeError::Encode(e)
+    }
+}
+
+impl From<DecodeError> for SerdeError {
+    fn from(e: DecodeError) -> Self {
+        SerdeError::Decode(e)
+    }
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerdeError::Custom(msg) => f.write_str(msg),
+            SerdeError::NotAMessage => {
+                f.write_str("на верхнем уровне ожидалась структура или отображение")
+            }
+            SerdeError::Encode(e) => e.fmt(f),
+            SerdeError::Decode(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for SerdeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SerdeError::Encode(e) => Some(e),
+            SerdeError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+/// Кодирование структуры как сообщения
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct User {
+///     name: String,
+///     age: Option<u8>,
+/// }
+///
+/// let user = User { name: "Rust".into(), age: Some(10) };
+/// let enc = custom_codec::to_vec(&user).unwrap();
+/// assert_eq!(custom_codec::from_slice::<User>(&enc).unwrap(), user);
+/// ```
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, SerdeError> {
+    to_vec_with(value, &EncodeOptions::default())
+}
+
+/// Кодирование структуры как сообщения с настройками
+pub fn to_vec_with<T: Serialize + ?Sized>(
+    value: &T,
+    opts: &EncodeOptions,
+) -> Result<Vec<u8>, SerdeError> {
+    let mut ser = Serializer::with_options(Vec::new(), opts);
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
+/// Декодирование сообщения в структуру
+pub fn from_slice<T: DeserializeOwned>(data: &[u8]) -> Result<T, SerdeError> {
+    from_slice_with(data, &DecodeOptions::unlimited())
+}
+
+/// Декодирование сообщения в структуру с ограничениями
+///
+/// Сообщение разбирается по мере обхода, без промежуточного `Value`;
+/// `duplicate_map_keys` не применяется.
+pub fn from_slice_with<T: DeserializeOwned>(
+    data: &[u8],
+    opts: &DecodeOptions,
+) -> Result<T, SerdeError> {
+    T::deserialize(&mut Deserializer::with_options(data, opts))
+}
+
+/// Преобразование значения serde в `Value`
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, SerdeError> {
+    value.serialize(ValueSerializer)
+}
+
+/// Преобразование `Value` в значение serde
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, SerdeError> {
+    T::deserialize(ValueDeserializer(value))
+}
+
+/// Сборка `Value` из значения serde
+struct ValueSerializer;
+
+fn variant_value(index: u32, name: &str, payload: Option<Value>) -> Value {
+    Value::Enum { variant: index, name: Some(name.to_owned()), payload: payload.map(Box::new) }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = VariantSerializer<SeqSerializer>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = VariantSerializer<StructSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, SerdeError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, SerdeError> {
+        Ok(Value::Int8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, SerdeError> {
+        Ok(Value::Int16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, SerdeError> {
+        Ok(Value::Int32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, SerdeError> {
+        Ok(Value::Int64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, SerdeError> {
+        Ok(Value::UInt8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, SerdeError> {
+        Ok(Value::UInt16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, SerdeError> {
+        Ok(Value::UInt32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, SerdeError> {
+        Ok(Value::UInt64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, SerdeError> {
+        Ok(Value::Float32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, SerdeError> {
+        Ok(Value::Float64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, SerdeError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, SerdeError> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, SerdeError> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Value, SerdeError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, SerdeError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, SerdeError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<Value, SerdeError> {
+        Ok(variant_value(index, variant, None))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, SerdeError> {
+        Ok(variant_value(index, variant, Some(value.serialize(self)?)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, SerdeError> {
+        Ok(SeqSerializer { items: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<SeqSerializer>, SerdeError> {
+        let inner = self.serialize_seq(Some(len))?;
+        Ok(VariantSerializer { index, variant, inner })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, SerdeError> {
+        Ok(MapSerializer { entries: Vec::with_capacity(len.unwrap_or(0)), key: None })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<StructSerializer, SerdeError> {
+        Ok(StructSerializer { fields: Vec::with_capacity(len) })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<StructSerializer>, SerdeError> {
+        let inner = self.serialize_struct(variant, len)?;
+        Ok(VariantSerializer { index, variant, inner })
+    }
+}
+
+struct SeqSerializer {
+    items: Vec<Value>,
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.items.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::List(self.items))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// Отображение: со строковыми ключами — сообщение, иначе `Value::Map`
+pub(crate) fn map_value(entries: Vec<(Value, Value)>) -> Value {
+    if !entries.iter().all(|(k, _)| matches!(k, Value::String(_))) {
+        return Value::Map(entries);
+    }
+    let fields = entries.into_iter().map(|(k, value)| match k {
+        Value::String(key) => Field { key, value },
+        _ => unreachable!(),
+    });
+    Value::Message(fields.collect())
+}
+
+struct MapSerializer {
+    entries: Vec<(Value, Value)>,
+    key: Option<Value>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.key = Some(to_value(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self.key.take().expect("serialize_key вызывается перед serialize_value");
+        self.entries.push((key, to_value(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(map_value(self.entries))
+    }
+}
+
+struct StructSerializer {
+    fields: Vec<Field>,
+}
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.fields.push(Field { key: key.to_owned(), value: to_value(value)? });
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::Message(self.fields))
+    }
+}
+
+/// Содержимое варианта перечисления, собираемое `inner`
+struct VariantSerializer<S> {
+    index: u32,
+    variant: &'static str,
+    inner: S,
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<SeqSerializer> {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        let payload = ser::SerializeSeq::end(self.inner)?;
+        Ok(variant_value(self.index, self.variant, Some(payload)))
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<StructSerializer> {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        let payload = ser::SerializeStruct::end(self.inner)?;
+        Ok(variant_value(self.index, self.variant, Some(payload)))
+    }
+}
+
+/// Разбор `Value` в значение serde
+struct ValueDeserializer(Value);
+
+fn unexpected(value: &Value) -> Unexpected<'_> {
+    match value {
+        Value::Int8(i) => Unexpected::Signed(*i as i64),
+        Value::Int16(i) => Unexpected::Signed(*i as i64),
+        Value::Int32(i) => Unexpected::Signed(*i as i64),
+        Value::Int64(i) => Unexpected::Signed(*i),
+        Value::UInt8(u) => Unexpected::Unsigned(*u as u64),
+        Value::UInt16(u) => Unexpected::Unsigned(*u as u64),
+        Value::UInt32(u) => Unexpected::Unsigned(*u as u64),
+        Value::UInt64(u) => Unexpected::Unsigned(*u),
+        Value::Float32(f) => Unexpected::Float(*f as f64),
+        Value::Float64(f) => Unexpected::Float(*f),
+        Value::Bool(b) => Unexpected::Bool(*b),
+        Value::String(s) => Unexpected::Str(s),
+        Value::Bytes(b) => Unexpected::Bytes(b),
+        Value::Uuid(b) => Unexpected::Bytes(b),
+        Value::Null => Unexpected::Unit,
+        Value::Message(_) | Value::Map(_) => Unexpected::Map,
+        Value::List(_) => Unexpected::Seq,
+        Value::Enum { .. } => Unexpected::Enum,
+        Value::Timestamp(_) => Unexpected::Other("временная метка"),
+        Value::Decimal(_) => Unexpected::Other("десятичное число"),
+        Value::Unknown { .. } => Unexpected::Other("значение неизвестного типа"),
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.0 {
+            Value::Int32(i) => visitor.visit_i32(i),
+            Value::Float32(f) => visitor.visit_f32(f),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::String(s) => visitor.visit_string(s),
+            Value::Bytes(b) => visitor.visit_byte_buf(b),
+            Value::Message(fields) => {
+                let entries = fields.into_iter().map(|f| (Value::String(f.key), f.value));
+                visitor.visit_map(MapAccess { entries, value: None })
+            }
+            Value::Int64(i) => visitor.visit_i64(i),
+            Value::UInt64(u) => visitor.visit_u64(u),
+            Value::Float64(f) => visitor.visit_f64(f),
+            Value::Null => visitor.visit_unit(),
+            Value::List(items) => {
+                let mut seq = SeqAccess { items: items.into_iter() };
+                let value = visitor.visit_seq(&mut seq)?;
+                match seq.items.len() {
+                    0 => Ok(value),
+                    rest => Err(de::Error::invalid_length(rest, &"меньше элементов")),
+                }
+            }
+            Value::Map(entries) => {
+                visitor.visit_map(MapAccess { entries: entries.into_iter(), value: None })
+            }
+            Value::Timestamp(ts) => visitor.visit_i128(ts.unix_nanos()),
+            Value::Uuid(u) => visitor.visit_bytes(&u),
+            Value::Decimal(d) => visitor.visit_string(d.to_string()),
+            Value::Enum { variant, name, payload } => {
+                visitor.visit_enum(EnumAccess { variant, name, payload })
+            }
+            Value::Int8(i) => visitor.visit_i8(i),
+            Value::Int16(i) => visitor.visit_i16(i),
+            Value::UInt8(u) => visitor.visit_u8(u),
+            Value::UInt16(u) => visitor.visit_u16(u),
+            Value::UInt32(u) => visitor.visit_u32(u),
+            Value::Unknown { raw, .. } => visitor.visit_byte_buf(raw),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.0 {
+            Value::Enum { variant, name, payload } => {
+                visitor.visit_enum(EnumAccess { variant, name, payload })
+            }
+            // вариант без данных, записанный строкой
+            Value::String(s) => visitor.visit_enum(s.into_deserializer()),
+            other => Err(de::Error::invalid_type(unexpected(&other), &visitor)),
+        }
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct SeqAccess {
+    items: std::vec::IntoIter<Value>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        self.items.next().map(|v| seed.deserialize(ValueDeserializer(v))).transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+/// Поля сообщения и пары `Value::Map`
+struct MapAccess<I> {
+    entries: I,
+    value: Option<Value>,
+}
+
+impl<'de, I: ExactSizeIterator<Item = (Value, Value)>> de::MapAccess<'de> for MapAccess<I> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        let Some((key, value)) = self.entries.next() else { return Ok(None) };
+        self.value = Some(value);
+        seed.deserialize(ValueDeserializer(key)).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, SerdeError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let value = self.value.take().expect("next_key_seed вызывается перед next_value_seed");
+        seed.deserialize(ValueDeserializer(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumAccess {
+    variant: u32,
+    name: Option<String>,
+    payload: Option<Box<Value>>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = SerdeError;
+    type Variant = VariantAccess;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantAccess), SerdeError> {
+        // имя надёжнее номера: варианты могут переупорядочить
+        let variant = match self.name {
+            Some(name) => seed.deserialize(StringDeserializer::<SerdeError>::new(name))?,
+            None => seed.deserialize(U32Deserializer::<SerdeError>::new(self.variant))?,
+        };
+        Ok((variant, VariantAccess { payload: self.payload.map(|p| *p) }))
+    }
+}
+
+struct VariantAccess {
+    payload: Option<Value>,
+}
+
+impl VariantAccess {
+    fn payload(self, expected: &dyn de::Expected) -> Result<Value, SerdeError> {
+        self.payload.ok_or_else(|| de::Error::invalid_type(Unexpected::UnitVariant, expected))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        match self.payload {
+            None | Some(Value::Null) => Ok(()),
+            Some(other) => Err(de::Error::invalid_type(unexpected(&other), &"вариант без данных")),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, SerdeError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let payload = self.payload(&"вариант с данными")?;
+        seed.deserialize(ValueDeserializer(payload))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: Visitor<'de>,
+    {
+        let payload = self.payload(&visitor)?;
+        de::Deserializer::deserialize_seq(ValueDeserializer(payload), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let payload = self.payload(&visitor)?;
+        de::Deserializer::deserialize_map(ValueDeserializer(payload), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_message;
+    use ::serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Pair(i32, i32),
+        Rect { w: u32, h: u32 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        id: u64,
+        name: String,
+        tags: Vec<String>,
+        score: Option<f32>,
+        missing: Option<i8>,
+        #[serde(with = "serde_bytes_compat")]
+        raw: Vec<u8>,
+        shapes: Vec<Shape>,
+        counts: BTreeMap<u16, i64>,
+        point: (i16, bool, char),
+        nested: Inner,
+        unit: (),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Inner {
+        flag: bool,
+    }
+
+    /// Байты как `serialize_bytes`, без зависимости от serde_bytes
+    mod serde_bytes_compat {
+        use ::serde::{Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(v)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+            struct Bytes;
+            impl<'de> ::serde::de::Visitor<'de> for Bytes {
+                type Value = Vec<u8>;
+                fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.write_str("байты")
+                }
+                fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+                    Ok(v)
+                }
+            }
+            d.deserialize_byte_buf(Bytes)
+        }
+    }
+
+    fn record() -> Record {
+        Record {
+            id: 7,
+            name: "probe".into(),
+            tags: vec!["a".into(), "b".into()],
+            score: Some(0.5),
+            missing: None,
+            raw: vec![0, 1, 2],
+            shapes: vec![
+                Shape::Empty,
+                Shape::Circle(1.5),
+                Shape::Pair(-1, 2),
+                Shape::Rect { w: 3, h: 4 },
+            ],
+            counts: BTreeMap::from([(1, -10), (2, 20)]),
+            point: (-3, true, 'я'),
+            nested: Inner { flag: true },
+            unit: (),
+        }
+    }
+
+    #[test]
+    fn struct_roundtrip() {
+        let enc = to_vec(&record()).unwrap();
+        assert_eq!(from_slice::<Record>(&enc).unwrap(), record());
+
+        // структура — это сообщение с полями в порядке объявления
+        let fields = decode_message(&enc).unwrap();
+        assert_eq!(fields[0], Field { key: "id".into(), value: Value::UInt64(7) });
+        assert_eq!(fields[4].value, Value::Null);
+        assert_eq!(fields[5].value, Value::Bytes(vec![0, 1, 2]));
+        let Value::List(shapes) = &fields[6].value else { panic!("ожидался список") };
+        assert_eq!(
+            shapes[1],
+            Value::Enum {
+                variant: 1,
+                name: Some("Circle".into()),
+                payload: Some(Box::new(Value::Float64(1.5))),
+            }
+        );
+
+        let opts = EncodeOptions { profile: crate::Profile::compact(), ..EncodeOptions::default() };
+        let enc = to_vec_with(&record(), &opts).unwrap();
+        let dec = DecodeOptions { profile: crate::Profile::compact(), ..DecodeOptions::default() };
+        assert_eq!(from_slice_with::<Record>(&enc, &dec).unwrap(), record());
+    }
+
+    #[test]
+    fn maps_and_errors() {
+        // отображение со строковыми ключами кодируется как сообщение
+        let map = HashMap::from([("x".to_string(), 1i32)]);
+        let enc = to_vec(&map).unwrap();
+        let expected = [Field { key: "x".into(), value: Value::Int32(1) }];
+        assert_eq!(decode_message(&enc).unwrap(), expected);
+        assert_eq!(from_slice::<HashMap<String, i32>>(&enc).unwrap(), map);
+        // и вложенное тоже, а с другими ключами — как `Value::Map`
+        let nested = Value::List(vec![Value::Message(expected.to_vec())]);
+        assert_eq!(to_value(&vec![map]).unwrap(), nested);
+        let map = Value::Map(vec![(Value::UInt8(1), Value::UInt8(2))]);
+        assert_eq!(to_value(&BTreeMap::from([(1u8, 2u8)])).unwrap(), map);
+
+        assert_eq!(to_vec(&5u8), Err(SerdeError::NotAMessage));
+        assert_eq!(to_vec(&HashMap::from([(1u8, 2u8)])), Err(SerdeError::NotAMessage));
+
+        let err = from_value::<Inner>(Value::Message(Vec::new())).unwrap_err();
+        assert_eq!(err, SerdeError::Custom("missing field `flag`".into()));
+        let err = from_slice::<Inner>(&[1]).unwrap_err();
+        assert_eq!(err, SerdeError::Decode(DecodeError::UnexpectedEof { offset: 1 }));
+        assert!(from_value::<u8>(Value::Int32(300)).is_err());
+        assert_eq!(from_value::<u8>(Value::Int32(200)), Ok(200));
+    }
+}
@@ -0,0 +1,1145 @@
+//! Потоковые `Serializer` и `Deserializer` serde
+//!
+//! В отличие от `to_value`/`from_value` они не строят дерево `Value`:
+//! сериализатор пишет байты по мере обхода значения, десериализатор
+//! выдаёт посетителю элементы по мере чтения входа, строки и байты —
+//! срезами входа. Поэтому их можно соединить с другим форматом через
+//! `serde_transcode` и перекодировать данные любого размера.
+//!
+//! Верхний уровень — сообщение: поля подряд, без общего префикса длины.
+
+use std::io::Write;
+
+use ::serde::de::value::{BorrowedStrDeserializer, U32Deserializer};
+use ::serde::de::{self, DeserializeSeed, IntoDeserializer, Unexpected, Visitor};
+use ::serde::ser::{self, Serialize};
+
+use crate::decode::{Decoder, Item, Reader, Scope, UNLIMITED};
+use crate::encode::Writer;
+use crate::serde::to_value;
+use crate::value_ref::Borrowed;
+use crate::{DecodeError, DecodeOptions, EncodeOptions, Profile, SerdeError, Value, ValueRef};
+
+/// Запись сообщения в `Write` по мере сериализации
+///
+/// На верхнем уровне допускаются структуры и отображения со строковыми
+/// ключами. Каждое поле верхнего уровня собирается в буфере и
+/// отправляется в `W` целиком, так что память ограничена размером
+/// самого большого поля. При ошибке в `W` могут остаться уже записанные поля.
+///
+/// ```
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let mut out = Vec::new();
+/// Point { x: 1, y: 2 }.serialize(&mut custom_codec::Serializer::new(&mut out)).unwrap();
+/// assert_eq!(out, custom_codec::to_vec(&Point { x: 1, y: 2 }).unwrap());
+/// ```
+#[derive(Debug)]
+pub struct Serializer<W> {
+    w: W,
+    profile: Profile,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> Serializer<W> {
+    /// Стандартный профиль
+    pub fn new(w: W) -> Self {
+        Serializer::with_options(w, &EncodeOptions::default())
+    }
+
+    pub fn with_options(w: W, opts: &EncodeOptions) -> Self {
+        Serializer { w, profile: opts.profile, buf: Vec::new() }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.w
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = SerdeError;
+    type SerializeSeq = ser::Impossible<(), SerdeError>;
+    type SerializeTuple = ser::Impossible<(), SerdeError>;
+    type SerializeTupleStruct = ser::Impossible<(), SerdeError>;
+    type SerializeTupleVariant = ser::Impossible<(), SerdeError>;
+    type SerializeMap = TopFields<'a, W>;
+    type SerializeStruct = TopFields<'a, W>;
+    type SerializeStructVariant = ser::Impossible<(), SerdeError>;
+
+    fn serialize_bool(self, _: bool) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_char(self, _: char) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_str(self, _: &str) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_none(self) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<TopFields<'a, W>, SerdeError> {
+        Ok(TopFields { ser: self, key: None })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<TopFields<'a, W>, SerdeError> {
+        Ok(TopFields { ser: self, key: None })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        Err(SerdeError::NotAMessage)
+    }
+}
+
+/// Поля верхнего уровня: каждое записывается в `W`, как только готово
+pub struct TopFields<'a, W> {
+    ser: &'a mut Serializer<W>,
+    key: Option<String>,
+}
+
+impl<W: Write> TopFields<'_, W> {
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), SerdeError> {
+        let ser = &mut *self.ser;
+        ser.buf.clear();
+        value.serialize(ValueWriter { out: &mut ser.buf, profile: ser.profile, key: Some(key) })?;
+        ser.w.write_all(&ser.buf).map_err(|e| SerdeError::Encode(e.into()))
+    }
+}
+
+impl<W: Write> ser::SerializeMap for TopFields<'_, W> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        match to_value(key)? {
+            Value::String(key) => self.key = Some(key),
+            _ => return Err(SerdeError::NotAMessage),
+        }
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self.key.take().expect("serialize_key вызывается перед serialize_value");
+        self.field(&key, value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+}
+
+impl<W: Write> ser::SerializeStruct for TopFields<'_, W> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+}
+
+/// Запись одного значения в буфер; `key` — ключ, если значение — поле сообщения
+struct ValueWriter<'a, 'k> {
+    out: &'a mut Vec<u8>,
+    profile: Profile,
+    key: Option<&'k str>,
+}
+
+impl<'a> ValueWriter<'a, '_> {
+    fn item(self, value: Value) -> Result<(), SerdeError> {
+        Ok(Writer::vec(self.out, self.profile).item(self.key, &value)?)
+    }
+
+    /// Открытие контейнера `type_code`
+    fn open(self, type_code: u8) -> Result<Container<'a>, SerdeError> {
+        let start = Writer::vec(self.out, self.profile).open(type_code, self.key)?;
+        Ok(Container { out: self.out, profile: self.profile, start, outer: None })
+    }
+
+    /// Открытие `Value::Enum` с заголовком варианта
+    fn open_variant(
+        self,
+        index: u32,
+        variant: &str,
+        has_payload: bool,
+    ) -> Result<Container<'a>, SerdeError> {
+        let c = self.open(16)?;
+        Writer::vec(c.out, c.profile).enum_header(index, variant, has_payload)?;
+        Ok(c)
+    }
+}
+
+/// Открытый контейнер; `outer` — объемлющий `Value::Enum`, который
+/// закрывается вместе с ним
+struct Container<'a> {
+    out: &'a mut Vec<u8>,
+    profile: Profile,
+    start: usize,
+    outer: Option<usize>,
+}
+
+impl<'a> Container<'a> {
+    /// Вложенный контейнер, закрываемый вместе с этим
+    fn nest(self, type_code: u8) -> Result<Container<'a>, SerdeError> {
+        let start = Writer::vec(self.out, self.profile).open(type_code, None)?;
+        Ok(Container { out: self.out, profile: self.profile, start, outer: Some(self.start) })
+    }
+
+    fn element<T>(&mut self, key: Option<&str>, value: &T) -> Result<(), SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(ValueWriter { out: &mut *self.out, profile: self.profile, key })
+    }
+
+    fn close(self) -> Result<(), SerdeError> {
+        let mut w = Writer::vec(self.out, self.profile);
+        w.close(self.start)?;
+        if let Some(outer) = self.outer {
+            w.close(outer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for ValueWriter<'a, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+    type SerializeSeq = Container<'a>;
+    type SerializeTuple = Container<'a>;
+    type SerializeTupleStruct = Container<'a>;
+    type SerializeTupleVariant = Container<'a>;
+    type SerializeMap = MapWriter<'a>;
+    type SerializeStruct = Container<'a>;
+    type SerializeStructVariant = Container<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), SerdeError> {
+        self.item(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), SerdeError> {
+        self.item(Value::Int8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), SerdeError> {
+        self.item(Value::Int16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), SerdeError> {
+        self.item(Value::Int32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), SerdeError> {
+        self.item(Value::Int64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), SerdeError> {
+        self.item(Value::UInt8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), SerdeError> {
+        self.item(Value::UInt16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), SerdeError> {
+        self.item(Value::UInt32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), SerdeError> {
+        self.item(Value::UInt64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), SerdeError> {
+        self.item(Value::Float32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), SerdeError> {
+        self.item(Value::Float64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), SerdeError> {
+        self.item(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SerdeError> {
+        self.item(Value::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SerdeError> {
+        self.item(Value::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<(), SerdeError> {
+        self.item(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerdeError> {
+        self.item(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerdeError> {
+        self.item(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<(), SerdeError> {
+        self.open_variant(index, variant, false)?.close()
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        let mut c = self.open_variant(index, variant, true)?;
+        c.element(None, value)?;
+        c.close()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Container<'a>, SerdeError> {
+        self.open(11)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Container<'a>, SerdeError> {
+        self.open(11)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Container<'a>, SerdeError> {
+        self.open(11)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Container<'a>, SerdeError> {
+        self.open_variant(index, variant, true)?.nest(11)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapWriter<'a>, SerdeError> {
+        let code_pos = self.out.len();
+        Ok(MapWriter { inner: self.open(6)?, code_pos, mode: MapMode::Empty })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Container<'a>, SerdeError> {
+        self.open(6)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Container<'a>, SerdeError> {
+        self.open_variant(index, variant, true)?.nest(6)
+    }
+}
+
+impl ser::SerializeSeq for Container<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(None, value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTuple for Container<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(None, value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTupleStruct for Container<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(None, value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTupleVariant for Container<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(None, value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.close()
+    }
+}
+
+impl ser::SerializeStruct for Container<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.element(Some(key), value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.close()
+    }
+}
+
+impl ser::SerializeStructVariant for Container<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.element(Some(key), value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.close()
+    }
+}
+
+/// Вид отображения определяется по первому ключу
+enum MapMode {
+    Empty,
+    /// строковые ключи: `Value::Message`; ключ ждёт своего значения
+    Message(Option<String>),
+    Map,
+}
+
+/// Отображение: со строковыми ключами — сообщение, иначе `Value::Map`
+struct MapWriter<'a> {
+    inner: Container<'a>,
+    /// позиция кода типа: `Value::Message` заменяется на `Value::Map`,
+    /// если первый ключ не строка
+    code_pos: usize,
+    mode: MapMode,
+}
+
+impl ser::SerializeMap for MapWriter<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        let key = to_value(key)?;
+        match (&self.mode, key) {
+            (MapMode::Empty | MapMode::Message(_), Value::String(key)) => {
+                self.mode = MapMode::Message(Some(key));
+                Ok(())
+            }
+            (MapMode::Message(_), _) => {
+                Err(ser::Error::custom("после строковых ключей отображения встретился нестроковый"))
+            }
+            (_, key) => {
+                self.inner.out[self.code_pos] = 12;
+                self.mode = MapMode::Map;
+                self.inner.element(None, &key)
+            }
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        match &mut self.mode {
+            MapMode::Message(key) => {
+                let key = key.take().expect("serialize_key вызывается перед serialize_value");
+                self.inner.element(Some(&key), value)
+            }
+            _ => self.inner.element(None, value),
+        }
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.inner.close()
+    }
+}
+
+/// Чтение сообщения по мере обхода посетителем
+///
+/// Строки и ключи выдаются посетителю срезами входа
+/// (`visit_borrowed_str`), так что `&'de str` в структурах не копируется.
+/// Ограничение `duplicate_map_keys` не применяется: повторы ключей
+/// обрабатывает сам десериализуемый тип.
+pub struct Deserializer<'de, 'o> {
+    dec: Decoder<'o, Reader<'de>>,
+    len: usize,
+    /// число открытых контейнеров
+    depth: usize,
+    /// элемент, заголовок которого уже прочитан вместе с ключом
+    pending: Option<Item<'de, Borrowed>>,
+}
+
+impl<'de> Deserializer<'de, 'static> {
+    /// Ограничения `DecodeOptions::unlimited()`, стандартный профиль
+    pub fn new(data: &'de [u8]) -> Self {
+        Deserializer::with_options(data, &UNLIMITED)
+    }
+}
+
+impl<'de, 'o> Deserializer<'de, 'o> {
+    pub fn with_options(data: &'de [u8], opts: &'o DecodeOptions) -> Self {
+        let dec = Decoder::new(Reader::new(data), opts);
+        Deserializer { dec, len: data.len(), depth: 0, pending: None }
+    }
+
+    /// Смещение следующего непрочитанного байта
+    pub fn offset(&self) -> usize {
+        use crate::decode::Input;
+        self.dec.input.pos()
+    }
+
+    fn next_item(
+        &mut self,
+        keyed: bool,
+    ) -> Result<(Option<&'de str>, Item<'de, Borrowed>), SerdeError> {
+        Ok(self.dec.next_item(&Borrowed, keyed, self.depth)?)
+    }
+
+    /// Элемент, с которого начинается следующее значение
+    fn item(&mut self) -> Result<Item<'de, Borrowed>, SerdeError> {
+        match self.pending.take() {
+            Some(item) => Ok(item),
+            None => Ok(self.next_item(false)?.1),
+        }
+    }
+
+    /// Пропуск значения, заголовок которого прочитан, но которое не
+    /// понадобилось посетителю
+    fn discard_pending(&mut self) -> Result<(), SerdeError> {
+        match self.pending.take() {
+            Some(Item::Open(scope) | Item::Enum { scope, .. }) => Ok(self.dec.close(&scope)?),
+            _ => Ok(()),
+        }
+    }
+
+    /// Выход из контейнера: непрочитанные поля и пары пропускаются
+    fn leave(&mut self, scope: &Scope) -> Result<(), SerdeError> {
+        self.discard_pending()?;
+        self.depth -= 1;
+        Ok(self.dec.close(scope)?)
+    }
+
+    fn at_end(&self, scope: Option<&Scope>) -> bool {
+        match scope {
+            Some(scope) => self.dec.scope_done(scope),
+            None => self.offset() >= self.len,
+        }
+    }
+}
+
+impl<'de, 'o> de::Deserializer<'de> for &mut Deserializer<'de, 'o> {
+    type Error = SerdeError;
+
+    /// Верхний уровень всегда читается как отображение полей
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let value = visitor.visit_map(Fields { de: &mut *self, scope: None })?;
+        self.discard_pending()?;
+        Ok(value)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+/// Десериализатор одного значения внутри сообщения
+struct ItemDeserializer<'a, 'de, 'o> {
+    de: &'a mut Deserializer<'de, 'o>,
+}
+
+fn visit_scalar<'de, V>(value: ValueRef<'de>, visitor: V) -> Result<V::Value, SerdeError>
+where
+    V: Visitor<'de>,
+{
+    match value {
+        ValueRef::Int8(i) => visitor.visit_i8(i),
+        ValueRef::Int16(i) => visitor.visit_i16(i),
+        ValueRef::Int32(i) => visitor.visit_i32(i),
+        ValueRef::Int64(i) => visitor.visit_i64(i),
+        ValueRef::UInt8(u) => visitor.visit_u8(u),
+        ValueRef::UInt16(u) => visitor.visit_u16(u),
+        ValueRef::UInt32(u) => visitor.visit_u32(u),
+        ValueRef::UInt64(u) => visitor.visit_u64(u),
+        ValueRef::Float32(f) => visitor.visit_f32(f),
+        ValueRef::Float64(f) => visitor.visit_f64(f),
+        ValueRef::Bool(b) => visitor.visit_bool(b),
+        ValueRef::String(s) => visitor.visit_borrowed_str(s),
+        ValueRef::Bytes(b) => visitor.visit_borrowed_bytes(b),
+        ValueRef::Null => visitor.visit_unit(),
+        ValueRef::Timestamp(ts) => visitor.visit_i128(ts.unix_nanos()),
+        ValueRef::Uuid(u) => visitor.visit_bytes(&u),
+        ValueRef::Decimal(d) => visitor.visit_string(d.to_string()),
+        ValueRef::Unknown { raw, .. } => visitor.visit_borrowed_bytes(raw),
+        ValueRef::Message(_) | ValueRef::List(_) | ValueRef::Map(_) | ValueRef::Enum { .. } => {
+            unreachable!("next_item не собирает контейнеры")
+        }
+    }
+}
+
+impl<'de> ItemDeserializer<'_, 'de, '_> {
+    fn visit_item<V: Visitor<'de>>(
+        self,
+        item: Item<'de, Borrowed>,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let de = self.de;
+        let scope = match item {
+            Item::Scalar(value) => return visit_scalar(value, visitor),
+            Item::Open(scope) => scope,
+            Item::Enum { variant, name, has_payload, scope } => {
+                de.depth += 1;
+                let access = EnumAccess { de: &mut *de, variant, name, has_payload };
+                let value = visitor.visit_enum(access)?;
+                if !de.dec.scope_done(&scope) {
+                    // лишние байты после содержимого перечисления
+                    let offset = de.offset();
+                    return Err(DecodeError::InvalidValue { offset, type_code: 16 }.into());
+                }
+                de.leave(&scope)?;
+                return Ok(value);
+            }
+        };
+        de.depth += 1;
+        let value = match scope.type_code {
+            6 => visitor.visit_map(Fields { de: &mut *de, scope: Some(&scope) })?,
+            11 => {
+                let value = visitor.visit_seq(Items { de: &mut *de, scope: &scope })?;
+                let mut rest = 0;
+                while !de.dec.scope_done(&scope) {
+                    de.pending = Some(de.item()?);
+                    de.discard_pending()?;
+                    rest += 1;
+                }
+                if rest > 0 {
+                    return Err(de::Error::invalid_length(rest, &"меньше элементов"));
+                }
+                value
+            }
+            _ => visitor.visit_map(Entries { de: &mut *de, scope: &scope })?,
+        };
+        de.leave(&scope)?;
+        Ok(value)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ItemDeserializer<'_, 'de, '_> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let item = self.de.item()?;
+        self.visit_item(item, visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.de.item()? {
+            Item::Scalar(ValueRef::Null) => visitor.visit_none(),
+            item => {
+                self.de.pending = Some(item);
+                visitor.visit_some(self)
+            }
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.de.item()? {
+            item @ Item::Enum { .. } => self.visit_item(item, visitor),
+            // вариант без данных, записанный строкой
+            Item::Scalar(ValueRef::String(s)) => visitor.visit_enum(s.into_deserializer()),
+            Item::Scalar(other) => {
+                Err(de::Error::invalid_type(unexpected(&other), &visitor))
+            }
+            Item::Open(scope) => {
+                self.de.pending = Some(Item::Open(scope));
+                self.de.discard_pending()?;
+                Err(de::Error::invalid_type(Unexpected::Map, &visitor))
+            }
+        }
+    }
+
+    /// Копия байт: посетители `byte_buf` часто не умеют принимать срез
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.de.item()? {
+            Item::Scalar(ValueRef::Bytes(b)) => visitor.visit_byte_buf(b.to_vec()),
+            item => self.visit_item(item, visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        // содержимое пропускается без разбора
+        self.de.pending = Some(self.de.item()?);
+        self.de.discard_pending()?;
+        visitor.visit_unit()
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+fn unexpected<'a>(value: &'a ValueRef<'_>) -> Unexpected<'a> {
+    match value {
+        ValueRef::Int8(i) => Unexpected::Signed(*i as i64),
+        ValueRef::Int16(i) => Unexpected::Signed(*i as i64),
+        ValueRef::Int32(i) => Unexpected::Signed(*i as i64),
+        ValueRef::Int64(i) => Unexpected::Signed(*i),
+        ValueRef::UInt8(u) => Unexpected::Unsigned(*u as u64),
+        ValueRef::UInt16(u) => Unexpected::Unsigned(*u as u64),
+        ValueRef::UInt32(u) => Unexpected::Unsigned(*u as u64),
+        ValueRef::UInt64(u) => Unexpected::Unsigned(*u),
+        ValueRef::Float32(f) => Unexpected::Float(*f as f64),
+        ValueRef::Float64(f) => Unexpected::Float(*f),
+        ValueRef::Bool(b) => Unexpected::Bool(*b),
+        ValueRef::String(s) => Unexpected::Str(s),
+        ValueRef::Bytes(b) => Unexpected::Bytes(b),
+        ValueRef::Uuid(b) => Unexpected::Bytes(b),
+        ValueRef::Null => Unexpected::Unit,
+        ValueRef::Message(_) | ValueRef::Map(_) => Unexpected::Map,
+        ValueRef::List(_) => Unexpected::Seq,
+        ValueRef::Enum { .. } => Unexpected::Enum,
+        ValueRef::Timestamp(_) => Unexpected::Other("временная метка"),
+        ValueRef::Decimal(_) => Unexpected::Other("десятичное число"),
+        ValueRef::Unknown { .. } => Unexpected::Other("значение неизвестного типа"),
+    }
+}
+
+/// Поля сообщения; `scope` — `None` на верхнем уровне
+struct Fields<'a, 'de, 'o> {
+    de: &'a mut Deserializer<'de, 'o>,
+    scope: Option<&'a Scope>,
+}
+
+impl<'de> de::MapAccess<'de> for Fields<'_, 'de, '_> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        self.de.discard_pending()?;
+        if self.de.at_end(self.scope) {
+            return Ok(None);
+        }
+        let (key, item) = self.de.next_item(true)?;
+        self.de.pending = Some(item);
+        let key = key.expect("поле сообщения читается с ключом");
+        seed.deserialize(BorrowedStrDeserializer::new(key)).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, SerdeError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(ItemDeserializer { de: &mut *self.de })
+    }
+}
+
+/// Элементы списка
+struct Items<'a, 'de, 'o> {
+    de: &'a mut Deserializer<'de, 'o>,
+    scope: &'a Scope,
+}
+
+impl<'de> de::SeqAccess<'de> for Items<'_, 'de, '_> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        if self.de.at_end(Some(self.scope)) {
+            return Ok(None);
+        }
+        seed.deserialize(ItemDeserializer { de: &mut *self.de }).map(Some)
+    }
+}
+
+/// Пары `Value::Map`
+struct Entries<'a, 'de, 'o> {
+    de: &'a mut Deserializer<'de, 'o>,
+    scope: &'a Scope,
+}
+
+impl<'de> de::MapAccess<'de> for Entries<'_, 'de, '_> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        if self.de.at_end(Some(self.scope)) {
+            return Ok(None);
+        }
+        seed.deserialize(ItemDeserializer { de: &mut *self.de }).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, SerdeError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(ItemDeserializer { de: &mut *self.de })
+    }
+}
+
+struct EnumAccess<'a, 'de, 'o> {
+    de: &'a mut Deserializer<'de, 'o>,
+    variant: u32,
+    name: Option<&'de str>,
+    has_payload: bool,
+}
+
+impl<'a, 'de, 'o> de::EnumAccess<'de> for EnumAccess<'a, 'de, 'o> {
+    type Error = SerdeError;
+    type Variant = VariantAccess<'a, 'de, 'o>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), SerdeError> {
+        // имя надёжнее номера: варианты могут переупорядочить
+        let variant = match self.name {
+            Some(name) => seed.deserialize(BorrowedStrDeserializer::<SerdeError>::new(name))?,
+            None => seed.deserialize(U32Deserializer::<SerdeError>::new(self.variant))?,
+        };
+        Ok((variant, VariantAccess { de: self.de, has_payload: self.has_payload }))
+    }
+}
+
+struct VariantAccess<'a, 'de, 'o> {
+    de: &'a mut Deserializer<'de, 'o>,
+    has_payload: bool,
+}
+
+impl<'a, 'de, 'o> VariantAccess<'a, 'de, 'o> {
+    fn payload(self, expected: &dyn de::Expected) -> Result<Self, SerdeError> {
+        match self.has_payload {
+            true => Ok(self),
+            false => Err(de::Error::invalid_type(Unexpected::UnitVariant, expected)),
+        }
+    }
+
+    fn element(self) -> ItemDeserializer<'a, 'de, 'o> {
+        ItemDeserializer { de: self.de }
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess<'_, 'de, '_> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        if !self.has_payload {
+            return Ok(());
+        }
+        match self.de.item()? {
+            Item::Scalar(ValueRef::Null) => Ok(()),
+            Item::Scalar(other) => {
+                Err(de::Error::invalid_type(unexpected(&other), &"вариант без данных"))
+            }
+            item => {
+                self.de.pending = Some(item);
+                self.de.discard_pending()?;
+                Err(de::Error::invalid_type(Unexpected::Map, &"вариант без данных"))
+            }
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, SerdeError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.payload(&"вариант с данными")?.element())
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self.payload(&visitor)?.element(), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_map(self.payload(&visitor)?.element(), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_message, Field};
+    use ::serde::Deserialize;
+
+    #[test]
+    fn transcode_json() {
+        let json = r#"{"id":7,"name":"probe","tags":["a",null,true],"pos":{"x":-1.5,"y":2}}"#;
+        let mut ser = Serializer::new(Vec::new());
+        serde_transcode::transcode(&mut serde_json::Deserializer::from_str(json), &mut ser)
+            .unwrap();
+        let enc = ser.into_inner();
+
+        let fields = decode_message(&enc).unwrap();
+        assert_eq!(fields[0], Field { key: "id".into(), value: Value::UInt64(7) });
+        let pos = Value::Message(vec![
+            Field { key: "x".into(), value: Value::Float64(-1.5) },
+            Field { key: "y".into(), value: Value::UInt64(2) },
+        ]);
+        assert_eq!(fields[3], Field { key: "pos".into(), value: pos });
+
+        let mut back = Vec::new();
+        let mut de = Deserializer::new(&enc);
+        serde_transcode::transcode(&mut de, &mut serde_json::Serializer::new(&mut back)).unwrap();
+        assert_eq!(String::from_utf8(back).unwrap(), json);
+
+        // верхний уровень JSON — не объект; serde_json дописывает позицию
+        let mut ser = Serializer::new(Vec::new());
+        let mut array = serde_json::Deserializer::from_str("[1]");
+        let err = serde_transcode::transcode(&mut array, &mut ser).unwrap_err();
+        assert!(err.to_string().starts_with(&SerdeError::NotAMessage.to_string()));
+    }
+
+    #[test]
+    fn borrowed_strings() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct View<'a> {
+            name: &'a str,
+            #[serde(default)]
+            items: Vec<&'a str>,
+        }
+
+        let fields = [
+            Field { key: "skip".into(), value: Value::List(vec![Value::Int32(1)]) },
+            Field { key: "name".into(), value: Value::String("probe".into()) },
+            Field {
+                key: "items".into(),
+                value: Value::List(vec![Value::String("a".into()), Value::String("b".into())]),
+            },
+        ];
+        let enc = crate::encode_message(&fields).unwrap();
+        let view = View::deserialize(&mut Deserializer::new(&enc)).unwrap();
+        assert_eq!(view, View { name: "probe", items: vec!["a", "b"] });
+
+        let err = View::deserialize(&mut Deserializer::new(&enc[..enc.len() - 1])).unwrap_err();
+        assert!(matches!(err, SerdeError::Decode(DecodeError::UnexpectedEof { .. })));
+    }
+}
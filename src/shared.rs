@@ -0,0 +1,203 @@
+//! Интеграция с `bytes`: запись в `BytesMut` и значения `Bytes`,
+//! разделяющие память с входным буфером
+
+use ::bytes::{BufMut, Bytes, BytesMut};
+
+use crate::encode::Writer;
+use crate::{
+    decode_field_ref_with, decode_message_ref, Decimal, DecodeError, DecodeOptions, EncodeError,
+    EncodeOptions, Field, FieldRef, Timestamp, Value, ValueRef,
+};
+
+/// `Value`, в котором `Bytes` — срез общего входного буфера
+///
+/// Клонирование такого значения не копирует данные байтовых полей.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SharedValue {
+    Int32(i32),
+    Float32(f32),
+    Bool(bool),
+    String(String),
+    Bytes(Bytes),
+    Message(Vec<SharedField>),
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    Null,
+    List(Vec<SharedValue>),
+    Map(Vec<(SharedValue, SharedValue)>),
+    Timestamp(Timestamp),
+    Uuid([u8; 16]),
+    Decimal(Decimal),
+    Enum { variant: u32, name: Option<String>, payload: Option<Box<SharedValue>> },
+    Int8(i8),
+    Int16(i16),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    /// Значение неизвестного типа, см. `Value::Unknown`
+    Unknown { type_code: u8, raw: Bytes },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedField {
+    pub key: String,
+    pub value: SharedValue,
+}
+
+impl SharedValue {
+    /// Значение с собственной копией байт
+    pub fn into_owned(self) -> Value {
+        match self {
+            SharedValue::Int32(i) => Value::Int32(i),
+            SharedValue::Float32(f) => Value::Float32(f),
+            SharedValue::Bool(b) => Value::Bool(b),
+            SharedValue::String(s) => Value::String(s),
+            SharedValue::Bytes(b) => Value::Bytes(b.into()),
+            SharedValue::Message(fields) => {
+                Value::Message(fields.into_iter().map(SharedField::into_owned).collect())
+            }
+            SharedValue::Int64(i) => Value::Int64(i),
+            SharedValue::UInt64(u) => Value::UInt64(u),
+            SharedValue::Float64(f) => Value::Float64(f),
+            SharedValue::Null => Value::Null,
+            SharedValue::List(items) => {
+                Value::List(items.into_iter().map(SharedValue::into_owned).collect())
+            }
+            SharedValue::Map(entries) => Value::Map(
+                entries.into_iter().map(|(k, v)| (k.into_owned(), v.into_owned())).collect(),
+            ),
+            SharedValue::Timestamp(ts) => Value::Timestamp(ts),
+            SharedValue::Uuid(u) => Value::Uuid(u),
+            SharedValue::Decimal(d) => Value::Decimal(d),
+            SharedValue::Enum { variant, name, payload } => Value::Enum {
+                variant,
+                name,
+                payload: payload.map(|p| Box::new(p.into_owned())),
+            },
+            SharedValue::Int8(i) => Value::Int8(i),
+            SharedValue::Int16(i) => Value::Int16(i),
+            SharedValue::UInt8(u) => Value::UInt8(u),
+            SharedValue::UInt16(u) => Value::UInt16(u),
+            SharedValue::UInt32(u) => Value::UInt32(u),
+            SharedValue::Unknown { type_code, raw } => {
+                Value::Unknown { type_code, raw: raw.into() }
+            }
+        }
+    }
+
+    /// `ValueRef` из `data`, байтовые значения которого становятся срезами `data`
+    fn share(value: ValueRef<'_>, data: &Bytes) -> Self {
+        match value {
+            ValueRef::Int32(i) => SharedValue::Int32(i),
+            ValueRef::Float32(f) => SharedValue::Float32(f),
+            ValueRef::Bool(b) => SharedValue::Bool(b),
+            ValueRef::String(s) => SharedValue::String(s.to_owned()),
+            ValueRef::Bytes(b) => SharedValue::Bytes(data.slice_ref(b)),
+            ValueRef::Message(fields) => SharedValue::Message(
+                fields.into_iter().map(|f| SharedField::share(f, data)).collect(),
+            ),
+            ValueRef::Int64(i) => SharedValue::Int64(i),
+            ValueRef::UInt64(u) => SharedValue::UInt64(u),
+            ValueRef::Float64(f) => SharedValue::Float64(f),
+            ValueRef::Null => SharedValue::Null,
+            ValueRef::List(items) => SharedValue::List(
+                items.into_iter().map(|v| SharedValue::share(v, data)).collect(),
+            ),
+            ValueRef::Map(entries) => SharedValue::Map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (SharedValue::share(k, data), SharedValue::share(v, data)))
+                    .collect(),
+            ),
+            ValueRef::Timestamp(ts) => SharedValue::Timestamp(ts),
+            ValueRef::Uuid(u) => SharedValue::Uuid(u),
+            ValueRef::Decimal(d) => SharedValue::Decimal(d),
+            ValueRef::Enum { variant, name, payload } => SharedValue::Enum {
+                variant,
+                name: name.map(str::to_owned),
+                payload: payload.map(|p| Box::new(SharedValue::share(*p, data))),
+            },
+            ValueRef::Int8(i) => SharedValue::Int8(i),
+            ValueRef::Int16(i) => SharedValue::Int16(i),
+            ValueRef::UInt8(u) => SharedValue::UInt8(u),
+            ValueRef::UInt16(u) => SharedValue::UInt16(u),
+            ValueRef::UInt32(u) => SharedValue::UInt32(u),
+            ValueRef::Unknown { type_code, raw } => {
+                SharedValue::Unknown { type_code, raw: data.slice_ref(raw) }
+            }
+        }
+    }
+}
+
+impl SharedField {
+    pub fn into_owned(self) -> Field {
+        Field { key: self.key, value: self.value.into_owned() }
+    }
+
+    fn share(field: FieldRef<'_>, data: &Bytes) -> Self {
+        SharedField { key: field.key.to_owned(), value: SharedValue::share(field.value, data) }
+    }
+}
+
+/// Кодирование поля в конец `BytesMut`
+pub fn encode_field_to_bytes(field: &Field, buf: &mut BytesMut) -> Result<(), EncodeError> {
+    encode_field_to_bytes_with(field, buf, &EncodeOptions::default())
+}
+
+/// То же, что [`encode_field_to_bytes`], с настройками
+pub fn encode_field_to_bytes_with(
+    field: &Field,
+    buf: &mut BytesMut,
+    opts: &EncodeOptions,
+) -> Result<(), EncodeError> {
+    buf.reserve(field.encoded_len_with(&opts.profile));
+    Writer::new(&mut buf.writer(), opts.profile).field(field)
+}
+
+/// Декодирование поля; байтовые значения ссылаются на память `data`
+pub fn decode_field_shared(data: &Bytes) -> Result<SharedField, DecodeError> {
+    decode_field_shared_with(data, &DecodeOptions::unlimited())
+}
+
+/// То же, что [`decode_field_shared`], с ограничениями и профилем
+pub fn decode_field_shared_with(
+    data: &Bytes,
+    opts: &DecodeOptions,
+) -> Result<SharedField, DecodeError> {
+    let field = decode_field_ref_with(data, opts)?;
+    Ok(SharedField::share(field, data))
+}
+
+/// Декодирование сообщения; байтовые значения ссылаются на память `data`
+pub fn decode_message_shared(
+    data: &Bytes,
+    opts: &DecodeOptions,
+) -> Result<Vec<SharedField>, DecodeError> {
+    let fields = decode_message_ref(data, opts)?;
+    Ok(fields.into_iter().map(|f| SharedField::share(f, data)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_field, encode_field};
+
+    #[test]
+    fn bytes_share_input() {
+        let f = Field {
+            key: "blob".into(),
+            value: Value::List(vec![Value::Bytes(vec![7; 32]), Value::String("s".into())]),
+        };
+        let mut buf = BytesMut::from(&b"prefix"[..]);
+        encode_field_to_bytes(&f, &mut buf).unwrap();
+        assert_eq!(&buf[6..], encode_field(&f).unwrap());
+
+        let data = buf.freeze().slice(6..);
+        let shared = decode_field_shared(&data).unwrap();
+        let SharedValue::List(items) = &shared.value else { panic!("ожидался список") };
+        let SharedValue::Bytes(b) = &items[0] else { panic!("ожидались байты") };
+        assert!(data.as_ptr_range().contains(&b.as_ptr()));
+        assert_eq!(shared.into_owned(), decode_field(&data).unwrap());
+    }
+}
@@ -0,0 +1,222 @@
+//! Правка закодированных сообщений без декодирования
+//!
+//! Сообщение — поля подряд, поэтому новое поле просто дописывается в
+//! конец, а удаляемое вырезается: у полей перед ним читаются только
+//! заголовки, значения пропускаются по длине. У конверта при этом
+//! исправляются длина тела и контрольная сумма. При ошибке буфер не
+//! меняется.
+
+use std::ops::Range;
+
+use crate::crc32;
+use crate::decode::{Decoder, Input, Reader};
+use crate::envelope::envelope_body;
+use crate::{
+    encode_field_with, DecodeError, DecodeOptions, EncodeError, EncodeOptions, Error, Field,
+    Profile, MAGIC,
+};
+
+/// Дописывание поля в закодированное сообщение или конверт
+///
+/// Сообщение без конверта считается записанным в исходном профиле.
+///
+/// ```
+/// use custom_codec::{append_field, decode_enveloped, encode_enveloped, Field, Value};
+///
+/// let id = Field { key: "id".into(), value: Value::Int32(7) };
+/// let route = Field { key: "route".into(), value: Value::String("eu-1".into()) };
+/// let mut enc = encode_enveloped(&[id.clone()]).unwrap();
+/// append_field(&mut enc, &route).unwrap();
+/// assert_eq!(decode_enveloped(&enc).unwrap(), [id, route]);
+/// ```
+pub fn append_field(encoded: &mut Vec<u8>, field: &Field) -> Result<(), Error> {
+    append_field_with(encoded, field, Profile::STANDARD)
+}
+
+/// То же, что [`append_field`], для сообщения без конверта в `profile`;
+/// у конверта профиль берётся из заголовка
+pub fn append_field_with(
+    encoded: &mut Vec<u8>,
+    field: &Field,
+    profile: Profile,
+) -> Result<(), Error> {
+    if !encoded.starts_with(&MAGIC) {
+        let opts = EncodeOptions { profile, ..EncodeOptions::default() };
+        encoded.extend_from_slice(&encode_field_with(field, &opts)?);
+        return Ok(());
+    }
+
+    let (body, profile) = envelope_body(encoded)?;
+    let crc = body.end < encoded.len();
+    let opts = EncodeOptions { profile, ..EncodeOptions::default() };
+    let bytes = encode_field_with(field, &opts)?;
+    let len = body.len() + bytes.len();
+    let len = u32::try_from(len).map_err(|_| EncodeError::LengthOverflow { len })?;
+    encoded.truncate(body.end);
+    encoded.extend_from_slice(&bytes);
+    encoded[7..11].copy_from_slice(&len.to_be_bytes());
+    if crc {
+        let crc = crc32::checksum(encoded);
+        encoded.extend_from_slice(&crc.to_be_bytes());
+    }
+    Ok(())
+}
+
+/// Удаление первого поля верхнего уровня с ключом `key`
+///
+/// Возвращает `false`, если такого поля нет; буфер тогда не меняется.
+/// Сообщение без конверта считается записанным в исходном профиле.
+///
+/// ```
+/// use custom_codec::{decode_message, encode_message, remove_field, Field, Value};
+///
+/// let id = Field { key: "id".into(), value: Value::Int32(7) };
+/// let token = Field { key: "token".into(), value: Value::String("secret".into()) };
+/// let mut enc = encode_message(&[token, id.clone()]).unwrap();
+/// assert_eq!(remove_field(&mut enc, "token"), Ok(true));
+/// assert_eq!(decode_message(&enc).unwrap(), [id]);
+/// ```
+pub fn remove_field(encoded: &mut Vec<u8>, key: &str) -> Result<bool, DecodeError> {
+    remove_field_with(encoded, key, Profile::STANDARD)
+}
+
+/// То же, что [`remove_field`], для сообщения без конверта в `profile`;
+/// у конверта профиль берётся из заголовка
+pub fn remove_field_with(
+    encoded: &mut Vec<u8>,
+    key: &str,
+    profile: Profile,
+) -> Result<bool, DecodeError> {
+    let envelope = encoded.starts_with(&MAGIC);
+    let (body, profile) = match envelope {
+        true => envelope_body(encoded)?,
+        false => (0..encoded.len(), profile),
+    };
+    let Some(field) = find_field(encoded, &body, key, profile)? else {
+        return Ok(false);
+    };
+    let crc = body.end < encoded.len();
+    let removed = field.len();
+    encoded.drain(field);
+    if !envelope {
+        return Ok(true);
+    }
+    // тело только укоротилось, поэтому длина помещается в u32
+    let len = (body.len() - removed) as u32;
+    encoded[7..11].copy_from_slice(&len.to_be_bytes());
+    if crc {
+        encoded.truncate(body.end - removed);
+        let crc = crc32::checksum(encoded);
+        encoded.extend_from_slice(&crc.to_be_bytes());
+    }
+    Ok(true)
+}
+
+/// Границы первого поля с ключом `key` в теле `body`
+fn find_field(
+    data: &[u8],
+    body: &Range<usize>,
+    key: &str,
+    profile: Profile,
+) -> Result<Option<Range<usize>>, DecodeError> {
+    let opts = DecodeOptions { profile, ..DecodeOptions::unlimited() };
+    let mut decoder = Decoder::new(Reader::at(&data[..body.end], body.start), &opts);
+    loop {
+        let start = decoder.input.pos();
+        if start == body.end {
+            return Ok(None);
+        }
+        let (field_key, end) = decoder.skip_field()?;
+        if field_key.as_ref() == key.as_bytes() {
+            return Ok(Some(start..end));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decode_enveloped, decode_message, decode_message_with, encode_enveloped_with,
+        encode_message, encode_message_with, envelope_options, Checksum, DecodeError,
+        DecodeOptions, SchemaId, Value,
+    };
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn append_plain() {
+        let fields = vec![field("id", Value::Int32(7))];
+        let route = field("route", Value::String("eu-1".into()));
+        let mut enc = encode_message(&fields).unwrap();
+        append_field(&mut enc, &route).unwrap();
+        assert_eq!(decode_message(&enc).unwrap(), [fields[0].clone(), route.clone()]);
+
+        let compact = EncodeOptions { profile: Profile::compact(), ..EncodeOptions::default() };
+        let mut enc = encode_message_with(&fields, &compact).unwrap();
+        append_field_with(&mut enc, &route, Profile::compact()).unwrap();
+        let opts = DecodeOptions { profile: Profile::compact(), ..DecodeOptions::default() };
+        assert_eq!(decode_message_with(&enc, &opts).unwrap(), [fields[0].clone(), route]);
+    }
+
+    #[test]
+    fn append_envelope() {
+        let opts = EncodeOptions {
+            profile: Profile::compact(),
+            checksum: Checksum::Crc32,
+            schema_id: Some(SchemaId { id: 1, version: 2 }),
+        };
+        let fields = vec![field("id", Value::Int32(7))];
+        let route = field("route", Value::String("eu-1".into()));
+        let mut enc = encode_enveloped_with(&fields, &opts).unwrap();
+        append_field(&mut enc, &route).unwrap();
+        let expected = vec![fields[0].clone(), route.clone()];
+        assert_eq!(enc, encode_enveloped_with(&expected, &opts).unwrap());
+        assert_eq!(decode_enveloped(&enc).unwrap(), expected);
+        assert_eq!(envelope_options(&enc), Ok(opts));
+
+        // повреждённый конверт не трогается
+        let last = enc.len() - 1;
+        enc[last] ^= 1;
+        let before = enc.clone();
+        let err = append_field(&mut enc, &route).unwrap_err();
+        assert!(matches!(err, Error::Decode(DecodeError::ChecksumMismatch { .. })));
+        assert_eq!(enc, before);
+    }
+
+    #[test]
+    fn remove() {
+        let fields = vec![
+            field("a", Value::Int32(1)),
+            field("token", Value::String("secret".into())),
+            field("b", Value::Message(vec![field("token", Value::Bool(true))])),
+            field("token", Value::Bool(false)),
+        ];
+        let mut enc = encode_message(&fields).unwrap();
+        assert_eq!(remove_field(&mut enc, "token"), Ok(true));
+        let expected = [&fields[..1], &fields[2..]].concat();
+        assert_eq!(decode_message(&enc).unwrap(), expected);
+        assert_eq!(remove_field(&mut enc, "token"), Ok(true));
+        assert_eq!(remove_field(&mut enc, "token"), Ok(false));
+        assert_eq!(decode_message(&enc).unwrap(), [fields[0].clone(), fields[2].clone()]);
+
+        let opts = EncodeOptions {
+            profile: Profile::compact(),
+            checksum: Checksum::Crc32,
+            schema_id: Some(SchemaId { id: 1, version: 2 }),
+        };
+        let mut enc = encode_enveloped_with(&fields, &opts).unwrap();
+        assert_eq!(remove_field(&mut enc, "a"), Ok(true));
+        assert_eq!(enc, encode_enveloped_with(&fields[1..], &opts).unwrap());
+        assert_eq!(remove_field(&mut enc, "missing"), Ok(false));
+        assert_eq!(decode_enveloped(&enc).unwrap(), &fields[1..]);
+
+        let mut broken = encode_message(&fields).unwrap();
+        broken.truncate(broken.len() - 1);
+        let before = broken.clone();
+        assert!(matches!(remove_field(&mut broken, "x"), Err(DecodeError::UnexpectedEof { .. })));
+        assert_eq!(broken, before);
+    }
+}
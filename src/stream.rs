@@ -0,0 +1,183 @@
+//! Поток сообщений в конвертах из `AsyncRead`
+//!
+//! Границы сообщений берутся из заголовков конвертов. Данные читаются
+//! только по запросу очередного элемента, так что медленный потребитель
+//! естественно притормаживает чтение из сокета.
+
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::envelope::enveloped_len;
+use crate::{decode_enveloped_with, DecodeError, DecodeOptions, Message, HEADER_LEN};
+
+/// Сколько байт буфера выделяется за одно чтение: испорченная длина в
+/// заголовке не приводит к огромной аллокации
+const READ_CHUNK: usize = 64 << 10;
+
+/// `Stream` сообщений, закодированных `encode_enveloped`
+///
+/// Смещения в ошибках отсчитываются от начала потока; после ошибки поток
+/// завершается.
+#[derive(Debug)]
+pub struct MessageStream<R> {
+    inner: R,
+    opts: DecodeOptions,
+    /// начало текущего конверта; `buf[..filled]` уже прочитано
+    buf: Vec<u8>,
+    filled: usize,
+    /// смещение текущего конверта от начала потока
+    consumed: usize,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> MessageStream<R> {
+    /// Поток с ограничениями `DecodeOptions::default()`
+    pub fn new(inner: R) -> Self {
+        MessageStream::with_options(inner, DecodeOptions::default())
+    }
+
+    /// Ограничения применяются к каждому сообщению; `max_total_len`
+    /// проверяется по заголовку конверта, до чтения тела
+    pub fn with_options(inner: R, opts: DecodeOptions) -> Self {
+        MessageStream { inner, opts, buf: Vec::new(), filled: 0, consumed: 0, done: false }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Следующий конверт целиком; `None` — поток кончился на границе
+    fn poll_envelope(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<usize>, DecodeError>> {
+        loop {
+            let target = match self.filled < HEADER_LEN {
+                true => HEADER_LEN,
+                false => enveloped_len(&self.buf[..HEADER_LEN], &self.opts)?,
+            };
+            if self.filled == target {
+                return Poll::Ready(Ok(Some(target)));
+            }
+            let end = target.min(self.filled + READ_CHUNK);
+            if self.buf.len() < end {
+                self.buf.resize(end, 0);
+            }
+            let mut read = ReadBuf::new(&mut self.buf[self.filled..end]);
+            let offset = self.filled;
+            ready!(Pin::new(&mut self.inner).poll_read(cx, &mut read))
+                .map_err(|e| DecodeError::Io { offset, kind: e.kind() })?;
+            match read.filled().len() {
+                0 if self.filled == 0 => return Poll::Ready(Ok(None)),
+                0 => return Poll::Ready(Err(DecodeError::UnexpectedEof { offset })),
+                n => self.filled += n,
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for MessageStream<R> {
+    type Item = Result<Message, DecodeError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let item = match ready!(this.poll_envelope(cx)) {
+            Ok(Some(len)) => {
+                let msg = decode_enveloped_with(&this.buf[..len], &this.opts).map(Message::from);
+                this.filled = 0;
+                this.buf.clear();
+                let offset = this.consumed;
+                this.consumed += len;
+                msg.map_err(|e| e.shifted(offset))
+            }
+            Ok(None) => {
+                this.done = true;
+                return Poll::Ready(None);
+            }
+            Err(e) => Err(e.shifted(this.consumed)),
+        };
+        this.done = item.is_err();
+        Poll::Ready(Some(item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode_enveloped_with, Checksum, EncodeOptions, Field, Limit, Profile, Value};
+    use std::future::poll_fn;
+    use tokio::io::AsyncWriteExt;
+
+    fn sample(n: i64) -> Vec<Field> {
+        vec![
+            Field { key: "id".into(), value: Value::Int64(n) },
+            Field { key: "name".into(), value: Value::String("probe".into()) },
+        ]
+    }
+
+    async fn next<R>(s: &mut MessageStream<R>) -> Option<Result<Message, DecodeError>>
+    where
+        R: AsyncRead + Unpin,
+    {
+        poll_fn(|cx| Pin::new(&mut *s).poll_next(cx)).await
+    }
+
+    #[tokio::test]
+    async fn messages_from_pipe() {
+        let opts = EncodeOptions {
+            profile: Profile::compact(),
+            checksum: Checksum::Crc32,
+            ..EncodeOptions::default()
+        };
+        let (mut tx, rx) = tokio::io::duplex(5);
+        let writer = async {
+            for n in 0..3 {
+                tx.write_all(&encode_enveloped_with(&sample(n), &opts).unwrap()).await.unwrap();
+            }
+            drop(tx);
+        };
+        let reader = async {
+            let mut stream = MessageStream::new(rx);
+            let mut msgs = Vec::new();
+            while let Some(msg) = next(&mut stream).await {
+                msgs.push(msg.unwrap());
+            }
+            msgs
+        };
+        let ((), msgs) = tokio::join!(writer, reader);
+        let expected: Vec<Message> = (0..3).map(|n| Message::from(sample(n))).collect();
+        assert_eq!(msgs, expected);
+    }
+
+    #[tokio::test]
+    async fn errors_end_stream() {
+        let enc = crate::encode_enveloped(&sample(1)).unwrap();
+        let mut data = enc.clone();
+        data.extend_from_slice(&enc[..enc.len() - 2]);
+        let mut stream = MessageStream::new(&data[..]);
+        assert!(next(&mut stream).await.unwrap().is_ok());
+        assert_eq!(
+            next(&mut stream).await,
+            Some(Err(DecodeError::UnexpectedEof { offset: 2 * enc.len() - 2 }))
+        );
+        assert_eq!(next(&mut stream).await, None);
+
+        // размер конверта проверяется по заголовку
+        let opts = DecodeOptions { max_total_len: 16, ..DecodeOptions::default() };
+        let mut stream = MessageStream::with_options(&enc[..], opts);
+        assert_eq!(
+            next(&mut stream).await,
+            Some(Err(DecodeError::LimitExceeded { offset: 7, limit: Limit::TotalLength }))
+        );
+    }
+}
@@ -0,0 +1,394 @@
+//! Декодирование полей из данных, приходящих частями
+//!
+//! В отличие от `decode_field_from`, декодер не читает сам: данные
+//! передаются ему через `feed` кусками произвольного размера, например
+//! по мере прихода из неблокирующего сокета.
+
+use crate::decode::{Decoder, Input, Reader};
+use crate::encode::type_code;
+use crate::{
+    decode_enveloped, encode_enveloped_with, Checksum, DecodeError, DecodeOptions, DuplicateKeys,
+    EncodeError, EncodeOptions, Field, Profile, UnknownTypes, Value, HEADER_LEN,
+};
+
+/// Версия раскладки снимка состояния
+const SNAPSHOT_VERSION: u8 = 2;
+
+/// Ключи полей снимка в порядке их следования
+const SNAPSHOT_KEYS: [&str; 11] = [
+    "version",
+    "consumed",
+    "failed",
+    "max_depth",
+    "max_value_len",
+    "max_total_len",
+    "max_fields",
+    "duplicate_map_keys",
+    "unknown_types",
+    "profile",
+    "buf",
+];
+
+/// Декодер, накапливающий данные до получения поля целиком
+///
+/// ```
+/// use custom_codec::{encode_field, Field, StreamingDecoder, Value};
+///
+/// let f = Field { key: "n".into(), value: Value::Int32(7) };
+/// let enc = encode_field(&f).unwrap();
+/// let mut dec = StreamingDecoder::new();
+/// dec.feed(&enc[..5]);
+/// assert!(dec.next_field().is_none());
+/// dec.feed(&enc[5..]);
+/// assert_eq!(dec.next_field(), Some(Ok(f)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StreamingDecoder {
+    /// полученные данные; прочитанные поля занимают `buf[..start]`
+    buf: Vec<u8>,
+    /// начало ещё не прочитанных данных в `buf`
+    start: usize,
+    opts: DecodeOptions,
+    /// смещение `buf[start]` от начала потока
+    consumed: usize,
+    /// после ошибки поток не читается: граница следующего поля неизвестна
+    failed: bool,
+}
+
+impl StreamingDecoder {
+    /// Декодер с ограничениями `DecodeOptions::default()`
+    pub fn new() -> Self {
+        StreamingDecoder::default()
+    }
+
+    /// Ограничения применяются к каждому полю по отдельности и
+    /// проверяются по заголовку, до накопления данных поля
+    pub fn with_options(opts: DecodeOptions) -> Self {
+        StreamingDecoder { opts, ..StreamingDecoder::default() }
+    }
+
+    /// Добавление очередной порции данных
+    pub fn feed(&mut self, data: &[u8]) {
+        if !self.failed {
+            self.buf.extend_from_slice(data);
+        }
+    }
+
+    /// Следующее поле, если оно получено целиком
+    ///
+    /// `None` — данных пока недостаточно. Смещения в ошибках отсчитываются
+    /// от начала потока; после ошибки декодер больше полей не выдаёт.
+    pub fn next_field(&mut self) -> Option<Result<Field, DecodeError>> {
+        if self.failed {
+            return None;
+        }
+        match self.try_next() {
+            Ok(field) => field.map(Ok),
+            Err(e) => {
+                self.failed = true;
+                self.buf = Vec::new();
+                self.start = 0;
+                Some(Err(e.shifted(self.consumed)))
+            }
+        }
+    }
+
+    fn try_next(&mut self) -> Result<Option<Field>, DecodeError> {
+        let pending = self.pending();
+        let end = match Decoder::new(Reader::new(pending), &self.opts).field_end() {
+            Ok(end) if end <= pending.len() => end,
+            Ok(_) | Err(DecodeError::UnexpectedEof { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut dec = Decoder::new(Reader::new(&pending[..end]), &self.opts);
+        let field = dec.read_field()?;
+        debug_assert_eq!(dec.input.pos(), end);
+        self.start += end;
+        self.consumed += end;
+        // сдвиг на каждое поле сделал бы разбор потока квадратичным;
+        // сдвиг только после прочтения половины буфера — линейным
+        if self.start > self.buf.len() / 2 {
+            self.buf.drain(..self.start);
+            self.start = 0;
+        }
+        Ok(Some(field))
+    }
+
+    /// Полученные, но ещё не декодированные данные
+    fn pending(&self) -> &[u8] {
+        &self.buf[self.start..]
+    }
+
+    /// Число полученных, но ещё не декодированных байт
+    pub fn buffered(&self) -> usize {
+        self.pending().len()
+    }
+
+    /// Снимок состояния для продолжения декодирования в другом процессе
+    ///
+    /// Снимок — сообщение в конверте с CRC-32, содержащее настройки,
+    /// позицию в потоке и ещё не декодированные данные.
+    pub fn snapshot(&self) -> Result<Vec<u8>, EncodeError> {
+        let o = &self.opts;
+        let limit = |n: usize| Value::UInt64(n as u64);
+        let duplicates = match o.duplicate_map_keys {
+            DuplicateKeys::Allow => 0,
+            DuplicateKeys::Reject => 1,
+            DuplicateKeys::KeepFirst => 2,
+            DuplicateKeys::KeepLast => 3,
+        };
+        let unknown = match o.unknown_types {
+            UnknownTypes::Reject => 0,
+            UnknownTypes::Preserve => 1,
+        };
+        let values = [
+            Value::UInt8(SNAPSHOT_VERSION),
+            limit(self.consumed),
+            Value::Bool(self.failed),
+            limit(o.max_depth),
+            limit(o.max_value_len),
+            limit(o.max_total_len),
+            limit(o.max_fields),
+            Value::UInt8(duplicates),
+            Value::UInt8(unknown),
+            Value::UInt8(o.profile.to_bits()),
+            Value::Bytes(self.pending().to_vec()),
+        ];
+        let fields: Vec<Field> = SNAPSHOT_KEYS
+            .iter()
+            .zip(values)
+            .map(|(key, value)| Field { key: (*key).into(), value })
+            .collect();
+        let opts = EncodeOptions { checksum: Checksum::Crc32, ..EncodeOptions::default() };
+        encode_enveloped_with(&fields, &opts)
+    }
+
+    /// Декодер из снимка `snapshot`
+    ///
+    /// Поле снимка с неожиданным ключом или значением отвергается как
+    /// `InvalidValue` со смещением этого поля.
+    pub fn restore(data: &[u8]) -> Result<Self, DecodeError> {
+        let fields = decode_enveloped(data)?;
+        if fields.len() < SNAPSHOT_KEYS.len() {
+            return Err(DecodeError::UnexpectedEof { offset: data.len() });
+        }
+        let mut offset = HEADER_LEN;
+        let mut values = Vec::with_capacity(SNAPSHOT_KEYS.len());
+        for (f, key) in fields.into_iter().zip(SNAPSHOT_KEYS) {
+            let invalid = DecodeError::InvalidValue { offset, type_code: type_code(&f.value) };
+            offset += f.encoded_len();
+            if f.key != key {
+                return Err(invalid);
+            }
+            values.push((f.value, invalid));
+        }
+        let mut values = values.into_iter();
+        let mut next = || values.next().expect("число полей проверено");
+        let limit = |next: (Value, DecodeError)| match next {
+            (Value::UInt64(n), _) => Ok(usize::try_from(n).unwrap_or(usize::MAX)),
+            (_, invalid) => Err(invalid),
+        };
+        // порядок вызовов соответствует SNAPSHOT_KEYS
+        match next() {
+            (Value::UInt8(SNAPSHOT_VERSION), _) => {}
+            (_, invalid) => return Err(invalid),
+        }
+        let consumed = limit(next())?;
+        let failed = match next() {
+            (Value::Bool(b), _) => b,
+            (_, invalid) => return Err(invalid),
+        };
+        let max_depth = limit(next())?;
+        let max_value_len = limit(next())?;
+        let max_total_len = limit(next())?;
+        let max_fields = limit(next())?;
+        let duplicate_map_keys = match next() {
+            (Value::UInt8(0), _) => DuplicateKeys::Allow,
+            (Value::UInt8(1), _) => DuplicateKeys::Reject,
+            (Value::UInt8(2), _) => DuplicateKeys::KeepFirst,
+            (Value::UInt8(3), _) => DuplicateKeys::KeepLast,
+            (_, invalid) => return Err(invalid),
+        };
+        let unknown_types = match next() {
+            (Value::UInt8(0), _) => UnknownTypes::Reject,
+            (Value::UInt8(1), _) => UnknownTypes::Preserve,
+            (_, invalid) => return Err(invalid),
+        };
+        let profile = match next() {
+            (Value::UInt8(bits), invalid) => Profile::from_bits(bits).ok_or(invalid)?,
+            (_, invalid) => return Err(invalid),
+        };
+        let buf = match next() {
+            (Value::Bytes(buf), _) => buf,
+            (_, invalid) => return Err(invalid),
+        };
+        let opts = DecodeOptions {
+            max_depth,
+            max_value_len,
+            max_total_len,
+            max_fields,
+            duplicate_map_keys,
+            unknown_types,
+            profile,
+        };
+        Ok(StreamingDecoder { buf, start: 0, opts, consumed, failed })
+    }
+
+    /// Завершение потока: ошибка, если последнее поле пришло не целиком
+    pub fn finish(self) -> Result<(), DecodeError> {
+        match self.buffered() {
+            0 => Ok(()),
+            len => Err(DecodeError::UnexpectedEof { offset: self.consumed + len }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode_message, encode_message_with, EncodeOptions, Limit, Profile, Value};
+
+    fn sample() -> Vec<Field> {
+        vec![
+            Field { key: "a".into(), value: Value::String("first".into()) },
+            Field { key: "flag".into(), value: Value::Bool(true) },
+            Field {
+                key: "m".into(),
+                value: Value::Message(vec![Field { key: "x".into(), value: Value::Int64(-3) }]),
+            },
+        ]
+    }
+
+    #[test]
+    fn byte_by_byte() {
+        for profile in [Profile::STANDARD, Profile::compact()] {
+            let opts = EncodeOptions { profile, ..EncodeOptions::default() };
+            let enc = encode_message_with(&sample(), &opts).unwrap();
+            let dec_opts = DecodeOptions { profile, ..DecodeOptions::default() };
+            let mut dec = StreamingDecoder::with_options(dec_opts);
+            let mut fields = Vec::new();
+            for b in &enc {
+                dec.feed(std::slice::from_ref(b));
+                while let Some(f) = dec.next_field() {
+                    fields.push(f.unwrap());
+                }
+            }
+            assert_eq!(fields, sample());
+            assert_eq!(dec.buffered(), 0);
+            dec.finish().unwrap();
+        }
+    }
+
+    #[test]
+    fn many_fields_in_one_feed() {
+        let fields: Vec<_> =
+            (0..10_000).map(|i| Field { key: "n".into(), value: Value::Int64(i) }).collect();
+        let enc = crate::encode_message(&fields).unwrap();
+        let mut dec = StreamingDecoder::new();
+        dec.feed(&enc);
+        for (i, f) in fields.iter().enumerate() {
+            assert_eq!(dec.next_field(), Some(Ok(f.clone())));
+            assert_eq!(dec.buffered(), enc.len() / fields.len() * (fields.len() - i - 1));
+            // прочитанное начало буфера сдвигается, только когда оно больше половины
+            assert!(dec.start <= dec.buf.len() / 2);
+        }
+        assert!(dec.buf.is_empty());
+        dec.finish().unwrap();
+    }
+
+    #[test]
+    fn resumed_from_snapshot() {
+        let opts = EncodeOptions { profile: Profile::compact(), ..EncodeOptions::default() };
+        let enc = encode_message_with(&sample(), &opts).unwrap();
+        let dec_opts = DecodeOptions {
+            profile: Profile::compact(),
+            max_depth: 3,
+            duplicate_map_keys: DuplicateKeys::KeepLast,
+            ..DecodeOptions::default()
+        };
+        let mut dec = StreamingDecoder::with_options(dec_opts.clone());
+        let half = enc.len() / 2;
+        dec.feed(&enc[..half]);
+        let first = dec.next_field().unwrap().unwrap();
+
+        let snapshot = dec.snapshot().unwrap();
+        let mut resumed = StreamingDecoder::restore(&snapshot).unwrap();
+        assert_eq!(resumed.opts, dec_opts);
+        assert_eq!(resumed.buffered(), dec.buffered());
+        resumed.feed(&enc[half..]);
+        let mut fields = vec![first];
+        while let Some(f) = resumed.next_field() {
+            fields.push(f.unwrap());
+        }
+        assert_eq!(fields, sample());
+        resumed.finish().unwrap();
+
+        // ошибки после восстановления отсчитываются от начала потока
+        let first = crate::encode_field(&sample()[0]).unwrap();
+        let mut dec = StreamingDecoder::new();
+        dec.feed(&first);
+        dec.next_field().unwrap().unwrap();
+        let mut resumed = StreamingDecoder::restore(&dec.snapshot().unwrap()).unwrap();
+        resumed.feed(&[0x55, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let err = DecodeError::InvalidTypeCode { offset: first.len(), code: 0x55 };
+        assert_eq!(resumed.next_field(), Some(Err(err)));
+    }
+
+    #[test]
+    fn invalid_snapshots() {
+        let snapshot = StreamingDecoder::new().snapshot().unwrap();
+        let mut bad = snapshot.clone();
+        bad[HEADER_LEN + 20] ^= 1;
+        assert!(matches!(
+            StreamingDecoder::restore(&bad),
+            Err(DecodeError::ChecksumMismatch { .. })
+        ));
+
+        // снимок неизвестной версии
+        let mut fields = decode_enveloped(&snapshot).unwrap();
+        fields[0].value = Value::UInt8(9);
+        let opts = EncodeOptions { checksum: Checksum::Crc32, ..EncodeOptions::default() };
+        let enc = encode_enveloped_with(&fields, &opts).unwrap();
+        assert_eq!(
+            StreamingDecoder::restore(&enc).err(),
+            Some(DecodeError::InvalidValue { offset: HEADER_LEN, type_code: 19 })
+        );
+        fields.truncate(3);
+        let enc = encode_enveloped_with(&fields, &opts).unwrap();
+        let err = DecodeError::UnexpectedEof { offset: enc.len() };
+        assert_eq!(StreamingDecoder::restore(&enc).err(), Some(err));
+    }
+
+    #[test]
+    fn errors_and_limits() {
+        let enc = encode_message(&sample()).unwrap();
+        let mut dec = StreamingDecoder::new();
+        dec.feed(&enc[..enc.len() - 1]);
+        assert_eq!(dec.next_field().unwrap().unwrap(), sample()[0]);
+        assert_eq!(dec.next_field().unwrap().unwrap(), sample()[1]);
+        assert!(dec.next_field().is_none());
+        assert_eq!(dec.finish(), Err(DecodeError::UnexpectedEof { offset: enc.len() - 1 }));
+
+        // второе поле с неизвестным кодом типа
+        let mut bad = enc.clone();
+        let second = sample()[0].encoded_len();
+        bad[second] = 0x55;
+        let mut dec = StreamingDecoder::new();
+        dec.feed(&bad);
+        assert!(dec.next_field().unwrap().is_ok());
+        assert_eq!(
+            dec.next_field(),
+            Some(Err(DecodeError::InvalidTypeCode { offset: second, code: 0x55 }))
+        );
+        assert_eq!(dec.next_field(), None);
+
+        // длина значения отвергается по заголовку, не дожидаясь данных
+        let opts = DecodeOptions { max_value_len: 4, ..DecodeOptions::default() };
+        let mut dec = StreamingDecoder::with_options(opts);
+        dec.feed(&enc[..10]);
+        assert_eq!(
+            dec.next_field(),
+            Some(Err(DecodeError::LimitExceeded { offset: 6, limit: Limit::ValueLength }))
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! Поля с числовыми тегами вместо строковых ключей
+//!
+//! Ключ поля заменяется тегом (varint), а соответствие тегов ключам
+//! задаётся таблицей [`KeyTable`]. Таблица либо пишется один раз в начале
+//! данных (`encode_tagged`), либо известна обеим сторонам заранее
+//! (`encode_with_table`). Формат заголовка: число ключей, затем каждый
+//! ключ как длина (varint) и байты UTF-8; тег ключа — его номер в таблице.
+
+use std::collections::HashMap;
+
+use crate::decode::{Decoder, Input, Reader};
+use crate::encode::Writer;
+use crate::{
+    decode_message_with, encode_message_with, utf8, varint, DecodeError, DecodeOptions, EncodeError,
+    EncodeOptions, Error, Field, Limit, Value,
+};
+
+/// Соответствие числовых тегов ключам полей
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyTable {
+    keys: Vec<String>,
+    tags: HashMap<String, u32>,
+}
+
+impl KeyTable {
+    pub fn new() -> Self {
+        KeyTable::default()
+    }
+
+    /// Таблица всех ключей сообщения, включая вложенные, в порядке появления
+    pub fn from_fields(fields: &[Field]) -> Self {
+        let mut table = KeyTable::new();
+        for f in fields {
+            visit_keys(f, &mut |key| {
+                table.insert(key);
+            });
+        }
+        table
+    }
+
+    /// Тег ключа; новый ключ получает следующий свободный тег
+    pub fn insert(&mut self, key: &str) -> u32 {
+        if let Some(&tag) = self.tags.get(key) {
+            return tag;
+        }
+        let tag = self.keys.len() as u32;
+        self.keys.push(key.to_owned());
+        self.tags.insert(key.to_owned(), tag);
+        tag
+    }
+
+    pub fn tag(&self, key: &str) -> Option<u32> {
+        self.tags.get(key).copied()
+    }
+
+    pub fn key(&self, tag: u32) -> Option<&str> {
+        self.keys.get(tag as usize).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Ключи в порядке тегов
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(String::as_str)
+    }
+
+    fn write_header(&self, out: &mut Vec<u8>) {
+        varint::write(out, self.keys.len() as u64).expect("запись в Vec не завершается ошибкой");
+        for key in &self.keys {
+            varint::write(out, key.len() as u64).expect("запись в Vec не завершается ошибкой");
+            out.extend_from_slice(key.as_bytes());
+        }
+    }
+}
+
+/// Обход ключей поля и всех вложенных полей
+fn visit_keys<'f>(field: &'f Field, f: &mut impl FnMut(&'f str)) {
+    f(&field.key);
+    visit_value_keys(&field.value, f);
+}
+
+fn visit_value_keys<'f>(value: &'f Value, f: &mut impl FnMut(&'f str)) {
+    match value {
+        Value::Message(fields) => fields.iter().for_each(|field| visit_keys(field, f)),
+        Value::List(items) => items.iter().for_each(|v| visit_value_keys(v, f)),
+        Value::Map(entries) => entries.iter().for_each(|(k, v)| {
+            visit_value_keys(k, f);
+            visit_value_keys(v, f);
+        }),
+        Value::Enum { payload: Some(p), .. } => visit_value_keys(p, f),
+        _ => {}
+    }
+}
+
+/// Кодирование сообщения с таблицей ключей в заголовке
+pub fn encode_tagged(fields: &[Field], opts: &EncodeOptions) -> Result<Vec<u8>, EncodeError> {
+    let table = KeyTable::from_fields(fields);
+    let mut out = Vec::new();
+    table.write_header(&mut out);
+    write_tagged(fields, &table, opts, &mut out)?;
+    Ok(out)
+}
+
+/// Кодирование сообщения с тегами по заранее известной таблице
+///
+/// Таблица в данные не пишется.
+pub fn encode_with_table(
+    fields: &[Field],
+    table: &KeyTable,
+    opts: &EncodeOptions,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut missing = None;
+    for f in fields {
+        visit_keys(f, &mut |key| {
+            if missing.is_none() && table.tag(key).is_none() {
+                missing = Some(key);
+            }
+        });
+    }
+    if let Some(key) = missing {
+        return Err(EncodeError::UnknownKey { key: key.to_owned() });
+    }
+    let mut out = Vec::new();
+    write_tagged(fields, table, opts, &mut out)?;
+    Ok(out)
+}
+
+fn write_tagged(
+    fields: &[Field],
+    table: &KeyTable,
+    opts: &EncodeOptions,
+    out: &mut Vec<u8>,
+) -> Result<(), EncodeError> {
+    let mut w = Writer::vec(out, opts.profile).with_tags(table);
+    fields.iter().try_for_each(|f| w.field(f))
+}
+
+/// Декодирование сообщения, закодированного `encode_tagged`
+pub fn decode_tagged(data: &[u8], opts: &DecodeOptions) -> Result<Vec<Field>, DecodeError> {
+    let mut dec = Decoder::new(Reader::new(data), opts);
+    let table = read_header(&mut dec)?;
+    read_tagged(data, dec.input.pos(), &table, opts)
+}
+
+/// Декодирование сообщения с тегами по заранее известной таблице
+pub fn decode_with_table(
+    data: &[u8],
+    table: &KeyTable,
+    opts: &DecodeOptions,
+) -> Result<Vec<Field>, DecodeError> {
+    read_tagged(data, 0, table, opts)
+}
+
+/// Перевод сообщения с тегами в обычное, с ключами
+pub fn tagged_to_keyed(data: &[u8], opts: &DecodeOptions) -> Result<Vec<u8>, Error> {
+    let fields = decode_tagged(data, opts)?;
+    let enc = EncodeOptions { profile: opts.profile, ..EncodeOptions::default() };
+    Ok(encode_message_with(&fields, &enc)?)
+}
+
+/// Перевод обычного сообщения в сообщение с тегами и таблицей ключей
+pub fn keyed_to_tagged(data: &[u8], opts: &DecodeOptions) -> Result<Vec<u8>, Error> {
+    let fields = decode_message_with(data, opts)?;
+    let enc = EncodeOptions { profile: opts.profile, ..EncodeOptions::default() };
+    Ok(encode_tagged(&fields, &enc)?)
+}
+
+fn read_tagged(
+    data: &[u8],
+    pos: usize,
+    table: &KeyTable,
+    opts: &DecodeOptions,
+) -> Result<Vec<Field>, DecodeError> {
+    let mut dec = Decoder::new(Reader::at(data, pos), opts).with_tags(table);
+    let mut fields = Vec::new();
+    while dec.input.pos() < data.len() {
+        fields.push(dec.read_field()?);
+    }
+    Ok(fields)
+}
+
+/// Чтение таблицы ключей из заголовка с проверкой ограничений
+fn read_header<'a, I: Input<'a>>(dec: &mut Decoder<'_, I>) -> Result<KeyTable, DecodeError> {
+    let opts = dec.opts();
+    let offset = dec.input.pos();
+    let count = dec.read_varint()?;
+    if count > opts.max_fields as u64 {
+        return Err(DecodeError::LimitExceeded { offset, limit: Limit::FieldCount });
+    }
+    let mut table = KeyTable::new();
+    for _ in 0..count {
+        let len_offset = dec.input.pos();
+        let len = usize::try_from(dec.read_varint()?)
+            .map_err(|_| DecodeError::InvalidVarint { offset: len_offset })?;
+        let key_offset = dec.input.pos();
+        if len > opts.max_value_len {
+            let limit = Limit::ValueLength;
+            return Err(DecodeError::LimitExceeded { offset: len_offset, limit });
+        }
+        if key_offset.saturating_add(len) > opts.max_total_len {
+            let limit = Limit::TotalLength;
+            return Err(DecodeError::LimitExceeded { offset: len_offset, limit });
+        }
+        let key = utf8::cow(dec.take(len)?)
+            .ok_or(DecodeError::InvalidUtf8 { offset: key_offset })?;
+        if table.tag(&key).is_some() {
+            return Err(DecodeError::DuplicateKey { offset: len_offset });
+        }
+        table.insert(&key);
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode_message, Profile};
+
+    fn sample() -> Vec<Field> {
+        let point = |x| {
+            Value::Message(vec![
+                Field { key: "x".into(), value: Value::Int32(x) },
+                Field { key: "y".into(), value: Value::Int32(-x) },
+            ])
+        };
+        vec![
+            Field { key: "name".into(), value: Value::String("probe".into()) },
+            Field { key: "points".into(), value: Value::List(vec![point(1), point(2), point(3)]) },
+        ]
+    }
+
+    #[test]
+    fn tagged_roundtrip() {
+        let fields = sample();
+        let table = KeyTable::from_fields(&fields);
+        assert_eq!(table.keys().collect::<Vec<_>>(), ["name", "points", "x", "y"]);
+
+        let opts = EncodeOptions::default();
+        let enc = encode_tagged(&fields, &opts).unwrap();
+        assert!(enc.len() < encode_message(&fields).unwrap().len());
+        assert_eq!(decode_tagged(&enc, &DecodeOptions::default()).unwrap(), fields);
+
+        let compact = EncodeOptions { profile: Profile::compact(), ..EncodeOptions::default() };
+        let dec = DecodeOptions { profile: Profile::compact(), ..DecodeOptions::default() };
+        let enc = encode_with_table(&fields, &table, &compact).unwrap();
+        assert_eq!(decode_with_table(&enc, &table, &dec).unwrap(), fields);
+    }
+
+    #[test]
+    fn translate_between_layouts() {
+        let keyed = encode_message(&sample()).unwrap();
+        let opts = DecodeOptions::default();
+        let tagged = keyed_to_tagged(&keyed, &opts).unwrap();
+        assert_eq!(tagged_to_keyed(&tagged, &opts).unwrap(), keyed);
+    }
+
+    #[test]
+    fn tag_errors() {
+        let fields = sample();
+        let mut table = KeyTable::new();
+        table.insert("name");
+        assert_eq!(
+            encode_with_table(&fields, &table, &EncodeOptions::default()).unwrap_err(),
+            EncodeError::UnknownKey { key: "points".into() }
+        );
+
+        // тег 5 при таблице из одного ключа
+        let enc = [3, 5, 0, 0, 0, 1, 1];
+        assert_eq!(
+            decode_with_table(&enc, &table, &DecodeOptions::default()).unwrap_err(),
+            DecodeError::UnknownTag { offset: 1, tag: 5 }
+        );
+
+        // повторяющийся ключ в заголовке
+        let enc = [2, 1, b'a', 1, b'a'];
+        assert_eq!(
+            decode_tagged(&enc, &DecodeOptions::default()).unwrap_err(),
+            DecodeError::DuplicateKey { offset: 3 }
+        );
+    }
+}
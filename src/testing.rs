@@ -0,0 +1,5 @@
+//! Помощники для тестов в зависимых крейтах
+//!
+//! Модуль `strategies` — стратегии proptest для значений формата.
+
+pub mod strategies;
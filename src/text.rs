@@ -0,0 +1,794 @@
+//! Текстовая запись полей в духе текстового формата protobuf
+//!
+//! ```text
+//! probe {
+//!   id: 42
+//!   name: "Rust"
+//!   port: 8080u16
+//!   tags: ["a", "b"]
+//!   inner { ok: true }
+//! }
+//! ```
+//!
+//! Поле — `ключ: значение`, а для сообщения — `ключ { поля }`. Ключ —
+//! идентификатор (`[A-Za-z_][A-Za-z0-9_]*`) или строка в кавычках. Запятые,
+//! точки с запятой и комментарии от `#` до конца строки — разделители.
+//! Значения:
+//!
+//! - целые без суффикса — `Int64` (не помещающиеся — `UInt64`), с суффиксом
+//!   `i8`, `i16`, `i32`, `i64`, `u8`, `u16`, `u32`, `u64` — указанный тип;
+//! - дробные — `Float64`, с суффиксом `f32` — `Float32`; `nan`, `inf`,
+//!   `-inf`, `nanf32`, `inff32`, `-inff32`;
+//! - `true`, `false`, `null`;
+//! - `"строка"` с экранированием `\n \r \t \0 \\ \" \u{…}`;
+//! - `b"байты"` с экранированием `\xNN`;
+//! - `[значения]` — `List`, `{ поля }` — `Message`, `map { k => v }` — `Map`;
+//! - `timestamp(secs, nanos)`, `uuid("…")`, `decimal("1.25")`;
+//! - `enum(variant, name: "Имя", payload: значение)`, `name` и `payload`
+//!   необязательны;
+//! - `unknown(код, b"данные")` — `Unknown`.
+//!
+//! `from_text(&to_text(f))` возвращает то же поле.
+
+use std::fmt::{self, Write};
+
+use crate::typed::{parse_hyphenated, Hyphenated};
+use crate::{Decimal, Field, Timestamp, Value};
+
+/// Наибольшая вложенность сообщений, списков, отображений и перечислений
+/// в тексте, как `max_depth` у `DecodeOptions::default()`: разбор
+/// рекурсивен, и без предела глубокий текст переполнил бы стек
+pub const MAX_TEXT_DEPTH: usize = 64;
+
+/// Текст не разбирается как поле
+///
+/// `line` и `column` считаются с единицы, столбец — в символах.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextError {
+    /// На позиции ожидалось `expected`
+    Unexpected { line: usize, column: usize, expected: &'static str },
+    /// Литерал не задаёт значение, например число вне диапазона; `what` — что за литерал
+    InvalidLiteral { line: usize, column: usize, what: &'static str },
+    /// Вложенность контейнеров больше [`MAX_TEXT_DEPTH`]
+    TooDeep { line: usize, column: usize },
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextError::Unexpected { line, column, expected } => {
+                write!(f, "{line}:{column}: ожидалось {expected}")
+            }
+            TextError::InvalidLiteral { line, column, what } => {
+                write!(f, "{line}:{column}: неверный литерал {what}")
+            }
+            TextError::TooDeep { line, column } => {
+                write!(f, "{line}:{column}: вложенность больше {MAX_TEXT_DEPTH}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+/// Поле в текстовой записи; вложенные сообщения — с отступом в два пробела
+///
+/// ```
+/// use custom_codec::{from_text, to_text, Field, Value};
+///
+/// let f = Field {
+///     key: "probe".into(),
+///     value: Value::Message(vec![
+///         Field { key: "key".into(), value: Value::Int64(42) },
+///         Field { key: "name".into(), value: Value::String("Rust".into()) },
+///     ]),
+/// };
+/// let text = to_text(&f);
+/// assert_eq!(text, "probe {\n  key: 42\n  name: \"Rust\"\n}\n");
+/// assert_eq!(from_text(&text).unwrap(), f);
+/// ```
+pub fn to_text(field: &Field) -> String {
+    let mut out = String::new();
+    write(&mut out, Piece::Line(field, 0));
+    out
+}
+
+/// Значение в одну строку, как справа от `ключ: ` в `to_text`
+///
+/// ```
+/// use custom_codec::{value_to_text, Value};
+///
+/// let v = Value::List(vec![Value::UInt8(1), Value::String("a".into())]);
+/// assert_eq!(value_to_text(&v), r#"[1u8, "a"]"#);
+/// ```
+pub fn value_to_text(value: &Value) -> String {
+    let mut out = String::new();
+    write(&mut out, Piece::Value(value));
+    out
+}
+
+/// Что осталось записать; запись без рекурсии, как у кодировщика
+enum Piece<'v> {
+    Text(&'static str),
+    Indent(usize),
+    Key(&'v str),
+    /// поле на отдельной строке с отступом
+    Line(&'v Field, usize),
+    Value(&'v Value),
+}
+
+fn write(out: &mut String, first: Piece<'_>) {
+    let mut stack = vec![first];
+    while let Some(piece) = stack.pop() {
+        match piece {
+            Piece::Text(s) => out.push_str(s),
+            Piece::Indent(indent) => out.extend(std::iter::repeat_n(' ', indent)),
+            Piece::Key(key) => write_key(out, key),
+            Piece::Line(field, indent) => write_line(out, field, indent, &mut stack),
+            Piece::Value(value) => write_value(out, value, &mut stack),
+        }
+    }
+}
+
+fn write_line<'v>(out: &mut String, field: &'v Field, indent: usize, stack: &mut Vec<Piece<'v>>) {
+    out.extend(std::iter::repeat_n(' ', indent));
+    write_key(out, &field.key);
+    match &field.value {
+        Value::Message(fields) if fields.is_empty() => out.push_str(" {}\n"),
+        Value::Message(fields) => {
+            out.push_str(" {\n");
+            stack.extend([Piece::Text("}\n"), Piece::Indent(indent)]);
+            stack.extend(fields.iter().rev().map(|f| Piece::Line(f, indent + 2)));
+        }
+        value => {
+            out.push_str(": ");
+            stack.extend([Piece::Text("\n"), Piece::Value(value)]);
+        }
+    }
+}
+
+fn is_ident(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn write_key(out: &mut String, key: &str) {
+    if is_ident(key) {
+        out.push_str(key);
+    } else {
+        write_string(out, key);
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\0' => out.push_str("\\0"),
+            c if c.is_control() => write!(out, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_float(out: &mut String, f: f64, suffix: &str) {
+    if f.is_nan() {
+        out.push_str("nan");
+    } else if f.is_infinite() {
+        out.push_str(if f > 0.0 { "inf" } else { "-inf" });
+    } else {
+        write!(out, "{f:?}").unwrap();
+    }
+    out.push_str(suffix);
+}
+
+fn write_bytes(out: &mut String, bytes: &[u8]) {
+    out.push_str("b\"");
+    for &b in bytes {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            0x20..=0x7e => out.push(b as char),
+            _ => write!(out, "\\x{b:02x}").unwrap(),
+        }
+    }
+    out.push('"');
+}
+
+/// Скаляр целиком; у контейнера — начало, остальное добавляется в `stack`
+fn write_value<'v>(out: &mut String, value: &'v Value, stack: &mut Vec<Piece<'v>>) {
+    match value {
+        Value::Int8(i) => write!(out, "{i}i8").unwrap(),
+        Value::Int16(i) => write!(out, "{i}i16").unwrap(),
+        Value::Int32(i) => write!(out, "{i}i32").unwrap(),
+        Value::Int64(i) => write!(out, "{i}").unwrap(),
+        Value::UInt8(u) => write!(out, "{u}u8").unwrap(),
+        Value::UInt16(u) => write!(out, "{u}u16").unwrap(),
+        Value::UInt32(u) => write!(out, "{u}u32").unwrap(),
+        Value::UInt64(u) => write!(out, "{u}u64").unwrap(),
+        // Debug f32 даёт кратчайшую запись, которая читается обратно как то же f32
+        Value::Float32(f) if f.is_finite() => write!(out, "{f:?}f32").unwrap(),
+        Value::Float32(f) => write_float(out, *f as f64, "f32"),
+        Value::Float64(f) => write_float(out, *f, ""),
+        Value::Bool(b) => write!(out, "{b}").unwrap(),
+        Value::String(s) => write_string(out, s),
+        Value::Bytes(bytes) => write_bytes(out, bytes),
+        Value::Null => out.push_str("null"),
+        Value::Message(fields) => {
+            out.push('{');
+            stack.push(Piece::Text(if fields.is_empty() { "}" } else { " }" }));
+            for f in fields.iter().rev() {
+                let sep = match &f.value {
+                    Value::Message(_) => " ",
+                    _ => ": ",
+                };
+                stack.extend([Piece::Value(&f.value), Piece::Text(sep), Piece::Key(&f.key)]);
+                stack.push(Piece::Text(" "));
+            }
+        }
+        Value::List(items) => {
+            out.push('[');
+            stack.push(Piece::Text("]"));
+            for (i, item) in items.iter().enumerate().rev() {
+                stack.push(Piece::Value(item));
+                if i > 0 {
+                    stack.push(Piece::Text(", "));
+                }
+            }
+        }
+        Value::Map(entries) => {
+            out.push_str("map {");
+            stack.push(Piece::Text(if entries.is_empty() { "}" } else { " }" }));
+            for (i, (k, v)) in entries.iter().enumerate().rev() {
+                stack.extend([Piece::Value(v), Piece::Text(" => "), Piece::Value(k)]);
+                stack.push(Piece::Text(if i > 0 { ", " } else { " " }));
+            }
+        }
+        Value::Timestamp(ts) => write!(out, "timestamp({}, {})", ts.secs, ts.nanos).unwrap(),
+        Value::Uuid(u) => write!(out, "uuid(\"{}\")", Hyphenated(u)).unwrap(),
+        Value::Decimal(d) => write!(out, "decimal(\"{d}\")").unwrap(),
+        Value::Enum { variant, name, payload } => {
+            write!(out, "enum({variant}").unwrap();
+            if let Some(name) = name {
+                out.push_str(", name: ");
+                write_string(out, name);
+            }
+            stack.push(Piece::Text(")"));
+            if let Some(payload) = payload {
+                stack.extend([Piece::Value(payload), Piece::Text(", payload: ")]);
+            }
+        }
+        Value::Unknown { type_code, raw } => {
+            write!(out, "unknown({type_code}, ").unwrap();
+            write_bytes(out, raw);
+            out.push(')');
+        }
+    }
+}
+
+/// Поле из текстовой записи; после поля допустимы только разделители
+pub fn from_text(text: &str) -> Result<Field, TextError> {
+    let mut p = Parser { text, pos: 0, depth: 0 };
+    let field = p.field()?;
+    p.skip();
+    if p.pos < text.len() {
+        return Err(p.unexpected("конец текста"));
+    }
+    Ok(field)
+}
+
+/// Значение, записанное в `text` с байта `pos`, и позиция после него
+pub(crate) fn value_at(text: &str, pos: usize) -> Result<(Value, usize), TextError> {
+    let mut p = Parser { text, pos, depth: 0 };
+    let value = p.value()?;
+    Ok((value, p.pos))
+}
+
+/// Число ASCII-цифр в начале строки
+fn digits(s: &str) -> usize {
+    s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len())
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    /// Число открытых контейнеров
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn location(&self, pos: usize) -> (usize, usize) {
+        let before = &self.text[..pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        (line, column)
+    }
+
+    fn unexpected(&self, expected: &'static str) -> TextError {
+        let (line, column) = self.location(self.pos);
+        TextError::Unexpected { line, column, expected }
+    }
+
+    fn invalid(&self, start: usize, what: &'static str) -> TextError {
+        let (line, column) = self.location(start);
+        TextError::InvalidLiteral { line, column, what }
+    }
+
+    /// Вход в контейнер, начатый на байте `start`; выход — `depth -= 1`
+    fn enter(&mut self, start: usize) -> Result<(), TextError> {
+        if self.depth == MAX_TEXT_DEPTH {
+            let (line, column) = self.location(start);
+            return Err(TextError::TooDeep { line, column });
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// Пропуск пробелов, запятых, точек с запятой и комментариев
+    fn skip(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start_matches(|c: char| c.is_whitespace() || ",;".contains(c));
+            self.pos += rest.len() - trimmed.len();
+            if !self.rest().starts_with('#') {
+                return;
+            }
+            self.pos += self.rest().find('\n').unwrap_or(self.rest().len());
+        }
+    }
+
+    /// Пропуск разделителей и символа `c`, если он следующий
+    fn eat(&mut self, c: char) -> bool {
+        self.skip();
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), TextError> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        self.pos += len;
+        Some(&self.text[self.pos - len..self.pos])
+    }
+
+    fn key(&mut self) -> Result<String, TextError> {
+        self.skip();
+        if self.peek() == Some('"') {
+            return self.string();
+        }
+        self.ident().map(str::to_owned).ok_or_else(|| self.unexpected("ключ"))
+    }
+
+    fn field(&mut self) -> Result<Field, TextError> {
+        let key = self.key()?;
+        let value = if self.eat(':') {
+            self.value()?
+        } else if self.eat('{') {
+            self.fields()?
+        } else {
+            return Err(self.unexpected("`:` или `{`"));
+        };
+        Ok(Field { key, value })
+    }
+
+    /// Поля до `}`; `{` уже прочитана
+    fn fields(&mut self) -> Result<Value, TextError> {
+        self.enter(self.pos - 1)?;
+        let mut fields = Vec::new();
+        while !self.eat('}') {
+            if self.pos == self.text.len() {
+                return Err(self.unexpected("`}`"));
+            }
+            fields.push(self.field()?);
+        }
+        self.depth -= 1;
+        Ok(Value::Message(fields))
+    }
+
+    fn value(&mut self) -> Result<Value, TextError> {
+        self.skip();
+        let start = self.pos;
+        match self.peek() {
+            Some('"') => return self.string().map(Value::String),
+            Some('{') => {
+                self.pos += 1;
+                return self.fields();
+            }
+            Some('[') => {
+                self.enter(start)?;
+                self.pos += 1;
+                let mut items = Vec::new();
+                while !self.eat(']') {
+                    if self.pos == self.text.len() {
+                        return Err(self.unexpected("`]`"));
+                    }
+                    items.push(self.value()?);
+                }
+                self.depth -= 1;
+                return Ok(Value::List(items));
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' => return self.number(),
+            _ => {}
+        }
+        if self.rest().starts_with("b\"") {
+            self.pos += 1;
+            return self.bytes().map(Value::Bytes);
+        }
+        let Some(ident) = self.ident() else {
+            return Err(self.unexpected("значение"));
+        };
+        Ok(match ident {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            "null" => Value::Null,
+            "nan" => Value::Float64(f64::NAN),
+            "inf" => Value::Float64(f64::INFINITY),
+            "nanf32" => Value::Float32(f32::NAN),
+            "inff32" => Value::Float32(f32::INFINITY),
+            "map" => {
+                self.enter(start)?;
+                self.expect('{', "`{`")?;
+                let mut entries = Vec::new();
+                while !self.eat('}') {
+                    let k = self.value()?;
+                    self.skip();
+                    if !self.rest().starts_with("=>") {
+                        return Err(self.unexpected("`=>`"));
+                    }
+                    self.pos += 2;
+                    entries.push((k, self.value()?));
+                }
+                self.depth -= 1;
+                Value::Map(entries)
+            }
+            "timestamp" => {
+                self.expect('(', "`(`")?;
+                let secs = self.integer(i64::try_from)?;
+                let nanos = self.integer(u32::try_from)?;
+                self.expect(')', "`)`")?;
+                let ts = Timestamp::new(secs, nanos);
+                Value::Timestamp(ts.ok_or_else(|| self.invalid(start, "timestamp"))?)
+            }
+            "uuid" => {
+                self.expect('(', "`(`")?;
+                let text = self.string()?;
+                self.expect(')', "`)`")?;
+                Value::Uuid(parse_hyphenated(&text).ok_or_else(|| self.invalid(start, "uuid"))?)
+            }
+            "decimal" => {
+                self.expect('(', "`(`")?;
+                let text = self.string()?;
+                self.expect(')', "`)`")?;
+                let d = Decimal::parse(&text);
+                Value::Decimal(d.ok_or_else(|| self.invalid(start, "decimal"))?)
+            }
+            "enum" => {
+                self.enter(start)?;
+                self.expect('(', "`(`")?;
+                let variant = self.integer(u32::try_from)?;
+                let (mut name, mut payload) = (None, None);
+                while !self.eat(')') {
+                    match self.key()?.as_str() {
+                        "name" if name.is_none() => {
+                            self.expect(':', "`:`")?;
+                            name = Some(self.string()?);
+                        }
+                        "payload" if payload.is_none() => {
+                            self.expect(':', "`:`")?;
+                            payload = Some(Box::new(self.value()?));
+                        }
+                        _ => return Err(self.unexpected("`name`, `payload` или `)`")),
+                    }
+                }
+                self.depth -= 1;
+                Value::Enum { variant, name, payload }
+            }
+            "unknown" => {
+                self.expect('(', "`(`")?;
+                let type_code = self.integer(u8::try_from)?;
+                self.skip();
+                if !self.rest().starts_with("b\"") {
+                    return Err(self.unexpected("байты"));
+                }
+                self.pos += 1;
+                let raw = self.bytes()?;
+                self.expect(')', "`)`")?;
+                Value::Unknown { type_code, raw }
+            }
+            _ => {
+                self.pos = start;
+                return Err(self.unexpected("значение"));
+            }
+        })
+    }
+
+    /// Целое без суффикса для аргументов `timestamp`, `enum` и `unknown`
+    fn integer<T, E>(&mut self, convert: impl Fn(i128) -> Result<T, E>) -> Result<T, TextError> {
+        self.skip();
+        let start = self.pos;
+        let rest = self.rest();
+        let body = rest.strip_prefix('-').unwrap_or(rest);
+        let len = rest.len() - body.len() + digits(body);
+        self.pos += len;
+        let n: i128 = rest[..len].parse().map_err(|_| self.unexpected("целое"))?;
+        convert(n).map_err(|_| self.invalid(start, "целое"))
+    }
+
+    fn number(&mut self) -> Result<Value, TextError> {
+        let start = self.pos;
+        let rest = self.rest();
+        let body = rest.strip_prefix('-').unwrap_or(rest);
+        let negative = body.len() != rest.len();
+        if negative && body.starts_with("inf") {
+            self.pos += 1;
+            return match self.ident() {
+                Some("inf") => Ok(Value::Float64(f64::NEG_INFINITY)),
+                Some("inff32") => Ok(Value::Float32(f32::NEG_INFINITY)),
+                _ => {
+                    self.pos = start;
+                    Err(self.unexpected("значение"))
+                }
+            };
+        }
+        let mut len = digits(body);
+        if len == 0 {
+            return Err(self.unexpected("число"));
+        }
+        let mut float = false;
+        if body[len..].starts_with('.') && digits(&body[len + 1..]) > 0 {
+            float = true;
+            len += 1 + digits(&body[len + 1..]);
+        }
+        if body[len..].starts_with(['e', 'E']) {
+            let exp = &body[len + 1..];
+            let sign = usize::from(exp.starts_with(['+', '-']));
+            let exp_digits = digits(&exp[sign..]);
+            if exp_digits > 0 {
+                float = true;
+                len += 1 + sign + exp_digits;
+            }
+        }
+        let literal = &rest[..rest.len() - body.len() + len];
+        self.pos += literal.len();
+        let suffix = self.ident().unwrap_or("");
+        let bad = |what| self.invalid(start, what);
+        Ok(match (suffix, float) {
+            ("", false) => match literal.parse::<i64>() {
+                Ok(i) => Value::Int64(i),
+                Err(_) => Value::UInt64(literal.parse().map_err(|_| bad("целое"))?),
+            },
+            ("", true) | ("f64", _) => Value::Float64(literal.parse().map_err(|_| bad("f64"))?),
+            ("f32", _) => Value::Float32(literal.parse().map_err(|_| bad("f32"))?),
+            ("i8", false) => Value::Int8(literal.parse().map_err(|_| bad("i8"))?),
+            ("i16", false) => Value::Int16(literal.parse().map_err(|_| bad("i16"))?),
+            ("i32", false) => Value::Int32(literal.parse().map_err(|_| bad("i32"))?),
+            ("i64", false) => Value::Int64(literal.parse().map_err(|_| bad("i64"))?),
+            ("u8", false) => Value::UInt8(literal.parse().map_err(|_| bad("u8"))?),
+            ("u16", false) => Value::UInt16(literal.parse().map_err(|_| bad("u16"))?),
+            ("u32", false) => Value::UInt32(literal.parse().map_err(|_| bad("u32"))?),
+            ("u64", false) => Value::UInt64(literal.parse().map_err(|_| bad("u64"))?),
+            _ => return Err(bad("числовой суффикс")),
+        })
+    }
+
+    /// Строка в кавычках; следующий символ — `"`
+    fn string(&mut self) -> Result<String, TextError> {
+        self.expect('"', "строка")?;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            let Some(c) = self.peek() else {
+                return Err(self.unexpected("`\"`"));
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let c = self.peek().ok_or_else(|| self.unexpected("экранирование"))?;
+                    self.pos += c.len_utf8();
+                    out.push(match c {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        '0' => '\0',
+                        '\\' | '"' => c,
+                        'u' => {
+                            let rest = self.rest();
+                            let hex = rest.strip_prefix('{').and_then(|r| r.split_once('}'));
+                            let code = hex.and_then(|(h, _)| u32::from_str_radix(h, 16).ok());
+                            let c = code.and_then(char::from_u32);
+                            let c = c.ok_or_else(|| self.invalid(start, "\\u{…}"))?;
+                            self.pos += hex.map_or(0, |(h, _)| h.len() + 2);
+                            c
+                        }
+                        _ => return Err(self.invalid(start, "экранирование")),
+                    });
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    /// Байты `b"…"`; `b` уже прочитана
+    fn bytes(&mut self) -> Result<Vec<u8>, TextError> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let start = self.pos;
+            let Some(c) = self.peek() else {
+                return Err(self.unexpected("`\"`"));
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let c = self.peek().ok_or_else(|| self.unexpected("экранирование"))?;
+                    self.pos += 1;
+                    out.push(match c {
+                        'n' => b'\n',
+                        'r' => b'\r',
+                        't' => b'\t',
+                        '0' => 0,
+                        '\\' | '"' => c as u8,
+                        'x' => {
+                            let hex = self.rest().get(..2).filter(|h| {
+                                h.bytes().all(|b| b.is_ascii_hexdigit())
+                            });
+                            let b = hex.and_then(|h| u8::from_str_radix(h, 16).ok());
+                            self.pos += 2;
+                            b.ok_or_else(|| self.invalid(start, "\\xNN"))?
+                        }
+                        _ => return Err(self.invalid(start, "экранирование")),
+                    });
+                }
+                c if c.is_ascii() => out.push(c as u8),
+                _ => return Err(self.invalid(start, "байт вне ASCII")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn text_roundtrip() {
+        let f = field(
+            "probe",
+            Value::Message(vec![
+                field("id", Value::Int64(-42)),
+                field("big", Value::UInt64(u64::MAX)),
+                field("small", Value::Int8(-3)),
+                field("ratio", Value::Float32(0.1)),
+                field("exp", Value::Float64(1e-7)),
+                field("neg", Value::Float64(f64::NEG_INFINITY)),
+                field("name", Value::String("say \"hi\"\n\u{1}".into())),
+                field("blob", Value::Bytes(vec![b'a', 0, 255, b'"'])),
+                field("none", Value::Null),
+                field("tags", Value::List(vec![Value::Bool(true), Value::Message(vec![])])),
+                field("lookup", Value::Map(vec![(Value::Int32(1), Value::String("one".into()))])),
+                field("at", Value::Timestamp(Timestamp { secs: -5, nanos: 7 })),
+                field("uuid", Value::Uuid([0xab; 16])),
+                field("sum", Value::Decimal(Decimal { mantissa: -125, scale: 2 })),
+                field(
+                    "state",
+                    Value::Enum {
+                        variant: 2,
+                        name: Some("Done".into()),
+                        payload: Some(Box::new(Value::Message(vec![field("x", Value::Int64(1))]))),
+                    },
+                ),
+                field("with space", Value::Message(vec![field("empty", Value::Message(vec![]))])),
+                field("future", Value::Unknown { type_code: 0x55, raw: vec![1, b'x'] }),
+            ]),
+        );
+        let text = to_text(&f);
+        assert!(text.contains("  ratio: 0.1f32\n"), "{text}");
+        assert!(text.contains("  state: enum(2, name: \"Done\", payload: { x: 1 })\n"), "{text}");
+        assert!(text.contains("  \"with space\" {\n    empty {}\n  }\n"), "{text}");
+        assert!(text.contains("  future: unknown(85, b\"\\x01x\")\n"), "{text}");
+        assert_eq!(from_text(&text).unwrap(), f);
+    }
+
+    #[test]
+    fn text_parse_errors() {
+        let fixture = "msg { # комментарий\n  a: 1, b: -2.5e3; c { } list: [1 2] }";
+        let expected = field(
+            "msg",
+            Value::Message(vec![
+                field("a", Value::Int64(1)),
+                field("b", Value::Float64(-2500.0)),
+                field("c", Value::Message(vec![])),
+                field("list", Value::List(vec![Value::Int64(1), Value::Int64(2)])),
+            ]),
+        );
+        assert_eq!(from_text(fixture).unwrap(), expected);
+
+        let err = |line, column, expected| TextError::Unexpected { line, column, expected };
+        assert_eq!(from_text("a 1"), Err(err(1, 3, "`:` или `{`")));
+        assert_eq!(from_text("a {\n  b: }"), Err(err(2, 6, "значение")));
+        assert_eq!(from_text("a: 1 b: 2"), Err(err(1, 6, "конец текста")));
+        assert_eq!(
+            from_text("a: 300u8"),
+            Err(TextError::InvalidLiteral { line: 1, column: 4, what: "u8" })
+        );
+        assert_eq!(
+            from_text("a: uuid(\"x\")"),
+            Err(TextError::InvalidLiteral { line: 1, column: 4, what: "uuid" })
+        );
+    }
+
+    #[test]
+    fn depth_limit() {
+        let list = |depth| format!("a: {}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(from_text(&list(MAX_TEXT_DEPTH)).is_ok());
+        let too_deep = TextError::TooDeep { line: 1, column: 4 + MAX_TEXT_DEPTH };
+        assert_eq!(from_text(&list(MAX_TEXT_DEPTH + 1)), Err(too_deep.clone()));
+        // без предела такой текст переполнил бы стек
+        assert_eq!(from_text(&list(1_000_000)), Err(too_deep));
+
+        let nested = format!("a {{{}", "b {".repeat(MAX_TEXT_DEPTH));
+        assert!(matches!(from_text(&nested), Err(TextError::TooDeep { .. })));
+        let mixed = format!("a: {}", "map { 1 => enum(1 payload: [".repeat(MAX_TEXT_DEPTH));
+        assert!(matches!(from_text(&mixed), Err(TextError::TooDeep { .. })));
+    }
+
+    #[test]
+    fn deep_values_are_written_without_recursion() {
+        let depth = 10_000;
+        let (mut list, mut msg) = (Value::Null, Value::Null);
+        for _ in 0..depth {
+            list = Value::List(vec![list]);
+            msg = Value::Message(vec![field("m", msg)]);
+        }
+        let f = field("l", list);
+        let expected = format!("l: {}null{}\n", "[".repeat(depth), "]".repeat(depth));
+        assert_eq!(to_text(&f), expected);
+        let expected = format!("{}{{ m: null{}", "{ m ".repeat(depth - 1), " }".repeat(depth));
+        assert_eq!(value_to_text(&msg), expected);
+
+        // удаление таких значений рекурсивно, поэтому они разбираются вручную
+        let mut list = f.value;
+        while let Value::List(mut items) = list {
+            list = items.pop().unwrap();
+        }
+        while let Value::Message(mut fields) = msg {
+            msg = fields.pop().unwrap().value;
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+
+use crate::endian::Number;
+use crate::Endianness;
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+/// Момент времени в UTC: секунды от эпохи Unix и наносекунды внутри секунды
+///
+/// На проводе занимает 12 байт: `secs` (i64) и `nanos` (u32).
+/// `nanos` всегда меньше 1 000 000 000, декодер отвергает иные значения.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    pub secs: i64,
+    pub nanos: u32,
+}
+
+impl Timestamp {
+    /// Момент времени; `None`, если `nanos` не меньше секунды
+    pub const fn new(secs: i64, nanos: u32) -> Option<Self> {
+        if nanos < NANOS_PER_SEC {
+            Some(Timestamp { secs, nanos })
+        } else {
+            None
+        }
+    }
+
+    /// Из числа наносекунд от эпохи
+    pub const fn from_unix_nanos(nanos: i128) -> Option<Self> {
+        let secs = nanos.div_euclid(NANOS_PER_SEC as i128);
+        if secs < i64::MIN as i128 || secs > i64::MAX as i128 {
+            return None;
+        }
+        Some(Timestamp { secs: secs as i64, nanos: nanos.rem_euclid(NANOS_PER_SEC as i128) as u32 })
+    }
+
+    /// Число наносекунд от эпохи
+    pub const fn unix_nanos(&self) -> i128 {
+        self.secs as i128 * NANOS_PER_SEC as i128 + self.nanos as i128
+    }
+
+    pub(crate) fn to_bytes(self, endian: Endianness) -> [u8; 12] {
+        let mut out = [0u8; 12];
+        out[..8].copy_from_slice(&self.secs.to_bytes(endian));
+        out[8..].copy_from_slice(&self.nanos.to_bytes(endian));
+        out
+    }
+
+    pub(crate) fn from_bytes(bytes: [u8; 12], endian: Endianness) -> Option<Self> {
+        let (secs, nanos) = bytes.split_at(8);
+        Timestamp::new(
+            i64::from_bytes(secs.try_into().unwrap(), endian),
+            u32::from_bytes(nanos.try_into().unwrap(), endian),
+        )
+    }
+}
+
+/// Момент времени не представим в целевом типе
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampOutOfRange;
+
+impl fmt::Display for TimestampOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("момент времени вне допустимого диапазона")
+    }
+}
+
+impl std::error::Error for TimestampOutOfRange {}
+
+#[cfg(feature = "chrono")]
+mod chrono_impls {
+    use super::*;
+    use chrono::{DateTime, Utc};
+
+    impl From<DateTime<Utc>> for Timestamp {
+        fn from(dt: DateTime<Utc>) -> Self {
+            // секунда координации хранится в chrono как nanos >= 10^9
+            let nanos = dt.timestamp_subsec_nanos().min(NANOS_PER_SEC - 1);
+            Timestamp { secs: dt.timestamp(), nanos }
+        }
+    }
+
+    impl TryFrom<Timestamp> for DateTime<Utc> {
+        type Error = TimestampOutOfRange;
+
+        fn try_from(ts: Timestamp) -> Result<Self, Self::Error> {
+            DateTime::from_timestamp(ts.secs, ts.nanos).ok_or(TimestampOutOfRange)
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+mod time_impls {
+    use super::*;
+    use time::OffsetDateTime;
+
+    impl From<OffsetDateTime> for Timestamp {
+        fn from(dt: OffsetDateTime) -> Self {
+            // диапазон OffsetDateTime заведомо уже диапазона Timestamp
+            Timestamp::from_unix_nanos(dt.unix_timestamp_nanos()).unwrap()
+        }
+    }
+
+    impl TryFrom<Timestamp> for OffsetDateTime {
+        type Error = TimestampOutOfRange;
+
+        fn try_from(ts: Timestamp) -> Result<Self, Self::Error> {
+            OffsetDateTime::from_unix_timestamp_nanos(ts.unix_nanos())
+                .map_err(|_| TimestampOutOfRange)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_nanos_before_epoch() {
+        let ts = Timestamp::from_unix_nanos(-1).unwrap();
+        assert_eq!(ts, Timestamp { secs: -1, nanos: 999_999_999 });
+        assert_eq!(ts.unix_nanos(), -1);
+        assert_eq!(Timestamp::new(0, NANOS_PER_SEC), None);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_conversion() {
+        let dt = chrono::DateTime::from_timestamp(1_700_000_000, 5).unwrap();
+        let ts = Timestamp::from(dt);
+        assert_eq!(ts, Timestamp { secs: 1_700_000_000, nanos: 5 });
+        assert_eq!(chrono::DateTime::try_from(ts), Ok(dt));
+        assert!(chrono::DateTime::<chrono::Utc>::try_from(Timestamp { secs: i64::MAX, nanos: 0 })
+            .is_err());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_conversion() {
+        let dt = time::OffsetDateTime::from_unix_timestamp_nanos(-1_500_000_000).unwrap();
+        let ts = Timestamp::from(dt);
+        assert_eq!(ts, Timestamp { secs: -2, nanos: 500_000_000 });
+        assert_eq!(time::OffsetDateTime::try_from(ts), Ok(dt));
+    }
+}
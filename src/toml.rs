@@ -0,0 +1,174 @@
+//! Сообщения в TOML и обратно
+//!
+//! Документ — таблица ключей полей. В TOML меньше типов, чем в формате,
+//! поэтому преобразование с потерями, как для JSON:
+//!
+//! - целые — целые TOML (`UInt64` больше `i64::MAX` не записывается);
+//! - `Float32` и `Float64` — дробные; `Bool` и `String` — как есть;
+//! - `Bytes` — строка base64, `Uuid` и `Decimal` — строки;
+//! - `Message` — таблица, `List` — массив, `Map` — массив пар `[ключ, значение]`;
+//! - `Timestamp` — таблица `{secs, nanos}`;
+//! - вариант перечисления — таблица `{имя: содержимое}` или, без
+//!   содержимого, строка с именем.
+//!
+//! В TOML нет null, поэтому `Value::Null` не записывается. При чтении целые
+//! становятся `Int64`, дробные — `Float64`, даты и время — строками в
+//! записи TOML. Порядок ключей сохраняется. Для редактирования без потери
+//! типов подходит YAML.
+
+use std::fmt;
+
+use ::toml::{Table, Value as Toml};
+
+use crate::typed::{type_name, Hyphenated};
+use crate::{base64, Field, Value};
+
+/// Поля не переводятся в TOML или обратно
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TomlError {
+    /// Текст не разбирается как TOML
+    Syntax(String),
+    /// Значение не представимо в TOML: `Null` или слишком большое `UInt64`
+    Unsupported { what: &'static str },
+}
+
+impl fmt::Display for TomlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TomlError::Syntax(msg) => write!(f, "неверный TOML: {msg}"),
+            TomlError::Unsupported { what } => write!(f, "значение {what} не записывается в TOML"),
+        }
+    }
+}
+
+impl std::error::Error for TomlError {}
+
+/// Поля как документ TOML
+///
+/// ```
+/// use custom_codec::{to_toml, Field, Value};
+///
+/// let fields = vec![
+///     Field { key: "port".into(), value: Value::UInt16(8080) },
+///     Field { key: "host".into(), value: Value::String("localhost".into()) },
+/// ];
+/// assert_eq!(to_toml(&fields).unwrap(), "port = 8080\nhost = \"localhost\"\n");
+/// ```
+pub fn to_toml(fields: &[Field]) -> Result<String, TomlError> {
+    ::toml::to_string(&table(fields)?).map_err(|e| TomlError::Syntax(e.to_string()))
+}
+
+/// Поля из документа TOML
+pub fn from_toml(text: &str) -> Result<Vec<Field>, TomlError> {
+    let table: Table = text.parse().map_err(|e: ::toml::de::Error| {
+        TomlError::Syntax(e.message().to_owned())
+    })?;
+    Ok(fields(&table))
+}
+
+fn table(fields: &[Field]) -> Result<Table, TomlError> {
+    fields.iter().map(|f| Ok((f.key.clone(), value_to_toml(&f.value)?))).collect()
+}
+
+fn fields(table: &Table) -> Vec<Field> {
+    table.iter().map(|(k, v)| Field { key: k.clone(), value: value_from_toml(v) }).collect()
+}
+
+/// Значение без ключа
+pub fn value_to_toml(value: &Value) -> Result<Toml, TomlError> {
+    Ok(match value {
+        Value::Int8(i) => Toml::Integer(*i as i64),
+        Value::Int16(i) => Toml::Integer(*i as i64),
+        Value::Int32(i) => Toml::Integer(*i as i64),
+        Value::Int64(i) => Toml::Integer(*i),
+        Value::UInt8(u) => Toml::Integer(*u as i64),
+        Value::UInt16(u) => Toml::Integer(*u as i64),
+        Value::UInt32(u) => Toml::Integer(*u as i64),
+        Value::UInt64(u) => Toml::Integer(
+            i64::try_from(*u).map_err(|_| TomlError::Unsupported { what: "UInt64" })?,
+        ),
+        Value::Float32(f) => Toml::Float(*f as f64),
+        Value::Float64(f) => Toml::Float(*f),
+        Value::Bool(b) => Toml::Boolean(*b),
+        Value::String(s) => Toml::String(s.clone()),
+        Value::Bytes(b) => Toml::String(base64::encode(b)),
+        Value::Null | Value::Unknown { .. } => {
+            return Err(TomlError::Unsupported { what: type_name(value) })
+        }
+        Value::Message(fields) => Toml::Table(table(fields)?),
+        Value::List(items) => {
+            Toml::Array(items.iter().map(value_to_toml).collect::<Result<_, _>>()?)
+        }
+        Value::Map(entries) => Toml::Array(
+            entries
+                .iter()
+                .map(|(k, v)| Ok(Toml::Array(vec![value_to_toml(k)?, value_to_toml(v)?])))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Timestamp(ts) => Toml::Table(Table::from_iter([
+            ("secs".to_owned(), Toml::Integer(ts.secs)),
+            ("nanos".to_owned(), Toml::Integer(ts.nanos as i64)),
+        ])),
+        Value::Uuid(u) => Toml::String(Hyphenated(u).to_string()),
+        Value::Decimal(d) => Toml::String(d.to_string()),
+        Value::Enum { variant, name, payload } => {
+            let name = name.clone().unwrap_or_else(|| variant.to_string());
+            match payload {
+                Some(p) => Toml::Table(Table::from_iter([(name, value_to_toml(p)?)])),
+                None => Toml::String(name),
+            }
+        }
+    })
+}
+
+/// Значение из TOML
+pub fn value_from_toml(toml: &Toml) -> Value {
+    match toml {
+        Toml::Integer(i) => Value::Int64(*i),
+        Toml::Float(f) => Value::Float64(*f),
+        Toml::Boolean(b) => Value::Bool(*b),
+        Toml::String(s) => Value::String(s.clone()),
+        Toml::Datetime(dt) => Value::String(dt.to_string()),
+        Toml::Array(items) => Value::List(items.iter().map(value_from_toml).collect()),
+        Toml::Table(table) => Value::Message(fields(table)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Timestamp;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn toml_roundtrip() {
+        let fields = vec![
+            field("name", Value::String("svc".into())),
+            field("ratio", Value::Float64(0.5)),
+            field("tags", Value::List(vec![Value::Int64(1), Value::Int64(2)])),
+            field("db", Value::Message(vec![field("port", Value::Int64(5432))])),
+        ];
+        let text = to_toml(&fields).unwrap();
+        assert_eq!(text, "name = \"svc\"\nratio = 0.5\ntags = [1, 2]\n\n[db]\nport = 5432\n");
+        assert_eq!(from_toml(&text).unwrap(), fields);
+
+        let ts = field("at", Value::Timestamp(Timestamp { secs: 5, nanos: 7 }));
+        let back = from_toml(&to_toml(&[ts]).unwrap()).unwrap();
+        let secs_nanos = vec![field("secs", Value::Int64(5)), field("nanos", Value::Int64(7))];
+        assert_eq!(back, vec![field("at", Value::Message(secs_nanos))]);
+    }
+
+    #[test]
+    fn toml_errors() {
+        let null = [field("x", Value::Null)];
+        assert_eq!(to_toml(&null), Err(TomlError::Unsupported { what: "Null" }));
+        let big = [field("x", Value::UInt64(u64::MAX))];
+        assert_eq!(to_toml(&big), Err(TomlError::Unsupported { what: "UInt64" }));
+        assert!(matches!(from_toml("x = "), Err(TomlError::Syntax(_))));
+        let date = from_toml("d = 1979-05-27T07:32:00Z").unwrap();
+        assert_eq!(date, vec![field("d", Value::String("1979-05-27T07:32:00Z".into()))]);
+    }
+}
@@ -0,0 +1,680 @@
+//! Вспомогательные инструменты вокруг формата
+//!
+//! [`generate_dissector`] по схеме пишет диссектор Wireshark на Lua для
+//! сообщений в рамках `[длина u32 BE][сообщение]` поверх TCP, в исходном
+//! профиле. Диссектор разбирает любые поля, а по схеме подписывает ожидаемые
+//! типы, даёт скалярам фильтры вида `ccodec_probe.schema.inner.ok` и
+//! отмечает неизвестные, пропущенные обязательные и несовпавшие по типу поля.
+//!
+//! Файл кладётся в каталог плагинов Wireshark; порт выбирается через
+//! «Decode As…».
+//!
+//! [`inspect`] разбирает сообщение в дерево [`Node`] со смещениями каждой
+//! части элемента — для разбора испорченных данных без своей программы.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::decode::{Decoder, Input, Reader, Scope};
+use crate::envelope::envelope_body;
+use crate::{DecodeError, DecodeOptions, Schema, SchemaType, Value, MAGIC};
+
+/// Коды и имена типов на проводе
+const TYPE_NAMES: [(u8, &str); 21] = [
+    (1, "Int32"),
+    (2, "Float32"),
+    (3, "Bool"),
+    (4, "String"),
+    (5, "Bytes"),
+    (6, "Message"),
+    (7, "Int64"),
+    (8, "UInt64"),
+    (9, "Float64"),
+    (10, "Null"),
+    (11, "List"),
+    (12, "Map"),
+    (13, "Timestamp"),
+    (14, "Uuid"),
+    (15, "Decimal"),
+    (16, "Enum"),
+    (17, "Int8"),
+    (18, "Int16"),
+    (19, "UInt8"),
+    (20, "UInt16"),
+    (21, "UInt32"),
+];
+
+/// Разбор полей, значений и элементов; не зависит от схемы
+const RUNTIME: &str = r#"
+local function type_name(code)
+  return TYPES[code] or ("код " .. code)
+end
+
+local function scalar_text(code, r)
+  local len = r:len()
+  if code == 1 and len == 4 then return tostring(r:int())
+  elseif code == 2 and len == 4 then return tostring(r:float())
+  elseif code == 3 and len == 1 then return tostring(r:uint() ~= 0)
+  elseif code == 4 then return '"' .. r:string(ENC_UTF_8) .. '"'
+  elseif code == 5 then return len .. " байт"
+  elseif code == 7 and len == 8 then return tostring(r:int64())
+  elseif code == 8 and len == 8 then return tostring(r:uint64())
+  elseif code == 9 and len == 8 then return tostring(r:float())
+  elseif code == 10 and len == 0 then return "null"
+  elseif code == 13 and len == 12 then
+    return string.format("%s с %d нс", tostring(r(0, 8):int64()), r(8, 4):uint())
+  elseif code == 14 and len == 16 then return r:bytes():tohex()
+  elseif code == 15 and len == 17 then
+    return string.format("%s, масштаб %d", r(1, 16):bytes():tohex(), r(0, 1):uint())
+  elseif code == 17 and len == 1 then return tostring(r:int())
+  elseif code == 18 and len == 2 then return tostring(r:int())
+  elseif code == 19 and len == 1 then return tostring(r:uint())
+  elseif code == 20 and len == 2 then return tostring(r:uint())
+  elseif code == 21 and len == 4 then return tostring(r:uint())
+  end
+  return nil
+end
+
+local dissect_value, dissect_element, dissect_fields
+
+-- Элемент списка, отображения или содержимое варианта: код, длина, значение
+dissect_element = function(buf, pos, stop, tree, label, desc)
+  if pos + 5 > stop then
+    tree:add_proto_expert_info(E.malformed)
+    return nil
+  end
+  local code = buf(pos, 1):uint()
+  local len = buf(pos + 1, 4):uint()
+  if pos + 5 + len > stop then
+    tree:add_proto_expert_info(E.malformed)
+    return nil
+  end
+  local item = tree:add(F.element, buf(pos, 5 + len))
+  item:set_text(label .. ": " .. type_name(code))
+  item:add(F.type, buf(pos, 1))
+  item:add(F.len, buf(pos + 1, 4))
+  dissect_value(buf, pos + 5, code, len, item, desc)
+  return 5 + len
+end
+
+dissect_value = function(buf, pos, code, len, tree, desc)
+  local stop = pos + len
+  if desc and desc.code and desc.code ~= code and code ~= 10 then
+    tree:add_proto_expert_info(E.type, "ожидался " .. desc.name)
+    desc = nil
+  end
+  if code == 6 then
+    dissect_fields(buf, pos, stop, tree, desc and desc.sub)
+  elseif code == 11 then
+    local i = 0
+    while pos < stop do
+      local size = dissect_element(buf, pos, stop, tree, "[" .. i .. "]", desc and desc.item)
+      if not size then return end
+      pos = pos + size
+      i = i + 1
+    end
+  elseif code == 12 then
+    local i = 0
+    while pos < stop do
+      local size = dissect_element(buf, pos, stop, tree, "[" .. i .. "].key", desc and desc.key)
+      if not size then return end
+      pos = pos + size
+      size = dissect_element(buf, pos, stop, tree, "[" .. i .. "].value", desc and desc.val)
+      if not size then return end
+      pos = pos + size
+      i = i + 1
+    end
+  elseif code == 16 then
+    if len < 5 then
+      tree:add_proto_expert_info(E.malformed)
+      return
+    end
+    tree:add(F.variant, buf(pos, 4))
+    local flags = buf(pos + 4, 1):uint()
+    pos = pos + 5
+    if flags % 2 == 1 then
+      if pos + 4 > stop or pos + 4 + buf(pos, 4):uint() > stop then
+        tree:add_proto_expert_info(E.malformed)
+        return
+      end
+      local n = buf(pos, 4):uint()
+      tree:add(F.variant_name, buf(pos + 4, n))
+      pos = pos + 4 + n
+    end
+    if math.floor(flags / 2) % 2 == 1 then
+      dissect_element(buf, pos, stop, tree, "payload", nil)
+    end
+  else
+    local r = buf(pos, len)
+    local text = scalar_text(code, r)
+    if not text then
+      -- значение известного типа неверной длины; неизвестный тип не ошибка
+      if TYPES[code] then
+        tree:add_proto_expert_info(E.malformed)
+      end
+      tree:add(F.value, r)
+      return
+    end
+    if desc and desc.pf and desc.code == code then
+      if code == 13 then
+        tree:add(desc.pf, r, NSTime.new(r(0, 8):int64():tonumber(), r(8, 4):uint()))
+      elseif code == 15 then
+        tree:add(desc.pf, r, text)
+      else
+        tree:add(desc.pf, r)
+      end
+    else
+      tree:add(F.value, r):set_text("Значение: " .. text)
+    end
+    tree:append_text(" = " .. text)
+  end
+end
+
+-- Поля сообщения: код, длина ключа, ключ, длина значения, значение
+dissect_fields = function(buf, pos, stop, tree, schema)
+  local seen = {}
+  while pos < stop do
+    if pos + 5 > stop then
+      tree:add_proto_expert_info(E.malformed)
+      return
+    end
+    local code = buf(pos, 1):uint()
+    local klen = buf(pos + 1, 4):uint()
+    local vpos = pos + 9 + klen
+    if vpos > stop or vpos + buf(vpos - 4, 4):uint() > stop then
+      tree:add_proto_expert_info(E.malformed)
+      return
+    end
+    local vlen = buf(vpos - 4, 4):uint()
+    local key = buf(pos + 5, klen):string(ENC_UTF_8)
+    local item = tree:add(F.field, buf(pos, 9 + klen + vlen))
+    item:set_text(key .. ": " .. type_name(code))
+    item:add(F.type, buf(pos, 1))
+    item:add(F.key, buf(pos + 5, klen))
+    item:add(F.len, buf(vpos - 4, 4))
+    local desc = schema and schema.fields[key]
+    if schema and not desc and not schema.allow_unknown then
+      item:add_proto_expert_info(E.unknown)
+    end
+    if code ~= 10 then
+      seen[key] = true
+    end
+    dissect_value(buf, vpos, code, vlen, item, desc)
+    pos = vpos + vlen
+  end
+  if schema then
+    for key, desc in pairs(schema.fields) do
+      if desc.required and not seen[key] then
+        tree:add_proto_expert_info(E.missing, "нет обязательного поля " .. key)
+      end
+    end
+  end
+end
+
+local function frame_len(buf, pinfo, offset)
+  return buf(offset, 4):uint() + 4
+end
+
+local function dissect_frame(buf, pinfo, tree)
+  pinfo.cols.protocol = proto.name
+  local len = buf(0, 4):uint()
+  local root = tree:add(proto, buf(0, len + 4))
+  root:add(F.frame_len, buf(0, 4))
+  dissect_fields(buf, 4, len + 4, root, SCHEMA)
+  return len + 4
+end
+
+function proto.dissector(buf, pinfo, tree)
+  dissect_tcp_pdus(buf, tree, 4, frame_len, dissect_frame)
+  return buf:len()
+end
+
+DissectorTable.get("tcp.port"):add_for_decode_as(proto)
+"#;
+
+/// Диссектор Wireshark на Lua для сообщений схемы `schema` в рамках
+///
+/// Протокол называется `ccodec_<имя схемы>`, для безымянной — `ccodec`.
+///
+/// ```
+/// use custom_codec::tooling::generate_dissector;
+/// use custom_codec::{Schema, SchemaType};
+///
+/// let mut schema = Schema::named("Probe");
+/// schema.required("id", SchemaType::Int64);
+///
+/// let lua = generate_dissector(&schema);
+/// assert!(lua.contains(r#"Proto("ccodec_probe", "custom_codec Probe")"#));
+/// assert!(lua.contains(r#"ProtoField.int64("ccodec_probe.schema.id", "id")"#));
+/// ```
+pub fn generate_dissector(schema: &Schema) -> String {
+    let proto = match schema.name() {
+        Some(name) => format!("ccodec_{}", abbrev_part(name)),
+        None => "ccodec".to_owned(),
+    };
+    let title = format!("custom_codec {}", schema.name().unwrap_or("message"));
+    let mut gen = Generator { proto, fields: Vec::new(), abbrevs: HashSet::new() };
+    let table = gen.schema_table(schema, "", "", 0);
+    let p = &gen.proto;
+
+    let mut out = String::new();
+    writeln!(out, "-- Диссектор Wireshark для {title}").unwrap();
+    writeln!(out, "-- Сгенерирован custom_codec::tooling::generate_dissector").unwrap();
+    writeln!(out, "-- Рамки [длина u32 BE][сообщение] в исходном профиле").unwrap();
+    writeln!(out, "-- Порт выбирается через «Decode As…»").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "local proto = Proto({}, {})", lua_string(p), lua_string(&title)).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "local TYPES = {{").unwrap();
+    for (code, name) in TYPE_NAMES {
+        writeln!(out, "  [{code}] = \"{name}\",").unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "local F = {{").unwrap();
+    writeln!(out, "  frame_len = ProtoField.uint32(\"{p}.frame_len\", \"Длина рамки\"),").unwrap();
+    writeln!(out, "  field = ProtoField.none(\"{p}.field\", \"Поле\"),").unwrap();
+    writeln!(out, "  element = ProtoField.none(\"{p}.element\", \"Элемент\"),").unwrap();
+    writeln!(out, "  type = ProtoField.uint8(\"{p}.type\", \"Тип\", base.DEC, TYPES),").unwrap();
+    writeln!(out, "  key = ProtoField.string(\"{p}.key\", \"Ключ\"),").unwrap();
+    writeln!(out, "  len = ProtoField.uint32(\"{p}.len\", \"Длина\"),").unwrap();
+    writeln!(out, "  value = ProtoField.bytes(\"{p}.value\", \"Значение\"),").unwrap();
+    writeln!(out, "  variant = ProtoField.uint32(\"{p}.variant\", \"Вариант\"),").unwrap();
+    writeln!(out, "  variant_name = ProtoField.string(\"{p}.variant_name\", \"Имя варианта\"),")
+        .unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "-- Скалярные поля схемы").unwrap();
+    writeln!(out, "local P = {{}}").unwrap();
+    for (i, field) in gen.fields.iter().enumerate() {
+        writeln!(out, "P[{}] = {field}", i + 1).unwrap();
+    }
+    writeln!(out).unwrap();
+    writeln!(out, "local all_fields = {{}}").unwrap();
+    writeln!(out, "for _, f in pairs(F) do table.insert(all_fields, f) end").unwrap();
+    writeln!(out, "for _, f in ipairs(P) do table.insert(all_fields, f) end").unwrap();
+    writeln!(out, "proto.fields = all_fields").unwrap();
+    writeln!(out).unwrap();
+    let expert = |out: &mut String, name: &str, text: &str, group: &str, severity: &str| {
+        writeln!(
+            out,
+            "  {name} = ProtoExpert.new(\"{p}.expert.{name}\", \"{text}\", \
+             expert.group.{group}, expert.severity.{severity}),"
+        )
+        .unwrap();
+    };
+    writeln!(out, "local E = {{").unwrap();
+    expert(&mut out, "malformed", "Данные обрезаны или неверной длины", "MALFORMED", "ERROR");
+    expert(&mut out, "unknown", "Поля нет в схеме", "PROTOCOL", "WARN");
+    expert(&mut out, "missing", "Нет обязательного поля", "PROTOCOL", "WARN");
+    expert(&mut out, "type", "Тип не совпадает со схемой", "PROTOCOL", "WARN");
+    writeln!(out, "}}").unwrap();
+    writeln!(out, "proto.experts = {{ E.malformed, E.unknown, E.missing, E.type }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "-- Схема: код и имя типа, поле Wireshark, вложенные схемы").unwrap();
+    writeln!(out, "local SCHEMA = {table}").unwrap();
+    out.push_str(RUNTIME);
+    out
+}
+
+struct Generator {
+    proto: String,
+    /// Объявления `ProtoField` в порядке номеров в таблице `P`
+    fields: Vec<String>,
+    abbrevs: HashSet<String>,
+}
+
+impl Generator {
+    fn schema_table(&mut self, schema: &Schema, path: &str, label: &str, indent: usize) -> String {
+        let pad = "  ".repeat(indent);
+        let mut out = String::from("{\n");
+        writeln!(out, "{pad}  allow_unknown = {},", schema.allow_unknown()).unwrap();
+        writeln!(out, "{pad}  fields = {{").unwrap();
+        for f in schema.fields() {
+            let path = join(path, &abbrev_part(&f.key));
+            let label = join(label, &f.key);
+            let desc = self.desc(&f.ty, &path, &label, indent + 2, Some(f.required));
+            writeln!(out, "{pad}    [{}] = {desc},", lua_string(&f.key)).unwrap();
+        }
+        writeln!(out, "{pad}  }},").unwrap();
+        write!(out, "{pad}}}").unwrap();
+        out
+    }
+
+    /// Описание типа: `code`, `name`, `required` для полей сообщения и
+    /// `pf`, `sub`, `item`, `key`, `val` по виду типа
+    fn desc(
+        &mut self,
+        ty: &SchemaType,
+        path: &str,
+        label: &str,
+        indent: usize,
+        required: Option<bool>,
+    ) -> String {
+        let mut parts = Vec::new();
+        if let Some(code) = wire_code(ty) {
+            parts.push(format!("code = {code}"));
+        }
+        parts.push(format!("name = {}", lua_string(&ty.to_string())));
+        if let Some(required) = required {
+            parts.push(format!("required = {required}"));
+        }
+        match ty {
+            SchemaType::Message(schema) => {
+                parts.push(format!("sub = {}", self.schema_table(schema, path, label, indent)))
+            }
+            SchemaType::List(item) => {
+                parts.push(format!("item = {}", self.desc(item, path, label, indent, None)))
+            }
+            SchemaType::Map(k, v) => {
+                let (kp, kl) = (join(path, "key"), join(label, "key"));
+                parts.push(format!("key = {}", self.desc(k, &kp, &kl, indent, None)));
+                let (vp, vl) = (join(path, "value"), join(label, "value"));
+                parts.push(format!("val = {}", self.desc(v, &vp, &vl, indent, None)));
+            }
+            scalar => {
+                if let Some(ctor) = proto_field(scalar) {
+                    parts.push(format!("pf = P[{}]", self.field(ctor, path, label)));
+                }
+            }
+        }
+        format!("{{ {} }}", parts.join(", "))
+    }
+
+    /// Номер нового `ProtoField` в `P`; повтор фильтра получает суффикс
+    fn field(&mut self, ctor: &str, path: &str, label: &str) -> usize {
+        let base = format!("{}.schema.{path}", self.proto);
+        let mut abbrev = base.clone();
+        let mut n = 2;
+        while !self.abbrevs.insert(abbrev.clone()) {
+            abbrev = format!("{base}_{n}");
+            n += 1;
+        }
+        let decl = format!("ProtoField.{ctor}({}, {})", lua_string(&abbrev), lua_string(label));
+        self.fields.push(decl);
+        self.fields.len()
+    }
+}
+
+fn join(path: &str, part: &str) -> String {
+    match path {
+        "" => part.to_owned(),
+        _ => format!("{path}.{part}"),
+    }
+}
+
+/// Часть имени фильтра: строчные латинские буквы, цифры и `_`
+fn abbrev_part(s: &str) -> String {
+    let part: String = s
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if part.is_empty() {
+        "_".to_owned()
+    } else {
+        part
+    }
+}
+
+/// Код типа на проводе; `None` для `Any`
+fn wire_code(ty: &SchemaType) -> Option<u8> {
+    Some(match ty {
+        SchemaType::Any => return None,
+        SchemaType::Int32 => 1,
+        SchemaType::Float32 => 2,
+        SchemaType::Bool => 3,
+        SchemaType::String => 4,
+        SchemaType::Bytes => 5,
+        SchemaType::Message(_) => 6,
+        SchemaType::Int64 => 7,
+        SchemaType::UInt64 => 8,
+        SchemaType::Float64 => 9,
+        SchemaType::List(_) => 11,
+        SchemaType::Map(..) => 12,
+        SchemaType::Timestamp => 13,
+        SchemaType::Uuid => 14,
+        SchemaType::Decimal => 15,
+        SchemaType::Enum => 16,
+        SchemaType::Int8 => 17,
+        SchemaType::Int16 => 18,
+        SchemaType::UInt8 => 19,
+        SchemaType::UInt16 => 20,
+        SchemaType::UInt32 => 21,
+    })
+}
+
+/// Конструктор `ProtoField` для скалярного типа
+fn proto_field(ty: &SchemaType) -> Option<&'static str> {
+    Some(match ty {
+        SchemaType::Bool => "bool",
+        SchemaType::Int8 => "int8",
+        SchemaType::Int16 => "int16",
+        SchemaType::Int32 => "int32",
+        SchemaType::Int64 => "int64",
+        SchemaType::UInt8 => "uint8",
+        SchemaType::UInt16 => "uint16",
+        SchemaType::UInt32 => "uint32",
+        SchemaType::UInt64 => "uint64",
+        SchemaType::Float32 => "float",
+        SchemaType::Float64 => "double",
+        SchemaType::String | SchemaType::Decimal => "string",
+        SchemaType::Bytes => "bytes",
+        SchemaType::Uuid => "guid",
+        SchemaType::Timestamp => "absolute_time",
+        _ => return None,
+    })
+}
+
+/// Строка Lua в двойных кавычках
+fn lua_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_ascii_control() => write!(out, "\\{:03}", c as u8).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Имя типа по его коду на проводе
+pub fn type_name(type_code: u8) -> Option<&'static str> {
+    TYPE_NAMES.iter().find(|&&(code, _)| code == type_code).map(|&(_, name)| name)
+}
+
+/// Элемент закодированного сообщения и положение его частей во входе
+///
+/// Части идут подряд: код типа с `offset`, префикс длины ключа до
+/// `key_offset`, ключ до `len_offset`, префикс длины до `value_offset`,
+/// значение до `end`. У элементов без ключа `key_offset == len_offset`,
+/// у встроенных скаляров (`Profile::inline_scalars`) значение пусто, у
+/// ключей-тегов префикса длины ключа нет.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    /// Ключ поля; `None` у элементов списков, отображений и перечислений
+    pub key: Option<String>,
+    pub type_code: u8,
+    pub offset: usize,
+    pub key_offset: usize,
+    pub len_offset: usize,
+    pub value_offset: usize,
+    pub end: usize,
+    /// Значение скаляра; у перечисления — номер и имя варианта без
+    /// содержимого, у остальных контейнеров `None`
+    pub value: Option<Value>,
+    /// Поля сообщения, элементы списка, ключи и значения отображения
+    /// попеременно или содержимое варианта
+    pub children: Vec<Node>,
+}
+
+/// Дерево элементов сообщения со смещениями для отладки данных
+///
+/// Конверт распознаётся по сигнатуре `MAGIC`: профиль берётся из него,
+/// а смещения отсчитываются от начала конверта. Ограничения `opts`
+/// применяются, кроме `duplicate_map_keys`.
+///
+/// ```
+/// use custom_codec::tooling::inspect;
+/// use custom_codec::{encode_message, DecodeOptions, Field, Value};
+///
+/// let fields = vec![Field { key: "id".into(), value: Value::Int32(7) }];
+/// let nodes = inspect(&encode_message(&fields).unwrap(), &DecodeOptions::default()).unwrap();
+/// assert_eq!(nodes[0].key.as_deref(), Some("id"));
+/// assert_eq!((nodes[0].value_offset, nodes[0].end), (11, 15));
+/// ```
+pub fn inspect(data: &[u8], opts: &DecodeOptions) -> Result<Vec<Node>, DecodeError> {
+    let (body, opts) = match data.starts_with(&MAGIC) {
+        true => {
+            let (body, profile) = envelope_body(data)?;
+            (body, DecodeOptions { profile, ..opts.clone() })
+        }
+        false => (0..data.len(), opts.clone()),
+    };
+    let mut decoder = Decoder::new(Reader::at(&data[..body.end], body.start), &opts);
+    let mut roots = Vec::new();
+    // открытые контейнеры; обход без рекурсии, как у декодера
+    let mut open: Vec<(Node, Scope)> = Vec::new();
+    loop {
+        let pos = decoder.input.pos();
+        match open.last() {
+            None if pos == body.end => return Ok(roots),
+            Some((node, scope)) if decoder.scope_done(scope) => {
+                // у ключа отображения должно быть значение
+                if node.type_code == 12 && node.children.len() % 2 == 1 {
+                    return Err(DecodeError::UnexpectedEof { offset: scope.end });
+                }
+                let (node, scope) = open.pop().unwrap();
+                decoder.close(&scope)?;
+                attach(&mut open, &mut roots, node);
+                continue;
+            }
+            Some((node, _)) if node.type_code == 16 && !node.children.is_empty() => {
+                return Err(DecodeError::InvalidValue { offset: pos, type_code: 16 });
+            }
+            _ => {}
+        }
+        let keyed = !matches!(open.last(), Some((node, _)) if node.type_code != 6);
+        match decoder.next_node(keyed, open.len())? {
+            (node, Some(scope)) => open.push((node, scope)),
+            (node, None) => attach(&mut open, &mut roots, node),
+        }
+    }
+}
+
+fn attach(open: &mut [(Node, Scope)], roots: &mut Vec<Node>, node: Node) {
+    match open.last_mut() {
+        Some((parent, _)) => parent.children.push(node),
+        None => roots.push(node),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dissector_describes_schema() {
+        let mut inner = Schema::named("Inner");
+        inner.required("ok", SchemaType::Bool);
+        let mut schema = Schema::named("Probe");
+        schema.required("id", SchemaType::Int64);
+        schema.optional("tags", SchemaType::List(Box::new(SchemaType::String)));
+        schema.optional("inner", SchemaType::Message(inner));
+        schema.optional("any", SchemaType::Any);
+
+        let lua = generate_dissector(&schema);
+        assert!(lua.contains("P[1] = ProtoField.int64(\"ccodec_probe.schema.id\", \"id\")\n"));
+        assert!(lua.contains("P[2] = ProtoField.string(\"ccodec_probe.schema.tags\", \"tags\")\n"));
+        let ok = "P[3] = ProtoField.bool(\"ccodec_probe.schema.inner.ok\", \"inner.ok\")\n";
+        assert!(lua.contains(ok));
+        assert!(lua.contains(
+            "    [\"id\"] = { code = 7, name = \"Int64\", required = true, pf = P[1] },\n"
+        ));
+        assert!(lua.contains(
+            "    [\"tags\"] = { code = 11, name = \"List<String>\", required = false, \
+             item = { code = 4, name = \"String\", pf = P[2] } },\n"
+        ));
+        assert!(lua.contains("        [\"ok\"] = { code = 3, name = \"Bool\", required = true"));
+        assert!(lua.contains("    [\"any\"] = { name = \"Any\", required = false },\n"));
+        assert!(lua.contains("  [21] = \"UInt32\",\n"));
+        assert!(lua.ends_with("DissectorTable.get(\"tcp.port\"):add_for_decode_as(proto)\n"));
+    }
+
+    #[test]
+    fn dissector_names() {
+        let mut schema = Schema::new();
+        schema.required("Display name", SchemaType::String);
+        schema.required("display-name", SchemaType::Decimal);
+        schema.required("\"q\"\n", SchemaType::Uuid);
+
+        let lua = generate_dissector(&schema);
+        assert!(lua.contains("Proto(\"ccodec\", \"custom_codec message\")"));
+        assert!(lua.contains("(\"ccodec.schema.display_name\", \"Display name\")"));
+        assert!(lua.contains("(\"ccodec.schema.display_name_2\", \"display-name\")"));
+        assert!(lua.contains("ProtoField.guid(\"ccodec.schema._q__\", \"\\\"q\\\"\\n\")"));
+        assert_eq!(lua_string("a\u{1}б"), "\"a\\001б\"");
+    }
+
+    fn field(key: &str, value: Value) -> crate::Field {
+        crate::Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn inspect_offsets() {
+        let fields = vec![
+            field("m", Value::Message(vec![field("a", Value::Bool(true))])),
+            field("l", Value::List(vec![Value::UInt8(1)])),
+        ];
+        let data = crate::encode_message(&fields).unwrap();
+        let nodes = inspect(&data, &DecodeOptions::default()).unwrap();
+        let m = &nodes[0];
+        let parts = (m.offset, m.key_offset, m.len_offset, m.value_offset, m.end);
+        assert_eq!(parts, (0, 5, 6, 10, 21));
+        assert_eq!(m.children, vec![Node {
+            key: Some("a".into()),
+            type_code: 3,
+            offset: 10,
+            key_offset: 15,
+            len_offset: 16,
+            value_offset: 20,
+            end: 21,
+            value: Some(Value::Bool(true)),
+            children: Vec::new(),
+        }]);
+        let item = &nodes[1].children[0];
+        assert_eq!((item.key.as_deref(), item.offset, item.key_offset), (None, 31, 32));
+        assert_eq!((item.value_offset, item.end), (36, 37));
+        assert_eq!(item.value, Some(Value::UInt8(1)));
+
+        // ошибки те же, что у декодера
+        let cut = &data[..data.len() - 1];
+        let err = inspect(cut, &DecodeOptions::default()).unwrap_err();
+        assert_eq!(err, crate::decode_message(cut).unwrap_err());
+    }
+
+    #[test]
+    fn inspect_envelope() {
+        let payload = Some(Box::new(Value::Int32(1)));
+        let value = Value::Enum { variant: 2, name: None, payload };
+        let opts = crate::EncodeOptions {
+            profile: crate::Profile::compact(),
+            ..crate::EncodeOptions::default()
+        };
+        let data = crate::encode_enveloped_with(&[field("e", value)], &opts).unwrap();
+        let nodes = inspect(&data, &DecodeOptions::default()).unwrap();
+        assert_eq!((nodes[0].offset, nodes[0].type_code), (crate::HEADER_LEN, 16));
+        let variant = Value::Enum { variant: 2, name: None, payload: None };
+        assert_eq!(nodes[0].value, Some(variant));
+        // встроенный скаляр без длины и значения
+        let payload = &nodes[0].children[0];
+        assert_eq!(payload.value, Some(Value::Int32(1)));
+        assert_eq!((payload.len_offset, payload.value_offset), (payload.end, payload.end));
+        assert_eq!(type_name(payload.type_code), None);
+        assert_eq!(type_name(16), Some("Enum"));
+    }
+}
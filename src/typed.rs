@@ -0,0 +1,744 @@
+//! Типизированные значения: трейты `Encode` и `Decode`
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+use crate::{Decimal, Field, Timestamp, Value};
+
+/// Преобразование в значение формата
+///
+/// Структура с `#[derive(Encode, Decode)]` (признак `derive`) становится
+/// `Value::Message`, где каждое поле структуры — поле сообщения. Атрибуты
+/// полей:
+///
+/// - `#[codec(rename = "k")]` — ключ `k` вместо имени поля;
+/// - `#[codec(skip)]` — поле не записывается, при чтении `Default::default()`;
+/// - `#[codec(default)]` — при отсутствии ключа `Default::default()`;
+/// - `#[codec(default = "path")]` — при отсутствии ключа вызов `path()`.
+///
+/// Без `default` отсутствие ключа — ошибка `ValueError::MissingField`, кроме
+/// полей `Option<T>`: они читаются как `None`, как у структур `codegen`,
+/// которые `None` не записывают. Лишние поля сообщения при чтении пропускаются. Кортежные структуры
+/// записываются как `Value::List`, структуры с одним безымянным полем —
+/// как значение этого поля, структуры без полей — как `Value::Null`.
+///
+/// Вариант перечисления записывается как `Value::Message` с именем варианта
+/// в поле `type`; поля варианта-структуры следуют за ним, содержимое
+/// кортежного варианта записывается в поле `value` по правилам кортежных
+/// структур. Атрибуты перечисления и вариантов:
+///
+/// - `#[codec(tag = "kind", content = "data")]` — другие ключи имени и содержимого;
+/// - `#[codec(rename = "v")]` — имя `v` вместо имени варианта;
+/// - `#[codec(other)]` — вариант без полей для неизвестных имён; без него
+///   неизвестное имя даёт `ValueError::UnknownVariant`.
+pub trait Encode {
+    fn encode_value(&self) -> Value;
+}
+
+/// Восстановление из значения формата
+pub trait Decode: Sized {
+    fn decode_value(v: &Value) -> Result<Self, ValueError>;
+
+    /// Значение поля структуры, ключа которого нет в сообщении;
+    /// `None` — поле обязательно
+    fn decode_missing() -> Option<Self> {
+        None
+    }
+}
+
+/// Значение не соответствует ожидаемому типу
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueError {
+    /// Значение другого типа; `expected` — ожидаемый тип
+    InvalidType { expected: &'static str, found: &'static str },
+    /// В сообщении нет обязательного поля
+    MissingField { key: String },
+    /// Число не помещается в целевой тип
+    OutOfRange { expected: &'static str },
+    /// В кортеже другое число элементов
+    InvalidLength { expected: usize, found: usize },
+    /// Имя варианта не соответствует ни одному варианту перечисления
+    UnknownVariant { name: String },
+    /// Ошибка в значении поля `key`
+    InField { key: String, source: Box<ValueError> },
+}
+
+impl ValueError {
+    /// Ошибка о значении `v` вместо ожидаемого типа
+    pub fn invalid_type(expected: &'static str, v: &Value) -> Self {
+        ValueError::InvalidType { expected, found: type_name(v) }
+    }
+
+    /// Привязка ошибки к полю `key`
+    pub fn in_field(self, key: &str) -> Self {
+        ValueError::InField { key: key.to_owned(), source: Box::new(self) }
+    }
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueError::InvalidType { expected, found } => {
+                write!(f, "ожидался {expected}, получен {found}")
+            }
+            ValueError::MissingField { key } => write!(f, "нет поля {key:?}"),
+            ValueError::OutOfRange { expected } => write!(f, "число вне диапазона {expected}"),
+            ValueError::InvalidLength { expected, found } => {
+                write!(f, "ожидалось элементов: {expected}, получено: {found}")
+            }
+            ValueError::UnknownVariant { name } => write!(f, "неизвестный вариант {name:?}"),
+            ValueError::InField { key, source } => write!(f, "поле {key:?}: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for ValueError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ValueError::InField { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Имя варианта `Value` для сообщений об ошибках
+pub(crate) fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Int8(_) => "Int8",
+        Value::Int16(_) => "Int16",
+        Value::Int32(_) => "Int32",
+        Value::Int64(_) => "Int64",
+        Value::UInt8(_) => "UInt8",
+        Value::UInt16(_) => "UInt16",
+        Value::UInt32(_) => "UInt32",
+        Value::UInt64(_) => "UInt64",
+        Value::Float32(_) => "Float32",
+        Value::Float64(_) => "Float64",
+        Value::Bool(_) => "Bool",
+        Value::String(_) => "String",
+        Value::Bytes(_) => "Bytes",
+        Value::Message(_) => "Message",
+        Value::Null => "Null",
+        Value::List(_) => "List",
+        Value::Map(_) => "Map",
+        Value::Timestamp(_) => "Timestamp",
+        Value::Uuid(_) => "Uuid",
+        Value::Decimal(_) => "Decimal",
+        Value::Enum { .. } => "Enum",
+        Value::Unknown { .. } => "Unknown",
+    }
+}
+
+/// UUID в виде `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`
+pub(crate) struct Hyphenated<'a>(pub(crate) &'a [u8; 16]);
+
+impl fmt::Display for Hyphenated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                f.write_str("-")?;
+            }
+            write!(f, "{b:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// UUID из записи `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` в любом регистре
+pub(crate) fn parse_hyphenated(text: &str) -> Option<[u8; 16]> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 36 || [8, 13, 18, 23].iter().any(|&i| bytes[i] != b'-') {
+        return None;
+    }
+    let hex: Vec<u8> = bytes.iter().copied().filter(|&b| b != b'-').collect();
+    if hex.len() != 32 || !hex.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let mut out = [0u8; 16];
+    for (o, pair) in out.iter_mut().zip(hex.chunks(2)) {
+        *o = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(out)
+}
+
+/// Поля сообщения, в которое закодирована структура; для кода `derive`
+pub fn message_fields(v: &Value) -> Result<&[Field], ValueError> {
+    match v {
+        Value::Message(fields) => Ok(fields),
+        other => Err(ValueError::invalid_type("Message", other)),
+    }
+}
+
+/// Значение первого поля с ключом `key`
+pub fn find_field<'a>(fields: &'a [Field], key: &str) -> Option<&'a Value> {
+    fields.iter().find(|f| f.key == key).map(|f| &f.value)
+}
+
+/// Значение `[default = …]` из схемы, записанное в `codegen` через `value_to_text`
+pub fn schema_default(text: &str) -> Value {
+    crate::from_text(&format!("default: {text}"))
+        .expect("значение по умолчанию из схемы записано value_to_text")
+        .value
+}
+
+/// Значение обязательного поля `key`
+pub fn required_field<'a>(fields: &'a [Field], key: &str) -> Result<&'a Value, ValueError> {
+    find_field(fields, key).ok_or_else(|| ValueError::MissingField { key: key.to_owned() })
+}
+
+/// Имя варианта перечисления из поля `tag`
+pub fn variant_name<'a>(fields: &'a [Field], tag: &str) -> Result<&'a str, ValueError> {
+    match required_field(fields, tag)? {
+        Value::String(name) => Ok(name),
+        other => Err(ValueError::invalid_type("String", other).in_field(tag)),
+    }
+}
+
+/// Элементы кортежной структуры ровно из `len` значений
+pub fn tuple_items(v: &Value, len: usize) -> Result<&[Value], ValueError> {
+    match v {
+        Value::List(items) if items.len() == len => Ok(items),
+        Value::List(items) => {
+            Err(ValueError::InvalidLength { expected: len, found: items.len() })
+        }
+        other => Err(ValueError::invalid_type("List", other)),
+    }
+}
+
+/// Байты из `Value::Bytes`; для кода `codegen`
+pub fn decode_bytes(v: &Value) -> Result<Vec<u8>, ValueError> {
+    match v {
+        Value::Bytes(b) => Ok(b.clone()),
+        other => Err(ValueError::invalid_type("Bytes", other)),
+    }
+}
+
+/// UUID из `Value::Uuid`; для кода `codegen`
+pub fn decode_uuid(v: &Value) -> Result<[u8; 16], ValueError> {
+    match v {
+        Value::Uuid(u) => Ok(*u),
+        other => Err(ValueError::invalid_type("Uuid", other)),
+    }
+}
+
+/// Элементы списка, каждый через `item`; для кода `codegen`
+pub fn decode_list<T>(
+    v: &Value,
+    item: impl Fn(&Value) -> Result<T, ValueError>,
+) -> Result<Vec<T>, ValueError> {
+    match v {
+        Value::List(items) => items.iter().map(item).collect(),
+        other => Err(ValueError::invalid_type("List", other)),
+    }
+}
+
+/// Пары отображения в исходном порядке; для кода `codegen`
+pub fn decode_entries<K, V>(
+    v: &Value,
+    key: impl Fn(&Value) -> Result<K, ValueError>,
+    value: impl Fn(&Value) -> Result<V, ValueError>,
+) -> Result<Vec<(K, V)>, ValueError> {
+    match v {
+        Value::Map(entries) => entries.iter().map(|(k, v)| Ok((key(k)?, value(v)?))).collect(),
+        other => Err(ValueError::invalid_type("Map", other)),
+    }
+}
+
+impl Encode for Value {
+    fn encode_value(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl Decode for Value {
+    fn decode_value(v: &Value) -> Result<Self, ValueError> {
+        Ok(v.clone())
+    }
+}
+
+macro_rules! direct {
+    ($($ty:ty => $variant:ident),* $(,)?) => {$(
+        impl Encode for $ty {
+            fn encode_value(&self) -> Value {
+                Value::$variant(self.clone())
+            }
+        }
+
+        impl Decode for $ty {
+            fn decode_value(v: &Value) -> Result<Self, ValueError> {
+                match v {
+                    Value::$variant(x) => Ok(x.clone()),
+                    other => Err(ValueError::invalid_type(stringify!($variant), other)),
+                }
+            }
+        }
+    )*};
+}
+
+direct! {
+    bool => Bool,
+    f32 => Float32,
+    String => String,
+    Timestamp => Timestamp,
+    Decimal => Decimal,
+}
+
+impl Encode for f64 {
+    fn encode_value(&self) -> Value {
+        Value::Float64(*self)
+    }
+}
+
+/// `Float32` расширяется без потерь
+impl Decode for f64 {
+    fn decode_value(v: &Value) -> Result<Self, ValueError> {
+        match v {
+            Value::Float64(x) => Ok(*x),
+            Value::Float32(x) => Ok(*x as f64),
+            other => Err(ValueError::invalid_type("Float64", other)),
+        }
+    }
+}
+
+/// Любое целое значение как `i128`
+fn integer(v: &Value) -> Option<i128> {
+    Some(match *v {
+        Value::Int8(i) => i as i128,
+        Value::Int16(i) => i as i128,
+        Value::Int32(i) => i as i128,
+        Value::Int64(i) => i as i128,
+        Value::UInt8(u) => u as i128,
+        Value::UInt16(u) => u as i128,
+        Value::UInt32(u) => u as i128,
+        Value::UInt64(u) => u as i128,
+        _ => return None,
+    })
+}
+
+/// Целые записываются своим вариантом, а читаются из любого целого
+/// значения, если число помещается в тип
+macro_rules! ints {
+    ($($ty:ty => $variant:ident as $wire:ty),* $(,)?) => {$(
+        impl Encode for $ty {
+            fn encode_value(&self) -> Value {
+                Value::$variant(*self as $wire)
+            }
+        }
+
+        impl Decode for $ty {
+            fn decode_value(v: &Value) -> Result<Self, ValueError> {
+                let expected = stringify!($variant);
+                let i = integer(v).ok_or_else(|| ValueError::invalid_type(expected, v))?;
+                <$ty>::try_from(i).map_err(|_| ValueError::OutOfRange { expected })
+            }
+        }
+    )*};
+}
+
+ints! {
+    i8 => Int8 as i8,
+    i16 => Int16 as i16,
+    i32 => Int32 as i32,
+    i64 => Int64 as i64,
+    isize => Int64 as i64,
+    u8 => UInt8 as u8,
+    u16 => UInt16 as u16,
+    u32 => UInt32 as u32,
+    u64 => UInt64 as u64,
+    usize => UInt64 as u64,
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode_value(&self) -> Value {
+        (**self).encode_value()
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for Box<T> {
+    fn encode_value(&self) -> Value {
+        (**self).encode_value()
+    }
+}
+
+impl<T: Decode> Decode for Box<T> {
+    fn decode_value(v: &Value) -> Result<Self, ValueError> {
+        T::decode_value(v).map(Box::new)
+    }
+}
+
+impl Encode for str {
+    fn encode_value(&self) -> Value {
+        Value::String(self.to_owned())
+    }
+}
+
+/// `None` записывается как `Value::Null`
+impl<T: Encode> Encode for Option<T> {
+    fn encode_value(&self) -> Value {
+        self.as_ref().map_or(Value::Null, T::encode_value)
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode_value(v: &Value) -> Result<Self, ValueError> {
+        match v {
+            Value::Null => Ok(None),
+            v => T::decode_value(v).map(Some),
+        }
+    }
+
+    /// Отсутствующее поле — `None`
+    fn decode_missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode_value(&self) -> Value {
+        Value::List(self.iter().map(T::encode_value).collect())
+    }
+}
+
+/// `Vec<u8>` тоже записывается как `Value::List`; для `Value::Bytes`
+/// служит сам `Value`
+impl<T: Encode> Encode for Vec<T> {
+    fn encode_value(&self) -> Value {
+        self.as_slice().encode_value()
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode_value(v: &Value) -> Result<Self, ValueError> {
+        match v {
+            Value::List(items) => items.iter().map(T::decode_value).collect(),
+            other => Err(ValueError::invalid_type("List", other)),
+        }
+    }
+}
+
+fn encode_map<'a, K, V>(entries: impl Iterator<Item = (&'a K, &'a V)>) -> Value
+where
+    K: Encode + 'a,
+    V: Encode + 'a,
+{
+    Value::Map(entries.map(|(k, v)| (k.encode_value(), v.encode_value())).collect())
+}
+
+fn decode_map<K: Decode, V: Decode, M: FromIterator<(K, V)>>(v: &Value) -> Result<M, ValueError> {
+    match v {
+        Value::Map(entries) => entries
+            .iter()
+            .map(|(k, v)| Ok((K::decode_value(k)?, V::decode_value(v)?)))
+            .collect(),
+        other => Err(ValueError::invalid_type("Map", other)),
+    }
+}
+
+impl<K: Encode, V: Encode, S> Encode for HashMap<K, V, S> {
+    fn encode_value(&self) -> Value {
+        encode_map(self.iter())
+    }
+}
+
+impl<K, V, S> Decode for HashMap<K, V, S>
+where
+    K: Decode + Eq + Hash,
+    V: Decode,
+    S: BuildHasher + Default,
+{
+    fn decode_value(v: &Value) -> Result<Self, ValueError> {
+        decode_map(v)
+    }
+}
+
+impl<K: Encode, V: Encode> Encode for BTreeMap<K, V> {
+    fn encode_value(&self) -> Value {
+        encode_map(self.iter())
+    }
+}
+
+impl<K: Decode + Ord, V: Decode> Decode for BTreeMap<K, V> {
+    fn decode_value(v: &Value) -> Result<Self, ValueError> {
+        decode_map(v)
+    }
+}
+
+impl Encode for () {
+    fn encode_value(&self) -> Value {
+        Value::Null
+    }
+}
+
+impl Decode for () {
+    fn decode_value(v: &Value) -> Result<Self, ValueError> {
+        match v {
+            Value::Null => Ok(()),
+            other => Err(ValueError::invalid_type("Null", other)),
+        }
+    }
+}
+
+/// Кортежи записываются как `Value::List`
+macro_rules! tuples {
+    ($($len:literal => ($($name:ident $idx:tt),+)),* $(,)?) => {$(
+        impl<$($name: Encode),+> Encode for ($($name,)+) {
+            fn encode_value(&self) -> Value {
+                Value::List(vec![$(self.$idx.encode_value()),+])
+            }
+        }
+
+        impl<$($name: Decode),+> Decode for ($($name,)+) {
+            fn decode_value(v: &Value) -> Result<Self, ValueError> {
+                let items = tuple_items(v, $len)?;
+                Ok(($($name::decode_value(&items[$idx])?,)+))
+            }
+        }
+    )*};
+}
+
+tuples! {
+    1 => (A 0),
+    2 => (A 0, B 1),
+    3 => (A 0, B 1, C 2),
+    4 => (A 0, B 1, C 2, D 3),
+    5 => (A 0, B 1, C 2, D 3, E 4),
+    6 => (A 0, B 1, C 2, D 3, E 4, F 5),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip<T: Encode + Decode + PartialEq + fmt::Debug>(x: T, v: Value) {
+        assert_eq!(x.encode_value(), v);
+        assert_eq!(T::decode_value(&v), Ok(x));
+    }
+
+    #[test]
+    fn std_types_roundtrip() {
+        roundtrip(-3i8, Value::Int8(-3));
+        roundtrip(7u16, Value::UInt16(7));
+        roundtrip(9usize, Value::UInt64(9));
+        roundtrip(1.5f32, Value::Float32(1.5));
+        roundtrip("s".to_string(), Value::String("s".into()));
+        roundtrip(Some(true), Value::Bool(true));
+        roundtrip(None::<bool>, Value::Null);
+        roundtrip(vec![1i32, 2], Value::List(vec![Value::Int32(1), Value::Int32(2)]));
+        roundtrip((1u8, "a".to_string()), Value::List(vec![Value::UInt8(1), "a".encode_value()]));
+
+        let map = BTreeMap::from([("a".to_string(), 1i64), ("b".to_string(), 2)]);
+        let entries = vec![
+            (Value::String("a".into()), Value::Int64(1)),
+            (Value::String("b".into()), Value::Int64(2)),
+        ];
+        roundtrip(map.clone(), Value::Map(entries.clone()));
+        let hash: HashMap<String, i64> = HashMap::decode_value(&Value::Map(entries)).unwrap();
+        assert_eq!(hash, map.into_iter().collect());
+
+        // целые читаются из значений другой ширины
+        assert_eq!(u8::decode_value(&Value::Int64(200)), Ok(200));
+        assert_eq!(i64::decode_value(&Value::UInt32(5)), Ok(5));
+        assert_eq!(f64::decode_value(&Value::Float32(0.5)), Ok(0.5));
+    }
+
+    #[test]
+    fn std_types_errors() {
+        let err = ValueError::OutOfRange { expected: "UInt8" };
+        assert_eq!(u8::decode_value(&Value::Int32(-1)), Err(err));
+        let err = ValueError::OutOfRange { expected: "Int64" };
+        assert_eq!(i64::decode_value(&Value::UInt64(u64::MAX)), Err(err));
+        let err = ValueError::InvalidType { expected: "Int32", found: "Float64" };
+        assert_eq!(i32::decode_value(&Value::Float64(1.0)), Err(err));
+
+        let list = Value::List(vec![Value::Int32(1), Value::Null]);
+        let err = ValueError::InvalidType { expected: "Int32", found: "Null" };
+        assert_eq!(Vec::<i32>::decode_value(&list), Err(err));
+        assert_eq!(Option::<Vec<i32>>::decode_value(&Value::Null), Ok(None));
+        let err = ValueError::InvalidLength { expected: 3, found: 2 };
+        assert_eq!(<(i32, i32, i32)>::decode_value(&list), Err(err));
+    }
+
+    #[cfg(feature = "derive")]
+    mod derived {
+        use super::*;
+        use crate::{Decode, Encode};
+
+        fn answer() -> i64 {
+            42
+        }
+
+        #[derive(Debug, PartialEq, Encode, Decode)]
+        struct User {
+            #[codec(rename = "user_id")]
+            id: i64,
+            name: String,
+            #[codec(skip)]
+            session: Option<String>,
+            #[codec(default)]
+            admin: bool,
+            #[codec(default = "answer")]
+            score: i64,
+            inner: Point,
+        }
+
+        #[derive(Debug, PartialEq, Encode, Decode)]
+        struct Point(i32, i32);
+
+        #[derive(Debug, PartialEq, Encode, Decode)]
+        struct Meters(f64);
+
+        #[derive(Debug, PartialEq, Encode, Decode)]
+        struct Nick {
+            nick: Option<String>,
+        }
+
+        #[test]
+        fn derived_struct_roundtrip() {
+            let user = User {
+                id: 7,
+                name: "ann".into(),
+                session: Some("s".into()),
+                admin: true,
+                score: 1,
+                inner: Point(1, -1),
+            };
+            let v = user.encode_value();
+            let point = Value::List(vec![Value::Int32(1), Value::Int32(-1)]);
+            assert_eq!(
+                v,
+                Value::Message(vec![
+                    Field { key: "user_id".into(), value: Value::Int64(7) },
+                    Field { key: "name".into(), value: Value::String("ann".into()) },
+                    Field { key: "admin".into(), value: Value::Bool(true) },
+                    Field { key: "score".into(), value: Value::Int64(1) },
+                    Field { key: "inner".into(), value: point.clone() },
+                ])
+            );
+            assert_eq!(User::decode_value(&v).unwrap(), User { session: None, ..user });
+
+            let partial = Value::Message(vec![
+                Field { key: "extra".into(), value: Value::Null },
+                Field { key: "inner".into(), value: point },
+                Field { key: "name".into(), value: Value::String("bob".into()) },
+                Field { key: "user_id".into(), value: Value::Int64(8) },
+            ]);
+            let bob = User::decode_value(&partial).unwrap();
+            assert_eq!((bob.admin, bob.score), (false, 42));
+
+            assert_eq!(Meters(1.5).encode_value(), Value::Float64(1.5));
+            assert_eq!(Meters::decode_value(&Value::Float64(1.5)), Ok(Meters(1.5)));
+
+            // отсутствующий `Option` — `None`
+            assert_eq!(Nick::decode_value(&Value::Message(Vec::new())), Ok(Nick { nick: None }));
+        }
+
+        #[test]
+        fn derived_struct_errors() {
+            let v = Value::Message(vec![Field { key: "user_id".into(), value: Value::Int64(1) }]);
+            let err = ValueError::MissingField { key: "name".into() };
+            assert_eq!(User::decode_value(&v), Err(err));
+
+            let v = Value::Message(vec![Field { key: "user_id".into(), value: Value::Bool(true) }]);
+            let err = ValueError::InvalidType { expected: "Int64", found: "Bool" };
+            assert_eq!(User::decode_value(&v), Err(err.in_field("user_id")));
+            assert_eq!(
+                User::decode_value(&Value::Null),
+                Err(ValueError::InvalidType { expected: "Message", found: "Null" })
+            );
+
+            let short = Value::List(vec![Value::Int32(1)]);
+            let err = ValueError::InvalidLength { expected: 2, found: 1 };
+            assert_eq!(Point::decode_value(&short), Err(err));
+            let text = User::decode_value(&v).unwrap_err().to_string();
+            assert_eq!(text, "поле \"user_id\": ожидался Int64, получен Bool");
+        }
+
+        #[derive(Debug, PartialEq, Encode, Decode)]
+        enum Shape {
+            Empty,
+            #[codec(rename = "circle")]
+            Circle { r: f64 },
+            Square(f64),
+            Line(i32, i32),
+        }
+
+        #[derive(Debug, PartialEq, Encode, Decode)]
+        #[codec(tag = "kind", content = "data")]
+        enum Event {
+            Ping(i64),
+            #[codec(other)]
+            Unknown,
+        }
+
+        #[derive(Debug, PartialEq, Encode, Decode)]
+        #[codec(tag = "kind")]
+        enum Keyword {
+            #[allow(non_camel_case_types)]
+            r#move { r#type: i32 },
+        }
+
+        fn field(key: &str, value: Value) -> Field {
+            Field { key: key.into(), value }
+        }
+
+        fn tagged(tag: &str, name: &str, rest: Vec<Field>) -> Value {
+            let mut fields = vec![Field { key: tag.into(), value: Value::String(name.into()) }];
+            fields.extend(rest);
+            Value::Message(fields)
+        }
+
+        #[test]
+        fn derived_enum_roundtrip() {
+            let line = Value::List(vec![Value::Int32(1), Value::Int32(2)]);
+            let cases = [
+                (Shape::Empty, tagged("type", "Empty", vec![])),
+                (
+                    Shape::Circle { r: 0.5 },
+                    tagged("type", "circle", vec![field("r", Value::Float64(0.5))]),
+                ),
+                (
+                    Shape::Square(2.0),
+                    tagged("type", "Square", vec![field("value", Value::Float64(2.0))]),
+                ),
+                (
+                    Shape::Line(1, 2),
+                    tagged("type", "Line", vec![field("value", line)]),
+                ),
+            ];
+            for (shape, value) in cases {
+                assert_eq!(shape.encode_value(), value);
+                assert_eq!(Shape::decode_value(&value), Ok(shape));
+            }
+
+            let ping = tagged("kind", "Ping", vec![field("data", Value::Int64(3))]);
+            assert_eq!(Event::Ping(3).encode_value(), ping);
+            assert_eq!(Event::decode_value(&ping), Ok(Event::Ping(3)));
+            assert_eq!(Event::decode_value(&tagged("kind", "Pong", vec![])), Ok(Event::Unknown));
+
+            // ключи и имена без префикса `r#`
+            let keyword = tagged("kind", "move", vec![field("type", Value::Int32(5))]);
+            assert_eq!(Keyword::r#move { r#type: 5 }.encode_value(), keyword);
+            assert_eq!(Keyword::decode_value(&keyword), Ok(Keyword::r#move { r#type: 5 }));
+        }
+
+        #[test]
+        fn derived_enum_errors() {
+            let err = ValueError::UnknownVariant { name: "Circle".into() };
+            assert_eq!(Shape::decode_value(&tagged("type", "Circle", vec![])), Err(err));
+
+            let no_tag = Value::Message(vec![]);
+            let err = ValueError::MissingField { key: "type".into() };
+            assert_eq!(Shape::decode_value(&no_tag), Err(err));
+
+            let bad_tag = Value::Message(vec![field("type", Value::Int32(1))]);
+            let err = ValueError::InvalidType { expected: "String", found: "Int32" };
+            assert_eq!(Shape::decode_value(&bad_tag), Err(err.in_field("type")));
+
+            let short = Value::List(vec![Value::Int32(1)]);
+            let short = tagged("type", "Line", vec![field("value", short)]);
+            let err = ValueError::InvalidLength { expected: 2, found: 1 };
+            assert_eq!(Shape::decode_value(&short), Err(err.in_field("value")));
+        }
+    }
+}
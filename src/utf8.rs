@@ -0,0 +1,43 @@
+//! Проверка UTF-8 ключей и строк; с feature `simdutf8` — векторными
+//! инструкциями процессора
+
+use std::borrow::Cow;
+
+/// Строка из байт, если они корректный UTF-8
+pub(crate) fn str(bytes: &[u8]) -> Option<&str> {
+    #[cfg(feature = "simdutf8")]
+    return simdutf8::basic::from_utf8(bytes).ok();
+    #[cfg(not(feature = "simdutf8"))]
+    return std::str::from_utf8(bytes).ok();
+}
+
+/// `String` без копирования байт
+pub(crate) fn string(bytes: Vec<u8>) -> Option<String> {
+    str(&bytes)?;
+    // SAFETY: байты только что проверены
+    Some(unsafe { String::from_utf8_unchecked(bytes) })
+}
+
+/// Строка, заимствующая вход, если он заимствован
+pub(crate) fn cow(bytes: Cow<'_, [u8]>) -> Option<Cow<'_, str>> {
+    match bytes {
+        Cow::Borrowed(b) => str(b).map(Cow::Borrowed),
+        Cow::Owned(v) => string(v).map(Cow::Owned),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_like_std() {
+        let cases: [&[u8]; 5] = [b"", b"ascii", "кириллица".as_bytes(), b"\xff", b"ab\xe2\x82"];
+        for bytes in cases {
+            let std = std::str::from_utf8(bytes).ok();
+            assert_eq!(str(bytes), std);
+            assert_eq!(string(bytes.to_vec()).as_deref(), std);
+            assert_eq!(cow(Cow::Borrowed(bytes)).as_deref(), std);
+        }
+    }
+}
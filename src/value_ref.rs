@@ -0,0 +1,264 @@
+//! Декодирование без копирования
+//!
+//! `FieldRef` и `ValueRef` повторяют `Field` и `Value`, но строки и байты
+//! в них — срезы входного буфера. Удобно, когда данные только
+//! просматриваются; `to_owned` переводит результат в обычный `Field`.
+
+use std::borrow::Cow;
+
+use crate::decode::{Decoder, Input, Reader, Tree, UNLIMITED};
+use crate::{utf8, Decimal, DecodeError, DecodeOptions, Field, Timestamp, Value};
+
+/// `Value`, заимствующее строки и байты из входа
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueRef<'a> {
+    Int32(i32),
+    Float32(f32),
+    Bool(bool),
+    String(&'a str),
+    Bytes(&'a [u8]),
+    Message(Vec<FieldRef<'a>>),
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    Null,
+    List(Vec<ValueRef<'a>>),
+    Map(Vec<(ValueRef<'a>, ValueRef<'a>)>),
+    Timestamp(Timestamp),
+    Uuid([u8; 16]),
+    Decimal(Decimal),
+    Enum { variant: u32, name: Option<&'a str>, payload: Option<Box<ValueRef<'a>>> },
+    Int8(i8),
+    Int16(i16),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    /// Значение неизвестного типа, см. `Value::Unknown`
+    Unknown { type_code: u8, raw: &'a [u8] },
+}
+
+/// `Field`, заимствующее ключ и данные из входа
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldRef<'a> {
+    pub key: &'a str,
+    pub value: ValueRef<'a>,
+}
+
+impl ValueRef<'_> {
+    /// Копия значения, не зависящая от входного буфера
+    pub fn to_owned(&self) -> Value {
+        match self {
+            ValueRef::Int32(i) => Value::Int32(*i),
+            ValueRef::Float32(f) => Value::Float32(*f),
+            ValueRef::Bool(b) => Value::Bool(*b),
+            ValueRef::String(s) => Value::String((*s).to_owned()),
+            ValueRef::Bytes(b) => Value::Bytes(b.to_vec()),
+            ValueRef::Message(fields) => {
+                Value::Message(fields.iter().map(|f| f.to_owned()).collect())
+            }
+            ValueRef::Int64(i) => Value::Int64(*i),
+            ValueRef::UInt64(u) => Value::UInt64(*u),
+            ValueRef::Float64(f) => Value::Float64(*f),
+            ValueRef::Null => Value::Null,
+            ValueRef::List(items) => Value::List(items.iter().map(|v| v.to_owned()).collect()),
+            ValueRef::Map(entries) => {
+                Value::Map(entries.iter().map(|(k, v)| (k.to_owned(), v.to_owned())).collect())
+            }
+            ValueRef::Timestamp(ts) => Value::Timestamp(*ts),
+            ValueRef::Uuid(u) => Value::Uuid(*u),
+            ValueRef::Decimal(d) => Value::Decimal(*d),
+            ValueRef::Enum { variant, name, payload } => Value::Enum {
+                variant: *variant,
+                name: name.map(str::to_owned),
+                payload: payload.as_ref().map(|p| Box::new(ValueRef::to_owned(p))),
+            },
+            ValueRef::Int8(i) => Value::Int8(*i),
+            ValueRef::Int16(i) => Value::Int16(*i),
+            ValueRef::UInt8(u) => Value::UInt8(*u),
+            ValueRef::UInt16(u) => Value::UInt16(*u),
+            ValueRef::UInt32(u) => Value::UInt32(*u),
+            ValueRef::Unknown { type_code, raw } => {
+                Value::Unknown { type_code: *type_code, raw: raw.to_vec() }
+            }
+        }
+    }
+}
+
+impl FieldRef<'_> {
+    /// Копия поля, не зависящая от входного буфера
+    pub fn to_owned(&self) -> Field {
+        Field { key: self.key.to_owned(), value: self.value.to_owned() }
+    }
+}
+
+/// Декодирование одного поля без копирования строк и байт
+pub fn decode_field_ref(data: &[u8]) -> Result<FieldRef<'_>, DecodeError> {
+    decode_field_ref_with(data, &UNLIMITED)
+}
+
+/// То же, что [`decode_field_ref`], с ограничениями и профилем
+pub fn decode_field_ref_with<'a>(
+    data: &'a [u8],
+    opts: &DecodeOptions,
+) -> Result<FieldRef<'a>, DecodeError> {
+    Decoder::new(Reader::new(data), opts).read_tree(&Borrowed)
+}
+
+/// Декодирование сообщения без копирования строк и байт
+pub fn decode_message_ref<'a>(
+    data: &'a [u8],
+    opts: &DecodeOptions,
+) -> Result<Vec<FieldRef<'a>>, DecodeError> {
+    let mut dec = Decoder::new(Reader::new(data), opts);
+    let mut fields = Vec::new();
+    while dec.input.pos() < data.len() {
+        fields.push(dec.read_tree(&Borrowed)?);
+    }
+    Ok(fields)
+}
+
+/// Сборка `FieldRef`; используется только с `Reader`, который
+/// всегда отдаёт срезы входа
+pub(crate) struct Borrowed;
+
+fn borrowed(bytes: Cow<'_, [u8]>) -> &[u8] {
+    match bytes {
+        Cow::Borrowed(b) => b,
+        Cow::Owned(_) => unreachable!("Reader не копирует данные"),
+    }
+}
+
+impl<'a> Tree<'a> for Borrowed {
+    type Str = &'a str;
+    type Value = ValueRef<'a>;
+    type Field = FieldRef<'a>;
+    type Fields = Vec<FieldRef<'a>>;
+    type Items = Vec<ValueRef<'a>>;
+    type Entries = Vec<(ValueRef<'a>, ValueRef<'a>)>;
+
+    fn str(&self, bytes: Cow<'a, [u8]>, offset: usize) -> Result<&'a str, DecodeError> {
+        utf8::str(borrowed(bytes)).ok_or(DecodeError::InvalidUtf8 { offset })
+    }
+
+    fn string(&self, s: &'a str) -> ValueRef<'a> {
+        ValueRef::String(s)
+    }
+
+    fn bytes(&self, bytes: Cow<'a, [u8]>) -> ValueRef<'a> {
+        ValueRef::Bytes(borrowed(bytes))
+    }
+
+    fn unknown(&self, type_code: u8, raw: Cow<'a, [u8]>) -> ValueRef<'a> {
+        ValueRef::Unknown { type_code, raw: borrowed(raw) }
+    }
+
+    fn scalar(&self, value: Value) -> ValueRef<'a> {
+        match value {
+            Value::Int32(i) => ValueRef::Int32(i),
+            Value::Float32(f) => ValueRef::Float32(f),
+            Value::Bool(b) => ValueRef::Bool(b),
+            Value::Int64(i) => ValueRef::Int64(i),
+            Value::UInt64(u) => ValueRef::UInt64(u),
+            Value::Float64(f) => ValueRef::Float64(f),
+            Value::Null => ValueRef::Null,
+            Value::Timestamp(ts) => ValueRef::Timestamp(ts),
+            Value::Uuid(u) => ValueRef::Uuid(u),
+            Value::Decimal(d) => ValueRef::Decimal(d),
+            Value::Int8(i) => ValueRef::Int8(i),
+            Value::Int16(i) => ValueRef::Int16(i),
+            Value::UInt8(u) => ValueRef::UInt8(u),
+            Value::UInt16(u) => ValueRef::UInt16(u),
+            Value::UInt32(u) => ValueRef::UInt32(u),
+            Value::String(_)
+            | Value::Bytes(_)
+            | Value::Unknown { .. }
+            | Value::Message(_)
+            | Value::List(_)
+            | Value::Map(_)
+            | Value::Enum { .. } => unreachable!("декодер собирает их сам"),
+        }
+    }
+
+    fn fields(&self) -> Self::Fields {
+        Vec::new()
+    }
+
+    fn items(&self) -> Self::Items {
+        Vec::new()
+    }
+
+    fn entries(&self) -> Self::Entries {
+        Vec::new()
+    }
+
+    fn message(&self, fields: Vec<FieldRef<'a>>) -> ValueRef<'a> {
+        ValueRef::Message(fields)
+    }
+
+    fn list(&self, items: Vec<ValueRef<'a>>) -> ValueRef<'a> {
+        ValueRef::List(items)
+    }
+
+    fn map(&self, entries: Vec<(ValueRef<'a>, ValueRef<'a>)>) -> ValueRef<'a> {
+        ValueRef::Map(entries)
+    }
+
+    fn enumeration(
+        &self,
+        variant: u32,
+        name: Option<&'a str>,
+        payload: Option<ValueRef<'a>>,
+    ) -> ValueRef<'a> {
+        ValueRef::Enum { variant, name, payload: payload.map(Box::new) }
+    }
+
+    fn field(&self, key: &'a str, value: ValueRef<'a>) -> FieldRef<'a> {
+        FieldRef { key, value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_field, decode_message, encode_field, encode_message};
+
+    fn sample() -> Field {
+        Field {
+            key: "user".into(),
+            value: Value::Message(vec![
+                Field { key: "name".into(), value: Value::String("Rust".into()) },
+                Field { key: "raw".into(), value: Value::Bytes(vec![0, 1, 2]) },
+                Field {
+                    key: "tags".into(),
+                    value: Value::Map(vec![(Value::String("k".into()), Value::Int32(7))]),
+                },
+            ]),
+        }
+    }
+
+    #[test]
+    fn borrows_from_input() {
+        let enc = encode_field(&sample()).unwrap();
+        let field = decode_field_ref(&enc).unwrap();
+        assert_eq!(field.to_owned(), decode_field(&enc).unwrap());
+
+        let ValueRef::Message(fields) = &field.value else { panic!("ожидалось сообщение") };
+        let ValueRef::String(name) = fields[0].value else { panic!("ожидалась строка") };
+        assert!(enc.as_ptr_range().contains(&name.as_ptr()));
+        assert_eq!(fields[1].value, ValueRef::Bytes(&[0, 1, 2]));
+    }
+
+    #[test]
+    fn message_ref_errors() {
+        let fields = vec![sample(), Field { key: "n".into(), value: Value::Null }];
+        let enc = encode_message(&fields).unwrap();
+        let refs = decode_message_ref(&enc, &DecodeOptions::default()).unwrap();
+        assert_eq!(refs.iter().map(FieldRef::to_owned).collect::<Vec<_>>(), fields);
+
+        let cut = &enc[..enc.len() - 1];
+        assert_eq!(
+            decode_message_ref(cut, &DecodeOptions::default()).unwrap_err(),
+            decode_message(cut).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! `Serialize` и `Deserialize` для `Value` и `Field`
+//!
+//! Значения отображаются на модель данных serde естественным образом:
+//! `Value::Message` — отображение ключей в значения, `Value::List` —
+//! последовательность, `Value::Null` — `()`. Так сообщение выводится в
+//! JSON или YAML в привычном виде и читается из файлов настроек.
+//!
+//! Отображение неточно: временная метка становится структурой
+//! `{secs, nanos}`, UUID и десятичное число — строками, вариант
+//! перечисления — отображением из одного элемента `{имя: содержимое}`,
+//! значение неизвестного типа — своими байтами без кода типа.
+//! При чтении отображение со строковыми ключами становится
+//! `Value::Message`, числа — вариантом той ширины, которую выдал формат.
+
+use std::fmt;
+
+use ::serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use ::serde::ser::{Serialize, SerializeMap, SerializeSeq, SerializeStruct, Serializer};
+
+use crate::serde::map_value;
+use crate::typed::Hyphenated;
+use crate::{Field, Timestamp, Value};
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Int8(i) => s.serialize_i8(*i),
+            Value::Int16(i) => s.serialize_i16(*i),
+            Value::Int32(i) => s.serialize_i32(*i),
+            Value::Int64(i) => s.serialize_i64(*i),
+            Value::UInt8(u) => s.serialize_u8(*u),
+            Value::UInt16(u) => s.serialize_u16(*u),
+            Value::UInt32(u) => s.serialize_u32(*u),
+            Value::UInt64(u) => s.serialize_u64(*u),
+            Value::Float32(f) => s.serialize_f32(*f),
+            Value::Float64(f) => s.serialize_f64(*f),
+            Value::Bool(b) => s.serialize_bool(*b),
+            Value::String(v) => s.serialize_str(v),
+            Value::Bytes(b) => s.serialize_bytes(b),
+            Value::Null => s.serialize_unit(),
+            Value::Message(fields) => {
+                let mut map = s.serialize_map(Some(fields.len()))?;
+                for f in fields {
+                    map.serialize_entry(&f.key, &f.value)?;
+                }
+                map.end()
+            }
+            Value::List(items) => {
+                let mut seq = s.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Value::Map(entries) => {
+                let mut map = s.serialize_map(Some(entries.len()))?;
+                for (k, v) in entries {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+            Value::Timestamp(ts) => {
+                let mut st = s.serialize_struct("Timestamp", 2)?;
+                st.serialize_field("secs", &ts.secs)?;
+                st.serialize_field("nanos", &ts.nanos)?;
+                st.end()
+            }
+            Value::Uuid(u) => s.collect_str(&Hyphenated(u)),
+            Value::Decimal(d) => s.collect_str(d),
+            Value::Unknown { raw, .. } => s.serialize_bytes(raw),
+            Value::Enum { variant, name, payload } => {
+                let mut map = s.serialize_map(Some(1))?;
+                match name {
+                    Some(name) => map.serialize_key(name)?,
+                    None => map.serialize_key(&variant.to_string())?,
+                }
+                match payload {
+                    Some(p) => map.serialize_value(p)?,
+                    None => map.serialize_value(&())?,
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl Serialize for Field {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut st = s.serialize_struct("Field", 2)?;
+        st.serialize_field("key", &self.key)?;
+        st.serialize_field("value", &self.value)?;
+        st.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Value, D::Error> {
+        d.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("значение custom_codec")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i8<E>(self, v: i8) -> Result<Value, E> {
+        Ok(Value::Int8(v))
+    }
+
+    fn visit_i16<E>(self, v: i16) -> Result<Value, E> {
+        Ok(Value::Int16(v))
+    }
+
+    fn visit_i32<E>(self, v: i32) -> Result<Value, E> {
+        Ok(Value::Int32(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Int64(v))
+    }
+
+    /// `i128` выдаёт только десериализатор крейта — для временной метки
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Value, E> {
+        Timestamp::from_unix_nanos(v).map(Value::Timestamp).ok_or_else(|| {
+            E::invalid_value(de::Unexpected::Other("i128"), &"временная метка в наносекундах")
+        })
+    }
+
+    fn visit_u8<E>(self, v: u8) -> Result<Value, E> {
+        Ok(Value::UInt8(v))
+    }
+
+    fn visit_u16<E>(self, v: u16) -> Result<Value, E> {
+        Ok(Value::UInt16(v))
+    }
+
+    fn visit_u32<E>(self, v: u32) -> Result<Value, E> {
+        Ok(Value::UInt32(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::UInt64(v))
+    }
+
+    fn visit_f32<E>(self, v: f32) -> Result<Value, E> {
+        Ok(Value::Float32(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float64(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::Bytes(v))
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Value, D::Error> {
+        Value::deserialize(d)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<Value, D::Error> {
+        Value::deserialize(d)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::List(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut entries: Vec<(Value, Value)> = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(map_value(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Field, D::Error> {
+        d.deserialize_struct("Field", &["key", "value"], FieldVisitor)
+    }
+}
+
+struct FieldVisitor;
+
+impl<'de> Visitor<'de> for FieldVisitor {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("поле с ключом key и значением value")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Field, A::Error> {
+        let key = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let value = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(Field { key, value })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Field, A::Error> {
+        let (mut key, mut value) = (None, None);
+        while let Some(name) = map.next_key::<String>()? {
+            match name.as_str() {
+                "key" if key.is_none() => key = Some(map.next_value()?),
+                "value" if value.is_none() => value = Some(map.next_value()?),
+                "key" | "value" => return Err(de::Error::duplicate_field("key или value")),
+                other => return Err(de::Error::unknown_field(other, &["key", "value"])),
+            }
+        }
+        Ok(Field {
+            key: key.ok_or_else(|| de::Error::missing_field("key"))?,
+            value: value.ok_or_else(|| de::Error::missing_field("value"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_slice, from_value, to_value, to_vec, Decimal, Timestamp};
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn value_through_serde() {
+        let msg = Value::Message(vec![
+            field("id", Value::UInt16(7)),
+            field("name", Value::String("probe".into())),
+            field("tags", Value::List(vec![Value::Bool(true), Value::Null])),
+            field("blob", Value::Bytes(vec![1, 2])),
+            field("by_id", Value::Map(vec![(Value::Int32(1), Value::Float64(0.5))])),
+        ]);
+        assert_eq!(to_value(&msg).unwrap(), msg);
+        assert_eq!(from_value::<Value>(msg.clone()).unwrap(), msg);
+
+        // через двоичный формат
+        let fields = match &msg {
+            Value::Message(fields) => fields.clone(),
+            _ => unreachable!(),
+        };
+        let enc = to_vec(&msg).unwrap();
+        assert_eq!(from_slice::<Value>(&enc).unwrap(), msg);
+        let f = field("inner", msg);
+        assert_eq!(from_value::<Field>(to_value(&f).unwrap()).unwrap(), f);
+        assert_eq!(crate::decode_message(&enc).unwrap(), fields);
+    }
+
+    #[test]
+    fn lossy_values() {
+        let ts = Value::Timestamp(Timestamp::new(5, 6).unwrap());
+        let expected =
+            Value::Message(vec![field("secs", Value::Int64(5)), field("nanos", Value::UInt32(6))]);
+        assert_eq!(to_value(&ts).unwrap(), expected);
+
+        let mut uuid = [0u8; 16];
+        uuid[15] = 0xab;
+        let text = "00000000-0000-0000-0000-0000000000ab";
+        assert_eq!(to_value(&Value::Uuid(uuid)).unwrap(), Value::String(text.into()));
+        let d = Value::Decimal(Decimal { mantissa: -125, scale: 2 });
+        assert_eq!(to_value(&d).unwrap(), Value::String("-1.25".into()));
+
+        let e = Value::Enum { variant: 3, name: None, payload: None };
+        let expected = Value::Message(vec![field("3", Value::Null)]);
+        assert_eq!(to_value(&e).unwrap(), expected);
+
+        // из двоичного формата и из `Value` метка читается как есть
+        let msg = Value::Message(vec![field("at", ts.clone())]);
+        let enc = crate::encode_message(&[field("at", ts)]).unwrap();
+        assert_eq!(from_slice::<Value>(&enc).unwrap(), msg);
+        assert_eq!(from_value::<Value>(msg.clone()).unwrap(), msg);
+
+        let no_value = Value::Message(vec![field("key", Value::String("k".into()))]);
+        let err = from_value::<Field>(no_value);
+        assert!(matches!(err, Err(crate::SerdeError::Custom(_))));
+    }
+}
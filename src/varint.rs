@@ -0,0 +1,79 @@
+//! LEB128: по 7 бит на байт, старший бит — признак продолжения
+
+use std::io::{self, Write};
+
+/// Максимальная длина varint для u64
+pub(crate) const MAX_LEN: usize = 10;
+
+pub(crate) fn encoded_len(v: u64) -> usize {
+    // каждые 7 значащих бит — один байт, ноль занимает один байт
+    (64 - (v | 1).leading_zeros() as usize).div_ceil(7)
+}
+
+pub(crate) fn write<W: Write + ?Sized>(w: &mut W, mut v: u64) -> io::Result<()> {
+    let mut buf = [0u8; MAX_LEN];
+    let mut n = 0;
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            buf[n] = byte;
+            n += 1;
+            break;
+        }
+        buf[n] = byte | 0x80;
+        n += 1;
+    }
+    w.write_all(&buf[..n])
+}
+
+/// Разбор varint, занимающего весь срез целиком
+pub(crate) fn decode(bytes: &[u8]) -> Option<u64> {
+    let (last, rest) = bytes.split_last()?;
+    if bytes.len() > MAX_LEN || last & 0x80 != 0 || rest.iter().any(|b| b & 0x80 == 0) {
+        return None;
+    }
+    // десятый байт может нести лишь один значащий бит
+    if bytes.len() == MAX_LEN && *last > 1 {
+        return None;
+    }
+    Some(bytes.iter().enumerate().fold(0, |v, (i, b)| v | ((b & 0x7f) as u64) << (7 * i)))
+}
+
+/// Zig-zag: 0, -1, 1, -2, … переходят в 0, 1, 2, 3, …
+pub(crate) fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+pub(crate) fn unzigzag(v: u64) -> i64 {
+    (v >> 1) as i64 ^ -((v & 1) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lengths() {
+        for (v, len) in [(0, 1), (127, 1), (128, 2), (16_383, 2), (16_384, 3), (u64::MAX, 10)] {
+            let mut out = Vec::new();
+            write(&mut out, v).unwrap();
+            assert_eq!(out.len(), len, "{v}");
+            assert_eq!(encoded_len(v), len, "{v}");
+        }
+        let mut out = Vec::new();
+        write(&mut out, 300).unwrap();
+        assert_eq!(out, [0xac, 0x02]);
+        assert_eq!(decode(&out), Some(300));
+        assert_eq!(decode(&[0xac]), None);
+        assert_eq!(decode(&[0xac, 0x02, 0x00]), None);
+    }
+
+    #[test]
+    fn zigzag_roundtrip() {
+        assert_eq!([0, -1, 1, -2].map(zigzag), [0, 1, 2, 3]);
+        for v in [i64::MIN, -300, 0, 63, i64::MAX] {
+            assert_eq!(unzigzag(zigzag(v)), v);
+        }
+    }
+}
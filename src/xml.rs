@@ -0,0 +1,303 @@
+//! Сообщения в XML и обратно
+//!
+//! Сообщение — корневой элемент, каждое поле — дочерний элемент с именем
+//! ключа. Скалярные значения записываются текстом, как в CSV; `Null` —
+//! пустой элемент `<key/>`; вложенное сообщение — элемент с дочерними
+//! элементами; `List` — элементы `<item>`. Поля из
+//! `XmlOptions::attributes` со скалярным значением записываются атрибутами
+//! родительского элемента. `Map` и перечисления не поддерживаются.
+//!
+//! В XML нет типов, поэтому при чтении текст и атрибуты становятся
+//! `String`, пустой элемент `<key/>` — `Null`, а `<key></key>` — пустой
+//! строкой. Элемент, все дочерние элементы которого называются `item`,
+//! читается как `List`, остальные элементы с дочерними — как `Message`;
+//! атрибуты идут в нём первыми полями.
+
+use std::fmt;
+
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+
+use crate::csv::scalar_text;
+use crate::typed::type_name;
+use crate::{Field, Value};
+
+/// Имя элемента списка
+pub const XML_LIST_ITEM: &str = "item";
+
+/// Настройки записи XML
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlOptions {
+    /// Имя корневого элемента, по умолчанию `message`
+    pub root: String,
+    /// Ключи полей, которые записываются атрибутами, а не элементами
+    pub attributes: Vec<String>,
+}
+
+impl Default for XmlOptions {
+    fn default() -> Self {
+        XmlOptions { root: "message".into(), attributes: Vec::new() }
+    }
+}
+
+/// Сообщение не переводится в XML или обратно
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlError {
+    /// Текст не разбирается как XML
+    Syntax(String),
+    /// Ключ — не допустимое имя элемента XML
+    InvalidName { key: String },
+    /// Значение не записывается в XML: `Map` или перечисление
+    Unsupported { what: &'static str },
+    /// Корневой элемент содержит текст, а не поля
+    NotAMessage,
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XmlError::Syntax(msg) => write!(f, "неверный XML: {msg}"),
+            XmlError::InvalidName { key } => write!(f, "ключ {key:?} — не имя элемента XML"),
+            XmlError::Unsupported { what } => write!(f, "значение {what} не записывается в XML"),
+            XmlError::NotAMessage => f.write_str("корневой элемент XML — не сообщение"),
+        }
+    }
+}
+
+impl std::error::Error for XmlError {}
+
+fn syntax(e: impl fmt::Display) -> XmlError {
+    XmlError::Syntax(e.to_string())
+}
+
+/// Сообщение как элемент `XmlOptions::root`
+///
+/// ```
+/// use custom_codec::{to_xml, Field, Value, XmlOptions};
+///
+/// let fields = vec![
+///     Field { key: "id".into(), value: Value::Int32(7) },
+///     Field { key: "name".into(), value: Value::String("a & b".into()) },
+/// ];
+/// let opts = XmlOptions { attributes: vec!["id".into()], ..XmlOptions::default() };
+/// assert_eq!(
+///     to_xml(&fields, &opts).unwrap(),
+///     r#"<message id="7"><name>a &amp; b</name></message>"#,
+/// );
+/// ```
+pub fn to_xml(fields: &[Field], opts: &XmlOptions) -> Result<String, XmlError> {
+    let mut writer = Writer::new(Vec::new());
+    element(&mut writer, &opts.root, fields, opts)?;
+    Ok(String::from_utf8(writer.into_inner()).expect("quick_xml пишет UTF-8"))
+}
+
+fn check_name(key: &str) -> Result<(), XmlError> {
+    let mut chars = key.chars();
+    let first = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_');
+    if first && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')) {
+        Ok(())
+    } else {
+        Err(XmlError::InvalidName { key: key.to_owned() })
+    }
+}
+
+fn write(writer: &mut Writer<Vec<u8>>, event: Event<'_>) -> Result<(), XmlError> {
+    writer.write_event(event).map_err(syntax)
+}
+
+/// Элемент `name` с полями `fields`
+fn element(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    fields: &[Field],
+    opts: &XmlOptions,
+) -> Result<(), XmlError> {
+    check_name(name)?;
+    let mut start = BytesStart::new(name);
+    let mut children = Vec::with_capacity(fields.len());
+    for f in fields {
+        let text = scalar_text(&f.value).filter(|_| opts.attributes.contains(&f.key));
+        match text {
+            Some(_) if f.value == Value::Null => {}
+            Some(text) => {
+                check_name(&f.key)?;
+                start.push_attribute((f.key.as_str(), text.as_str()));
+            }
+            None => children.push(f),
+        }
+    }
+    write(writer, Event::Start(start))?;
+    for f in children {
+        value(writer, &f.key, &f.value, opts)?;
+    }
+    write(writer, Event::End(BytesEnd::new(name)))
+}
+
+fn value(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    value: &Value,
+    opts: &XmlOptions,
+) -> Result<(), XmlError> {
+    check_name(name)?;
+    match value {
+        Value::Null => write(writer, Event::Empty(BytesStart::new(name))),
+        Value::Message(fields) => element(writer, name, fields, opts),
+        Value::List(items) => {
+            write(writer, Event::Start(BytesStart::new(name)))?;
+            for item in items {
+                self::value(writer, XML_LIST_ITEM, item, opts)?;
+            }
+            write(writer, Event::End(BytesEnd::new(name)))
+        }
+        Value::Map(_) | Value::Enum { .. } => {
+            Err(XmlError::Unsupported { what: type_name(value) })
+        }
+        scalar => {
+            let text = scalar_text(scalar).expect("вложенные значения разобраны выше");
+            write(writer, Event::Start(BytesStart::new(name)))?;
+            write(writer, Event::Text(BytesText::new(&text)))?;
+            write(writer, Event::End(BytesEnd::new(name)))
+        }
+    }
+}
+
+/// Незакрытый элемент при чтении
+struct Open {
+    key: String,
+    fields: Vec<Field>,
+    text: String,
+}
+
+impl Open {
+    fn new(start: &BytesStart<'_>) -> Result<Open, XmlError> {
+        let key = std::str::from_utf8(start.name().as_ref()).map_err(syntax)?.to_owned();
+        let mut fields = Vec::new();
+        for attr in start.attributes() {
+            let attr = attr.map_err(syntax)?;
+            let key = std::str::from_utf8(attr.key.as_ref()).map_err(syntax)?.to_owned();
+            let text = attr.unescape_value().map_err(syntax)?.into_owned();
+            fields.push(Field { key, value: Value::String(text) });
+        }
+        Ok(Open { key, fields, text: String::new() })
+    }
+
+    fn into_value(self) -> Value {
+        if self.fields.is_empty() {
+            Value::String(self.text)
+        } else if self.fields.iter().all(|f| f.key == XML_LIST_ITEM) {
+            Value::List(self.fields.into_iter().map(|f| f.value).collect())
+        } else {
+            Value::Message(self.fields)
+        }
+    }
+}
+
+/// Поля корневого элемента; его имя не проверяется
+pub fn from_xml(text: &str) -> Result<Vec<Field>, XmlError> {
+    let mut reader = Reader::from_str(text);
+    reader.config_mut().trim_text(true);
+    let mut stack: Vec<Open> = Vec::new();
+    loop {
+        let event = reader.read_event().map_err(syntax)?;
+        let done = match event {
+            Event::Start(start) => {
+                stack.push(Open::new(&start)?);
+                continue;
+            }
+            Event::Empty(start) => {
+                let open = Open::new(&start)?;
+                if stack.is_empty() {
+                    return Ok(open.fields);
+                }
+                let value = if open.fields.is_empty() {
+                    Value::Null
+                } else {
+                    Value::Message(open.fields)
+                };
+                Field { key: open.key, value }
+            }
+            Event::Text(text) => {
+                let top = stack.last_mut().ok_or(XmlError::NotAMessage)?;
+                top.text.push_str(&text.unescape().map_err(syntax)?);
+                continue;
+            }
+            Event::CData(data) => {
+                let top = stack.last_mut().ok_or(XmlError::NotAMessage)?;
+                top.text.push_str(&data.decode().map_err(syntax)?);
+                continue;
+            }
+            Event::End(_) => {
+                let open = stack.pop().expect("quick_xml проверяет парность тегов");
+                if stack.is_empty() {
+                    if open.fields.is_empty() && !open.text.is_empty() {
+                        return Err(XmlError::NotAMessage);
+                    }
+                    return Ok(open.fields);
+                }
+                let key = open.key.clone();
+                Field { key, value: open.into_value() }
+            }
+            Event::Eof => return Err(XmlError::Syntax("нет корневого элемента".into())),
+            Event::Comment(_) | Event::Decl(_) | Event::PI(_) | Event::DocType(_) => continue,
+        };
+        stack.last_mut().expect("корень закрывается выше").fields.push(done);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.into())
+    }
+
+    #[test]
+    fn xml_roundtrip() {
+        let fields = vec![
+            field("id", Value::Int64(7)),
+            field("name", string("<a & b>")),
+            field("empty", string("")),
+            field("none", Value::Null),
+            field("tags", Value::List(vec![string("x"), string("y")])),
+            field("inner", Value::Message(vec![field("ok", Value::Bool(true))])),
+        ];
+        let opts = XmlOptions {
+            root: "soap:Body".into(),
+            attributes: vec!["id".into(), "ok".into()],
+        };
+        let text = to_xml(&fields, &opts).unwrap();
+        assert_eq!(
+            text,
+            "<soap:Body id=\"7\"><name>&lt;a &amp; b&gt;</name><empty></empty><none/>\
+             <tags><item>x</item><item>y</item></tags><inner ok=\"true\"></inner></soap:Body>"
+        );
+        let back = vec![
+            field("id", string("7")),
+            field("name", string("<a & b>")),
+            field("empty", string("")),
+            field("none", Value::Null),
+            field("tags", Value::List(vec![string("x"), string("y")])),
+            field("inner", Value::Message(vec![field("ok", string("true"))])),
+        ];
+        assert_eq!(from_xml(&text).unwrap(), back);
+    }
+
+    #[test]
+    fn xml_errors() {
+        let opts = XmlOptions::default();
+        let bad_key = [field("1x", Value::Null)];
+        assert_eq!(to_xml(&bad_key, &opts), Err(XmlError::InvalidName { key: "1x".into() }));
+        let map = [field("m", Value::Map(vec![]))];
+        assert_eq!(to_xml(&map, &opts), Err(XmlError::Unsupported { what: "Map" }));
+        assert_eq!(from_xml("<m>text</m>"), Err(XmlError::NotAMessage));
+        assert!(matches!(from_xml("<m><a></m>"), Err(XmlError::Syntax(_))));
+        let cdata = from_xml("<m><!-- c --><a><![CDATA[<x>]]></a></m>").unwrap();
+        assert_eq!(cdata, vec![field("a", string("<x>"))]);
+    }
+}
@@ -0,0 +1,298 @@
+//! Сообщения в YAML и обратно без потери типов
+//!
+//! Документ — отображение ключей полей в значения. `Int64`, `Float64`,
+//! `Bool`, `String`, `Null`, `Message` и `List` записываются как обычные
+//! значения YAML; остальным нужен тег:
+//!
+//! - `!i8`, `!i16`, `!i32`, `!u8`, `!u16`, `!u32`, `!u64`, `!f32` — число;
+//! - `!bytes` — строка base64, `!uuid` и `!decimal` — строки;
+//! - `!timestamp` — отображение `{secs, nanos}`;
+//! - `!map` — последовательность пар `[ключ, значение]`;
+//! - `!fields` — последовательность пар `[ключ, значение]` для сообщения с
+//!   повторяющимися ключами: отображение YAML оставило бы только один;
+//! - `!enum` — отображение `{variant, name, payload}`, `name` и `payload`
+//!   необязательны.
+//!
+//! Поэтому `from_yaml(&to_yaml(fields))` возвращает те же поля. В тексте,
+//! написанном вручную, целые без тега читаются как `Int64` (или `UInt64`,
+//! если не помещаются), дробные — как `Float64`, а отображение с
+//! нестроковыми ключами — как `Value::Map`.
+
+use std::fmt;
+
+use serde_yaml::value::{Tag, TaggedValue};
+use serde_yaml::{Mapping, Number, Value as Yaml};
+
+use crate::typed::{parse_hyphenated, Hyphenated};
+use crate::{base64, Decimal, Field, Timestamp, Value};
+
+/// YAML не переводится в поля
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum YamlError {
+    /// Текст не разбирается как YAML
+    Syntax(String),
+    /// Документ — не отображение со строковыми ключами
+    NotAMessage,
+    /// Неизвестный тег или значение, не подходящее к тегу
+    InvalidTag { tag: String },
+}
+
+impl fmt::Display for YamlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            YamlError::Syntax(msg) => write!(f, "неверный YAML: {msg}"),
+            YamlError::NotAMessage => f.write_str("документ YAML — не сообщение"),
+            YamlError::InvalidTag { tag } => write!(f, "неверное значение с тегом !{tag}"),
+        }
+    }
+}
+
+impl std::error::Error for YamlError {}
+
+/// Поля как документ YAML
+///
+/// ```
+/// use custom_codec::{from_yaml, to_yaml, Field, Value};
+///
+/// let fields = vec![
+///     Field { key: "port".into(), value: Value::UInt16(8080) },
+///     Field { key: "host".into(), value: Value::String("localhost".into()) },
+/// ];
+/// let text = to_yaml(&fields);
+/// assert_eq!(text, "port: !u16 8080\nhost: localhost\n");
+/// assert_eq!(from_yaml(&text).unwrap(), fields);
+/// ```
+pub fn to_yaml(fields: &[Field]) -> String {
+    serde_yaml::to_string(&message(fields)).expect("serde_yaml::Value всегда записывается")
+}
+
+/// Поля из документа YAML
+pub fn from_yaml(text: &str) -> Result<Vec<Field>, YamlError> {
+    let yaml: Yaml = serde_yaml::from_str(text).map_err(|e| YamlError::Syntax(e.to_string()))?;
+    match value_from_yaml(&yaml)? {
+        Value::Message(fields) => Ok(fields),
+        _ => Err(YamlError::NotAMessage),
+    }
+}
+
+fn message(fields: &[Field]) -> Yaml {
+    let mut keys = std::collections::HashSet::new();
+    if !fields.iter().all(|f| keys.insert(f.key.as_str())) {
+        let pairs = fields
+            .iter()
+            .map(|f| Yaml::Sequence(vec![Yaml::String(f.key.clone()), value_to_yaml(&f.value)]));
+        return tagged("fields", Yaml::Sequence(pairs.collect()));
+    }
+    let map = fields.iter().map(|f| (Yaml::String(f.key.clone()), value_to_yaml(&f.value)));
+    Yaml::Mapping(map.collect())
+}
+
+fn tagged(tag: &str, value: Yaml) -> Yaml {
+    Yaml::Tagged(Box::new(TaggedValue { tag: Tag::new(tag), value }))
+}
+
+/// Значение без ключа
+pub fn value_to_yaml(value: &Value) -> Yaml {
+    match value {
+        Value::Int8(i) => tagged("i8", Yaml::from(*i)),
+        Value::Int16(i) => tagged("i16", Yaml::from(*i)),
+        Value::Int32(i) => tagged("i32", Yaml::from(*i)),
+        Value::Int64(i) => Yaml::from(*i),
+        Value::UInt8(u) => tagged("u8", Yaml::from(*u)),
+        Value::UInt16(u) => tagged("u16", Yaml::from(*u)),
+        Value::UInt32(u) => tagged("u32", Yaml::from(*u)),
+        Value::UInt64(u) => tagged("u64", Yaml::from(*u)),
+        Value::Float32(f) => tagged("f32", Yaml::from(*f)),
+        Value::Float64(f) => Yaml::from(*f),
+        Value::Bool(b) => Yaml::Bool(*b),
+        Value::String(s) => Yaml::String(s.clone()),
+        Value::Bytes(b) => tagged("bytes", Yaml::String(base64::encode(b))),
+        Value::Null => Yaml::Null,
+        Value::Message(fields) => message(fields),
+        Value::List(items) => Yaml::Sequence(items.iter().map(value_to_yaml).collect()),
+        Value::Map(entries) => {
+            let pairs = entries
+                .iter()
+                .map(|(k, v)| Yaml::Sequence(vec![value_to_yaml(k), value_to_yaml(v)]));
+            tagged("map", Yaml::Sequence(pairs.collect()))
+        }
+        Value::Timestamp(ts) => {
+            let mut map = Mapping::new();
+            map.insert("secs".into(), ts.secs.into());
+            map.insert("nanos".into(), ts.nanos.into());
+            tagged("timestamp", Yaml::Mapping(map))
+        }
+        Value::Uuid(u) => tagged("uuid", Yaml::String(Hyphenated(u).to_string())),
+        Value::Decimal(d) => tagged("decimal", Yaml::String(d.to_string())),
+        Value::Enum { variant, name, payload } => {
+            let mut map = Mapping::new();
+            map.insert("variant".into(), (*variant).into());
+            if let Some(name) = name {
+                map.insert("name".into(), name.as_str().into());
+            }
+            if let Some(payload) = payload {
+                map.insert("payload".into(), value_to_yaml(payload));
+            }
+            tagged("enum", Yaml::Mapping(map))
+        }
+        Value::Unknown { type_code, raw } => {
+            let mut map = Mapping::new();
+            map.insert("type_code".into(), (*type_code).into());
+            map.insert("raw".into(), base64::encode(raw).into());
+            tagged("unknown", Yaml::Mapping(map))
+        }
+    }
+}
+
+/// Значение из YAML
+pub fn value_from_yaml(yaml: &Yaml) -> Result<Value, YamlError> {
+    Ok(match yaml {
+        Yaml::Null => Value::Null,
+        Yaml::Bool(b) => Value::Bool(*b),
+        Yaml::Number(n) => number(n),
+        Yaml::String(s) => Value::String(s.clone()),
+        Yaml::Sequence(items) => {
+            Value::List(items.iter().map(value_from_yaml).collect::<Result<_, _>>()?)
+        }
+        Yaml::Mapping(map) if map.keys().all(Yaml::is_string) => Value::Message(
+            map.iter()
+                .map(|(k, v)| {
+                    let key = k.as_str().expect("проверено выше").to_owned();
+                    Ok(Field { key, value: value_from_yaml(v)? })
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        Yaml::Mapping(map) => Value::Map(
+            map.iter()
+                .map(|(k, v)| Ok((value_from_yaml(k)?, value_from_yaml(v)?)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Yaml::Tagged(t) => {
+            let tag = t.tag.to_string();
+            let tag = tag.trim_start_matches('!');
+            from_tagged(tag, &t.value).ok_or_else(|| YamlError::InvalidTag { tag: tag.into() })?
+        }
+    })
+}
+
+fn number(n: &Number) -> Value {
+    match (n.as_i64(), n.as_u64()) {
+        (Some(i), _) => Value::Int64(i),
+        (None, Some(u)) => Value::UInt64(u),
+        _ => Value::Float64(n.as_f64().expect("число YAML — целое или дробное")),
+    }
+}
+
+/// `None` для неизвестного тега или неподходящего значения
+fn from_tagged(tag: &str, yaml: &Yaml) -> Option<Value> {
+    let text = || yaml.as_str();
+    let int = || yaml.as_i64();
+    Some(match tag {
+        "i8" => Value::Int8(int()?.try_into().ok()?),
+        "i16" => Value::Int16(int()?.try_into().ok()?),
+        "i32" => Value::Int32(int()?.try_into().ok()?),
+        "u8" => Value::UInt8(yaml.as_u64()?.try_into().ok()?),
+        "u16" => Value::UInt16(yaml.as_u64()?.try_into().ok()?),
+        "u32" => Value::UInt32(yaml.as_u64()?.try_into().ok()?),
+        "u64" => Value::UInt64(yaml.as_u64()?),
+        "f32" => Value::Float32(yaml.as_f64()? as f32),
+        "bytes" => Value::Bytes(base64::decode(text()?)?),
+        "uuid" => Value::Uuid(parse_hyphenated(text()?)?),
+        "decimal" => Value::Decimal(Decimal::parse(text()?)?),
+        "timestamp" => {
+            let secs = yaml.get("secs")?.as_i64()?;
+            let nanos = yaml.get("nanos")?.as_u64()?.try_into().ok()?;
+            Value::Timestamp(Timestamp::new(secs, nanos)?)
+        }
+        "map" => Value::Map(
+            yaml.as_sequence()?
+                .iter()
+                .map(|pair| match pair.as_sequence()?.as_slice() {
+                    [k, v] => Some((value_from_yaml(k).ok()?, value_from_yaml(v).ok()?)),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?,
+        ),
+        "fields" => Value::Message(
+            yaml.as_sequence()?
+                .iter()
+                .map(|pair| match pair.as_sequence()?.as_slice() {
+                    [Yaml::String(key), v] => {
+                        Some(Field { key: key.clone(), value: value_from_yaml(v).ok()? })
+                    }
+                    _ => None,
+                })
+                .collect::<Option<_>>()?,
+        ),
+        "enum" => {
+            let variant = yaml.get("variant")?.as_u64()?.try_into().ok()?;
+            let name = match yaml.get("name") {
+                Some(name) => Some(name.as_str()?.to_owned()),
+                None => None,
+            };
+            let payload = match yaml.get("payload") {
+                Some(p) => Some(Box::new(value_from_yaml(p).ok()?)),
+                None => None,
+            };
+            Value::Enum { variant, name, payload }
+        }
+        "unknown" => {
+            let type_code = yaml.get("type_code")?.as_u64()?.try_into().ok()?;
+            let raw = base64::decode(yaml.get("raw")?.as_str()?)?;
+            Value::Unknown { type_code, raw }
+        }
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: Value) -> Field {
+        Field { key: key.into(), value }
+    }
+
+    #[test]
+    fn yaml_roundtrip() {
+        let fields = vec![
+            field("id", Value::Int64(-7)),
+            field("small", Value::Int8(-3)),
+            field("big", Value::UInt64(1)),
+            field("ratio", Value::Float32(0.5)),
+            field("blob", Value::Bytes(vec![0, 255])),
+            field("at", Value::Timestamp(Timestamp { secs: 5, nanos: 7 })),
+            field("uuid", Value::Uuid([0xab; 16])),
+            field("sum", Value::Decimal(Decimal { mantissa: -125, scale: 2 })),
+            field("nested", Value::Message(vec![field("tags", Value::List(vec![Value::Null]))])),
+            field("lookup", Value::Map(vec![(Value::Int32(1), Value::String("one".into()))])),
+            field("state", Value::Enum { variant: 2, name: Some("Done".into()), payload: None }),
+            field("future", Value::Unknown { type_code: 0x55, raw: vec![1, 2] }),
+        ];
+        let text = to_yaml(&fields);
+        assert!(text.contains("sum: !decimal '-1.25'\n"), "{text}");
+        assert_eq!(from_yaml(&text).unwrap(), fields);
+
+        // повторяющиеся ключи сохраняются на любом уровне
+        let repeated = vec![field("", Value::Int8(0)), field("", Value::Int8(0))];
+        let fields = vec![field("a", Value::Message(repeated.clone())), field("a", Value::Null)];
+        assert_eq!(from_yaml(&to_yaml(&repeated)).unwrap(), repeated);
+        assert_eq!(from_yaml(&to_yaml(&fields)).unwrap(), fields);
+        let bad = from_yaml("x: !fields [[1, 2]]\n");
+        assert_eq!(bad, Err(YamlError::InvalidTag { tag: "fields".into() }));
+    }
+
+    #[test]
+    fn yaml_hand_written() {
+        let text = "n: 1\nf: 1.5\nm: {1: a}\n";
+        let fields = vec![
+            field("n", Value::Int64(1)),
+            field("f", Value::Float64(1.5)),
+            field("m", Value::Map(vec![(Value::Int64(1), Value::String("a".into()))])),
+        ];
+        assert_eq!(from_yaml(text).unwrap(), fields);
+        assert_eq!(from_yaml("- 1\n"), Err(YamlError::NotAMessage));
+        assert_eq!(from_yaml("x: !u8 300\n"), Err(YamlError::InvalidTag { tag: "u8".into() }));
+        assert!(matches!(from_yaml("x: [\n"), Err(YamlError::Syntax(_))));
+    }
+}